@@ -0,0 +1,82 @@
+//! Times building the Barnes-Hut tree and measures the error of its accelerations
+//! against summing the gravity of every pair of particles. Run with
+//! `cargo bench --bench barnes_hut`, optionally followed by `-- <counts>` with a
+//! comma separated list of particle counts.
+
+mod common;
+
+use common::{median_time, time};
+
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use rayon::prelude::*;
+
+use nbody_simulation::barnes_hut::BarnesHutTree;
+use nbody_simulation::force::GravityField;
+use nbody_simulation::particle::ForceParams;
+use nbody_simulation::presets;
+use nbody_simulation::scalar::{PRECISION, Scalar, to_f64};
+use nbody_simulation::vector::{self, DIMENSIONS, Vector};
+
+/// Particle counts timed when none are given.
+const DEFAULT_COUNTS: [usize; 3] = [10_000, 50_000, 200_000];
+/// Builds timed for each particle count, the median of which is reported.
+const BUILDS: usize = 20;
+/// Values of theta the error is measured at.
+const THETAS: [Scalar; 4] = [0.3, 0.5, 0.7, 1.0];
+/// Particles whose accelerations are compared with the direct sum.
+const SAMPLES: usize = 500;
+/// Largest distance in meters each particle moves between the builds of a reused tree.
+const STEP: Scalar = 1.;
+
+fn main() {
+    let counts = common::counts(&DEFAULT_COUNTS);
+    let force = ForceParams::softened(5.);
+
+    println!("Barnes-Hut tree in {} dimensions and {} with {} thread(s)", DIMENSIONS, PRECISION, rayon::current_num_threads());
+    println!(
+        "{:>10}{:>12}{:>12}{:>12}{:>8}{:>14}{:>12}{:>12}",
+        "particles", "build", "par_build", "reused", "theta", "forces", "mean error", "max error",
+    );
+    for count in counts {
+        let mut particles = presets::random(count, 0);
+        let build = median_time(BUILDS, || time(|| BarnesHutTree::default().build(&particles, THETAS[0])));
+        let par_build = median_time(BUILDS, || time(|| BarnesHutTree::default().par_build(&particles, THETAS[0])));
+        // a tree reused between steps, with the particles moving a little before each build
+        let mut tree = BarnesHutTree::default();
+        let mut rng = StdRng::seed_from_u64(1);
+        let reused = median_time(BUILDS, || {
+            for particle in &mut particles {
+                particle.position += vector::map(Vector::ZERO, |_, _| rng.gen_range(-STEP..=STEP));
+            }
+            time(|| tree.par_build(&particles, THETAS[0]))
+        });
+
+        // the direct sum of a sample of the particles, spread through the particles
+        let samples: Vec<usize> = (0..count).step_by((count / SAMPLES).max(1)).collect();
+        let direct: Vec<Vector> = samples.par_iter().map(|&i| particles[i].net_acceleration(&particles, force)).collect();
+
+        for (i, theta) in THETAS.into_iter().enumerate() {
+            tree.par_build(&particles, theta);
+            let mut accelerations = Vec::new();
+            let forces = time(|| (0..count).into_par_iter().map(|index| tree.acceleration(index, &particles, force)).collect_into_vec(&mut accelerations));
+
+            let errors: Vec<f64> = samples
+                .iter()
+                .zip(&direct)
+                .map(|(&i, &direct)| vector::to_f64(accelerations[i]).distance(vector::to_f64(direct)) / vector::to_f64(direct).length())
+                .collect();
+            let mean = errors.iter().sum::<f64>() / errors.len() as f64;
+            let max = errors.iter().copied().fold(0., f64::max);
+
+            let (count, build, par_build, reused) = if i == 0 {
+                (count.to_string(), format!("{:.3?}", build), format!("{:.3?}", par_build), format!("{:.3?}", reused))
+            } else {
+                Default::default()
+            };
+            println!(
+                "{:>10}{:>12}{:>12}{:>12}{:>8}{:>14.3?}{:>12.2e}{:>12.2e}",
+                count, build, par_build, reused, to_f64(theta), forces, mean, max,
+            );
+        }
+    }
+}
@@ -0,0 +1,29 @@
+//! Timing and argument parsing shared by the benches.
+
+use std::time::{Duration, Instant};
+
+/// Particle counts given on the command line as comma separated lists, or the
+/// default counts if none are given.
+pub fn counts(default: &[usize]) -> Vec<usize> {
+    let counts: Vec<usize> = std::env::args()
+        .skip(1)
+        .filter(|arg| !arg.starts_with("--"))
+        .flat_map(|arg| arg.split(',').map(|count| count.trim().parse().expect("particle counts must be whole numbers")).collect::<Vec<_>>())
+        .collect();
+    if counts.is_empty() { default.to_vec() } else { counts }
+}
+
+/// Time taken by the function.
+pub fn time(f: impl FnOnce()) -> Duration {
+    let start = Instant::now();
+    f();
+    start.elapsed()
+}
+
+/// Median of the given number of timing samples, after taking one sample to warm up.
+pub fn median_time(samples: usize, mut sample: impl FnMut() -> Duration) -> Duration {
+    sample();
+    let mut times: Vec<Duration> = (0..samples).map(|_| sample()).collect();
+    times.sort_unstable();
+    times[samples / 2]
+}
@@ -0,0 +1,56 @@
+//! Times a step of the direct sum of gravity, comparing the kernel which skips
+//! the particle itself by its index with one which compares the id of every
+//! pair, and with summing each pair once for both particles, whose largest
+//! difference from the direct sum is also reported. Run with `cargo bench --bench direct_sum`, optionally followed by
+//! `-- <counts>` with a comma separated list of particle counts.
+
+mod common;
+
+use common::{median_time, time};
+
+use rayon::prelude::*;
+
+use nbody_simulation::force::{GravityField, PairwiseGravity};
+use nbody_simulation::particle::{ForceParams, Particle};
+use nbody_simulation::presets;
+use nbody_simulation::scalar::PRECISION;
+use nbody_simulation::vector::{self, DIMENSIONS, Vector};
+
+/// Particle counts timed when none are given.
+const DEFAULT_COUNTS: [usize; 3] = [1_000, 5_000, 10_000];
+/// Steps timed for each particle count, the median of which is reported.
+const STEPS: usize = 5;
+
+fn main() {
+    let counts = common::counts(&DEFAULT_COUNTS);
+    let force = ForceParams::softened(5.);
+
+    println!("Direct sum in {} dimensions and {} with {} thread(s)", DIMENSIONS, PRECISION, rayon::current_num_threads());
+    println!("{:>10}{:>14}{:>14}{:>14}{:>14}{:>12}", "particles", "by id", "by index", "pairs", "par pairs", "max error");
+    for count in counts {
+        let particles = presets::random(count, 0);
+        // the direct sum the error is measured against, kept apart from the buffer the timed sums write to
+        let direct: Vec<Vector> = (0..count).into_par_iter().map(|index| Particle::net_acceleration_at(index, &particles, force)).collect();
+        let mut accelerations = Vec::new();
+        let by_id = median_time(STEPS, || {
+            time(|| {
+                particles
+                    .par_iter()
+                    .map(|particle| particles.iter().filter(|other| other.id != particle.id).map(|other| particle.acceleration(other, force)).sum())
+                    .collect_into_vec(&mut accelerations)
+            })
+        });
+        let by_index = median_time(STEPS, || time(|| (0..count).into_par_iter().map(|index| Particle::net_acceleration_at(index, &particles, force)).collect_into_vec(&mut accelerations)));
+        let mut pairs = PairwiseGravity::default();
+        let by_pairs = median_time(STEPS, || time(|| pairs.build(&particles, force)));
+        let by_par_pairs = median_time(STEPS, || time(|| pairs.par_build(&particles, force)));
+
+        let error = (0..count)
+            .map(|index| {
+                let direct = vector::to_f64(direct[index]);
+                vector::to_f64(pairs.acceleration(index, &particles, force)).distance(direct) / direct.length()
+            })
+            .fold(0., f64::max);
+        println!("{:>10}{:>14.3?}{:>14.3?}{:>14.3?}{:>14.3?}{:>12.2e}", count, by_id, by_index, by_pairs, by_par_pairs, error);
+    }
+}
@@ -0,0 +1,82 @@
+//! Times preparing the sprite batch of a frame the way the graphical front end
+//! does, leaving out particles behind the camera or off screen, scaling the
+//! sprites of the others by their masses and depths and sorting them by depth in
+//! three dimensions, without opening a window. Needs the `gui` feature for the
+//! sprite type of coffee. Run with `cargo bench --bench sprites`, optionally
+//! followed by `-- <counts>` with a comma separated list of particle counts.
+
+mod common;
+
+use common::{median_time, time};
+
+use coffee::graphics::{Point, Rectangle, Sprite, Vector as ScreenVector};
+use rayon::prelude::*;
+
+use nbody_simulation::config::{Config, RenderConfig};
+use nbody_simulation::particle::Particle;
+use nbody_simulation::presets;
+use nbody_simulation::scalar::{PRECISION, Scalar};
+use nbody_simulation::vector::{DIMENSIONS, Vector, depth};
+
+/// Particle counts timed when none are given.
+const DEFAULT_COUNTS: [usize; 3] = [10_000, 50_000, 200_000];
+/// Frames timed for each particle count, the median of which is reported.
+const FRAMES: usize = 20;
+/// Size of the window in pixels.
+const SCREEN: (f32, f32) = (1280., 720.);
+/// Pixels outside the window a sprite can reach before it is left out of the frame, as in the front end
+const CULL_MARGIN: f32 = 8.;
+/// Largest factor sprites are scaled by for being close to the camera, as in the front end
+const MAX_DEPTH_SCALE: f32 = 10.;
+
+fn main() {
+    let counts = common::counts(&DEFAULT_COUNTS);
+    let render = Config::default().render;
+
+    println!("Sprite batch preparation in {} dimensions and {} with {} thread(s)", DIMENSIONS, PRECISION, rayon::current_num_threads());
+    println!("{:>10}{:>10}{:>14}", "particles", "drawn", "prepare");
+    for count in counts {
+        let particles = presets::random(count, 0);
+        // the window is as wide as the disk of particles, which are spread over a radius of 10 m times the root of their count
+        let scale = SCREEN.0 / (20. * (count as f32).sqrt());
+        let mut sprites = Vec::new();
+        let prepare = median_time(FRAMES, || time(|| prepare(&particles, &render, scale, &mut sprites)));
+        println!("{:>10}{:>10}{:>14.3?}", count, sprites.len(), prepare);
+    }
+}
+
+/// Sprites of the particles on screen with their depths, for a camera centered
+/// on the origin at the plane of zero depth.
+fn prepare(particles: &[Particle], render: &RenderConfig, scale: f32, sprites: &mut Vec<(Scalar, Sprite)>) {
+    let camera_offset = ScreenVector::new(SCREEN.0 / 2., SCREEN.1 / 2.);
+    let view_distance = (SCREEN.0 / scale) as Scalar;
+    sprites.clear();
+    sprites.par_extend(particles.par_iter().filter_map(|particle| {
+        let depth_scale = depth_scale(particle.position, 0., view_distance)?;
+        let sprite_scale = render.sprite_scale_of(particle.mass);
+        let offset = ScreenVector::new(render.sprite_width, render.sprite_height) * sprite_scale / 2.;
+        let corner = Point::new(particle.position.x as f32, particle.position.y as f32) * scale - offset * depth_scale;
+        let size = ScreenVector::new(render.sprite_width, render.sprite_height) * sprite_scale * depth_scale;
+        let screen = corner + camera_offset;
+        if screen.x + size.x < -CULL_MARGIN || screen.y + size.y < -CULL_MARGIN || screen.x > SCREEN.0 + CULL_MARGIN || screen.y > SCREEN.1 + CULL_MARGIN {
+            return None;
+        }
+        let source = render.kind_source(render.sprite_kind_of(particle.mass));
+        let sprite = Sprite {
+            source: Rectangle { x: source.x, y: source.y, width: source.width, height: source.height },
+            position: corner,
+            scale: (sprite_scale * depth_scale, sprite_scale * depth_scale),
+        };
+        Some((depth(particle.position), sprite))
+    }));
+    // particles further from the camera are drawn first so closer particles cover them
+    #[cfg(feature = "three-dimensions")]
+    sprites.par_sort_by(|a, b| a.0.total_cmp(&b.0));
+}
+
+/// Factor sprites at the position are scaled by for their distance from the camera,
+/// or None if the position is behind the camera, as in the front end.
+fn depth_scale(position: Vector, camera_depth: Scalar, view_distance: Scalar) -> Option<f32> {
+    let distance = view_distance + camera_depth - depth(position);
+    (distance > 0.).then(|| ((view_distance / distance) as f32).min(MAX_DEPTH_SCALE))
+}
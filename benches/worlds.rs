@@ -0,0 +1,43 @@
+//! Times a full update of each world, with the default physics and the Verlet
+//! integrator, so the algorithms can be compared on the same particles. Run with
+//! `cargo bench --bench worlds`, optionally followed by `-- <counts>` with a comma
+//! separated list of particle counts.
+
+mod common;
+
+use common::{median_time, time};
+
+use nbody_simulation::integrator::VerletIntegrator;
+use nbody_simulation::presets;
+use nbody_simulation::scalar::{PRECISION, Scalar};
+use nbody_simulation::vector::DIMENSIONS;
+use nbody_simulation::world::{Physics, RayonWorld, SequentialWorld, ThreadsWorld, World};
+
+/// Particle counts timed when none are given.
+const DEFAULT_COUNTS: [usize; 3] = [1_000, 5_000, 10_000];
+/// Updates timed for each world and particle count, the median of which is reported.
+const STEPS: usize = 5;
+/// Seconds simulated by each update.
+const DT: Scalar = 1.;
+
+fn main() {
+    let counts = common::counts(&DEFAULT_COUNTS);
+    let physics = Physics::default();
+
+    println!("World updates in {} dimensions and {} with the {:?} gravity solver", DIMENSIONS, PRECISION, physics.gravity_solver);
+    println!("{:>10}{:>12}{:>9}{:>14}", "particles", "algorithm", "threads", "update");
+    for count in counts {
+        let particles = presets::random(count, 0);
+        let worlds: [(&str, Box<dyn World>); 3] = [
+            ("sequential", Box::new(SequentialWorld::new(Vec::new(), Box::new(VerletIntegrator), physics))),
+            ("rayon", Box::new(RayonWorld::new(Vec::new(), Box::new(VerletIntegrator), physics))),
+            ("threads", Box::new(ThreadsWorld::new(0, Vec::new(), Box::new(VerletIntegrator), physics))),
+        ];
+        for (algorithm, mut world) in worlds {
+            // the world sets the radii and starting accelerations of the particles
+            world.set_particles(particles.clone());
+            let update = median_time(STEPS, || time(|| world.update(DT)));
+            println!("{:>10}{:>12}{:>9}{:>14.3?}", count, algorithm, world.num_threads(), update);
+        }
+    }
+}
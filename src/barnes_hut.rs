@@ -0,0 +1,362 @@
+use std::ops::Range;
+
+use rayon::prelude::*;
+
+use crate::force::GravityField;
+use crate::particle::{ForceParams, Particle, ParticleId};
+use crate::scalar::Scalar;
+use crate::vector::{self, DIMENSIONS, Vector};
+
+/// Bits of each coordinate in a Morton code, so the code fits in 64 bits
+const BITS: usize = 64 / DIMENSIONS;
+/// Children of a node, four in two dimensions and eight in three
+const CHILDREN: usize = 1 << DIMENSIONS;
+/// Most particles in a leaf
+const LEAF_SIZE: usize = 32;
+/// Nodes with more particles than this build their children in parallel
+const PARALLEL_SIZE: usize = 4096;
+/// Index used for a missing node
+const NONE: usize = usize::MAX;
+
+/// Position, mass and id of a particle, copied into the order of the tree.
+#[derive(Clone, Copy)]
+struct Body {
+    position: Vector,
+    mass: Scalar,
+    id: ParticleId,
+}
+
+/// Cell of the tree holding a range of the sorted bodies.
+#[derive(Clone, Copy, Default)]
+struct Node {
+    mass: Scalar,
+    center_of_mass: Vector,
+    /// Square of the distance from the center of mass beyond which the node is treated as a single particle
+    far_squared: Scalar,
+    /// Range of the bodies in the node
+    start: usize,
+    end: usize,
+    /// Index of the first child, or NONE for a leaf
+    first_child: usize,
+    /// Index of the node visited after this node when its children are skipped
+    next: usize,
+}
+
+/// Barnes-Hut tree, a quadtree in two dimensions and an octree in three, which
+/// approximates the gravity of a distant cell of particles by the gravity of
+/// its total mass at its center of mass.
+///
+/// The particles are sorted by the Morton code of their position, which lists
+/// the cells they are in from the largest to the smallest, so the particles of
+/// every cell are a contiguous range of the sorted order. A range of n particles
+/// has at most 2n - 1 nodes, as cells with a single occupied child are skipped,
+/// so each child is given a fixed part of the node pool after its parent and
+/// the children can be built in parallel. The nodes are linked in depth first
+/// order so they can be traversed without a stack, and the buffers are kept
+/// between builds so they don't need to be reallocated every update.
+#[derive(Default)]
+pub struct BarnesHutTree {
+    /// Morton code and index of each particle, sorted by code
+    codes: Vec<(u64, usize)>,
+    /// Particles in the order of their codes
+    bodies: Vec<Body>,
+    /// Pool of nodes, with the root at index 0
+    nodes: Vec<Node>,
+}
+
+impl BarnesHutTree {
+    /// Builds the tree of the particles. Larger values of theta treat closer
+    /// cells as single particles, which is faster but less accurate. Values
+    /// above 1 are treated as 1, so a particle is never part of a cell treated
+    /// as a single particle, and 0 sums the gravity of every pair of particles.
+    pub fn build(&mut self, particles: &[Particle], theta: Scalar) {
+        let bounds = particles.iter().map(|particle| (particle.position, particle.position)).fold(EMPTY_BOUNDS, union);
+        let (min, side) = cube(bounds);
+        let scale = (1u64 << BITS) as Scalar / side;
+        self.codes.clear();
+        self.codes.extend(particles.iter().enumerate().map(|(i, particle)| (morton_code(particle.position, min, scale), i)));
+        self.codes.sort_unstable_by_key(|&(code, _)| code);
+        self.bodies.clear();
+        self.bodies.extend(self.codes.iter().map(|&(_, i)| Body::from(&particles[i])));
+        self.build_nodes(min, side, theta, false);
+    }
+
+    /// Builds the tree of the particles using parallel iterators from the rayon library.
+    pub fn par_build(&mut self, particles: &[Particle], theta: Scalar) {
+        let bounds = particles.par_iter().map(|particle| (particle.position, particle.position)).reduce(|| EMPTY_BOUNDS, union);
+        let (min, side) = cube(bounds);
+        let scale = (1u64 << BITS) as Scalar / side;
+        self.codes.clear();
+        self.codes.par_extend(particles.par_iter().enumerate().map(|(i, particle)| (morton_code(particle.position, min, scale), i)));
+        self.codes.par_sort_unstable_by_key(|&(code, _)| code);
+        self.bodies.clear();
+        self.bodies.par_extend(self.codes.par_iter().map(|&(_, i)| Body::from(&particles[i])));
+        self.build_nodes(min, side, theta, true);
+    }
+
+    fn build_nodes(&mut self, min: Vector, side: Scalar, theta: Scalar, parallel: bool) {
+        let len = self.bodies.len();
+        if len == 0 {
+            return;
+        }
+        // the pool only grows, so it is reused by every later build with as many particles
+        let slots = 2 * len - 1;
+        if self.nodes.len() < slots {
+            self.nodes.resize(slots, Node::default());
+        }
+        let sorted = Sorted { codes: &self.codes, bodies: &self.bodies, min, side, theta: theta.min(1.) };
+        sorted.build_node(&mut self.nodes[..slots], 0, 0..len, NONE, parallel);
+    }
+}
+
+impl GravityField for BarnesHutTree {
+    fn acceleration(&self, index: usize, sources: &[Particle], force: ForceParams) -> Vector {
+        let target = &sources[index];
+        let mut acceleration = Vector::ZERO;
+        let mut index = if self.bodies.is_empty() { NONE } else { 0 };
+        while index != NONE {
+            let node = &self.nodes[index];
+            if force.separation(target.position, node.center_of_mass).length_squared() > node.far_squared {
+                acceleration += force.acceleration(target.position, node.center_of_mass, node.mass);
+                index = node.next;
+            } else if node.first_child == NONE {
+                for body in self.bodies[node.start..node.end].iter().filter(|body| body.id != target.id) {
+                    acceleration += force.acceleration(target.position, body.position, body.mass);
+                }
+                index = node.next;
+            } else {
+                index = node.first_child;
+            }
+        }
+        acceleration
+    }
+}
+
+impl From<&Particle> for Body {
+    fn from(particle: &Particle) -> Self {
+        Body { position: particle.position, mass: particle.mass, id: particle.id }
+    }
+}
+
+/// Sorted particles shared by the nodes while they are built.
+struct Sorted<'a> {
+    codes: &'a [(u64, usize)],
+    bodies: &'a [Body],
+    /// Corner and width of the cube containing every particle
+    min: Vector,
+    side: Scalar,
+    theta: Scalar,
+}
+
+/// Child of a node waiting to be built into its part of the node pool.
+struct Child<'a> {
+    index: usize,
+    range: Range<usize>,
+    slots: &'a mut [Node],
+    next: usize,
+}
+
+impl Sorted<'_> {
+    /// Builds the node of a range of the bodies into the first of the slots,
+    /// packing its descendants into the rest of the slots, and returns its mass
+    /// and center of mass. A range of n bodies is given 2n - 1 slots.
+    fn build_node(&self, slots: &mut [Node], index: usize, range: Range<usize>, next: usize, parallel: bool) -> (Scalar, Vector) {
+        // the smallest cell containing the range is given by the digits its first and last codes have in common
+        let first_code = self.codes[range.start].0;
+        let depth = match first_code ^ self.codes[range.end - 1].0 {
+            0 => BITS,
+            difference => BITS - 1 - (63 - difference.leading_zeros() as usize) / DIMENSIONS,
+        };
+        let (node, mut rest) = slots.split_first_mut().expect("every range has at least one slot");
+
+        let (mass, center_of_mass, first_child) = if range.len() <= LEAF_SIZE || depth == BITS {
+            let bodies = &self.bodies[range.clone()];
+            let (mass, center_of_mass) = bodies.iter().map(|body| (body.mass, body.position)).fold((0., Vector::ZERO), combine);
+            (mass, center_of_mass, NONE)
+        } else {
+            // split the range by the next digit of the codes, giving each occupied child its part of the slots
+            let mut children: [Option<Child>; CHILDREN] = Default::default();
+            let (mut start, mut child_index) = (range.start, index + 1);
+            for (value, child) in children.iter_mut().enumerate() {
+                let end = start + self.codes[start..range.end].partition_point(|&(code, _)| digit(code, depth) <= value);
+                if end > start {
+                    let child_slots = 2 * (end - start) - 1;
+                    let (slots, remaining) = std::mem::take(&mut rest).split_at_mut(child_slots);
+                    rest = remaining;
+                    *child = Some(Child { index: child_index, range: start..end, slots, next: NONE });
+                    (start, child_index) = (end, child_index + child_slots);
+                }
+            }
+
+            // link each child to the following child, and the last child to the node after this one
+            let mut following = next;
+            for child in children.iter_mut().rev().flatten() {
+                child.next = following;
+                following = child.index;
+            }
+
+            let mut summaries = [(0., Vector::ZERO); CHILDREN];
+            self.build_children(&mut children, &mut summaries, parallel && range.len() > PARALLEL_SIZE);
+            let (mass, center_of_mass) = summaries.into_iter().fold((0., Vector::ZERO), combine);
+            (mass, center_of_mass, following)
+        };
+
+        // a cell is far enough away once it is 1 / theta of its widths away from the
+        // center of mass, plus the distance from its center to its center of mass
+        let size = self.side / (1u64 << depth) as Scalar;
+        let far = size / self.theta + center_of_mass.distance(self.cell_center(first_code, depth, size));
+        *node = Node { mass, center_of_mass, far_squared: far * far, start: range.start, end: range.end, first_child, next };
+        (mass, center_of_mass)
+    }
+
+    /// Builds the children, storing the mass and center of mass of each child in
+    /// its summary. In parallel the children are split in half with rayon's join
+    /// until each half has one child, which unlike spawning doesn't allocate.
+    fn build_children(&self, children: &mut [Option<Child>], summaries: &mut [(Scalar, Vector)], parallel: bool) {
+        if !parallel || children.len() == 1 {
+            for (child, summary) in children.iter_mut().zip(summaries) {
+                if let Some(child) = child.take() {
+                    *summary = self.build_node(child.slots, child.index, child.range, child.next, parallel);
+                }
+            }
+            return;
+        }
+        let half = children.len() / 2;
+        let (first_children, last_children) = children.split_at_mut(half);
+        let (first_summaries, last_summaries) = summaries.split_at_mut(half);
+        rayon::join(
+            || self.build_children(first_children, first_summaries, parallel),
+            || self.build_children(last_children, last_summaries, parallel),
+        );
+    }
+
+    /// Center of the cell of the given depth and size containing the code.
+    fn cell_center(&self, code: u64, depth: usize, size: Scalar) -> Vector {
+        let corner: [u64; DIMENSIONS] = std::array::from_fn(|axis| {
+            (0..depth).fold(0, |corner, digit| (corner << 1) | ((code >> ((BITS - 1 - digit) * DIMENSIONS + DIMENSIONS - 1 - axis)) & 1))
+        });
+        vector::map(self.min, |axis, min| min + (corner[axis] as Scalar + 0.5) * size)
+    }
+}
+
+/// Bounds of no particles, which are replaced by the bounds of the first particle.
+const EMPTY_BOUNDS: (Vector, Vector) = (Vector::splat(Scalar::INFINITY), Vector::splat(Scalar::NEG_INFINITY));
+
+fn union(a: (Vector, Vector), b: (Vector, Vector)) -> (Vector, Vector) {
+    (a.0.min(b.0), a.1.max(b.1))
+}
+
+/// Corner and width of the cube around the bounds.
+fn cube((min, max): (Vector, Vector)) -> (Vector, Scalar) {
+    let side = (max - min).max_element();
+    (min, if side > 0. { side } else { 1. })
+}
+
+/// Interleaves the bits of the coordinates of the cell the position is in,
+/// from the most significant bit of each coordinate to the least.
+fn morton_code(position: Vector, min: Vector, scale: Scalar) -> u64 {
+    (0..DIMENSIONS).fold(0, |code, axis| {
+        let coordinate = (((position[axis] - min[axis]) * scale) as u64).min((1 << BITS) - 1);
+        code | (spread(coordinate) << (DIMENSIONS - 1 - axis))
+    })
+}
+
+/// Spreads the bits of a coordinate out so there is a zero bit between each of them.
+#[cfg(not(feature = "three-dimensions"))]
+fn spread(mut bits: u64) -> u64 {
+    bits = (bits | (bits << 16)) & 0x0000_ffff_0000_ffff;
+    bits = (bits | (bits << 8)) & 0x00ff_00ff_00ff_00ff;
+    bits = (bits | (bits << 4)) & 0x0f0f_0f0f_0f0f_0f0f;
+    bits = (bits | (bits << 2)) & 0x3333_3333_3333_3333;
+    (bits | (bits << 1)) & 0x5555_5555_5555_5555
+}
+
+/// Spreads the bits of a coordinate out so there are two zero bits between each of them.
+#[cfg(feature = "three-dimensions")]
+fn spread(mut bits: u64) -> u64 {
+    bits = (bits | (bits << 32)) & 0x001f_0000_0000_ffff;
+    bits = (bits | (bits << 16)) & 0x001f_0000_ff00_00ff;
+    bits = (bits | (bits << 8)) & 0x100f_00f0_0f00_f00f;
+    bits = (bits | (bits << 4)) & 0x10c3_0c30_c30c_30c3;
+    (bits | (bits << 2)) & 0x1249_2492_4924_9249
+}
+
+/// Digit of a Morton code choosing the child of a cell of the given depth.
+fn digit(code: u64, depth: usize) -> usize {
+    (code >> ((BITS - 1 - depth) * DIMENSIONS)) as usize & (CHILDREN - 1)
+}
+
+/// Combines the masses and centers of mass of two groups of particles. The
+/// center of mass is moved towards the other group instead of summing the
+/// products of the masses and positions, which overflow in single precision.
+fn combine((mass, center): (Scalar, Vector), (other_mass, other_center): (Scalar, Vector)) -> (Scalar, Vector) {
+    let total = mass + other_mass;
+    if total == 0. {
+        return (total, center);
+    }
+    (total, center + (other_center - center) * (other_mass / total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::presets;
+    use crate::scalar::to_f64;
+
+    /// Root mean square error of the accelerations of the tree relative to the
+    /// root mean square of the direct sum.
+    fn relative_error(tree: &BarnesHutTree, particles: &[Particle], force: ForceParams) -> f64 {
+        let (mut error, mut total) = (0., 0.);
+        for index in 0..particles.len() {
+            let exact = vector::to_f64(Particle::net_acceleration_at(index, particles, force));
+            error += (vector::to_f64(tree.acceleration(index, particles, force)) - exact).length_squared();
+            total += exact.length_squared();
+        }
+        (error / total).sqrt()
+    }
+
+    #[test]
+    fn accelerations_approach_the_direct_sum_as_theta_shrinks() {
+        let particles = presets::random(2000, 11);
+        let force = ForceParams::softened(1.);
+        let mut tree = BarnesHutTree::default();
+        let mut previous = 0.;
+        for theta in [0., 0.2, 0.5, 0.8] {
+            tree.build(&particles, theta);
+            let error = relative_error(&tree, &particles, force);
+            // the quadrupole moment left out of a cell grows as the square of theta
+            let bound = to_f64((0.02 * theta * theta).max(1e4 * Scalar::EPSILON));
+            assert!(error <= bound, "theta {}: error {:e} above {:e}", theta, error, bound);
+            assert!(error >= previous, "theta {}: error {:e} below {:e} of a smaller theta", theta, error, previous);
+            previous = error;
+        }
+    }
+
+    #[test]
+    fn parallel_build_matches_the_sequential_build() {
+        let particles = presets::random(3 * PARALLEL_SIZE, 12);
+        let force = ForceParams::softened(1.);
+        let (mut tree, mut par_tree) = (BarnesHutTree::default(), BarnesHutTree::default());
+        tree.build(&particles, 0.5);
+        par_tree.par_build(&particles, 0.5);
+        for index in (0..particles.len()).step_by(7) {
+            assert_eq!(tree.acceleration(index, &particles, force), par_tree.acceleration(index, &particles, force), "particle {}", index);
+        }
+    }
+
+    #[test]
+    fn reused_tree_matches_a_new_tree() {
+        let (large, small) = (presets::random(3000, 13), presets::random(500, 14));
+        let force = ForceParams::softened(1.);
+        let mut reused = BarnesHutTree::default();
+        for particles in [&large, &small, &large] {
+            reused.par_build(particles, 0.5);
+            let mut new = BarnesHutTree::default();
+            new.par_build(particles, 0.5);
+            for index in (0..particles.len()).step_by(5) {
+                assert_eq!(reused.acceleration(index, particles, force), new.acceleration(index, particles, force), "particle {} of {}", index, particles.len());
+            }
+        }
+        assert_eq!(BarnesHutTree::default().acceleration(0, &small, force), Vector::ZERO);
+    }
+}
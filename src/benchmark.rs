@@ -0,0 +1,323 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+/// Directory benchmark reports are written to.
+pub const REPORT_DIR: &str = "benchmarks";
+
+/// What a [`Benchmark`] is currently doing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BenchmarkStatus {
+    Idle,
+    Running,
+    /// Started but not recording samples until it is resumed
+    Paused,
+    Finished,
+    Cancelled,
+}
+
+/// Summary of a set of timing samples.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Statistics {
+    pub samples: usize,
+    pub mean: Duration,
+    pub min: Duration,
+    pub max: Duration,
+    pub median: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+}
+
+impl Statistics {
+    /// Summarizes the samples, returning None if there are none. Percentiles
+    /// use the nearest rank, so they are always one of the samples.
+    pub fn from_samples(samples: &[Duration]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort_unstable();
+        let total: Duration = sorted.iter().sum();
+        Some(Statistics {
+            samples: sorted.len(),
+            mean: total.div_f64(sorted.len() as f64),
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            median: percentile(&sorted, 50),
+            p95: percentile(&sorted, 95),
+            p99: percentile(&sorted, 99),
+        })
+    }
+}
+
+/// Nearest rank percentile of sorted, non-empty samples.
+fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+    let rank = (sorted.len() * percent).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+/// Records the time spent on physics and rendering for a fixed number of
+/// updates. Samples are only recorded while the benchmark is running.
+#[derive(Clone, Debug)]
+pub struct Benchmark {
+    status: BenchmarkStatus,
+    /// Number of physics updates to record before finishing
+    updates: usize,
+    physics_samples: Vec<Duration>,
+    render_samples: Vec<Duration>,
+}
+
+impl Benchmark {
+    pub fn new(updates: usize) -> Self {
+        Benchmark {
+            status: BenchmarkStatus::Idle,
+            updates,
+            physics_samples: Vec::with_capacity(updates),
+            render_samples: Vec::with_capacity(updates),
+        }
+    }
+
+    pub fn status(&self) -> BenchmarkStatus {
+        self.status
+    }
+
+    /// Whether the benchmark has been started and hasn't finished or been cancelled.
+    pub fn is_active(&self) -> bool {
+        matches!(self.status, BenchmarkStatus::Running | BenchmarkStatus::Paused)
+    }
+
+    /// Starts recording, discarding the samples of any previous run.
+    pub fn start(&mut self) {
+        self.physics_samples.clear();
+        self.render_samples.clear();
+        self.status = BenchmarkStatus::Running;
+    }
+
+    pub fn pause(&mut self) {
+        if self.status == BenchmarkStatus::Running {
+            self.status = BenchmarkStatus::Paused;
+        }
+    }
+
+    pub fn resume(&mut self) {
+        if self.status == BenchmarkStatus::Paused {
+            self.status = BenchmarkStatus::Running;
+        }
+    }
+
+    /// Stops recording, keeping the samples recorded so far.
+    pub fn cancel(&mut self) {
+        if self.is_active() {
+            self.status = BenchmarkStatus::Cancelled;
+        }
+    }
+
+    /// Records the time of a physics update, returning true if it was the last update of the benchmark.
+    pub fn record_physics(&mut self, time: Duration) -> bool {
+        if self.status != BenchmarkStatus::Running {
+            return false;
+        }
+        self.physics_samples.push(time);
+        if self.physics_samples.len() >= self.updates {
+            self.status = BenchmarkStatus::Finished;
+            return true;
+        }
+        false
+    }
+
+    /// Records the time spent drawing a frame.
+    pub fn record_render(&mut self, time: Duration) {
+        if self.status == BenchmarkStatus::Running {
+            self.render_samples.push(time);
+        }
+    }
+
+    /// Number of physics updates recorded and the number needed to finish.
+    pub fn progress(&self) -> (usize, usize) {
+        (self.physics_samples.len(), self.updates)
+    }
+
+    pub fn physics_statistics(&self) -> Option<Statistics> {
+        Statistics::from_samples(&self.physics_samples)
+    }
+
+    pub fn render_statistics(&self) -> Option<Statistics> {
+        Statistics::from_samples(&self.render_samples)
+    }
+
+    /// Creates a report of the recorded statistics and the settings they were
+    /// recorded with, along with metadata about the machine and build.
+    pub fn to_report(&self, algorithm: &str, particles: usize, threads: usize, dt: f64) -> BenchmarkReport {
+        BenchmarkReport {
+            algorithm: algorithm.to_string(),
+            particles,
+            threads,
+            dt,
+            physics: self.physics_statistics(),
+            render: self.render_statistics(),
+            metadata: Metadata::capture(),
+        }
+    }
+}
+
+/// Information about the machine and build a benchmark was run with.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    pub cpu_cores: usize,
+    /// Either debug or release
+    pub profile: String,
+    pub version: String,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+}
+
+impl Metadata {
+    pub fn capture() -> Self {
+        Metadata {
+            cpu_cores: std::thread::available_parallelism().map_or(1, |cores| cores.get()),
+            profile: String::from(if cfg!(debug_assertions) { "debug" } else { "release" }),
+            version: String::from(env!("CARGO_PKG_VERSION")),
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs()),
+        }
+    }
+}
+
+/// Results of a benchmark which can be saved to and loaded from a file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BenchmarkReport {
+    pub algorithm: String,
+    pub particles: usize,
+    pub threads: usize,
+    /// Seconds simulated by each update
+    pub dt: f64,
+    pub physics: Option<Statistics>,
+    pub render: Option<Statistics>,
+    pub metadata: Metadata,
+}
+
+impl fmt::Display for BenchmarkReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} algorithm, {} particles, {} thread(s), dt {} s",
+            self.algorithm, self.particles, self.threads, self.dt,
+        )?;
+        writeln!(
+            f,
+            "Version {} ({}) on {} core(s), recorded at {} s since the Unix epoch",
+            self.metadata.version, self.metadata.profile, self.metadata.cpu_cores, self.metadata.timestamp,
+        )?;
+        for (phase, statistics) in [("Physics", self.physics), ("Render", self.render)] {
+            match statistics {
+                Some(statistics) => writeln!(
+                    f,
+                    "{}: {} samples, mean {:.2?}, min {:.2?}, median {:.2?}, p95 {:.2?}, p99 {:.2?}, max {:.2?}",
+                    phase, statistics.samples, statistics.mean, statistics.min, statistics.median, statistics.p95, statistics.p99, statistics.max,
+                )?,
+                None => writeln!(f, "{}: no samples", phase)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes the report to a new JSON file in the directory, creating the
+/// directory if needed, and returns the path of the file.
+pub fn save_report(dir: &str, report: &BenchmarkReport) -> std::io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let name = format!("{}-{}-{}.json", report.metadata.timestamp, report.algorithm.to_lowercase(), report.particles);
+    let path = Path::new(dir).join(name);
+    let mut file = BufWriter::new(File::create(&path)?);
+    serde_json::to_writer_pretty(&mut file, report)?;
+    file.flush()?;
+    Ok(path)
+}
+
+/// Reads a report previously written by save_report.
+pub fn load_report(path: &str) -> std::io::Result<BenchmarkReport> {
+    let file = BufReader::new(File::open(path)?);
+    Ok(serde_json::from_reader(file)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn millis(samples: impl IntoIterator<Item = u64>) -> Vec<Duration> {
+        samples.into_iter().map(Duration::from_millis).collect()
+    }
+
+    #[test]
+    fn statistics_use_nearest_rank_percentiles() {
+        // shuffled so the statistics can't rely on the order of the samples
+        let mut samples = millis(1..=100);
+        samples.reverse();
+        samples.swap(10, 70);
+        let statistics = Statistics::from_samples(&samples).unwrap();
+        assert_eq!(statistics.samples, 100);
+        assert_eq!(statistics.mean, Duration::from_micros(50_500));
+        assert_eq!((statistics.min, statistics.max), (Duration::from_millis(1), Duration::from_millis(100)));
+        assert_eq!((statistics.median, statistics.p95, statistics.p99), (Duration::from_millis(50), Duration::from_millis(95), Duration::from_millis(99)));
+
+        let single = Statistics::from_samples(&millis([7])).unwrap();
+        assert_eq!([single.min, single.median, single.p95, single.p99, single.max], [Duration::from_millis(7); 5]);
+        assert_eq!(Statistics::from_samples(&[]), None);
+    }
+
+    #[test]
+    fn benchmark_only_records_while_running_and_finishes_after_its_updates() {
+        let mut benchmark = Benchmark::new(3);
+        assert!(!benchmark.record_physics(Duration::from_millis(1)), "recorded before starting");
+        benchmark.start();
+        assert!(!benchmark.record_physics(Duration::from_millis(2)));
+        benchmark.record_render(Duration::from_millis(20));
+        benchmark.pause();
+        assert_eq!(benchmark.status(), BenchmarkStatus::Paused);
+        assert!(benchmark.is_active());
+        assert!(!benchmark.record_physics(Duration::from_millis(100)));
+        benchmark.record_render(Duration::from_millis(100));
+        assert_eq!(benchmark.progress(), (1, 3));
+        benchmark.resume();
+        assert!(!benchmark.record_physics(Duration::from_millis(4)));
+        assert!(benchmark.record_physics(Duration::from_millis(6)));
+        assert_eq!(benchmark.status(), BenchmarkStatus::Finished);
+        assert!(!benchmark.record_physics(Duration::from_millis(100)), "recorded after finishing");
+        assert_eq!(benchmark.physics_statistics().map(|statistics| (statistics.samples, statistics.mean)), Some((3, Duration::from_millis(4))));
+        assert_eq!(benchmark.render_statistics().map(|statistics| statistics.samples), Some(1));
+
+        // starting again discards the samples, and cancelling keeps the ones recorded
+        benchmark.start();
+        assert_eq!(benchmark.progress(), (0, 3));
+        benchmark.record_physics(Duration::from_millis(1));
+        benchmark.cancel();
+        assert_eq!((benchmark.status(), benchmark.progress()), (BenchmarkStatus::Cancelled, (1, 3)));
+        benchmark.resume();
+        assert_eq!(benchmark.status(), BenchmarkStatus::Cancelled);
+    }
+
+    #[test]
+    fn saved_report_loads_back_with_the_machine_metadata() {
+        let mut benchmark = Benchmark::new(2);
+        benchmark.start();
+        benchmark.record_render(Duration::from_millis(16));
+        benchmark.record_physics(Duration::from_millis(3));
+        benchmark.record_physics(Duration::from_millis(5));
+        let report = benchmark.to_report("Rayon", 1000, 4, 0.5);
+        assert!(report.metadata.cpu_cores >= 1);
+        assert_eq!(report.metadata.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(report.metadata.profile, if cfg!(debug_assertions) { "debug" } else { "release" });
+
+        let dir = std::env::temp_dir().join(format!("nbody-benchmarks-{}", std::process::id()));
+        let path = save_report(dir.to_str().unwrap(), &report).unwrap();
+        let loaded = load_report(path.to_str().unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(path.file_name().unwrap().to_str().unwrap(), format!("{}-rayon-1000.json", report.metadata.timestamp));
+        assert_eq!(loaded.unwrap(), report);
+        assert!(report.to_string().contains("Physics: 2 samples, mean 4.00ms"), "{}", report);
+    }
+}
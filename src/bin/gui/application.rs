@@ -0,0 +1,2574 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use coffee::graphics::{Batch, Canvas, Color, Font, Frame, Image, Mesh, Point, Rectangle, Shape, Sprite, Transformation, Vector as ScreenVector, Window};
+use coffee::input::{keyboard, mouse, KeyboardAndMouse};
+use coffee::load::Task;
+use coffee::ui::{UserInterface, Renderer, Element, Row, Justify, Align, Column, Text, Slider, slider, Button, button, Checkbox, Radio};
+use coffee::{Game, Timer};
+use rayon::prelude::*;
+
+use nbody_simulation::benchmark::{Benchmark, BenchmarkStatus, Statistics, REPORT_DIR, save_report};
+use nbody_simulation::boundary::BoundaryMode;
+use nbody_simulation::clock::{format_time, TickPacer};
+use nbody_simulation::worker_pool::WorkerPool;
+use nbody_simulation::world::{World, Physics, ThreadsWorld, RayonWorld, SequentialWorld};
+use nbody_simulation::integrator::{Integrator, EulerIntegrator, VerletIntegrator, Rk4Integrator};
+use nbody_simulation::lagrange::lagrange_points;
+use nbody_simulation::measurement::{closing_rate, format_distance, format_magnitude, relative_speed, travel_time};
+use nbody_simulation::config::{Config, ConfigWatcher, RenderConfig, SpriteKind, SpriteSource, CONFIG_FILE};
+use nbody_simulation::diagnostics::{Diagnostics, center_of_mass, recenter};
+use nbody_simulation::events::{EventLog, save_event_log};
+use nbody_simulation::ephemeris::{load_ephemeris, parse_ephemeris, SOLAR_SYSTEM_J2000};
+use nbody_simulation::force::{Force, Gravity, LinearDrag, Springs, UniformField};
+use nbody_simulation::orbital::{orbital_elements, OrbitalElements};
+use nbody_simulation::particle::{Particle, ParticleId, ParticleNames, dominant_attractor};
+use nbody_simulation::presets;
+use nbody_simulation::recording::{Recording, Snapshot, save_recording, load_recording};
+use nbody_simulation::scalar::consts::PI;
+use nbody_simulation::scalar::{Scalar, to_f64};
+use nbody_simulation::scenario::load_scenario;
+use nbody_simulation::state::{SimulationState, save_state, load_state};
+use nbody_simulation::trajectory::TrajectoryLogger;
+use nbody_simulation::vector::{self, Vector, depth};
+
+use crate::fast_forward::FastForward;
+use crate::axes::Axes;
+use crate::cached_text::TextCache;
+use crate::heatmap::{self, Heatmap, HeatmapMode};
+use crate::interpolation::Interpolation;
+use crate::keybindings::{Action, KeyBindings};
+use crate::labels::{self, Label, Labels};
+use crate::lagrange;
+use crate::measure::{self, Measurement};
+use crate::minimap::Minimap;
+use crate::ship::Ship;
+use crate::spawner::Spawner;
+use crate::sprites::{self, BlendMode, FADE_LEVELS};
+use crate::stats::PerformanceStats;
+use crate::trails::Trails;
+use crate::time_ramp::TimeScaleRamp;
+use crate::undo::UndoHistory;
+
+/// Fraction of the default time scale the simulation runs at in slow motion
+const SLOW_MOTION: Scalar = 0.1;
+/// Number of the most recent events shown on screen
+const EVENT_LINES: usize = 5;
+/// Pixels the camera moves each tick while a movement key is held
+const PAN_SPEED: f32 = 5.;
+/// Distance in pixels from the cursor a particle can be selected
+const SELECT_RADIUS: f32 = 10.;
+/// Number of spawned particles which can be undone
+const UNDO_LEVELS: usize = 100;
+/// Number of sprites drawn along the line from a particle being spawned to the cursor
+const SPAWN_LINE_SPRITES: usize = 10;
+/// Pixels outside the window a sprite can reach before it is left out of the frame
+const CULL_MARGIN: f32 = 8.;
+/// Largest factor sprites are scaled by for being close to the camera
+const MAX_DEPTH_SCALE: f32 = 10.;
+/// Fraction of the view distance the camera moves along the view axis each tick while Q or E is held
+#[cfg(feature = "three-dimensions")]
+const DEPTH_SPEED: Scalar = 0.02;
+/// Real seconds the fastest orbit takes after fitting the view to a preset
+const FASTEST_ORBIT_SECONDS: Scalar = 5.;
+/// Points along the predicted orbit of the selected particle
+const ORBIT_POINTS: usize = 180;
+/// Color of the predicted orbit of the selected particle
+const ORBIT_COLOR: Color = Color { r: 1., g: 0.8, b: 0.3, a: 0.8 };
+/// Change in scale for each line scrolled with the mouse wheel
+const ZOOM_FACTOR: f32 = 1.1;
+/// Range the scale is clamped to, keeping positions on screen from underflowing or overflowing
+const MIN_SCALE: f32 = 1.0e-12;
+const MAX_SCALE: f32 = 1.0e3;
+/// Directory screenshots are saved in
+const SCREENSHOT_DIR: &str = "screenshots";
+/// Config fields only used when the application starts, which need a restart to change.
+const RESTART_FIELDS: [&str; 4] = ["heatmap_colors", "screen_width", "screen_height", "world_scale"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorldType {
+    Threads,
+    Rayon,
+    Sequential,
+}
+
+impl WorldType {
+    /// Every algorithm, in the order they are listed in the algorithm selector
+    const ALL: [WorldType; 3] = [WorldType::Sequential, WorldType::Rayon, WorldType::Threads];
+
+    /// Label of the algorithm in the algorithm selector
+    fn name(self) -> &'static str {
+        match self {
+            WorldType::Threads => "Threads",
+            WorldType::Rayon => "Rayon",
+            WorldType::Sequential => "Sequential",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum IntegratorType {
+    Euler,
+    Verlet,
+    Rk4,
+}
+
+/// Which property of the particles decides the color they are drawn with
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ColorMode {
+    Uniform,
+    Speed,
+    Mass,
+    Acceleration,
+}
+
+impl ColorMode {
+    fn next(self) -> Self {
+        match self {
+            ColorMode::Uniform => ColorMode::Speed,
+            ColorMode::Speed => ColorMode::Mass,
+            ColorMode::Mass => ColorMode::Acceleration,
+            ColorMode::Acceleration => ColorMode::Uniform,
+        }
+    }
+
+    /// The property of the particle that is colored, or None if every particle is drawn the same.
+    fn value(self, particle: &Particle) -> Option<Scalar> {
+        match self {
+            ColorMode::Uniform => None,
+            ColorMode::Speed => Some(particle.velocity.length()),
+            ColorMode::Mass => Some(particle.mass),
+            ColorMode::Acceleration => Some(particle.acceleration.length()),
+        }
+    }
+}
+
+/// Index of the color band a value falls in, splitting the range between min and max into
+/// bands of equal size on a logarithmic scale so properties spanning many orders of magnitude
+/// still use every band.
+fn color_band(value: Scalar, min: Scalar, max: Scalar, bands: usize) -> usize {
+    if value <= min || max <= min {
+        return 0;
+    }
+    let band = ((value / min).ln() / (max / min).ln() * bands as Scalar) as usize;
+    band.min(bands - 1)
+}
+
+/// What holding the left mouse button does, decided when the button is first pressed
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LeftButton {
+    Released,
+    Selecting,
+    /// Dragging from the anchor where a particle will be created when the button is released
+    Spawning { anchor: Vector, cursor: Vector },
+}
+
+pub struct Application {
+    /// Settings loaded from the config file
+    config: Config,
+    /// Reloads the config when the config file is edited
+    config_watcher: ConfigWatcher,
+    /// Stores the world data and handles updating of particles
+    world: Box<dyn World>,
+    /// The state of which world implementation is currently being used
+    world_type: WorldType,
+    /// The state of which integrator is currently being used
+    integrator_type: IntegratorType,
+    /// Position of the camera for render particles
+    camera_position: Point,
+    /// Depth of the plane drawn at the normal sprite size, moved along the view axis with Q and E
+    #[cfg(feature = "three-dimensions")]
+    camera_depth: Scalar,
+    /// Pixels per meter of the camera, changed by zooming
+    scale: f32,
+    /// Seconds simulated by each update
+    time_scale: Scalar,
+    /// Time scale chosen for the current particles by the config, a preset or a loaded save,
+    /// which the keyboard's slow motion is a tenth of
+    default_time_scale: Scalar,
+    /// Change of the time scale from the keyboard spread over a few updates
+    time_scale_ramp: TimeScaleRamp,
+    /// Cursor position of the previous tick while dragging the camera with the middle mouse button
+    drag_position: Option<Point>,
+    /// What the left mouse button is currently doing
+    left_button: LeftButton,
+    /// Id of the particle followed by the camera
+    selected_id: Option<ParticleId>,
+    /// Whether the camera moves with the center of mass of the particles while no particle is followed
+    center_of_mass_frame: bool,
+    /// Number of particles and their center of mass when the camera last moved with the center of mass
+    frame_center: Option<(usize, Vector)>,
+    /// Id of the particle under the cursor
+    hovered_id: Option<ParticleId>,
+    /// Id of the particle shown by the inspector instead of the hovered particle
+    pinned_id: Option<ParticleId>,
+    /// Distance between two points or particles picked with the mouse
+    measurement: Measurement,
+    /// Ids of the last two particles selected by clicking on them, the most recent last
+    selected_pair: [Option<ParticleId>; 2],
+    /// Whether the Lagrange points of the selected pair of particles are drawn
+    lagrange_enabled: bool,
+    /// Whether the predicted orbit of the selected particle is drawn
+    orbit_enabled: bool,
+    /// Position in the world under the cursor, where a click spawns a particle
+    cursor_world_position: Vector,
+    /// Axes through the origin with ticks adapting to the zoom, drawn while axes_enabled is set
+    axes: Axes,
+    axes_enabled: bool,
+    /// Whether particles are drawn with a size depending on their mass or all the same size
+    size_by_mass: bool,
+    /// The state of which property of the particles decides their color
+    color_mode: ColorMode,
+    /// How the sprites of overlapping particles are combined
+    blend_mode: BlendMode,
+    /// Whether the density of the particles is drawn as a heatmap, and whether the particles are drawn over it
+    heatmap_mode: HeatmapMode,
+    heatmap: Heatmap,
+    /// Overview of where all the particles are in a corner of the window, which moves the camera when clicked
+    minimap: Minimap,
+    minimap_enabled: bool,
+    /// Names of particles from presets, scenario files and saves, drawn next to them
+    names: ParticleNames,
+    labels: Labels,
+    /// Key of each action, and whether they are listed in an overlay
+    key_bindings: KeyBindings,
+    bindings_visible: bool,
+    /// Recent positions of the particles, drawn behind them when enabled
+    trails: Trails,
+    /// Positions of the particles before the latest update, which they are drawn moving from
+    interpolation: Interpolation,
+    /// Recent frame and update times shown in the performance overlay
+    stats: PerformanceStats,
+    trails_enabled: bool,
+    /// Particle steered with the arrow keys, whose engine is one of the forces while it runs
+    ship: Option<Ship>,
+    /// Springs holding launched rubble piles together, which are one of the forces while there are any
+    springs: Springs,
+    /// Closest approach of launched rubble piles in Roche limits of the particle they are launched at
+    rubble_periapsis: Scalar,
+    rubble_periapsis_slider: slider::State,
+    /// Fragments the selected particle is split into
+    split_fragments: usize,
+    split_fragments_slider: slider::State,
+    /// Energy of the explosion splitting the selected particle
+    split_energy: Scalar,
+    split_energy_slider: slider::State,
+    /// Mass of particles created with the mouse
+    spawn_mass: Scalar,
+    /// Velocity of particles created with the mouse
+    spawn_velocity: Vector,
+    /// Particles spawned by the user which can be undone and redone
+    undo_history: UndoHistory,
+    /// Stream of particles emitted from the cursor while the stream key is held
+    spawner: Spawner,
+    /// States of the stream rate, speed and spread sliders
+    stream_rate_slider: slider::State,
+    stream_speed_slider: slider::State,
+    stream_spread_slider: slider::State,
+    /// Whether particles created with the mouse orbit the particle pulling hardest on them instead of using the spawn velocity
+    spawn_orbiting: bool,
+    /// Eccentricity of the orbits particles are inserted into, with the spawn position at periapsis
+    orbit_eccentricity: Scalar,
+    orbit_eccentricity_slider: slider::State,
+    /// States of the spawn mass and velocity sliders
+    spawn_mass_slider: slider::State,
+    spawn_velocity_x_slider: slider::State,
+    spawn_velocity_y_slider: slider::State,
+    /// Lines showing the latest measurement of the energy and momentum of the
+    /// particles, formatted when they are measured rather than every frame
+    diagnostics_text: Vec<String>,
+    /// Latest merges, spawns and removals drained from the world
+    event_log: EventLog,
+    /// Lines showing the most recent events, formatted when events arrive
+    event_log_text: Vec<String>,
+    /// Lines listing the key of every action, formatted when the key bindings change
+    bindings_text: Vec<String>,
+    /// Text of the values shown every frame which usually stay the same between frames
+    texts: TextCache,
+    /// First measurement since the number of particles changed, with the number of particles
+    diagnostics_baseline: Option<(usize, Diagnostics)>,
+    /// File the diagnostics are logged to
+    diagnostics_log: Option<BufWriter<File>>,
+    /// Logger of the trajectories of particles while logging is started
+    trajectory_log: Option<TrajectoryLogger>,
+    /// Number of times the world has been updated
+    steps: u64,
+    /// Times of the physics updates and frames recorded while benchmarking
+    benchmark: Benchmark,
+    /// Recent snapshots of the particles, recorded while armed
+    recording: Recording,
+    recording_armed: bool,
+    /// Index of the recorded frame drawn instead of the particles while playing back the recording
+    playback_frame: Option<usize>,
+    /// Whether updating the world is paused
+    paused: bool,
+    /// Whether the world should be updated once on the next tick while paused
+    step_requested: bool,
+    /// Drops the ticks coffee runs to catch up after falling more than `max_catch_up` seconds behind
+    pacer: TickPacer,
+    /// Updates being run without drawing them, even while paused
+    fast_forward: Option<FastForward>,
+    /// Whether the next frame drawn is saved as a screenshot
+    screenshot_requested: bool,
+    /// Whether the sprite sheet is loaded again from the sprite file on the next interaction
+    sprite_reload_requested: bool,
+    /// Whether the window switches between fullscreen and windowed on the next interaction
+    fullscreen_requested: bool,
+    /// Width and height of the window at the last interaction, to keep the view centered when it is resized
+    window_size: (f32, f32),
+    /// Kinds of sprite chosen for particles by the presets they came from, instead of by their masses
+    sprite_kinds: HashMap<ParticleId, SpriteKind>,
+    /// Container for sprites of particles to render
+    batch: Batch,
+    /// Sprites of the particles on screen with their depths, kept between frames so they aren't reallocated
+    sprites: Vec<(Scalar, Sprite)>,
+    /// Particles left out of the last frame for being off screen or behind the camera
+    culled_sprites: usize,
+    /// Parameters used by the world when updating particles
+    physics: Physics,
+    /// Whether the particles are slowed by linear drag and accelerated by the uniform field, as well as gravity
+    drag_enabled: bool,
+    uniform_field_enabled: bool,
+    /// State of the softening length slider
+    softening_slider: slider::State,
+    theta_slider: slider::State,
+    /// State of the button cycling the gravity solver
+    gravity_solver_button: button::State,
+    /// State of the gravity multiplier slider
+    gravity_slider: slider::State,
+    /// State of the time scale slider
+    time_scale_slider: slider::State,
+    /// State of the world scale slider
+    world_scale_slider: slider::State,
+    /// State of the button cycling the collision mode
+    collision_mode_button: button::State,
+    /// States of the restitution and friction sliders of bouncing particles
+    restitution_slider: slider::State,
+    friction_slider: slider::State,
+    /// State of the button cycling the boundary mode
+    boundary_mode_button: button::State,
+    /// State of the button moving the particles into their center of mass frame
+    recenter_button: button::State,
+    /// Threads used by the threads world, kept when the world is rebuilt. The rayon
+    /// world is built with a thread pool of the same size.
+    pool: WorkerPool,
+    /// State of the slider changing the number of threads of the world
+    threads_slider: slider::State,
+    /// Time the physics of the latest update took, cleared when the world is rebuilt
+    step_time: Option<Duration>,
+}
+
+impl Application {
+    fn change_world_algorithm(&mut self, new_algorithm: WorldType) {
+        if new_algorithm == self.world_type {
+            return;
+        }
+        println!("Changed algorithm to {:?}", new_algorithm);
+        self.world_type = new_algorithm;
+        self.rebuild_world();
+    }
+
+    /// Resizes the worker pool of the threads world, and rebuilds the rayon world on a
+    /// thread pool of the new size.
+    fn change_num_threads(&mut self, num_threads: usize) {
+        // the threads world needs at least the main thread or updating would deadlock
+        self.pool.resize(num_threads.max(1));
+        if self.world_type == WorldType::Rayon {
+            self.rebuild_world();
+        }
+        println!("Changed number of threads to {}", self.pool.num_threads());
+    }
+
+    /// Creates a new world of the current type containing the particles of the old world.
+    fn rebuild_world(&mut self) {
+        let particles = self.world.get_particles();
+        self.replace_world(particles);
+    }
+
+    /// Creates a new world of the current type containing the given particles,
+    /// carrying over the clock of the old world.
+    fn replace_world(&mut self, particles: Vec<Particle>) {
+        // keep the events of the world being replaced
+        self.collect_events();
+        self.trails.clear();
+        self.interpolation.clear();
+        self.step_time = None;
+        let (integrator, time) = (self.create_integrator(), self.world.time());
+        self.world = match self.world_type {
+            WorldType::Threads => Box::new(ThreadsWorld::with_pool(self.pool.clone(), particles, integrator, self.physics)),
+            WorldType::Rayon => Box::new(RayonWorld::with_threads(particles, integrator, self.physics, self.pool.num_threads())),
+            WorldType::Sequential => Box::new(SequentialWorld::new(particles, integrator, self.physics)),
+        };
+        self.world.set_forces(self.create_forces());
+        self.world.set_time(time);
+    }
+
+    /// Gravity along with the other forces which are enabled.
+    fn create_forces(&self) -> Vec<Box<dyn Force>> {
+        let mut forces: Vec<Box<dyn Force>> = vec![Box::new(Gravity)];
+        if self.drag_enabled {
+            forces.push(Box::new(LinearDrag { coefficient: self.config.drag_coefficient }));
+        }
+        if self.uniform_field_enabled {
+            forces.push(Box::new(UniformField { acceleration: self.config.uniform_field }));
+        }
+        if !self.springs.is_empty() {
+            forces.push(Box::new(self.springs.clone()));
+        }
+        if let Some(thrust) = self.ship.and_then(|ship| ship.force()) {
+            forces.push(Box::new(thrust));
+        }
+        forces
+    }
+
+    /// Steers the selected particle with the arrow keys, or stops steering the ship.
+    fn toggle_ship(&mut self) {
+        if let Some(ship) = self.ship {
+            self.abandon_ship();
+            println!("Stopped steering particle {}", ship.id);
+            return;
+        }
+        match self.selected_id.filter(|_| self.playback_frame.is_none()) {
+            Some(id) => {
+                self.ship = Some(Ship::new(id, self.config.ship_fuel));
+                println!("Steering particle {} with the arrow keys", id);
+            }
+            None => println!("Select a particle to steer it"),
+        }
+    }
+
+    /// Stops steering the ship, turning its engine off.
+    fn abandon_ship(&mut self) {
+        if self.ship.take().is_some_and(|ship| ship.force().is_some()) {
+            self.world.set_forces(self.create_forces());
+        }
+    }
+
+    /// Uses the fuel of the ship for dt seconds of thrust, turning the engine off
+    /// when it runs out, and stops steering the ship once it no longer exists.
+    fn burn_fuel(&mut self, dt: Scalar) {
+        let Some(ship) = &mut self.ship else {
+            return;
+        };
+        let (id, out_of_fuel) = (ship.id, ship.burn(dt));
+        if out_of_fuel {
+            println!("Particle {} ran out of fuel", id);
+            self.world.set_forces(self.create_forces());
+        }
+        if self.find_particle(id).is_none() {
+            println!("Lost the ship, particle {} no longer exists", id);
+            self.abandon_ship();
+        }
+    }
+
+    /// Removes the springs of the launched rubble piles, before presets and saves
+    /// replace the particles and may give their ids to other particles.
+    fn clear_springs(&mut self) {
+        if !self.springs.is_empty() {
+            self.springs.clear();
+            self.world.set_forces(self.create_forces());
+        }
+    }
+
+    /// Launches a rubble pile from the position on a parabolic orbit around the most
+    /// massive particle, coming closest to it at the periapsis in Roche limits of the
+    /// pile. The pile is rubble_radius pixels across at the current zoom so it can be
+    /// seen, its mass is a fraction of the mass of the particle, and springs join its
+    /// neighbors so it holds together until the tides of the particle pull it apart.
+    fn launch_rubble_pile(&mut self, position: Vector) {
+        let Some(primary) = self.world.particles().iter().max_by(|a, b| a.mass.total_cmp(&b.mass)).cloned() else {
+            println!("There is no particle to launch a rubble pile at");
+            return;
+        };
+        let (force, config) = (self.physics.force_params(), &self.config);
+        let radius = (config.rubble_radius / self.scale) as Scalar;
+        let mass = primary.mass * config.rubble_mass_ratio;
+        let periapsis = self.rubble_periapsis * presets::roche_limit(radius, mass, primary.mass);
+        let velocity = primary.approach_velocity(position, periapsis, force);
+        let stiffness = presets::rubble_stiffness(config.rubble_strength, radius, mass, force.g);
+
+        let (pile, neighbors) = presets::rubble_pile(config.rubble_particles, radius, mass);
+        self.world.reserve(pile.len());
+        let ids: Vec<ParticleId> = pile.iter().map(|particle| self.world.create_particle(position + particle.position, velocity, particle.mass, false)).collect();
+        for &(a, b, rest_length) in &neighbors {
+            self.springs.connect(ids[a], ids[b], rest_length, stiffness);
+        }
+        self.sprite_kinds.extend(ids.iter().map(|&id| (id, SpriteKind::Debris)));
+        self.world.set_forces(self.create_forces());
+        println!("Launched a rubble pile of {} particles at particle {} with a periapsis of {:.3e} m", ids.len(), primary.id, periapsis);
+    }
+
+    fn change_integrator(&mut self, new_integrator: IntegratorType) {
+        println!("Changed integrator to {:?}", new_integrator);
+        self.integrator_type = new_integrator;
+        let integrator = self.create_integrator();
+        self.world.set_integrator(integrator);
+    }
+
+    /// Changes the multiplier of the gravitational constant, with negative multipliers making gravity repulsive.
+    fn set_gravity_multiplier(&mut self, multiplier: Scalar) {
+        self.physics.gravity_multiplier = multiplier;
+        println!("Changed gravity multiplier to {:e}", multiplier);
+        self.world.set_physics(self.physics);
+    }
+
+    fn change_gravity_solver(&mut self) {
+        self.physics.gravity_solver = self.physics.gravity_solver.next();
+        println!("Changed gravity solver to {:?}", self.physics.gravity_solver);
+        self.world.set_physics(self.physics);
+    }
+
+    fn change_collision_mode(&mut self) {
+        self.physics.collision_mode = self.physics.collision_mode.next();
+        println!("Changed collision mode to {:?}", self.physics.collision_mode);
+        self.world.set_physics(self.physics);
+    }
+
+    fn change_boundary_mode(&mut self) {
+        self.physics.boundary.mode = self.physics.boundary.mode.next();
+        println!("Changed boundary to {:?}", self.physics.boundary.mode);
+        self.world.set_physics(self.physics);
+    }
+
+    fn save_simulation(&mut self) {
+        let state = SimulationState {
+            particles: self.world.get_particles(),
+            world_scale: self.scale,
+            time_scale: self.time_scale,
+            time: self.world.time(),
+            names: self.names.clone(),
+        };
+        match save_state(&self.config.save_file, &state) {
+            Ok(()) => println!("Saved {} particles to {}", state.particles.len(), self.config.save_file),
+            Err(error) => println!("Failed to save to {}: {}", self.config.save_file, error),
+        }
+    }
+
+    fn load_simulation(&mut self) {
+        match load_state(&self.config.save_file) {
+            Ok(state) => {
+                println!("Loaded {} particles from {}", state.particles.len(), self.config.save_file);
+                self.scale = state.world_scale;
+                self.set_default_time_scale(state.time_scale);
+                self.springs.clear();
+                self.ship = None;
+                self.replace_world(state.particles);
+                self.world.set_time(state.time);
+                self.names = state.names;
+                self.sprite_kinds.clear();
+                self.undo_history.clear();
+            }
+            Err(error) => println!("Failed to load from {}: {}", self.config.save_file, error),
+        }
+    }
+
+    /// Adds the particles of the scenario file to the world.
+    fn load_scenario_file(&mut self) {
+        let Some(path) = self.config.scenario_file.clone() else {
+            println!("No scenario file set, set scenario_file in {}", CONFIG_FILE);
+            return;
+        };
+        match load_scenario(&path) {
+            Ok(particles) => {
+                self.world.reserve(particles.len());
+                for particle in &particles {
+                    let id = self.world.create_particle(particle.position, particle.velocity, particle.mass, particle.fixed);
+                    if let Some(name) = &particle.name {
+                        self.names.insert(id, name.clone());
+                    }
+                }
+                println!("Loaded {} particles from {}", particles.len(), path);
+                if self.config.auto_recenter {
+                    self.recenter();
+                }
+            }
+            Err(error) => println!("Failed to load scenario: {}", error),
+        }
+    }
+
+    /// Replaces the particles with the bodies of the ephemeris file, or the bundled
+    /// solar system if no file is set.
+    fn load_ephemeris_preset(&mut self, window: &Window) {
+        let ephemeris = match &self.config.ephemeris_file {
+            Some(path) => load_ephemeris(path),
+            None => parse_ephemeris(SOLAR_SYSTEM_J2000),
+        };
+        match ephemeris {
+            Ok(ephemeris) => {
+                println!("Loaded state vectors at {}", ephemeris.epoch);
+                let (particles, names) = ephemeris.particles(self.config.ephemeris_optional_bodies);
+                self.load_preset(particles, names, None, window);
+            }
+            Err(error) => println!("Failed to load ephemeris: {}", error),
+        }
+    }
+
+    /// Creates a particle at the position with the spawn mass, moving with either the spawn
+    /// velocity or the velocity of an orbit around the dominant attractor at the position,
+    /// plus the velocity from dragging the mouse. Retrograde orbits go around the attractor
+    /// the other way. Fixed particles are created at rest.
+    fn spawn_particle(&mut self, position: Vector, drag_velocity: Vector, fixed: bool, retrograde: bool) {
+        let orbital_velocity = self.spawn_orbiting
+            .then(|| {
+                let particles = self.world.particles();
+                let force = self.physics.force_params();
+                dominant_attractor(&particles, position, force).map(|attractor| {
+                    let relative = attractor.insertion_velocity(position, self.orbit_eccentricity, force) - attractor.velocity;
+                    attractor.velocity + if retrograde { -relative } else { relative }
+                })
+            })
+            .flatten();
+        let velocity = orbital_velocity.unwrap_or(self.spawn_velocity) + drag_velocity;
+        let id = self.world.create_particle(position, velocity, self.spawn_mass, fixed);
+        self.undo_history.spawned(id);
+    }
+
+    /// Moves the particles so their center of mass is at rest at the origin, moving the
+    /// camera and trails with them so the view doesn't jump.
+    fn recenter(&mut self) {
+        let mut particles = self.world.get_particles();
+        let offset = recenter(&mut particles);
+        self.world.set_particles(particles);
+        self.trails.translate(offset);
+        self.interpolation.clear();
+        self.camera_position -= ScreenVector::new(offset.x as f32, offset.y as f32) * self.scale;
+        self.frame_center = None;
+        println!("Moved the particles into their center of mass frame");
+    }
+
+    /// Replaces the particles of the world with a preset and its names, and fits the view to it.
+    /// The particles are drawn with the sprite of the kind, or of the kind of their mass if None.
+    fn load_preset(&mut self, mut particles: Vec<Particle>, names: ParticleNames, kind: Option<SpriteKind>, window: &Window) {
+        println!("Loaded preset of {} particles", particles.len());
+        if self.config.auto_recenter {
+            recenter(&mut particles);
+        }
+        self.clear_springs();
+        self.abandon_ship();
+        self.sprite_kinds = kind.map_or_else(HashMap::new, |kind| particles.iter().map(|particle| (particle.id, kind)).collect());
+        self.world.set_particles(particles);
+        self.world.set_time(0.);
+        self.names = names;
+        self.trails.clear();
+        self.interpolation.clear();
+        self.undo_history.clear();
+        self.selected_id = None;
+        self.selected_pair = [None; 2];
+        self.fit_view(window);
+    }
+
+    /// Zooms so every particle fits in the window and changes the time scale so the particle
+    /// orbiting the most massive particle fastest completes an orbit in a few seconds.
+    fn fit_view(&mut self, window: &Window) {
+        let particles = self.world.particles();
+        let total_mass: Scalar = particles.iter().map(|particle| particle.mass).sum();
+        let Some(heaviest) = particles.iter().max_by(|a, b| a.mass.total_cmp(&b.mass)).cloned() else {
+            return;
+        };
+        let center_of_mass = particles.iter().map(|particle| particle.position * particle.mass).sum::<Vector>() / total_mass;
+        let max_distance = particles.iter().map(|particle| particle.position.distance(center_of_mass)).fold(0., Scalar::max);
+        let fastest_orbit = particles
+            .iter()
+            .filter(|particle| particle.id != heaviest.id)
+            .map(|particle| {
+                let speed = particle.velocity.distance(heaviest.velocity);
+                2. * PI * particle.position.distance(heaviest.position) / speed
+            })
+            .filter(|period| period.is_finite() && *period > 0.)
+            .fold(Scalar::INFINITY, Scalar::min);
+        drop(particles);
+
+        if max_distance > 0. {
+            let scale = 0.9 * window.width().min(window.height()) / 2. / max_distance as f32;
+            self.scale = scale.clamp(MIN_SCALE, MAX_SCALE);
+        }
+        if fastest_orbit.is_finite() {
+            self.set_default_time_scale(fastest_orbit / (FASTEST_ORBIT_SECONDS * Self::TICKS_PER_SECOND as Scalar));
+        }
+        self.center_camera_on(center_of_mass, window.width(), window.height());
+    }
+
+    /// Moves the camera so the center of mass of the particles is in the middle of the window.
+    fn center_camera(&mut self, window: &Window) {
+        let center_of_mass = center_of_mass(&self.world.particles());
+        self.center_camera_on(center_of_mass, window.width(), window.height());
+    }
+
+    /// Moves the camera by how far the center of mass has moved since the last frame in
+    /// the center of mass frame, interpolating the center of mass like the particles.
+    /// The camera is not moved when the number of particles changes, as adding or
+    /// removing particles moves the center of mass without motion.
+    fn follow_center_of_mass(&mut self, alpha: Scalar) {
+        if !self.center_of_mass_frame {
+            self.frame_center = None;
+            return;
+        }
+        let particles = self.world.particles();
+        let center = (particles.len(), self.interpolation.center_of_mass(center_of_mass(&particles), alpha));
+        drop(particles);
+        if let Some((count, last)) = self.frame_center {
+            if count == center.0 {
+                let motion = center.1 - last;
+                self.camera_position -= ScreenVector::new(motion.x as f32, motion.y as f32) * self.scale;
+            }
+        }
+        self.frame_center = Some(center);
+    }
+
+    /// Lines of the user interface showing a measurement of the energy and
+    /// momentum of the particles and their changes since the baseline.
+    fn format_diagnostics(&self, diagnostics: Diagnostics) -> Vec<String> {
+        let baseline = self.diagnostics_baseline.map(|(_, baseline)| baseline).unwrap_or(diagnostics);
+        let energy = match (diagnostics.total_energy(), baseline.total_energy()) {
+            (Some(energy), Some(baseline_energy)) if baseline_energy != 0. => {
+                format!("Energy: {:.4e} J ({:+.3e} change)", energy, (energy - baseline_energy) / baseline_energy.abs())
+            }
+            (Some(energy), _) => format!("Energy: {:.4e} J", energy),
+            (None, _) => format!("Kinetic Energy: {:.4e} J (too many particles for potential)", diagnostics.kinetic_energy),
+        };
+        // merging conserves the momentum but not the angular momentum, so both are shown with their changes
+        let momentum_change = (diagnostics.momentum - baseline.momentum).length();
+        let angular_momentum = match baseline.angular_momentum.length() {
+            baseline_length if baseline_length > 0. => format!(
+                "Angular Momentum: {} kg m^2/s ({:+.3e} change)",
+                format_vector(diagnostics.angular_momentum.to_array()), (diagnostics.angular_momentum - baseline.angular_momentum).length() / baseline_length,
+            ),
+            _ => format!("Angular Momentum: {} kg m^2/s", format_vector(diagnostics.angular_momentum.to_array())),
+        };
+        vec![
+            energy,
+            format!("Momentum: {} kg m/s ({:.3e} change)", format_vector(diagnostics.momentum.to_array()), momentum_change),
+            angular_momentum,
+            format!("Total Mass: {:.4e} kg", diagnostics.total_mass),
+            format!("Center of Mass: {} m", format_vector(diagnostics.center_of_mass.to_array())),
+            format!("Center of Mass Velocity: {} m/s", format_vector(diagnostics.center_of_mass_velocity.to_array())),
+            format!("Center of Mass Drift: {:.3e} m", diagnostics.center_of_mass.distance(baseline.center_of_mass)),
+        ]
+    }
+
+    /// Measures the energy and momentum of the particles, logging them if there is a diagnostics file.
+    fn measure_diagnostics(&mut self) {
+        let particles = self.world.particles();
+        let diagnostics = Diagnostics::measure(&particles, self.physics.force_params(), self.config.max_potential_particles);
+        let num_particles = particles.len();
+        drop(particles);
+
+        // changes are measured from the first measurement with the current particles
+        if self.diagnostics_baseline.is_none_or(|(baseline_particles, _)| baseline_particles != num_particles) {
+            self.diagnostics_baseline = Some((num_particles, diagnostics));
+        }
+        self.diagnostics_text = self.format_diagnostics(diagnostics);
+
+        if let Some(log) = &mut self.diagnostics_log {
+            let potential_energy = diagnostics.potential_energy.map_or(String::new(), |energy| energy.to_string());
+            let result = write!(log, "{},{},{},{}", self.steps, num_particles, diagnostics.kinetic_energy, potential_energy)
+                .and_then(|()| vector::write_csv(log, diagnostics.momentum.to_array()))
+                .and_then(|()| vector::write_csv(log, diagnostics.center_of_mass.to_array()))
+                .and_then(|()| writeln!(log));
+            if let Err(error) = result {
+                println!("Failed to write diagnostics, stopped logging: {}", error);
+                self.diagnostics_log = None;
+            }
+        }
+    }
+
+    /// Changes the scale of the camera, moving it so the position on screen at the anchor stays in place.
+    fn zoom_to(&mut self, scale: f32, anchor: Point) {
+        let position = (anchor - self.camera_position) / self.scale;
+        self.scale = scale.clamp(MIN_SCALE, MAX_SCALE);
+        self.camera_position = anchor - position * self.scale;
+    }
+
+    /// Moves the camera so the position is in the middle of a window of the given size.
+    fn center_camera_on(&mut self, position: Vector, width: f32, height: f32) {
+        self.camera_position = Point::new(
+            width / 2. - position.x as f32 * self.scale,
+            height / 2. - position.y as f32 * self.scale,
+        );
+    }
+
+    /// Id of the particle closest to the position on screen, if one is within the selection radius.
+    fn particle_at(&self, position: Vector) -> Option<ParticleId> {
+        let radius = (SELECT_RADIUS / self.scale) as Scalar;
+        let position = vector::planar(position.x, position.y);
+        self.world
+            .particles()
+            .iter()
+            .map(|particle| (vector::planar(particle.position.x, particle.position.y).distance_squared(position), particle.id))
+            .filter(|&(distance_squared, _)| distance_squared <= radius * radius)
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, id)| id)
+    }
+
+    /// Depth of the plane drawn at the normal sprite size, which is always zero in two dimensions.
+    #[cfg(not(feature = "three-dimensions"))]
+    fn camera_depth(&self) -> Scalar {
+        0.
+    }
+
+    /// Depth of the plane drawn at the normal sprite size.
+    #[cfg(feature = "three-dimensions")]
+    fn camera_depth(&self) -> Scalar {
+        self.camera_depth
+    }
+
+    /// Position in the world under the cursor, on the plane drawn at the normal sprite size.
+    fn world_position(&self, x: Scalar, y: Scalar) -> Vector {
+        vector::with_depth(x, y, self.camera_depth())
+    }
+
+    /// Loads the sprite sheet again from the sprite file, so changes to the image
+    /// show without restarting. The current sprites are kept if it can't be loaded.
+    fn reload_sprites(&mut self, window: &mut Window) {
+        match sprites::load_sheet(&self.config.render).run(window.gpu()) {
+            Ok(sheet) => {
+                self.batch = Batch::new(sheet);
+                println!("Reloaded sprites from {}", self.config.render.sprite_file);
+            }
+            Err(error) => println!("Kept the current sprites, failed to load {}: {}", self.config.render.sprite_file, error),
+        }
+    }
+
+    /// The particle with the given id, if it still exists.
+    fn find_particle(&self, id: ParticleId) -> Option<Particle> {
+        self.world.particles().iter().find(|particle| particle.id == id).cloned()
+    }
+
+    /// The particle followed by the camera, if it still exists.
+    fn selected_particle(&self) -> Option<Particle> {
+        self.find_particle(self.selected_id?)
+    }
+
+    /// Pushes the selected particle in a direction of the screen by the nudge
+    /// speed. Nothing is pushed while the recording plays back, when the left
+    /// and right keys scrub through it instead.
+    fn nudge_selected(&mut self, x: Scalar, y: Scalar) {
+        // the arrow keys steer the ship instead
+        if self.ship.is_some() {
+            return;
+        }
+        let Some(id) = self.selected_id.filter(|_| self.playback_frame.is_none()) else {
+            return;
+        };
+        if !self.world.apply_impulse(id, vector::planar(x, y) * self.config.nudge_speed) {
+            self.selected_id = None;
+        }
+    }
+
+    /// The particle shown by the inspector, either the pinned particle or the
+    /// particle under the cursor, if it still exists.
+    fn inspected_particle(&self) -> Option<Particle> {
+        self.find_particle(self.pinned_id.or(self.hovered_id)?)
+    }
+
+    /// Position of an end of the measurement, with the velocity of its particle if it
+    /// is a particle, or None if its particle no longer exists.
+    fn measure_end(&self, end: measure::End) -> Option<(Vector, Option<Vector>)> {
+        match end {
+            measure::End::Position(position) => Some((position, None)),
+            measure::End::Particle(id) => self.find_particle(id).map(|particle| (particle.position, Some(particle.velocity))),
+        }
+    }
+
+    /// Osculating orbit of the selected particle around the particle pulling
+    /// hardest on it, with that particle.
+    fn selected_orbit(&self) -> Option<(Particle, OrbitalElements)> {
+        let particle = self.selected_particle()?;
+        let force = self.physics.force_params();
+        let central = dominant_attractor(&self.world.particles(), particle.position, force)?.clone();
+        let gravitational_parameter = to_f64(force.g) * (to_f64(central.mass) + to_f64(particle.mass));
+        let orbit = orbital_elements(force.separation(particle.position, central.position), particle.velocity - central.velocity, gravitational_parameter)?;
+        Some((central, orbit))
+    }
+
+    /// The last two particles selected by clicking on them, the heavier one
+    /// first, if both still exist.
+    fn lagrange_pair(&self) -> Option<(Particle, Particle)> {
+        let [Some(first), Some(second)] = self.selected_pair else {
+            return None;
+        };
+        let (first, second) = (self.find_particle(first)?, self.find_particle(second)?);
+        Some(if first.mass >= second.mass { (first, second) } else { (second, first) })
+    }
+
+    /// Ends of the measurement once both are picked.
+    fn measured_ends(&self) -> Option<[(Vector, Option<Vector>); 2]> {
+        let &[from, to] = self.measurement.ends() else {
+            return None;
+        };
+        Some([self.measure_end(from)?, self.measure_end(to)?])
+    }
+
+    /// Starts logging the trajectories of the particles to the trajectory file, or
+    /// stops logging and waits for the rest of the log to be written.
+    fn toggle_trajectory_log(&mut self) {
+        match self.trajectory_log.take() {
+            Some(logger) => match logger.finish() {
+                Ok(()) => println!("Stopped logging trajectories to {}", self.config.trajectory_file),
+                Err(error) => println!("Failed to write trajectories to {}: {}", self.config.trajectory_file, error),
+            },
+            None => {
+                let config = &self.config;
+                match TrajectoryLogger::new(&config.trajectory_file, config.trajectory_interval, config.trajectory_ids.clone(), config.trajectory_max_particles) {
+                    Ok(logger) => {
+                        println!("Started logging trajectories to {}", config.trajectory_file);
+                        self.trajectory_log = Some(logger);
+                    }
+                    Err(error) => println!("Failed to create trajectory file {}: {}", config.trajectory_file, error),
+                }
+            }
+        }
+    }
+
+    /// Starts recording snapshots of the particles, or stops recording. The
+    /// frames of the previous recording are discarded when recording starts.
+    fn toggle_recording(&mut self) {
+        self.recording_armed = !self.recording_armed;
+        if self.recording_armed {
+            // recreated so changes to the recording settings apply to the new recording
+            self.recording = Recording::new(self.config.record_interval, self.config.max_recorded_frames);
+            println!("Started recording");
+        } else {
+            println!("Stopped recording with {} frames", self.recording.len());
+        }
+    }
+
+    /// Starts playing back the recording from its oldest frame, or returns to the simulation.
+    fn toggle_playback(&mut self) {
+        if self.playback_frame.is_some() {
+            self.playback_frame = None;
+        } else if self.recording.is_empty() {
+            println!("Nothing has been recorded, start recording with V");
+        } else {
+            // stop recording so the frames being played back aren't changed
+            self.recording_armed = false;
+            self.playback_frame = Some(0);
+        }
+    }
+
+    /// The recorded frame being played back, if there is one.
+    fn playback_snapshot(&self) -> Option<&Snapshot> {
+        self.recording.frame(self.playback_frame?)
+    }
+
+    /// Does an action whose key was released. Actions done while their key is held
+    /// are checked every interaction instead.
+    fn perform(&mut self, action: Action, cursor: Vector, shift_pressed: bool, control_pressed: bool, window: &Window) {
+        match action {
+            Action::ShowBindings => self.bindings_visible = !self.bindings_visible,
+            // pause the simulation, or advance it by a single update while paused
+            Action::TogglePause => {
+                self.paused = !self.paused;
+                // the benchmark doesn't record while paused so single steps don't count towards it
+                if self.paused {
+                    self.benchmark.pause();
+                } else {
+                    self.benchmark.resume();
+                }
+                println!("{} simulation", if self.paused { "Paused" } else { "Resumed" });
+            }
+            Action::Step => self.step_requested |= self.paused,
+            Action::FastForward => {
+                if self.fast_forward.take().is_some() {
+                    println!("Stopped fast-forwarding");
+                } else if self.config.fast_forward_steps > 0 {
+                    self.fast_forward = Some(FastForward::new(self.config.fast_forward_steps));
+                    println!("Fast-forwarding {} updates", self.config.fast_forward_steps);
+                }
+            }
+            Action::CycleAlgorithm => match self.world_type {
+                WorldType::Threads => self.change_world_algorithm(WorldType::Rayon),
+                WorldType::Rayon => self.change_world_algorithm(WorldType::Sequential),
+                WorldType::Sequential => self.change_world_algorithm(WorldType::Threads),
+            },
+            Action::CycleIntegrator => match self.integrator_type {
+                IntegratorType::Euler => self.change_integrator(IntegratorType::Verlet),
+                IntegratorType::Verlet => self.change_integrator(IntegratorType::Rk4),
+                IntegratorType::Rk4 => self.change_integrator(IntegratorType::Euler),
+            },
+            Action::CycleCollisionMode => self.change_collision_mode(),
+            Action::CycleBoundary => self.change_boundary_mode(),
+            // scale gravity up or down by a power of ten, or flip it between attractive and repulsive
+            Action::ScaleGravity => {
+                let factor = if shift_pressed { 0.1 } else { 10. };
+                self.set_gravity_multiplier(self.physics.gravity_multiplier * factor);
+            }
+            Action::FlipGravity => self.set_gravity_multiplier(-self.physics.gravity_multiplier),
+            // move the particles into their center of mass frame, or keep the camera moving with the center of mass
+            Action::Recenter => self.recenter(),
+            Action::ToggleCenterOfMassFrame => {
+                self.center_of_mass_frame = !self.center_of_mass_frame;
+                println!("Center of mass frame {}", if self.center_of_mass_frame { "enabled" } else { "disabled" });
+            }
+            Action::ToggleOrbitInsertion => {
+                self.spawn_orbiting = !self.spawn_orbiting;
+                println!("Orbit insertion {}", if self.spawn_orbiting { "enabled" } else { "disabled" });
+            }
+            Action::LaunchRubblePile => self.launch_rubble_pile(cursor),
+            Action::SpawnHeavy => {
+                let id = self.world.create_particle(cursor, Vector::ZERO, 1.0e12, false);
+                self.undo_history.spawned(id);
+            }
+            // undo and redo spawning particles
+            Action::Undo if control_pressed => match self.undo_history.undo(self.world.as_mut()) {
+                Some(id) => println!("Undid spawning particle {}", id),
+                None => println!("Nothing to undo"),
+            },
+            Action::Redo if control_pressed => match self.undo_history.redo(self.world.as_mut()) {
+                Some(id) => println!("Redid spawning particle {}", id),
+                None => println!("Nothing to redo"),
+            },
+            Action::RemoveAll => {
+                self.world.clear();
+                self.clear_springs();
+                self.names.clear();
+                self.sprite_kinds.clear();
+                self.trails.clear();
+                self.interpolation.clear();
+                self.undo_history.clear();
+                println!("Removed every particle");
+            }
+            Action::Deselect => {
+                self.selected_id = None;
+                self.pinned_id = None;
+            }
+            Action::NudgeUp => self.nudge_selected(0., -1.),
+            Action::NudgeDown => self.nudge_selected(0., 1.),
+            Action::NudgeLeft => self.nudge_selected(-1., 0.),
+            Action::NudgeRight => self.nudge_selected(1., 0.),
+            Action::CenterCamera => self.center_camera(window),
+            Action::ToggleMeasurement => {
+                if self.measurement.is_active() {
+                    self.measurement.dismiss();
+                } else {
+                    self.measurement.start();
+                    println!("Click two points or particles to measure the distance between them");
+                }
+            }
+            Action::ToggleOrbit => {
+                self.orbit_enabled = !self.orbit_enabled;
+                if self.orbit_enabled && self.selected_id.is_none() {
+                    println!("Click a particle to show its orbit around the particle pulling hardest on it");
+                }
+            }
+            Action::ToggleLagrangePoints => {
+                self.lagrange_enabled = !self.lagrange_enabled;
+                if self.lagrange_enabled && self.lagrange_pair().is_none() {
+                    println!("Click two particles to show the Lagrange points of the lighter one orbiting the heavier one");
+                }
+            }
+            Action::ReloadSprites => self.sprite_reload_requested = true,
+            Action::ToggleFullscreen => self.fullscreen_requested = true,
+            Action::SlowDown => self.ramp_time_scale(self.time_scale_ramp.target(self.time_scale) / 2.),
+            Action::SpeedUp => self.ramp_time_scale(self.time_scale_ramp.target(self.time_scale) * 2.),
+            Action::SlowMotion => self.ramp_time_scale(self.default_time_scale * SLOW_MOTION),
+            Action::DefaultSpeed => self.ramp_time_scale(self.default_time_scale),
+            Action::ToggleAxes => self.axes_enabled = !self.axes_enabled,
+            Action::SaveEventLog => self.save_event_log(),
+            Action::SplitSelected => self.split_selected(),
+            Action::ToggleShip => self.toggle_ship(),
+            Action::ToggleSizeByMass => {
+                self.size_by_mass = !self.size_by_mass;
+                println!("Changed particle sizes to {}", if self.size_by_mass { "depend on mass" } else { "be uniform" });
+            }
+            Action::CycleColorMode => {
+                self.color_mode = self.color_mode.next();
+                println!("Changed color mode to {:?}", self.color_mode);
+            }
+            Action::CycleBlendMode => {
+                self.blend_mode = self.blend_mode.next();
+                println!("Changed blend mode to {:?}", self.blend_mode);
+            }
+            Action::CycleHeatmap => {
+                self.heatmap_mode = self.heatmap_mode.next();
+                println!("Changed heatmap to {:?}", self.heatmap_mode);
+            }
+            Action::ToggleMinimap => self.minimap_enabled = !self.minimap_enabled,
+            Action::ToggleTrails => {
+                self.trails_enabled = !self.trails_enabled;
+                self.trails.clear();
+                println!("{} trails", if self.trails_enabled { "Enabled" } else { "Disabled" });
+            }
+            Action::TogglePerformanceOverlay => self.stats.toggle(),
+            Action::Screenshot => self.screenshot_requested = true,
+            Action::ToggleBenchmark => self.toggle_benchmark(),
+            Action::ToggleTrajectoryLog => self.toggle_trajectory_log(),
+            Action::Save => self.save_simulation(),
+            Action::Load => self.load_simulation(),
+            Action::LoadScenario => self.load_scenario_file(),
+            Action::ToggleRecording => self.toggle_recording(),
+            Action::TogglePlayback => self.toggle_playback(),
+            Action::SaveRecording => self.save_recording(),
+            Action::LoadRecording => self.load_recording(),
+            Action::PresetSolarSystem => self.load_preset(presets::solar_system(), presets::solar_system_names(), None, window),
+            Action::PresetEphemeris => self.load_ephemeris_preset(window),
+            Action::PresetGalaxy => {
+                let galaxy = presets::galaxy(self.config.galaxy_particles, self.config.galaxy_radius, self.config.galaxy_mass, self.config.preset_seed);
+                self.load_preset(galaxy, ParticleNames::new(), Some(SpriteKind::Star), window);
+            }
+            Action::PresetGalaxyCollision => {
+                let galaxies = presets::galaxy_collision(self.config.galaxy_particles, self.config.galaxy_radius, self.config.galaxy_mass, self.config.preset_seed);
+                self.load_preset(galaxies, ParticleNames::new(), Some(SpriteKind::Star), window);
+            }
+            Action::PresetFigureEight => self.load_preset(presets::figure_eight(), ParticleNames::new(), Some(SpriteKind::Star), window),
+            Action::PresetLagrangeTriangle => self.load_preset(presets::lagrange_triangle(), ParticleNames::new(), Some(SpriteKind::Star), window),
+            Action::PresetCircumbinary => self.load_preset(presets::circumbinary(), ParticleNames::new(), None, window),
+            // undo and redo need Control held, so they do nothing on their own
+            Action::Undo | Action::Redo => {}
+            Action::Stream | Action::PanUp | Action::PanDown | Action::PanLeft | Action::PanRight | Action::MoveForward | Action::MoveBackward
+            | Action::PlaybackBack | Action::PlaybackForward => {}
+        }
+    }
+
+    /// Splits the selected particle into fragments flying apart, drawn as debris.
+    fn split_selected(&mut self) {
+        let Some(id) = self.selected_id else {
+            println!("Select a particle to split it");
+            return;
+        };
+        let fragments = self.world.split_particle(id, self.split_fragments, self.split_energy);
+        if fragments.is_empty() {
+            println!("Particle {} can't be split", id);
+            return;
+        }
+        println!("Split particle {} into {} fragments", id, fragments.len());
+        self.selected_id = None;
+        self.sprite_kinds.remove(&id);
+        self.sprite_kinds.extend(fragments.iter().map(|&fragment| (fragment, SpriteKind::Debris)));
+    }
+
+    /// Moves the events recorded by the world into the event log.
+    fn collect_events(&mut self) {
+        let events = self.world.drain_events();
+        if events.is_empty() {
+            return;
+        }
+        self.event_log.extend(events);
+        let skipped = self.event_log.len().saturating_sub(EVENT_LINES);
+        self.event_log_text = self.event_log.iter().skip(skipped).map(|event| event.to_string()).collect();
+    }
+
+    fn save_event_log(&mut self) {
+        self.collect_events();
+        match save_event_log(&self.config.event_log_file, &self.event_log) {
+            Ok(()) => println!("Saved {} events to {}", self.event_log.len(), self.config.event_log_file),
+            Err(error) => println!("Failed to save events to {}: {}", self.config.event_log_file, error),
+        }
+    }
+
+    fn save_recording(&mut self) {
+        match save_recording(&self.config.recording_file, &self.recording) {
+            Ok(()) => println!("Saved {} frames to {}", self.recording.len(), self.config.recording_file),
+            Err(error) => println!("Failed to save recording to {}: {}", self.config.recording_file, error),
+        }
+    }
+
+    /// Loads the recording file and starts playing it back.
+    fn load_recording(&mut self) {
+        match load_recording(&self.config.recording_file) {
+            Ok(recording) => {
+                println!("Loaded {} frames from {}", recording.len(), self.config.recording_file);
+                self.recording = recording;
+                self.recording_armed = false;
+                self.playback_frame = (!self.recording.is_empty()).then_some(0);
+            }
+            Err(error) => println!("Failed to load recording from {}: {}", self.config.recording_file, error),
+        }
+    }
+
+    /// Changes the seconds simulated by each update, unless the time step is
+    /// fixed so runs can be repeated exactly.
+    fn set_time_scale(&mut self, time_scale: Scalar) {
+        if !self.config.fixed_timestep {
+            self.time_scale = time_scale;
+            self.time_scale_ramp.cancel();
+        }
+    }
+
+    /// Sets the time scale chosen for the current particles, which the default speed key returns to.
+    fn set_default_time_scale(&mut self, time_scale: Scalar) {
+        self.set_time_scale(time_scale);
+        self.default_time_scale = self.time_scale;
+    }
+
+    /// Changes the time scale to the target over the next few updates.
+    fn ramp_time_scale(&mut self, target: Scalar) {
+        if self.config.fixed_timestep {
+            println!("The time scale is fixed by fixed_timestep");
+            return;
+        }
+        self.time_scale_ramp.start(self.time_scale, target);
+        println!("Changing time scale to {:.3}x", target / self.default_time_scale);
+    }
+
+    /// Reloads the config if the config file has been edited, keeping the current config if the file can't be loaded.
+    fn reload_config(&mut self) {
+        match self.config_watcher.poll() {
+            Some(Ok(config)) => self.apply_config(config),
+            Some(Err(error)) => println!("Kept the current config: {}", error),
+            None => {}
+        }
+    }
+
+    /// Replaces the config, updating the parts of the simulation using the settings that changed.
+    /// Settings only used when the application starts are kept until it is restarted.
+    fn apply_config(&mut self, config: Config) {
+        let changed = self.config.changed_fields(&config);
+        if changed.is_empty() {
+            return;
+        }
+        println!("Reloaded config, changed {}", changed.join(", "));
+        let restart: Vec<&str> = changed.iter().copied().filter(|field| RESTART_FIELDS.contains(field)).collect();
+        if !restart.is_empty() {
+            println!("Restart to apply the changes to {}", restart.join(", "));
+        }
+        self.config = config;
+
+        for field in changed {
+            match field {
+                "softening" => self.physics.softening = self.config.softening,
+                "gravitational_constant" => self.physics.gravitational_constant = self.config.gravitational_constant,
+                "gravity_multiplier" => self.physics.gravity_multiplier = self.config.gravity_multiplier,
+                "density" => self.physics.density = self.config.density,
+                "collision_mode" => self.physics.collision_mode = self.config.collision_mode,
+                "bounce" => self.physics.bounce = self.config.bounce,
+                "boundary" => self.physics.boundary = self.config.boundary,
+                "cell_size" => self.physics.cell_size = self.config.cell_size,
+                "adaptive_timestep" => self.physics.adaptive_timestep = self.config.adaptive_timestep,
+                "max_substeps" => self.physics.max_substeps = self.config.max_substeps,
+                "timestep_accuracy" => self.physics.timestep_accuracy = self.config.timestep_accuracy,
+                "gravity_solver" => self.physics.gravity_solver = self.config.gravity_solver,
+                "barnes_hut_theta" => self.physics.barnes_hut_theta = self.config.barnes_hut_theta,
+                "mesh_cells" => self.physics.mesh_cells = self.config.mesh_cells,
+                "time_scale" | "fixed_timestep" => {
+                    self.time_scale = self.config.time_scale;
+                    self.default_time_scale = self.config.time_scale;
+                    self.time_scale_ramp.cancel();
+                }
+                "drag_coefficient" | "uniform_field" => self.world.set_forces(self.create_forces()),
+                "num_threads" => self.change_num_threads(self.config.num_threads),
+                "stream_rate" => self.spawner.rate = self.config.stream_rate,
+                "stream_speed" => self.spawner.speed = self.config.stream_speed,
+                "stream_spread" => self.spawner.spread = self.config.stream_spread,
+                "rubble_periapsis" => self.rubble_periapsis = self.config.rubble_periapsis,
+                "split_fragments" => self.split_fragments = self.config.split_fragments,
+                "split_energy" => self.split_energy = self.config.split_energy,
+                "rubble_breaking_stretch" => {
+                    self.springs.breaking_stretch = self.config.rubble_breaking_stretch;
+                    self.world.set_forces(self.create_forces());
+                }
+                "center_of_mass_frame" => self.center_of_mass_frame = self.config.center_of_mass_frame,
+                "interpolate_rendering" => self.interpolation.clear(),
+                "sprite_file" | "sprite_width" | "sprite_height" | "blend_alpha" => self.sprite_reload_requested = true,
+                "max_catch_up" => self.pacer.set_max_catch_up(catch_up_duration(self.config.max_catch_up)),
+                "heatmap_cell_size" => self.heatmap.set_cell_size(self.config.render.heatmap_cell_size),
+                "minimap_size" => self.minimap.set_size(self.config.render.minimap_size),
+                "key_bindings" => match KeyBindings::new(&self.config.render.key_bindings) {
+                    Ok(key_bindings) => {
+                        self.bindings_text = key_bindings.lines().collect();
+                        self.key_bindings = key_bindings;
+                    }
+                    Err(error) => println!("Kept the current key bindings: {}", error),
+                },
+                "trail_length" | "trail_interval" | "max_trail_particles" => {
+                    self.trails = Trails::new(self.config.trail_length, self.config.trail_interval, self.config.max_trail_particles);
+                }
+                "diagnostics_file" => {
+                    self.diagnostics_log = self.config.diagnostics_file.as_deref().and_then(|path| {
+                        create_diagnostics_log(path)
+                            .map_err(|error| println!("Failed to create diagnostics file {}: {}", path, error))
+                            .ok()
+                    });
+                }
+                // the other settings are read from the config whenever they are used
+                _ => {}
+            }
+        }
+        self.world.set_physics(self.physics);
+    }
+
+    /// Starts a benchmark of the current algorithm, or cancels the running benchmark.
+    fn toggle_benchmark(&mut self) {
+        if self.benchmark.is_active() {
+            self.benchmark.cancel();
+            println!("Cancelled benchmark");
+            self.print_benchmark();
+        } else {
+            self.benchmark = Benchmark::new(self.config.benchmark_updates);
+            self.benchmark.start();
+            if self.paused {
+                self.benchmark.pause();
+            }
+            let (_, updates) = self.benchmark.progress();
+            println!("Started benchmark of {} updates with the {:?} algorithm and {} particles", updates, self.world_type, self.world.len());
+        }
+    }
+
+    /// Runs the next batch of a fast-forward with a single call to the world, skipping
+    /// the bookkeeping done after each drawn update. The trails are cleared as they
+    /// would jump across the skipped updates.
+    fn fast_forward_batch(&mut self) {
+        let Some(fast_forward) = &mut self.fast_forward else {
+            return;
+        };
+        // the engine of the ship is a force of the world until burn_fuel turns it off, so
+        // a batch ends with the update which burns the last of the fuel
+        let fuel_steps = self.ship.as_ref().and_then(|ship| ship.steps_of_fuel(self.time_scale));
+        let steps = fast_forward.next_batch().min(fuel_steps.unwrap_or(usize::MAX));
+        let start = Instant::now();
+        self.world.advance(steps, self.time_scale);
+        let finished = fast_forward.finish_batch(steps, start.elapsed());
+        self.burn_fuel(steps as Scalar * self.time_scale);
+        self.steps += steps as u64;
+        self.interpolation.clear();
+        self.trails.clear();
+        if finished {
+            self.fast_forward = None;
+            println!("Finished fast-forwarding, {} simulated", format_time(self.world.time()));
+            self.measure_diagnostics();
+        }
+    }
+
+    /// Prints the physics and render times recorded by the benchmark.
+    fn print_benchmark(&self) {
+        println!("Benchmark of the {:?} algorithm with {} particles and {} thread(s):", self.world_type, self.world.len(), self.world.num_threads());
+        for (phase, statistics) in [("Physics", self.benchmark.physics_statistics()), ("Render", self.benchmark.render_statistics())] {
+            match statistics {
+                Some(statistics) => println!("    {}", format_statistics(phase, &statistics)),
+                None => println!("    {}: no samples", phase),
+            }
+        }
+    }
+
+    /// Writes a report of the finished benchmark to the benchmarks directory.
+    fn save_benchmark(&self) {
+        let algorithm = format!("{:?}", self.world_type);
+        let report = self.benchmark.to_report(&algorithm, self.world.len(), self.world.num_threads(), to_f64(self.time_scale));
+        match save_report(REPORT_DIR, &report) {
+            Ok(path) => println!("Saved benchmark report to {}", path.display()),
+            Err(error) => println!("Failed to save benchmark report: {}", error),
+        }
+    }
+
+    /// Saves the particles drawn this frame as a PNG in the screenshot directory. The
+    /// frame can't be read back, so the particles and boundary are drawn again into a
+    /// canvas of the same size with the same camera, without the user interface. The
+    /// image is encoded and saved on another thread so drawing isn't held up.
+    fn save_screenshot(&self, frame: &mut Frame, camera_transform: Transformation, outline: Option<&Mesh>) {
+        let (width, height) = (frame.width() as u16, frame.height() as u16);
+        let gpu = frame.gpu();
+        let mut canvas = match Canvas::new(gpu, width, height) {
+            Ok(canvas) => canvas,
+            Err(error) => {
+                println!("Failed to take screenshot: {}", error);
+                return;
+            }
+        };
+        {
+            let mut target = canvas.as_target(gpu);
+            target.clear(Color::BLACK);
+            let mut camera = target.transform(camera_transform);
+            if let Some(outline) = outline {
+                outline.draw(&mut camera);
+            }
+            if self.heatmap_mode != HeatmapMode::Off {
+                self.heatmap.draw(&mut camera, -ScreenVector::new(self.camera_position.x, self.camera_position.y));
+            }
+            self.batch.draw(&mut camera);
+        }
+        let image = canvas.read_pixels(gpu);
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_millis());
+        let path = Path::new(SCREENSHOT_DIR).join(format!("screenshot-{}.png", timestamp));
+        std::thread::spawn(move || {
+            let result = fs::create_dir_all(SCREENSHOT_DIR).map_err(|error| error.to_string())
+                .and_then(|()| image.save(&path).map_err(|error| error.to_string()));
+            match result {
+                Ok(()) => println!("Saved screenshot to {}", path.display()),
+                Err(error) => println!("Failed to save screenshot to {}: {}", path.display(), error),
+            }
+        });
+    }
+
+    fn create_integrator(&self) -> Box<dyn Integrator> {
+        match self.integrator_type {
+            IntegratorType::Euler => Box::new(EulerIntegrator),
+            IntegratorType::Verlet => Box::new(VerletIntegrator),
+            IntegratorType::Rk4 => Box::new(Rk4Integrator),
+        }
+    }
+}
+
+impl Game for Application {
+    type Input = KeyboardAndMouse; // No input data
+    type LoadingScreen = (); // No loading screen
+    const TICKS_PER_SECOND: u16 = 60;
+
+    fn load(window: &Window) -> Task<Application> {
+        let config = Config::load().unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        });
+        let key_bindings = KeyBindings::new(&config.render.key_bindings).unwrap_or_else(|error| {
+            eprintln!("Invalid value for setting 'key_bindings': {}", error);
+            std::process::exit(1);
+        });
+        let bindings_text = key_bindings.lines().collect();
+        let physics = config.physics();
+        let pool = WorkerPool::new(config.num_threads);
+        let window_size = (window.width(), window.height());
+
+        let sheet = sprites::load_sheet(&config.render);
+        let palette = heatmap::palette(&config.render.heatmap_colors);
+        let images = Task::using_gpu(move |gpu| Ok((sheet.run(gpu)?, Image::from_colors(gpu, &palette)?, Font::from_bytes(gpu, labels::FONT)?)));
+        Task::stage("Loading sprites...", images).map(move |(sprite, palette, font)| {
+            let mut application = Application {
+                world: Box::new(ThreadsWorld::with_pool(pool.clone(), Vec::new(), Box::new(VerletIntegrator), physics)),
+                world_type: WorldType::Threads,
+                integrator_type: IntegratorType::Verlet,
+                camera_position: Point::new(window_size.0 / 2., window_size.1 / 2.),
+                #[cfg(feature = "three-dimensions")]
+                camera_depth: 0.,
+                scale: config.render.world_scale,
+                time_scale: config.time_scale,
+                default_time_scale: config.time_scale,
+                time_scale_ramp: TimeScaleRamp::default(),
+                drag_position: None,
+                left_button: LeftButton::Released,
+                selected_id: None,
+                center_of_mass_frame: config.center_of_mass_frame,
+                frame_center: None,
+                hovered_id: None,
+                pinned_id: None,
+                measurement: Measurement::default(),
+                selected_pair: [None; 2],
+                lagrange_enabled: false,
+                orbit_enabled: false,
+                cursor_world_position: Vector::ZERO,
+                axes: Axes::new(),
+                axes_enabled: false,
+                size_by_mass: true,
+                color_mode: ColorMode::Uniform,
+                blend_mode: BlendMode::Opaque,
+                heatmap_mode: HeatmapMode::Off,
+                heatmap: Heatmap::new(palette, config.render.heatmap_cell_size),
+                minimap: Minimap::new(config.render.minimap_size),
+                minimap_enabled: false,
+                names: ParticleNames::new(),
+                labels: Labels::new(font),
+                key_bindings,
+                bindings_visible: false,
+                trails: Trails::new(config.trail_length, config.trail_interval, config.max_trail_particles),
+                interpolation: Interpolation::default(),
+                stats: PerformanceStats::new(),
+                trails_enabled: false,
+                ship: None,
+                springs: Springs::new(config.rubble_breaking_stretch),
+                rubble_periapsis: config.rubble_periapsis,
+                rubble_periapsis_slider: slider::State::new(),
+                split_fragments: config.split_fragments,
+                split_fragments_slider: slider::State::new(),
+                split_energy: config.split_energy,
+                split_energy_slider: slider::State::new(),
+                spawn_mass: 1.0e2,
+                spawn_velocity: Vector::ZERO,
+                spawn_orbiting: false,
+                spawn_mass_slider: slider::State::new(),
+                spawn_velocity_x_slider: slider::State::new(),
+                spawn_velocity_y_slider: slider::State::new(),
+                orbit_eccentricity: 0.,
+                orbit_eccentricity_slider: slider::State::new(),
+                diagnostics_text: Vec::new(),
+                event_log: EventLog::default(),
+                event_log_text: Vec::new(),
+                bindings_text,
+                texts: TextCache::default(),
+                diagnostics_baseline: None,
+                diagnostics_log: None,
+                trajectory_log: None,
+                steps: 0,
+                benchmark: Benchmark::new(config.benchmark_updates),
+                recording: Recording::new(config.record_interval, config.max_recorded_frames),
+                recording_armed: false,
+                undo_history: UndoHistory::new(UNDO_LEVELS),
+                spawner: Spawner::new(config.stream_rate, config.stream_speed, config.stream_spread, config.preset_seed),
+                stream_rate_slider: slider::State::new(),
+                stream_speed_slider: slider::State::new(),
+                stream_spread_slider: slider::State::new(),
+                playback_frame: None,
+                paused: false,
+                step_requested: false,
+                pacer: TickPacer::new(Duration::from_secs(1) / Self::TICKS_PER_SECOND as u32, catch_up_duration(config.max_catch_up)),
+                fast_forward: None,
+                screenshot_requested: false,
+                sprite_reload_requested: false,
+                fullscreen_requested: false,
+                window_size,
+                sprite_kinds: HashMap::new(),
+                batch: Batch::new(sprite),
+                sprites: Vec::new(),
+                culled_sprites: 0,
+                physics,
+                drag_enabled: false,
+                uniform_field_enabled: false,
+                softening_slider: slider::State::new(),
+                theta_slider: slider::State::new(),
+                gravity_solver_button: button::State::new(),
+                gravity_slider: slider::State::new(),
+                time_scale_slider: slider::State::new(),
+                world_scale_slider: slider::State::new(),
+                collision_mode_button: button::State::new(),
+                restitution_slider: slider::State::new(),
+                friction_slider: slider::State::new(),
+                boundary_mode_button: button::State::new(),
+                recenter_button: button::State::new(),
+                pool,
+                threads_slider: slider::State::new(),
+                step_time: None,
+                config,
+                config_watcher: ConfigWatcher::new(CONFIG_FILE),
+            };
+            if let Some(path) = &application.config.diagnostics_file {
+                application.diagnostics_log = create_diagnostics_log(path)
+                    .map_err(|error| println!("Failed to create diagnostics file {}: {}", path, error))
+                    .ok();
+            }
+            if application.config.scenario_file.is_some() {
+                application.load_scenario_file();
+            }
+            application
+        })
+    }
+
+    fn draw(&mut self, frame: &mut Frame, timer: &Timer) {
+        let render_start = Instant::now();
+
+        // Clear the current frame
+        frame.clear(Color::BLACK);
+
+        // particles are drawn the fraction of the way to the next update from their previous positions,
+        // and particles wrapped across a periodic box are drawn where they are instead
+        let alpha = timer.next_tick_proximity().min(1.) as Scalar;
+        let boundary = self.physics.boundary;
+        let period = (boundary.mode == BoundaryMode::Periodic).then_some(boundary.size);
+
+        // follow the selected particle, returning to a free camera if it no longer exists
+        match self.selected_particle() {
+            Some(particle) => {
+                let position = self.interpolation.position(&particle, alpha, period);
+                self.center_camera_on(position, frame.width(), frame.height());
+                self.frame_center = None;
+            }
+            None => {
+                self.selected_id = None;
+                self.follow_center_of_mass(alpha);
+            }
+        }
+
+        // ends of the measurement where they are drawn, particles being drawn between their positions of the last two updates
+        let measure_points: Vec<Point> = self.measurement.ends().iter().filter_map(|&end| {
+            let position = match end {
+                measure::End::Position(position) => position,
+                measure::End::Particle(id) => self.interpolation.position(&self.find_particle(id)?, alpha, period),
+            };
+            Some(Point::new(position.x as f32, position.y as f32) * self.scale)
+        }).collect();
+        let measure_distance = self.measured_ends().map(|[(from, _), (to, _)]| format_distance(to_f64(from.distance(to))));
+        // Lagrange points of the selected pair of particles, moving with the particles as they are drawn
+        let lagrange_markers = self.lagrange_pair().filter(|_| self.lagrange_enabled).and_then(|(primary, secondary)| {
+            let (from, to) = (self.interpolation.position(&primary, alpha, period), self.interpolation.position(&secondary, alpha, period));
+            lagrange_points(from, primary.mass, to, secondary.mass, secondary.velocity - primary.velocity)
+        }).map(|points| points.map(|position| Point::new(position.x as f32, position.y as f32) * self.scale));
+        // the orbit is drawn around the central particle where it is drawn, escape trajectories aren't drawn
+        let orbit_path = self.selected_orbit().filter(|_| self.orbit_enabled).map(|(central, orbit)| {
+            let center = self.interpolation.position(&central, alpha, period);
+            orbit.points(ORBIT_POINTS).into_iter().map(|point| {
+                let position = center + vector::from_f64(point);
+                Point::new(position.x as f32, position.y as f32) * self.scale
+            }).collect::<Vec<Point>>()
+        }).filter(|points| !points.is_empty());
+
+        // the camera is one screen width away from the plane drawn at the normal size
+        let (view_distance, camera_depth) = ((frame.width() / self.scale) as Scalar, self.camera_depth());
+        let (screen_width, screen_height) = (frame.width(), frame.height());
+
+        // update camera position
+        let mut target = frame.as_target();
+        let camera_transform = Transformation::translate(ScreenVector::new(self.camera_position.x, self.camera_position.y));
+        let mut camera = target.transform(camera_transform);
+
+        // outline the box the particles are kept inside of
+        let outline = (boundary.mode != BoundaryMode::None).then(|| {
+            let (min, size) = (boundary.min(), boundary.size);
+            let outline = Rectangle {
+                x: min.x as f32 * self.scale,
+                y: min.y as f32 * self.scale,
+                width: size.x as f32 * self.scale,
+                height: size.y as f32 * self.scale,
+            };
+            let mut mesh = Mesh::new();
+            mesh.stroke(Shape::Rectangle(outline), Color::WHITE, 1.);
+            mesh
+        });
+        if let Some(outline) = &outline {
+            outline.draw(&mut camera);
+        }
+        if self.axes_enabled {
+            self.axes.draw(&mut camera, self.camera_position, self.scale, screen_width, screen_height);
+        }
+
+        // find the range of the colored property, ignoring values of 0 which can't be placed on a logarithmic scale
+        let particles = self.world.particles();
+        let color_mode = self.color_mode;
+        let (min, max) = particles
+            .par_iter()
+            .filter_map(|particle| color_mode.value(particle))
+            .filter(|&value| value > 0.)
+            .fold(|| (Scalar::INFINITY, 0.), |(min, max), value| (value.min(min), value.max(max)))
+            .reduce(|| (Scalar::INFINITY, 0.), |a, b| (a.0.min(b.0), a.1.max(b.1)));
+        // additive blending makes the sprites of heavier particles more opaque, on a logarithmic scale of the masses
+        let masses = match self.blend_mode {
+            BlendMode::Additive => mass_range(particles.par_iter().map(|particle| particle.mass)),
+            _ => (Scalar::INFINITY, 0.),
+        };
+
+        // count the particles in each cell of the heatmap, which is drawn under the sprites
+        let camera_offset = ScreenVector::new(self.camera_position.x, self.camera_position.y);
+        if self.heatmap_mode != HeatmapMode::Off {
+            let scale = self.scale;
+            let on_screen = |position: Vector| {
+                depth_scale(position, camera_depth, view_distance).map(|_| Point::new(position.x as f32, position.y as f32) * scale + camera_offset)
+            };
+            match self.playback_frame.and_then(|frame| self.recording.frame(frame)) {
+                Some(snapshot) => self.heatmap.update(snapshot.particles.par_iter().filter_map(|particle| on_screen(particle.position)), screen_width, screen_height),
+                None => self.heatmap.update(
+                    particles.par_iter().filter_map(|particle| on_screen(self.interpolation.position(particle, alpha, period))),
+                    screen_width,
+                    screen_height,
+                ),
+            }
+            self.heatmap.draw(&mut camera, -camera_offset);
+        }
+        let draw_particles = self.heatmap_mode != HeatmapMode::Only;
+        // the area of the world the camera sees, outlined on the minimap
+        let viewport = Rectangle {
+            x: -self.camera_position.x / self.scale,
+            y: -self.camera_position.y / self.scale,
+            width: screen_width / self.scale,
+            height: screen_height / self.scale,
+        };
+
+        // generate trails to draw, shrinking towards their oldest positions
+        self.batch.clear();
+        if self.trails_enabled {
+            let trail_length = self.trails.length() as f32;
+            for trail in self.trails.iter() {
+                for (age, position) in trail.iter().rev().enumerate() {
+                    let Some(depth_scale) = depth_scale(*position, camera_depth, view_distance) else {
+                        continue;
+                    };
+                    let trail_scale = self.config.render.sprite_scale / 2. * (1. - age as f32 / trail_length) * depth_scale;
+                    self.batch.add(Sprite {
+                        source: sprite_rectangle(self.config.render.sprite_source),
+                        position: Point::new(position.x as f32, position.y as f32) * self.scale
+                            - ScreenVector::new(self.config.render.sprite_width, self.config.render.sprite_height) * trail_scale / 2.,
+                        scale: (trail_scale, trail_scale),
+                    });
+                }
+            }
+        }
+
+        // draw the particle being spawned and a line to the cursor showing its velocity
+        if let LeftButton::Spawning { anchor, cursor } = self.left_button {
+            let spawn_scale = if self.size_by_mass { self.config.render.sprite_scale_of(self.spawn_mass) } else { self.config.render.sprite_scale };
+            let line_scale = self.config.render.min_sprite_scale;
+            let spawn_sprites = std::iter::once((anchor, spawn_scale)).chain(
+                (1..=SPAWN_LINE_SPRITES).map(|i| (anchor.lerp(cursor, i as Scalar / SPAWN_LINE_SPRITES as Scalar), line_scale)),
+            );
+            for (position, sprite_scale) in spawn_sprites {
+                self.batch.add(Sprite {
+                    source: sprite_rectangle(self.config.render.sprite_source),
+                    position: Point::new(position.x as f32, position.y as f32) * self.scale
+                        - ScreenVector::new(self.config.render.sprite_width, self.config.render.sprite_height) * sprite_scale / 2.,
+                    scale: (sprite_scale, sprite_scale),
+                });
+            }
+        }
+
+        // named particles in front of the camera and on screen are labeled, at their positions in the space of the camera
+        let highlighted = [self.selected_id, self.pinned_id, self.hovered_id];
+        let scale = self.scale;
+        let on_screen = |position: Vector| {
+            depth_scale(position, camera_depth, view_distance)?;
+            let point = Point::new(position.x as f32, position.y as f32) * scale;
+            let screen = point + camera_offset;
+            let visible = screen.x >= -CULL_MARGIN && screen.y >= -CULL_MARGIN && screen.x <= screen_width + CULL_MARGIN && screen.y <= screen_height + CULL_MARGIN;
+            visible.then_some(point)
+        };
+
+        // draw the recorded frame instead of the particles while playing back the recording
+        if let Some(snapshot) = self.playback_frame.and_then(|frame| self.recording.frame(frame)) {
+            let masses = match self.blend_mode {
+                BlendMode::Additive => mass_range(snapshot.particles.par_iter().map(|particle| particle.mass)),
+                _ => masses,
+            };
+            let sprites = snapshot.particles.iter().filter_map(|particle| {
+                let depth_scale = depth_scale(particle.position, camera_depth, view_distance)?;
+                let sprite_scale = depth_scale * if self.size_by_mass { self.config.render.sprite_scale_of(particle.mass) } else { self.config.render.sprite_scale };
+                let source = if particle.fixed { self.config.render.fixed_source } else { kind_source(&self.config.render, &self.sprite_kinds, particle.id, particle.mass) };
+                let (source, blend_scale) = blended_source(self.blend_mode, &self.config.render, source, particle.mass, masses);
+                Some(Sprite {
+                    source,
+                    position: Point::new(particle.position.x as f32, particle.position.y as f32) * self.scale
+                        - ScreenVector::new(self.config.render.sprite_width, self.config.render.sprite_height) * sprite_scale / 2.,
+                    scale: (sprite_scale * blend_scale, sprite_scale * blend_scale),
+                })
+            });
+            if draw_particles {
+                self.batch.extend(sprites);
+            }
+            self.batch.draw(&mut camera);
+            if !self.names.is_empty() {
+                let labels = labels_on_screen(&self.names, snapshot.particles.iter().map(|particle| (particle.id, particle.position)), highlighted, &on_screen);
+                self.labels.draw(&mut camera, &labels, self.config.render.label_spacing);
+            }
+            self.measurement.draw(&mut camera, &mut self.labels, &measure_points, measure_distance.as_deref());
+            if self.minimap_enabled {
+                self.minimap.draw(&mut camera, camera_offset, &snapshot.particles, |particle| particle.position, viewport, screen_width);
+            }
+            if std::mem::replace(&mut self.screenshot_requested, false) {
+                self.save_screenshot(frame, camera_transform, outline.as_ref());
+            }
+            return;
+        }
+
+        // generate particles to draw, leaving out particles behind the camera or off screen
+        self.sprites.clear();
+        self.sprites.par_extend(particles.par_iter().filter(|_| draw_particles).filter_map(|particle| {
+            let position = self.interpolation.position(particle, alpha, period);
+            let depth_scale = depth_scale(position, camera_depth, view_distance)?;
+            let (sprite_scale, offset) = if self.size_by_mass {
+                let sprite_scale = self.config.render.sprite_scale_of(particle.mass);
+                (sprite_scale, ScreenVector::new(self.config.render.sprite_width, self.config.render.sprite_height) * sprite_scale / 2.)
+            } else {
+                (self.config.render.sprite_scale, ScreenVector::new(self.config.render.horizontal_offset, self.config.render.vertical_offset))
+            };
+            let source = match color_mode.value(particle) {
+                _ if particle.fixed => self.config.render.fixed_source,
+                Some(value) => self.config.render.band_sources[color_band(value, min, max, self.config.render.band_sources.len())],
+                None => kind_source(&self.config.render, &self.sprite_kinds, particle.id, particle.mass),
+            };
+            let corner = Point::new(position.x as f32, position.y as f32) * self.scale - offset * depth_scale;
+            let size = ScreenVector::new(self.config.render.sprite_width, self.config.render.sprite_height) * sprite_scale * depth_scale;
+            let screen = corner + camera_offset;
+            if screen.x + size.x < -CULL_MARGIN || screen.y + size.y < -CULL_MARGIN || screen.x > screen_width + CULL_MARGIN || screen.y > screen_height + CULL_MARGIN {
+                return None;
+            }
+            let (source, blend_scale) = blended_source(self.blend_mode, &self.config.render, source, particle.mass, masses);
+            let sprite = Sprite {
+                source,
+                position: corner,
+                scale: (sprite_scale * depth_scale * blend_scale, sprite_scale * depth_scale * blend_scale),
+            };
+            Some((depth(position), sprite))
+        }));
+        self.culled_sprites = if draw_particles { particles.len() - self.sprites.len() } else { 0 };
+        // particles further from the camera are drawn first so closer particles cover them
+        #[cfg(feature = "three-dimensions")]
+        self.sprites.par_sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        // render screen, with the particles on top of the trails
+        self.batch.par_extend(self.sprites.par_drain(..).map(|(_, sprite)| sprite));
+        self.batch.draw(&mut camera);
+        if !self.names.is_empty() {
+            let positions = particles.iter().map(|particle| (particle.id, self.interpolation.position(particle, alpha, period)));
+            let labels = labels_on_screen(&self.names, positions, highlighted, &on_screen);
+            self.labels.draw(&mut camera, &labels, self.config.render.label_spacing);
+        }
+        self.measurement.draw(&mut camera, &mut self.labels, &measure_points, measure_distance.as_deref());
+        if let Some(points) = &lagrange_markers {
+            lagrange::draw(&mut camera, &mut self.labels, points);
+        }
+        if let Some(points) = orbit_path {
+            let mut mesh = Mesh::new();
+            mesh.stroke(Shape::Polyline { points }, ORBIT_COLOR, 1.);
+            mesh.draw(&mut camera);
+        }
+        if self.minimap_enabled {
+            self.minimap.draw(&mut camera, camera_offset, &particles, |particle| particle.position, viewport, screen_width);
+        }
+        let render_time = render_start.elapsed();
+        self.benchmark.record_render(render_time);
+        self.stats.record_frame(render_start, render_time);
+        if std::mem::replace(&mut self.screenshot_requested, false) {
+            self.save_screenshot(frame, camera_transform, outline.as_ref());
+        }
+    }
+
+    fn update(&mut self, _window: &Window) {
+        self.reload_config();
+        // events of the last update and of particles spawned or removed since
+        self.collect_events();
+        // after a slow load or a stall, only catch up on the most recent updates rather than all of them at once
+        if !self.pacer.tick(Instant::now()) {
+            return;
+        }
+        // the physics isn't run while playing back a recording
+        if self.playback_frame.is_some() {
+            self.interpolation.clear();
+            return;
+        }
+        if self.fast_forward.is_some() {
+            self.fast_forward_batch();
+            return;
+        }
+        if self.paused && !std::mem::replace(&mut self.step_requested, false) {
+            self.interpolation.clear();
+            return;
+        }
+        if self.config.interpolate_rendering {
+            let particles = self.world.particles();
+            self.interpolation.capture(&particles, center_of_mass(&particles));
+        }
+        if let Some(time_scale) = self.time_scale_ramp.next() {
+            self.time_scale = time_scale;
+        }
+        let physics_start = Instant::now();
+        self.world.update(self.time_scale);
+        let physics_time = physics_start.elapsed();
+        self.burn_fuel(self.time_scale);
+        self.step_time = Some(physics_time);
+        self.stats.record_tick(physics_start, physics_time);
+        self.stats.record_pool(self.pool.stats());
+        if self.benchmark.record_physics(physics_time) {
+            self.print_benchmark();
+            self.save_benchmark();
+        }
+        self.steps += 1;
+        if self.steps.is_multiple_of(self.config.diagnostics_interval.max(1) as u64) {
+            self.measure_diagnostics();
+        }
+        if self.trails_enabled {
+            self.trails.record(&self.world.particles());
+        }
+        if let Some(logger) = &mut self.trajectory_log {
+            logger.record(self.steps, &self.world.particles());
+        }
+        if self.recording_armed {
+            let world = &self.world;
+            self.recording.record(self.steps, || world.snapshot());
+        }
+    }
+
+    fn interact(&mut self, input: &mut Self::Input, window: &mut Window) {
+        // keep the point in the center of the view in the center when the window is resized or made fullscreen
+        let window_size = (window.width(), window.height());
+        if window_size != self.window_size {
+            self.camera_position += ScreenVector::new(window_size.0 - self.window_size.0, window_size.1 - self.window_size.1) / 2.;
+            self.window_size = window_size;
+        }
+
+        // calculate world position from screen positions
+        let cursor_position = input.mouse().cursor_position();
+        let x_position = ((cursor_position.x - self.camera_position.x) / self.scale) as Scalar;
+        let y_position = ((cursor_position.y - self.camera_position.y) / self.scale) as Scalar;
+
+        // zoom, moving the camera so the position under the cursor stays in place
+        let wheel_movement = input.mouse().wheel_movement().vertical;
+        if wheel_movement != 0. {
+            self.zoom_to(self.scale * ZOOM_FACTOR.powf(wheel_movement), cursor_position);
+        }
+
+        // drag the camera with the middle mouse button
+        if input.mouse().is_button_pressed(mouse::Button::Middle) {
+            if let Some(drag_position) = self.drag_position {
+                self.camera_position += cursor_position - drag_position;
+            }
+            self.drag_position = Some(cursor_position);
+        } else {
+            self.drag_position = None;
+        }
+
+        // do the actions of the keys released since the last interaction
+        let keyboard = input.keyboard();
+        let shift_pressed = keyboard.is_key_pressed(keyboard::KeyCode::LShift) || keyboard.is_key_pressed(keyboard::KeyCode::RShift);
+        let control_pressed = keyboard.is_key_pressed(keyboard::KeyCode::LControl) || keyboard.is_key_pressed(keyboard::KeyCode::RControl);
+        let position = self.world_position(x_position, y_position);
+        self.cursor_world_position = position;
+        for action in self.key_bindings.released(keyboard) {
+            self.perform(action, position, shift_pressed, control_pressed, window);
+        }
+
+        if std::mem::replace(&mut self.sprite_reload_requested, false) {
+            self.reload_sprites(window);
+        }
+        if std::mem::replace(&mut self.fullscreen_requested, false) {
+            window.toggle_fullscreen();
+        }
+
+        // remove the particle under the cursor
+        for &click in input.mouse().button_clicks(mouse::Button::Right) {
+            let position = (click - self.camera_position) / self.scale;
+            if let Some(id) = self.particle_at(self.world_position(position.x as Scalar, position.y as Scalar)) {
+                self.world.remove_particle(id);
+                println!("Removed particle {}", id);
+            }
+        }
+
+        // scrub through the frames of the recording while it plays back
+        if let Some(frame) = self.playback_frame {
+            if self.key_bindings.is_pressed(input.keyboard(), Action::PlaybackBack) {
+                self.playback_frame = Some(frame.saturating_sub(1));
+            }
+            if self.key_bindings.is_pressed(input.keyboard(), Action::PlaybackForward) {
+                self.playback_frame = Some((frame + 1).min(self.recording.len() - 1));
+            }
+        }
+
+        // select the particle under the cursor when the left mouse button is pressed, otherwise
+        // create a particle when it is released with a velocity from how far the mouse was dragged
+        self.hovered_id = self.particle_at(position);
+        if let Some(id) = self.pinned_id {
+            // unpin particles which were removed or merged into another particle
+            if !self.world.particles().iter().any(|particle| particle.id == id) {
+                self.pinned_id = None;
+            }
+        }
+        let world = &self.world;
+        self.measurement.retain_particles(|id| world.particles().iter().any(|particle| particle.id == id));
+        let left_pressed = input.mouse().is_button_pressed(mouse::Button::Left);
+        match self.left_button {
+            // pin the inspector to the hovered particle, or unpin it when nothing is hovered
+            LeftButton::Released if left_pressed && control_pressed => {
+                self.pinned_id = self.hovered_id;
+                self.left_button = LeftButton::Selecting;
+            }
+            // move the camera to the position clicked on the minimap, letting go of the followed particle
+            LeftButton::Released if left_pressed && self.minimap_enabled && self.minimap.area(window.width()).contains(cursor_position) => {
+                if let Some(target) = self.minimap.world_position(cursor_position, window.width()) {
+                    self.selected_id = None;
+                    self.center_camera_on(vector::planar(target.x as Scalar, target.y as Scalar), window.width(), window.height());
+                }
+                self.left_button = LeftButton::Selecting;
+            }
+            // pick an end of the measurement, the hovered particle or else the point under the cursor
+            LeftButton::Released if left_pressed && self.measurement.is_picking() => {
+                self.measurement.pick(match self.hovered_id {
+                    Some(id) => measure::End::Particle(id),
+                    None => measure::End::Position(position),
+                });
+                self.left_button = LeftButton::Selecting;
+            }
+            LeftButton::Released if left_pressed && self.drag_position.is_none() => {
+                self.left_button = match self.hovered_id {
+                    Some(id) => {
+                        println!("Selected particle {}", id);
+                        self.selected_id = Some(id);
+                        if self.selected_pair[1] != Some(id) {
+                            self.selected_pair = [self.selected_pair[1], Some(id)];
+                        }
+                        LeftButton::Selecting
+                    }
+                    None => LeftButton::Spawning { anchor: position, cursor: position },
+                };
+            }
+            LeftButton::Spawning { anchor, .. } if left_pressed => {
+                self.left_button = LeftButton::Spawning { anchor, cursor: position };
+            }
+            LeftButton::Spawning { anchor, .. } => {
+                let keyboard = input.keyboard();
+                let fixed = keyboard.is_key_pressed(keyboard::KeyCode::LShift) || keyboard.is_key_pressed(keyboard::KeyCode::RShift);
+                let retrograde = keyboard.is_key_pressed(keyboard::KeyCode::LAlt) || keyboard.is_key_pressed(keyboard::KeyCode::RAlt);
+                self.spawn_particle(anchor, (position - anchor) * self.config.drag_velocity_scale, fixed, retrograde);
+                self.left_button = LeftButton::Released;
+            }
+            _ if !left_pressed => self.left_button = LeftButton::Released,
+            _ => (),
+        }
+
+        // emit a stream of particles from the cursor while the stream key is held
+        if self.key_bindings.is_pressed(input.keyboard(), Action::Stream) {
+            for (position, velocity) in self.spawner.emit(position, Instant::now()) {
+                let id = self.world.create_particle(position, velocity, self.spawn_mass, false);
+                self.undo_history.spawned(id);
+            }
+        } else {
+            self.spawner.stop();
+        }
+
+        // thrust the ship along the held arrow keys, in the directions of the screen
+        if self.ship.is_some() {
+            let held = |action| if self.playback_frame.is_none() && self.key_bindings.is_pressed(input.keyboard(), action) { 1. } else { 0. };
+            let direction = vector::planar(held(Action::NudgeRight) - held(Action::NudgeLeft), held(Action::NudgeDown) - held(Action::NudgeUp));
+            let thrust = self.config.ship_thrust;
+            if self.ship.as_mut().is_some_and(|ship| ship.steer(direction, thrust)) {
+                self.world.set_forces(self.create_forces());
+            }
+        }
+
+        // move camera
+        if self.key_bindings.is_pressed(input.keyboard(), Action::PanUp) {
+            self.camera_position.y += PAN_SPEED;
+        }
+        if self.key_bindings.is_pressed(input.keyboard(), Action::PanDown) {
+            self.camera_position.y -= PAN_SPEED;
+        }
+        if self.key_bindings.is_pressed(input.keyboard(), Action::PanLeft) {
+            self.camera_position.x += PAN_SPEED;
+        }
+        if self.key_bindings.is_pressed(input.keyboard(), Action::PanRight) {
+            self.camera_position.x -= PAN_SPEED;
+        }
+        #[cfg(feature = "three-dimensions")]
+        {
+            let view_distance = (window.width() / self.scale) as Scalar;
+            if self.key_bindings.is_pressed(input.keyboard(), Action::MoveForward) {
+                self.camera_depth += DEPTH_SPEED * view_distance;
+            }
+            if self.key_bindings.is_pressed(input.keyboard(), Action::MoveBackward) {
+                self.camera_depth -= DEPTH_SPEED * view_distance;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Message {
+    /// Seconds simulated each real second
+    TimeScaleChanged(Scalar),
+    /// Meters per pixel
+    WorldScaleChanged(f32),
+    /// Kilograms
+    SpawnMassChanged(Scalar),
+    /// Meters per second
+    SpawnVelocityXChanged(Scalar),
+    SpawnVelocityYChanged(Scalar),
+    SpawnOrbitingToggled(bool),
+    OrbitEccentricityChanged(Scalar),
+    /// Roche limits
+    RubblePeriapsisChanged(Scalar),
+    SplitFragmentsChanged(usize),
+    /// Joules
+    SplitEnergyChanged(Scalar),
+    /// Particles per second
+    StreamRateChanged(Scalar),
+    /// Meters per second
+    StreamSpeedChanged(Scalar),
+    /// Degrees
+    StreamSpreadChanged(Scalar),
+    AdaptiveTimestepToggled(bool),
+    SofteningChanged(f32),
+    ThetaChanged(f32),
+    /// Size of the gravity multiplier, keeping its sign
+    GravityMultiplierChanged(Scalar),
+    RepulsiveGravityToggled(bool),
+    DragToggled(bool),
+    UniformFieldToggled(bool),
+    CycleGravitySolver,
+    CycleCollisionMode,
+    /// Fraction of the speed kept by bouncing particles
+    RestitutionChanged(Scalar),
+    /// Coefficient of friction between bouncing particles
+    FrictionChanged(Scalar),
+    CycleBoundaryMode,
+    Recenter,
+    CenterOfMassFrameToggled(bool),
+    AlgorithmSelected(WorldType),
+    ThreadsChanged(usize),
+}
+
+impl UserInterface for Application {
+    type Message = Message;
+
+    type Renderer = Renderer;
+
+    fn react(&mut self, message: Self::Message, window: &mut Window) {
+        match message {
+            Message::TimeScaleChanged(time_scale) => self.set_time_scale(time_scale / Self::TICKS_PER_SECOND as Scalar),
+            Message::WorldScaleChanged(world_scale) => {
+                // zoom around the middle of the window
+                self.zoom_to(1. / world_scale, Point::new(window.width() / 2., window.height() / 2.));
+            }
+            Message::SofteningChanged(softening) => {
+                self.physics.softening = softening as Scalar;
+                self.world.set_physics(self.physics);
+            }
+            Message::ThetaChanged(theta) => {
+                self.physics.barnes_hut_theta = theta as Scalar;
+                self.world.set_physics(self.physics);
+            }
+            Message::GravityMultiplierChanged(multiplier) => {
+                self.set_gravity_multiplier(multiplier.copysign(self.physics.gravity_multiplier));
+            }
+            Message::RepulsiveGravityToggled(repulsive) => {
+                self.set_gravity_multiplier(if repulsive { -1. } else { 1. } * self.physics.gravity_multiplier.abs());
+            }
+            Message::DragToggled(enabled) => {
+                self.drag_enabled = enabled;
+                self.world.set_forces(self.create_forces());
+            }
+            Message::UniformFieldToggled(enabled) => {
+                self.uniform_field_enabled = enabled;
+                self.world.set_forces(self.create_forces());
+            }
+            Message::SpawnMassChanged(mass) => self.spawn_mass = mass,
+            Message::SpawnVelocityXChanged(x) => self.spawn_velocity.x = x,
+            Message::SpawnVelocityYChanged(y) => self.spawn_velocity.y = y,
+            Message::SpawnOrbitingToggled(orbiting) => self.spawn_orbiting = orbiting,
+            Message::OrbitEccentricityChanged(eccentricity) => self.orbit_eccentricity = eccentricity,
+            Message::RubblePeriapsisChanged(periapsis) => self.rubble_periapsis = periapsis,
+            Message::SplitFragmentsChanged(fragments) => self.split_fragments = fragments,
+            Message::SplitEnergyChanged(energy) => self.split_energy = energy,
+            Message::StreamRateChanged(rate) => self.spawner.rate = rate,
+            Message::StreamSpeedChanged(speed) => self.spawner.speed = speed,
+            Message::StreamSpreadChanged(spread) => self.spawner.spread = spread,
+            Message::AdaptiveTimestepToggled(adaptive_timestep) => {
+                self.physics.adaptive_timestep = adaptive_timestep;
+                self.world.set_physics(self.physics);
+            }
+            Message::CycleGravitySolver => self.change_gravity_solver(),
+            Message::CycleCollisionMode => self.change_collision_mode(),
+            Message::RestitutionChanged(restitution) => {
+                self.physics.bounce.restitution = restitution;
+                self.world.set_physics(self.physics);
+            }
+            Message::FrictionChanged(friction) => {
+                self.physics.bounce.friction = friction;
+                self.world.set_physics(self.physics);
+            }
+            Message::CycleBoundaryMode => self.change_boundary_mode(),
+            Message::Recenter => self.recenter(),
+            Message::CenterOfMassFrameToggled(enabled) => self.center_of_mass_frame = enabled,
+            Message::AlgorithmSelected(world_type) => self.change_world_algorithm(world_type),
+            Message::ThreadsChanged(num_threads) => {
+                if num_threads != self.world.num_threads() {
+                    self.change_num_threads(num_threads);
+                }
+            }
+        }
+    }
+
+    fn layout(&mut self, window: &Window,) -> Element<'_, Message> {
+        let mut diagnostics_column = Column::new().padding(10);
+        for line in &self.diagnostics_text {
+            diagnostics_column = diagnostics_column.push(Text::new(line));
+        }
+
+        let mut events_column = Column::new().padding(10);
+        for line in &self.event_log_text {
+            events_column = events_column.push(Text::new(line).size(14));
+        }
+
+        let mut selected_column = Column::new().padding(10);
+        if let Some(particle) = self.selected_particle() {
+            selected_column = selected_column
+                .push(Text::new(self.texts.get("selected_id", particle.id, |id| format!("Following particle {}", id))))
+                .push(Text::new(self.texts.get("selected_position", particle.position.to_array(), |position| format!("Position: {} m", format_vector(position)))))
+                .push(Text::new(self.texts.get("selected_velocity", particle.velocity.to_array(), |velocity| format!("Velocity: {} m/s", format_vector(velocity)))))
+                .push(Text::new(self.texts.get("selected_mass", particle.mass, |mass| format!("Mass: {:.3e} kg", mass))));
+        }
+        if let Some((central, orbit)) = self.selected_orbit() {
+            let central = (central.id, self.names.get(&central.id).cloned());
+            selected_column = selected_column.push(Text::new(self.texts.get("orbit_central", central, |(id, name)| format!("Orbiting {}", particle_name(*id, name.as_deref())))));
+            if let (Some(apoapsis), Some(period)) = (orbit.apoapsis, orbit.period) {
+                selected_column = selected_column
+                    .push(Text::new(self.texts.get("orbit_semi_major_axis", orbit.semi_major_axis, |&axis| format!("Semi-major axis: {}", format_distance(axis)))))
+                    .push(Text::new(self.texts.get("orbit_eccentricity", orbit.eccentricity, |eccentricity| format!("Eccentricity: {:.4}", eccentricity))))
+                    .push(Text::new(self.texts.get("orbit_periapsis", orbit.periapsis, |&periapsis| format!("Periapsis: {}", format_distance(periapsis)))))
+                    .push(Text::new(self.texts.get("orbit_apoapsis", apoapsis, |&apoapsis| format!("Apoapsis: {}", format_distance(apoapsis)))))
+                    .push(Text::new(self.texts.get("orbit_period", period, |&period| format!("Period: {}", format_time(period)))));
+            } else {
+                selected_column = selected_column
+                    .push(Text::new(self.texts.get("orbit_escape", orbit.eccentricity, |eccentricity| format!("Escape trajectory (e = {:.4})", eccentricity))))
+                    .push(Text::new(self.texts.get("orbit_periapsis", orbit.periapsis, |&periapsis| format!("Periapsis: {}", format_distance(periapsis)))));
+            }
+        }
+
+        // the inspected particle may have been removed or merged since the last tick, in which case nothing is shown
+        let mut inspector_column = Column::new().padding(10);
+        if let Some(particle) = self.inspected_particle() {
+            let heading = (particle.id, self.names.get(&particle.id).cloned(), self.pinned_id == Some(particle.id));
+            inspector_column = inspector_column
+                .push(Text::new(self.texts.get("inspected_id", heading, |(id, name, pinned)| {
+                    let name = name.as_ref().map_or(String::new(), |name| format!(" ({})", name));
+                    format!("Particle {}{}{}", id, name, if *pinned { " (pinned)" } else { "" })
+                })))
+                .push(Text::new(self.texts.get("inspected_mass", particle.mass, |mass| format!("Mass: {:.3e} kg", mass))))
+                .push(Text::new(self.texts.get("inspected_position", particle.position.to_array(), |position| format!("Position: {} m", format_vector(position)))))
+                .push(Text::new(self.texts.get("inspected_speed", particle.velocity.length(), |&speed| format!("Speed: {} m/s", format_magnitude(to_f64(speed))))))
+                .push(Text::new(self.texts.get("inspected_acceleration", particle.acceleration.length(), |&acceleration| {
+                    format!("Acceleration: {} m/s^2", format_magnitude(to_f64(acceleration)))
+                })));
+        }
+
+        // the relative speed and closing rate are only shown between two particles
+        let mut measure_column = Column::new().padding(10);
+        if self.measurement.is_picking() {
+            let picked = self.measurement.ends().len();
+            measure_column = measure_column.push(Text::new(self.texts.get("measure_picking", picked, |picked| {
+                format!("Measuring: click a point or particle ({}/2)", picked + 1)
+            })));
+        }
+        if let Some([(from, from_velocity), (to, to_velocity)]) = self.measured_ends() {
+            let distance = to_f64(from.distance(to));
+            let speed = to_f64(self.config.measure_speed);
+            measure_column = measure_column
+                .push(Text::new(self.texts.get("measure_distance", distance, |&distance| format!("Distance: {}", format_distance(distance)))))
+                .push(Text::new(self.texts.get("measure_travel_time", (distance, speed), |&(distance, speed)| {
+                    let travel = travel_time(distance, speed).map_or(String::from("-"), format_time);
+                    format!("Travel time at {:.3e} m/s: {}", speed, travel)
+                })));
+            if let (Some(from_velocity), Some(to_velocity)) = (from_velocity, to_velocity) {
+                let relative_speed = relative_speed(from_velocity, to_velocity);
+                let closing_rate = closing_rate(from, from_velocity, to, to_velocity);
+                measure_column = measure_column
+                    .push(Text::new(self.texts.get("measure_relative_speed", relative_speed, |speed| format!("Relative speed: {:.3e} m/s", speed))))
+                    .push(Text::new(self.texts.get("measure_closing_rate", closing_rate, |rate| format!("Closing rate: {:+.3e} m/s", rate))));
+            }
+        }
+
+        if self.lagrange_enabled {
+            let pair = self.lagrange_pair().map(|(primary, secondary)| [primary, secondary].map(|particle| (particle.id, self.names.get(&particle.id).cloned())));
+            let status = self.texts.get("lagrange", pair, |pair| match pair {
+                Some([(primary, primary_name), (secondary, secondary_name)]) => format!(
+                    "Lagrange points of {} orbiting {}",
+                    particle_name(*secondary, secondary_name.as_deref()),
+                    particle_name(*primary, primary_name.as_deref()),
+                ),
+                None => String::from("Lagrange points: click two particles"),
+            });
+            measure_column = measure_column.push(Text::new(status));
+        }
+
+        let mut benchmark_column = Column::new().padding(10);
+        if self.benchmark.is_active() {
+            let progress = (self.benchmark.progress(), self.benchmark.status() == BenchmarkStatus::Paused);
+            benchmark_column = benchmark_column.push(Text::new(self.texts.get("benchmark_progress", progress, |&((recorded, updates), paused)| {
+                format!("Benchmark: {}/{} updates{}", recorded, updates, if paused { " (paused)" } else { "" })
+            })));
+            let phases = [
+                ("benchmark_physics", "Physics", self.benchmark.physics_statistics()),
+                ("benchmark_render", "Render", self.benchmark.render_statistics()),
+            ];
+            for (name, phase, statistics) in phases {
+                if let Some(statistics) = statistics {
+                    benchmark_column = benchmark_column.push(Text::new(self.texts.get(name, statistics, |statistics| format_statistics(phase, statistics))));
+                }
+            }
+        }
+
+        let mut stats_column = Column::new().padding(10);
+        if self.stats.is_visible() {
+            if let Some(fps) = self.stats.fps() {
+                stats_column = stats_column.push(Text::new(self.texts.get("stats_fps", fps, |(fps, average)| format!("FPS: {:.0} (average {:.1})", fps, average))));
+            }
+            if let Some(tick_rate) = self.stats.tick_rate() {
+                stats_column = stats_column.push(Text::new(self.texts.get("stats_tick_rate", tick_rate, |tick_rate| format!("Updates: {:.1} / s", tick_rate))));
+            }
+            for (name, phase, times) in [("stats_physics", "Physics", self.stats.physics_ms()), ("stats_render", "Render", self.stats.render_ms())] {
+                if let Some(times) = times {
+                    stats_column = stats_column.push(Text::new(self.texts.get(name, times, |(time, average)| {
+                        format!("{}: {:.2} ms (average {:.2} ms)", phase, time, average)
+                    })));
+                }
+            }
+            stats_column = stats_column
+                .push(Text::new(self.texts.get("stats_particles", self.world.len(), |count| format!("Particles: {}", count))))
+                .push(Text::new(self.texts.get("stats_culled_sprites", self.culled_sprites, |culled| format!("Culled sprites: {}", culled))))
+                .push(Text::new(self.texts.get("stats_algorithm", self.world_type, |world_type| format!("Algorithm: {:?}", world_type))))
+                .push(Text::new(self.texts.get("stats_threads", self.world.num_threads(), |threads| format!("Threads: {}", threads))));
+            if let Some(utilization) = self.stats.pool_utilization() {
+                stats_column = stats_column.push(Text::new(self.texts.get("stats_pool", (self.pool.num_threads(), utilization), |(threads, (utilization, average))| {
+                    format!("Worker pool: {} threads, {:.0}% busy (average {:.0}%)", threads, utilization * 100., average * 100.)
+                })));
+            }
+        }
+
+        let time_scale = if self.paused { 0. } else { self.time_scale * Self::TICKS_PER_SECOND as Scalar };
+        let mut status_column = Column::new().padding(10);
+        if self.paused {
+            status_column = status_column.push(Text::new("PAUSED").size(40).color(Color::RED));
+        }
+        if self.time_scale_ramp.is_displayed() {
+            let multiplier = self.time_scale_ramp.target(self.time_scale) / self.default_time_scale;
+            status_column = status_column.push(Text::new(self.texts.get("time_scale_multiplier", multiplier, |multiplier| format!("{:.3}x", multiplier))).size(40));
+        }
+        if let Some(ship) = &self.ship {
+            let ship = (ship.id, ship.fuel, ship.force().is_some());
+            status_column = status_column.push(Text::new(self.texts.get("ship", ship, |&(id, fuel, thrusting)| {
+                format!("Ship {}: {} m/s of fuel{}", id, format_magnitude(to_f64(fuel)), if thrusting { " (thrusting)" } else { "" })
+            })));
+        }
+        if let Some(fast_forward) = &self.fast_forward {
+            let progress = fast_forward.progress();
+            status_column = status_column
+                .push(Text::new("FAST-FORWARDING").size(40).color(Color::RED))
+                .push(Text::new(self.texts.get("fast_forward", progress, |&(done, total)| {
+                    format!("Update {}/{} ({:.0}%)", done, total, done as f64 / total as f64 * 100.)
+                })));
+        }
+        if let (Some(frame), Some(snapshot)) = (self.playback_frame, self.playback_snapshot()) {
+            let playback = (frame, self.recording.len(), snapshot.step);
+            status_column = status_column
+                .push(Text::new("PLAYBACK").size(40).color(Color::RED))
+                .push(Text::new(self.texts.get("playback", playback, |(frame, frames, step)| format!("Frame {}/{} (update {})", frame + 1, frames, step))));
+        } else if self.recording_armed {
+            status_column = status_column
+                .push(Text::new("RECORDING").size(40).color(Color::RED))
+                .push(Text::new(self.texts.get("recording", self.recording.len(), |frames| format!("{} frame(s)", frames))));
+        }
+
+        let mut bindings_column = Column::new().padding(10);
+        if self.bindings_visible {
+            for line in &self.bindings_text {
+                bindings_column = bindings_column.push(Text::new(line).size(14));
+            }
+        }
+
+        // the sequential world always runs on one thread, so its thread count is grayed out without a slider
+        let mut algorithm_column = Column::new().padding(10).push(Text::new("Algorithm:"));
+        for world_type in WorldType::ALL {
+            algorithm_column = algorithm_column.push(Radio::new(world_type, world_type.name(), Some(self.world_type), Message::AlgorithmSelected));
+        }
+        let num_threads = self.world.num_threads();
+        let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+        algorithm_column = algorithm_column.push(Text::new(self.texts.get("step_time", self.step_time, |step_time| {
+            let step_time = step_time.map_or(String::from("-"), |time| format!("{:.2} ms", time.as_secs_f64() * 1e3));
+            format!("Step time: {}", step_time)
+        })));
+        algorithm_column = match self.world_type {
+            WorldType::Sequential => algorithm_column.push(Text::new("Number of Threads: 1").color(Color::new(0.5, 0.5, 0.5, 1.))),
+            WorldType::Threads | WorldType::Rayon => algorithm_column
+                .push(Text::new(self.texts.get("num_threads", num_threads, |threads| format!("Number of Threads: {}", threads))))
+                .push(Slider::new(
+                    &mut self.threads_slider,
+                    1.0..=cores.max(num_threads) as f32,
+                    num_threads as f32,
+                    |threads| Message::ThreadsChanged(threads.round() as usize),
+                )),
+        };
+
+        // the cursor is where a click would spawn a particle, with the spacing of the axis ticks while they are drawn
+        let cursor = (self.cursor_world_position.to_array(), self.axes_enabled.then(|| Axes::tick_spacing(self.scale)));
+
+        Row::new()
+            .padding(20)
+            .spacing(20)
+            .width(window.width() as u32)
+            .height(window.height() as u32)
+            .justify_content(Justify::Center)
+            .align_items(Align::End)
+            .push(bindings_column)
+            .push(stats_column)
+            .push(status_column)
+            .push(Column::new()
+                .padding(10)
+                // the scale sliders move through powers of ten
+                .push(Text::new(self.texts.get("scale", self.scale, |scale| format!("Scale: {} meter(s) / pixel", 1. / scale))))
+                .push(Text::new(self.texts.get("cursor", cursor, |(position, tick_spacing)| {
+                    let mut text = format!("Cursor: {} m", format_vector(position));
+                    if let Some(tick_spacing) = tick_spacing {
+                        text += &format!(" (ticks every {})", format_distance(*tick_spacing));
+                    }
+                    text
+                })))
+                .push(Slider::new(
+                    &mut self.world_scale_slider,
+                    0.0..=10.,
+                    -self.scale.log10(),
+                    |exponent| Message::WorldScaleChanged(10f32.powf(exponent)),
+                ))
+                .push(Text::new(self.texts.get("particles", self.world.len(), |count| format!("Number of particles: {}", count))))
+                .push(Text::new(self.texts.get("integrator", self.integrator_type, |integrator| format!("Integrator: {:?}", integrator))))
+                .push(Text::new(self.texts.get("color", self.color_mode, |color_mode| format!("Color: {:?}", color_mode))))
+                .push(Text::new(self.texts.get("simulated_time", self.world.time(), |&time| format!("Simulated time: {}", format_time(time)))))
+                .push(Text::new(self.texts.get("time_scale", time_scale, |&time_scale| {
+                    format!("Time Scale: {} seconds / 1 real second", format_magnitude(to_f64(time_scale)))
+                })))
+                .push(Slider::new(
+                    &mut self.time_scale_slider,
+                    0.0..=6.,
+                    (self.time_scale * Self::TICKS_PER_SECOND as Scalar).log10() as f32,
+                    |exponent| Message::TimeScaleChanged(Scalar::powf(10., exponent as Scalar)),
+                )))
+            .push(Column::new()
+                .padding(10)
+                .push(Text::new(self.texts.get("softening", self.physics.softening, |&softening| {
+                    format!("Softening: {} meter(s)", format_magnitude(to_f64(softening)))
+                })))
+                .push(Slider::new(&mut self.softening_slider, 0.1..=50., self.physics.softening as f32, Message::SofteningChanged))
+                .push(Button::new(
+                    &mut self.gravity_solver_button,
+                    self.texts.get("gravity_solver", self.physics.gravity_solver, |solver| format!("Gravity solver: {:?}", solver)),
+                ).on_press(Message::CycleGravitySolver))
+                .push(Text::new(self.texts.get("theta", self.physics.barnes_hut_theta, |theta| format!("Barnes-Hut theta: {:.2}", theta))))
+                .push(Slider::new(&mut self.theta_slider, 0.0..=1., self.physics.barnes_hut_theta as f32, Message::ThetaChanged))
+                .push(Text::new(self.texts.get("gravity", self.physics.gravity_multiplier, |multiplier| format!("Gravity: {:e} x G", multiplier))))
+                // the gravity slider moves through whole powers of ten
+                .push(Slider::new(
+                    &mut self.gravity_slider,
+                    -6.0..=6.,
+                    self.physics.gravity_multiplier.abs().log10().clamp(-6., 6.) as f32,
+                    |exponent| Message::GravityMultiplierChanged(Scalar::powi(10., exponent.round() as i32)),
+                ))
+                .push(Checkbox::new(self.physics.gravity_multiplier < 0., "Repulsive gravity", Message::RepulsiveGravityToggled))
+                .push(Text::new(self.texts.get("forces", self.world.force_names(), |names| format!("Forces: {}", names.join(", ")))))
+                .push(Checkbox::new(self.drag_enabled, "Linear drag", Message::DragToggled))
+                .push(Checkbox::new(self.uniform_field_enabled, "Uniform field", Message::UniformFieldToggled))
+                .push(Button::new(
+                    &mut self.collision_mode_button,
+                    self.texts.get("collision_mode", self.physics.collision_mode, |mode| format!("Collisions: {:?}", mode)),
+                ).on_press(Message::CycleCollisionMode))
+                .push(Text::new(self.texts.get("restitution", self.physics.bounce.restitution, |restitution| format!("Bounce Restitution: {:.2}", restitution))))
+                .push(Slider::new(&mut self.restitution_slider, 0.0..=1., self.physics.bounce.restitution as f32, |restitution| Message::RestitutionChanged(restitution as Scalar)))
+                .push(Text::new(self.texts.get("friction", self.physics.bounce.friction, |friction| format!("Bounce Friction: {:.2}", friction))))
+                .push(Slider::new(&mut self.friction_slider, 0.0..=1., self.physics.bounce.friction as f32, |friction| Message::FrictionChanged(friction as Scalar)))
+                .push(Button::new(
+                    &mut self.boundary_mode_button,
+                    self.texts.get("boundary_mode", self.physics.boundary.mode, |mode| format!("Boundary: {:?}", mode)),
+                ).on_press(Message::CycleBoundaryMode))
+                .push(Button::new(&mut self.recenter_button, "Recenter").on_press(Message::Recenter))
+                .push(Checkbox::new(self.center_of_mass_frame, "Center of mass frame", Message::CenterOfMassFrameToggled))
+                .push(Checkbox::new(self.physics.adaptive_timestep, "Adaptive time step", Message::AdaptiveTimestepToggled))
+                .push(Text::new(self.texts.get("substeps", self.world.substeps(), |substeps| format!("Substeps: {}", substeps)))))
+            .push(algorithm_column)
+            .push(Column::new()
+                .padding(10)
+                .push(Text::new(self.texts.get("spawn_mass", self.spawn_mass, |mass| format!("Spawn Mass: {:.2e} kg", mass))))
+                // the mass slider moves through powers of ten
+                .push(Slider::new(
+                    &mut self.spawn_mass_slider,
+                    0.0..=30.,
+                    self.spawn_mass.log10() as f32,
+                    |exponent| Message::SpawnMassChanged(Scalar::powf(10., exponent as Scalar)),
+                ))
+                .push(Text::new(self.texts.get("spawn_velocity", (self.spawn_velocity.x, self.spawn_velocity.y), |&(x, y)| {
+                    format!("Spawn Velocity: ({}, {}) m/s", format_magnitude(to_f64(x)), format_magnitude(to_f64(y)))
+                })))
+                .push(Slider::new(&mut self.spawn_velocity_x_slider, -10.0..=10., self.spawn_velocity.x as f32, |x| Message::SpawnVelocityXChanged(x as Scalar)))
+                .push(Slider::new(&mut self.spawn_velocity_y_slider, -10.0..=10., self.spawn_velocity.y as f32, |y| Message::SpawnVelocityYChanged(y as Scalar)))
+                .push(Checkbox::new(self.spawn_orbiting, "Orbit the strongest attractor", Message::SpawnOrbitingToggled))
+                .push(Text::new(self.texts.get("orbit_eccentricity_setting", self.orbit_eccentricity, |eccentricity| format!("Orbit Eccentricity: {:.2}", eccentricity))))
+                .push(Slider::new(&mut self.orbit_eccentricity_slider, 0.0..=0.95, self.orbit_eccentricity as f32, |e| Message::OrbitEccentricityChanged(e as Scalar)))
+                .push(Text::new(self.texts.get("rubble_periapsis", self.rubble_periapsis, |periapsis| format!("Rubble Pile Periapsis: {:.2} Roche limits", periapsis))))
+                .push(Slider::new(&mut self.rubble_periapsis_slider, 0.1..=3., self.rubble_periapsis as f32, |periapsis| Message::RubblePeriapsisChanged(periapsis as Scalar)))
+                .push(Text::new(self.texts.get("split_fragments", self.split_fragments, |fragments| format!("Split Fragments: {}", fragments))))
+                .push(Slider::new(&mut self.split_fragments_slider, 2.0..=32., self.split_fragments as f32, |fragments| Message::SplitFragmentsChanged(fragments.round() as usize)))
+                .push(Text::new(self.texts.get("split_energy", self.split_energy, |energy| format!("Split Energy: {:.2e} J", energy))))
+                // the energy slider moves through powers of ten
+                .push(Slider::new(
+                    &mut self.split_energy_slider,
+                    0.0..=30.,
+                    self.split_energy.max(1.).log10() as f32,
+                    |exponent| Message::SplitEnergyChanged(Scalar::powf(10., exponent as Scalar)),
+                )))
+            .push(Column::new()
+                .padding(10)
+                .push(Text::new(self.texts.get("stream_rate", self.spawner.rate, |rate| format!("Stream Rate: {:.0} particles/s", rate))))
+                .push(Slider::new(&mut self.stream_rate_slider, 1.0..=500., self.spawner.rate as f32, |rate| Message::StreamRateChanged(rate as Scalar)))
+                .push(Text::new(self.texts.get("stream_speed", self.spawner.speed, |&speed| format!("Stream Speed: {} m/s", format_magnitude(to_f64(speed))))))
+                .push(Slider::new(&mut self.stream_speed_slider, 0.0..=100., self.spawner.speed as f32, |speed| Message::StreamSpeedChanged(speed as Scalar)))
+                .push(Text::new(self.texts.get("stream_spread", self.spawner.spread, |spread| format!("Stream Spread: {:.0} degrees", spread))))
+                .push(Slider::new(&mut self.stream_spread_slider, 0.0..=360., self.spawner.spread as f32, |spread| Message::StreamSpreadChanged(spread as Scalar)))
+                .push(Text::new(self.texts.get("stream_direction", self.spawner.direction_degrees(), |direction| format!("Stream Direction: {:.0} degrees", direction)))))
+            .push(diagnostics_column)
+            .push(events_column)
+            .push(selected_column)
+            .push(inspector_column)
+            .push(measure_column)
+            .push(benchmark_column)
+        .into()
+    }
+}
+
+/// Real time caught up on from the `max_catch_up` setting, where a negative
+/// time catches up on a single tick and an infinite time on every tick.
+fn catch_up_duration(seconds: f64) -> Duration {
+    Duration::try_from_secs_f64(seconds.max(0.)).unwrap_or(Duration::MAX)
+}
+
+/// Sprite of a particle while the particles aren't colored, of the kind its
+/// preset chose or else of the kind of its mass.
+fn kind_source(render: &RenderConfig, kinds: &HashMap<ParticleId, SpriteKind>, id: ParticleId, mass: Scalar) -> SpriteSource {
+    render.kind_source(kinds.get(&id).copied().unwrap_or_else(|| render.sprite_kind_of(mass)))
+}
+
+/// Describes the statistics of a phase of the benchmark on one line.
+/// Name of a particle, or its id if it wasn't named.
+fn particle_name(id: ParticleId, name: Option<&str>) -> String {
+    name.map_or_else(|| format!("particle {}", id), String::from)
+}
+
+fn format_statistics(phase: &str, statistics: &Statistics) -> String {
+    format!(
+        "{}: mean {:.2?}, min {:.2?}, median {:.2?}, p95 {:.2?}, p99 {:.2?}, max {:.2?}",
+        phase, statistics.mean, statistics.min, statistics.median, statistics.p95, statistics.p99, statistics.max,
+    )
+}
+
+/// Area of the sprite strip to draw a sprite from.
+/// Area of the sprite sheet a particle is drawn from with the blend mode, and the factor
+/// its scale is multiplied by as the faded copies of the sprites are smaller.
+fn blended_source(blend_mode: BlendMode, render: &RenderConfig, source: SpriteSource, mass: Scalar, (min_mass, max_mass): (Scalar, Scalar)) -> (Rectangle<u16>, f32) {
+    match blend_mode {
+        BlendMode::Opaque => (sprite_rectangle(source), 1.),
+        BlendMode::Additive => sprites::faded_source(source, color_band(mass, min_mass, max_mass, FADE_LEVELS), render),
+        BlendMode::Alpha => sprites::faded_source(source, 0, render),
+    }
+}
+
+/// Smallest and largest of the positive masses.
+fn mass_range(masses: impl ParallelIterator<Item = Scalar>) -> (Scalar, Scalar) {
+    masses
+        .filter(|&mass| mass > 0.)
+        .fold(|| (Scalar::INFINITY, 0.), |(min, max), mass| (mass.min(min), mass.max(max)))
+        .reduce(|| (Scalar::INFINITY, 0.), |a, b| (a.0.min(b.0), a.1.max(b.1)))
+}
+
+fn sprite_rectangle(source: SpriteSource) -> Rectangle<u16> {
+    Rectangle { x: source.x, y: source.y, width: source.width, height: source.height }
+}
+
+/// Factor sprites at the position are scaled by for their distance from the camera,
+/// or None if the position is behind the camera. The camera looks along the view axis
+/// from the view distance in front of the plane at the camera depth, so sprites on
+/// that plane are drawn at their normal size. In two dimensions this is always 1.
+fn depth_scale(position: Vector, camera_depth: Scalar, view_distance: Scalar) -> Option<f32> {
+    let distance = view_distance + camera_depth - depth(position);
+    (distance > 0.).then(|| ((view_distance / distance) as f32).min(MAX_DEPTH_SCALE))
+}
+
+/// Labels of the named particles on screen, the particles which are hovered, pinned or selected being highlighted.
+fn labels_on_screen<'a>(
+    names: &'a ParticleNames,
+    particles: impl Iterator<Item = (ParticleId, Vector)>,
+    highlighted: [Option<ParticleId>; 3],
+    on_screen: &impl Fn(Vector) -> Option<Point>,
+) -> Vec<Label<'a>> {
+    particles
+        .filter_map(|(id, position)| {
+            let name = names.get(&id)?;
+            Some(Label { point: on_screen(position)?, name, highlighted: highlighted.contains(&Some(id)) })
+        })
+        .collect()
+}
+
+/// Components of a vector in scientific notation, such as `(1.000e0, 2.000e0)`.
+fn format_vector<T: std::fmt::LowerExp>(components: impl IntoIterator<Item = T>) -> String {
+    let components: Vec<String> = components.into_iter().map(|component| format!("{:.3e}", component)).collect();
+    format!("({})", components.join(", "))
+}
+
+/// Creates the diagnostics file and writes the header.
+fn create_diagnostics_log(path: &str) -> std::io::Result<BufWriter<File>> {
+    let mut log = BufWriter::new(File::create(path)?);
+    writeln!(
+        log,
+        "step,particles,kinetic_energy,potential_energy,{},{}",
+        vector::csv_header("momentum_"), vector::csv_header("center_of_mass_"),
+    )?;
+    Ok(log)
+}
@@ -0,0 +1,75 @@
+use coffee::graphics::{Color, Mesh, Point, Shape, Target};
+
+/// Smallest distance in pixels between the ticks of the axes
+const MIN_TICK_SPACING: f32 = 60.;
+/// Length in pixels of the ticks on either side of an axis
+const TICK_LENGTH: f32 = 4.;
+/// Half the width in pixels of the crosshair at the origin
+const CROSSHAIR_SIZE: f32 = 12.;
+/// Color of the axes, ticks and crosshair
+const COLOR: Color = Color { r: 0.6, g: 0.6, b: 0.6, a: 0.6 };
+
+/// Which ticks are in view at which zoom, which the mesh is built for
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct View {
+    scale: f32,
+    /// Indices of the first and last ticks in view along each axis
+    x_ticks: (i64, i64),
+    y_ticks: (i64, i64),
+}
+
+/// Axes through the origin of the world with a crosshair at the origin and
+/// ticks every power of ten meters, the smallest power keeping the ticks at
+/// least [`MIN_TICK_SPACING`] pixels apart at the current zoom. The mesh is only
+/// built again when the zoom changes or the view moves past a tick.
+pub struct Axes {
+    mesh: Mesh,
+    view: Option<View>,
+}
+
+impl Axes {
+    pub fn new() -> Self {
+        Axes { mesh: Mesh::new(), view: None }
+    }
+
+    /// Meters between the ticks at the scale in pixels per meter.
+    pub fn tick_spacing(scale: f32) -> f64 {
+        10f64.powf((MIN_TICK_SPACING as f64 / scale as f64).log10().ceil())
+    }
+
+    /// Draws the axes in the space of the camera, which is offset by the camera
+    /// position from the screen of the given width and height.
+    pub fn draw(&mut self, target: &mut Target<'_>, camera_position: Point, scale: f32, width: f32, height: f32) {
+        let spacing = Self::tick_spacing(scale);
+        let ticks = |offset: f32, length: f32| {
+            let (min, max) = ((-offset / scale) as f64, ((length - offset) / scale) as f64);
+            ((min / spacing).floor() as i64, (max / spacing).ceil() as i64)
+        };
+        let view = View { scale, x_ticks: ticks(camera_position.x, width), y_ticks: ticks(camera_position.y, height) };
+        if self.view != Some(view) {
+            self.mesh = build_mesh(view, spacing);
+            self.view = Some(view);
+        }
+        self.mesh.draw(target);
+    }
+}
+
+/// Mesh of the axes, crosshair and ticks in view, in pixels from the origin.
+fn build_mesh(view: View, spacing: f64) -> Mesh {
+    let pixels = |tick: i64| (tick as f64 * spacing) as f32 * view.scale;
+    let line = |from: Point, to: Point| Shape::Polyline { points: vec![from, to] };
+    let mut mesh = Mesh::new();
+    let (x_ticks, y_ticks) = (view.x_ticks.0..=view.x_ticks.1, view.y_ticks.0..=view.y_ticks.1);
+    mesh.stroke(line(Point::new(pixels(view.x_ticks.0), 0.), Point::new(pixels(view.x_ticks.1), 0.)), COLOR, 1.);
+    mesh.stroke(line(Point::new(0., pixels(view.y_ticks.0)), Point::new(0., pixels(view.y_ticks.1))), COLOR, 1.);
+    for x in x_ticks.filter(|&tick| tick != 0).map(pixels) {
+        mesh.stroke(line(Point::new(x, -TICK_LENGTH), Point::new(x, TICK_LENGTH)), COLOR, 1.);
+    }
+    for y in y_ticks.filter(|&tick| tick != 0).map(pixels) {
+        mesh.stroke(line(Point::new(-TICK_LENGTH, y), Point::new(TICK_LENGTH, y)), COLOR, 1.);
+    }
+    mesh.stroke(Shape::Circle { center: Point::new(0., 0.), radius: CROSSHAIR_SIZE / 2. }, COLOR, 1.);
+    mesh.stroke(line(Point::new(-CROSSHAIR_SIZE, 0.), Point::new(CROSSHAIR_SIZE, 0.)), COLOR, 2.);
+    mesh.stroke(line(Point::new(0., -CROSSHAIR_SIZE), Point::new(0., CROSSHAIR_SIZE)), COLOR, 2.);
+    mesh
+}
@@ -0,0 +1,47 @@
+use std::any::Any;
+use std::collections::HashMap;
+
+/// Text of the user interface which is only formatted again when the value it
+/// shows changes, rather than on every pass of the layout.
+#[derive(Debug)]
+pub struct CachedText<T> {
+    /// Value the text was last formatted from
+    value: Option<T>,
+    text: String,
+}
+
+impl<T> Default for CachedText<T> {
+    fn default() -> Self {
+        Self { value: None, text: String::new() }
+    }
+}
+
+impl<T: PartialEq> CachedText<T> {
+    /// The text of the value, formatting it if the value changed since the text was last formatted.
+    pub fn get(&mut self, value: T, format: impl FnOnce(&T) -> String) -> &str {
+        if self.value.as_ref() != Some(&value) {
+            self.text = format(&value);
+            self.value = Some(value);
+        }
+        &self.text
+    }
+}
+
+/// Texts of the user interface cached by a name for each line, so that a line
+/// is only formatted again when the value it shows changes.
+#[derive(Debug, Default)]
+pub struct TextCache {
+    texts: HashMap<&'static str, Box<dyn Any>>,
+}
+
+impl TextCache {
+    /// The text of the line called `name` showing the value, formatting it if
+    /// the value changed since the line was last formatted.
+    pub fn get<T: PartialEq + 'static>(&mut self, name: &'static str, value: T, format: impl FnOnce(&T) -> String) -> &str {
+        let text = self.texts.entry(name).or_insert_with(|| Box::new(CachedText::<T>::default()));
+        if !text.is::<CachedText<T>>() {
+            *text = Box::new(CachedText::<T>::default());
+        }
+        text.downcast_mut::<CachedText<T>>().expect("the text was just replaced by one of this type").get(value, format)
+    }
+}
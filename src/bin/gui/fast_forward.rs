@@ -0,0 +1,42 @@
+use std::time::Duration;
+
+/// Wall clock time each tick spends fast-forwarding, so the window keeps
+/// drawing the progress while the updates run.
+const TICK_BUDGET: Duration = Duration::from_millis(100);
+
+/// Runs a number of updates back to back without drawing the particles in
+/// between, in batches sized to take about [`TICK_BUDGET`] each tick.
+#[derive(Debug)]
+pub struct FastForward {
+    total: usize,
+    remaining: usize,
+    /// Updates run by the next batch, adjusted to the time the previous batch took
+    batch: usize,
+}
+
+impl FastForward {
+    pub fn new(steps: usize) -> Self {
+        FastForward { total: steps, remaining: steps, batch: 1 }
+    }
+
+    /// Updates to run in the next batch.
+    pub fn next_batch(&self) -> usize {
+        self.batch.min(self.remaining)
+    }
+
+    /// Counts the updates of a batch which took the given time, returning
+    /// whether every update has been run.
+    pub fn finish_batch(&mut self, steps: usize, elapsed: Duration) -> bool {
+        self.remaining -= steps;
+        // aim the next batch at the budget, growing at most tenfold in case the batch was unusually quick
+        let per_step = elapsed.as_secs_f64() / steps.max(1) as f64;
+        let target = if per_step > 0. { (TICK_BUDGET.as_secs_f64() / per_step) as usize } else { usize::MAX };
+        self.batch = target.clamp(1, steps.max(1).saturating_mul(10));
+        self.remaining == 0
+    }
+
+    /// Updates run so far and in total.
+    pub fn progress(&self) -> (usize, usize) {
+        (self.total - self.remaining, self.total)
+    }
+}
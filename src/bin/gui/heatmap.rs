@@ -0,0 +1,109 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use coffee::graphics::{Batch, Color, Image, Point, Rectangle, Sprite, Target, Transformation, Vector as ScreenVector};
+use rayon::prelude::*;
+
+use nbody_simulation::config::RgbColor;
+
+/// Colors in the palette image the colors of the heatmap are interpolated into
+const PALETTE_SIZE: usize = 256;
+
+/// Whether the heatmap is drawn, and whether the particles are drawn over it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HeatmapMode {
+    Off,
+    UnderParticles,
+    Only,
+}
+
+impl HeatmapMode {
+    pub fn next(self) -> Self {
+        match self {
+            HeatmapMode::Off => HeatmapMode::UnderParticles,
+            HeatmapMode::UnderParticles => HeatmapMode::Only,
+            HeatmapMode::Only => HeatmapMode::Off,
+        }
+    }
+}
+
+/// Number of particles in each cell of a grid covering the window, drawn as
+/// quads colored by the logarithm of the count so both sparse and dense regions
+/// are visible. The counts and quads are kept between frames, so the grid is
+/// only reallocated when the window or the cell size changes.
+pub struct Heatmap {
+    cell_size: f32,
+    columns: usize,
+    rows: usize,
+    counts: Vec<AtomicU32>,
+    /// Quads of the cells with particles, drawn from a palette image one pixel per color
+    batch: Batch,
+}
+
+impl Heatmap {
+    /// Creates a heatmap drawn with the palette image made from [`palette`].
+    pub fn new(palette: Image, cell_size: f32) -> Self {
+        Heatmap { cell_size: cell_size.max(1.), columns: 0, rows: 0, counts: Vec::new(), batch: Batch::new(palette) }
+    }
+
+    pub fn set_cell_size(&mut self, cell_size: f32) {
+        self.cell_size = cell_size.max(1.);
+    }
+
+    /// Counts the screen positions in each cell of a grid covering a window of
+    /// the given size, and fills the batch with the quads of the cells which have
+    /// any. Positions outside the window are ignored.
+    pub fn update(&mut self, positions: impl ParallelIterator<Item = Point>, width: f32, height: f32) {
+        let (columns, rows) = ((width / self.cell_size).ceil().max(0.) as usize, (height / self.cell_size).ceil().max(0.) as usize);
+        if (columns, rows) != (self.columns, self.rows) {
+            (self.columns, self.rows) = (columns, rows);
+            self.counts = (0..columns * rows).map(|_| AtomicU32::new(0)).collect();
+        } else {
+            self.counts.par_iter().for_each(|count| count.store(0, Ordering::Relaxed));
+        }
+
+        let (cell_size, counts) = (self.cell_size, &self.counts);
+        positions.for_each(|position| {
+            if position.x >= 0. && position.y >= 0. && position.x < width && position.y < height {
+                let (column, row) = ((position.x / cell_size) as usize, (position.y / cell_size) as usize);
+                counts[row.min(rows - 1) * columns + column.min(columns - 1)].fetch_add(1, Ordering::Relaxed);
+            }
+        });
+
+        let max = counts.par_iter().map(|count| count.load(Ordering::Relaxed)).max().unwrap_or(0);
+        let scale = (PALETTE_SIZE - 1) as f32 / (max as f32).ln_1p();
+        self.batch.clear();
+        self.batch.par_extend(counts.par_iter().enumerate().filter_map(|(i, count)| {
+            let count = count.load(Ordering::Relaxed);
+            (count > 0).then(|| Sprite {
+                source: Rectangle { x: ((count as f32).ln_1p() * scale).round() as u16, y: 0, width: 1, height: 1 },
+                position: Point::new((i % columns) as f32, (i / columns) as f32) * cell_size,
+                scale: (cell_size, cell_size),
+            })
+        }));
+    }
+
+    /// Draws the cells of the last update, offset so they line up with the window.
+    pub fn draw(&self, target: &mut Target<'_>, offset: ScreenVector) {
+        self.batch.draw(&mut target.transform(Transformation::translate(offset)));
+    }
+}
+
+/// Colors of the palette image, interpolated evenly between the colors of the heatmap.
+pub fn palette(colors: &[RgbColor]) -> Vec<Color> {
+    let colors: Vec<[f32; 3]> = colors.iter().map(|color| [color.r, color.g, color.b].map(|channel| channel as f32 / 255.)).collect();
+    (0..PALETTE_SIZE)
+        .map(|i| {
+            let [r, g, b] = match colors.len() {
+                0 => [1.; 3],
+                1 => colors[0],
+                len => {
+                    let position = i as f32 / (PALETTE_SIZE - 1) as f32 * (len - 1) as f32;
+                    let low = (position as usize).min(len - 2);
+                    let t = position - low as f32;
+                    [0, 1, 2].map(|channel| colors[low][channel] + (colors[low + 1][channel] - colors[low][channel]) * t)
+                }
+            };
+            Color::new(r, g, b, 1.)
+        })
+        .collect()
+}
@@ -0,0 +1,51 @@
+use std::collections::HashMap;
+
+use nbody_simulation::particle::Particle;
+use nbody_simulation::scalar::Scalar;
+use nbody_simulation::vector::Vector;
+
+/// Positions of the particles before the latest physics update, kept by the renderer
+/// and keyed by particle id so particles can be drawn between their previous and
+/// current positions when frames are drawn faster than the physics updates.
+#[derive(Default)]
+pub struct Interpolation {
+    /// Position of each particle before the latest update
+    previous: HashMap<usize, Vector>,
+    /// Center of mass of the particles before the latest update
+    previous_center: Option<Vector>,
+}
+
+impl Interpolation {
+    /// Records the positions of the particles before they are updated.
+    pub fn capture(&mut self, particles: &[Particle], center_of_mass: Vector) {
+        self.previous.clear();
+        self.previous.extend(particles.iter().map(|particle| (particle.id, particle.position)));
+        self.previous_center = Some(center_of_mass);
+    }
+
+    /// Forgets the previous positions, so the particles are drawn at their current
+    /// positions until the next update. Used when the particles are moved or replaced
+    /// outside of an update, which interpolating across would smear.
+    pub fn clear(&mut self) {
+        self.previous.clear();
+        self.previous_center = None;
+    }
+
+    /// Position of the particle the fraction alpha of the way from its previous to its
+    /// current position. Particles created since the last update are drawn at their current
+    /// position, as are particles which moved more than half the period in a periodic box,
+    /// which were wrapped to the other side of it.
+    pub fn position(&self, particle: &Particle, alpha: Scalar, period: Option<Vector>) -> Vector {
+        match self.previous.get(&particle.id) {
+            Some(&previous) if !period.is_some_and(|period| ((particle.position - previous).abs() * 2.).cmpgt(period).any()) => {
+                previous.lerp(particle.position, alpha)
+            }
+            _ => particle.position,
+        }
+    }
+
+    /// Center of mass the fraction alpha of the way from its previous to the current center of mass.
+    pub fn center_of_mass(&self, center_of_mass: Vector, alpha: Scalar) -> Vector {
+        self.previous_center.map_or(center_of_mass, |previous| previous.lerp(center_of_mass, alpha))
+    }
+}
@@ -0,0 +1,179 @@
+use coffee::input::keyboard::{KeyCode, Keyboard};
+
+use nbody_simulation::config::KeyBinding;
+
+macro_rules! actions {
+    ($($action:ident $name:literal $key:ident $description:literal,)*) => {
+        /// Something done with a key. Every action has a default key, which can be
+        /// replaced in the key_bindings setting using the name of the action.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum Action {
+            $($action,)*
+        }
+
+        impl Action {
+            /// Every action, in the order they are listed in the key bindings overlay
+            pub const ALL: &'static [Action] = &[$(Action::$action,)*];
+
+            /// Name of the action in the key_bindings setting
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Action::$action => $name,)*
+                }
+            }
+
+            pub fn description(self) -> &'static str {
+                match self {
+                    $(Action::$action => $description,)*
+                }
+            }
+
+            fn default_key(self) -> KeyCode {
+                match self {
+                    $(Action::$action => KeyCode::$key,)*
+                }
+            }
+        }
+    };
+}
+
+actions! {
+    ShowBindings "show_bindings" F1 "Show or hide the key bindings",
+    TogglePause "toggle_pause" Space "Pause or resume the simulation",
+    Step "step" Period "Advance the paused simulation by one update",
+    SlowDown "slow_down" LBracket "Halve the time scale over the next few updates",
+    SpeedUp "speed_up" RBracket "Double the time scale over the next few updates",
+    SlowMotion "slow_motion" Minus "Slow the time scale to a tenth of the default of the current particles",
+    DefaultSpeed "default_speed" Equals "Return the time scale to the default of the current particles",
+    FastForward "fast_forward" F8 "Run many updates without drawing them, or stop fast-forwarding",
+    CycleAlgorithm "cycle_algorithm" Tab "Cycle the world algorithm",
+    CycleIntegrator "cycle_integrator" I "Cycle the integrator",
+    CycleCollisionMode "cycle_collision_mode" C "Cycle how colliding particles are handled",
+    CycleBoundary "cycle_boundary" O "Cycle the boundary of the world",
+    ScaleGravity "scale_gravity" G "Make gravity ten times stronger, or weaker with Shift",
+    FlipGravity "flip_gravity" N "Switch gravity between attractive and repulsive",
+    Recenter "recenter" X "Move the particles into their center of mass frame",
+    ToggleCenterOfMassFrame "toggle_center_of_mass_frame" F "Keep the camera moving with the center of mass",
+    ToggleOrbitInsertion "toggle_orbit_insertion" K "Insert spawned particles into orbits",
+    SpawnHeavy "spawn_heavy" Key2 "Create a heavy particle at the cursor",
+    LaunchRubblePile "launch_rubble_pile" F7 "Launch a rubble pile from the cursor past the most massive particle",
+    Stream "stream" H "Emit a stream of particles from the cursor while held",
+    Undo "undo" Z "Undo spawning a particle while Control is held",
+    Redo "redo" Y "Redo spawning a particle while Control is held",
+    RemoveAll "remove_all" R "Remove every particle",
+    Deselect "deselect" Escape "Stop following and inspecting the selected particle",
+    NudgeUp "nudge_up" Up "Push the selected particle up",
+    NudgeDown "nudge_down" Down "Push the selected particle down",
+    NudgeLeft "nudge_left" Left "Push the selected particle left, outside of playback",
+    NudgeRight "nudge_right" Right "Push the selected particle right, outside of playback",
+    CenterCamera "center_camera" Home "Move the camera to the center of mass",
+    PanUp "pan_up" W "Move the camera up while held",
+    PanDown "pan_down" S "Move the camera down while held",
+    PanLeft "pan_left" A "Move the camera left while held",
+    PanRight "pan_right" D "Move the camera right while held",
+    MoveForward "move_forward" Q "Move the camera forwards while held, in three dimensions",
+    MoveBackward "move_backward" E "Move the camera backwards while held, in three dimensions",
+    ToggleMeasurement "toggle_measurement" M "Measure between two clicked points or particles, or dismiss the measurement",
+    ToggleAxes "toggle_axes" Grave "Show or hide the axes through the origin with ticks every power of ten meters",
+    ToggleShip "toggle_ship" Insert "Steer the selected particle with the arrow keys instead of nudging it, or stop steering",
+    SplitSelected "split_selected" Apostrophe "Split the selected particle into fragments flying apart, set by the split sliders",
+    ToggleOrbit "toggle_orbit" End "Show or hide the predicted orbit of the selected particle around the particle pulling hardest on it",
+    ToggleLagrangePoints "toggle_lagrange_points" Comma "Show or hide the Lagrange points of the last two selected particles",
+    ReloadSprites "reload_sprites" Slash "Load the sprite file again, keeping the current sprites if it can't be loaded",
+    ToggleSizeByMass "toggle_size_by_mass" Semicolon "Switch between sizing particles by mass and a uniform size",
+    CycleColorMode "cycle_color_mode" P "Cycle which property decides the color of the particles",
+    CycleBlendMode "cycle_blend_mode" U "Cycle how overlapping sprites are combined",
+    CycleHeatmap "cycle_heatmap" F4 "Cycle the density heatmap",
+    ToggleMinimap "toggle_minimap" F2 "Show or hide the minimap",
+    ToggleTrails "toggle_trails" T "Show or hide the trails of the particles",
+    TogglePerformanceOverlay "toggle_performance_overlay" F3 "Show or hide the performance overlay",
+    ToggleFullscreen "toggle_fullscreen" F11 "Switch the window between fullscreen and windowed",
+    Screenshot "screenshot" F12 "Save the next frame as a screenshot",
+    ToggleBenchmark "toggle_benchmark" Key1 "Start or stop the benchmark",
+    ToggleTrajectoryLog "toggle_trajectory_log" J "Start or stop logging the trajectories of the particles",
+    Save "save" F5 "Save the particles",
+    Load "load" F9 "Load the saved particles",
+    LoadScenario "load_scenario" L "Add the particles of the scenario file",
+    ToggleRecording "toggle_recording" V "Start or stop recording snapshots",
+    TogglePlayback "toggle_playback" B "Play back the recording, or return to the simulation",
+    PlaybackBack "playback_back" Left "Step back through the recording while held",
+    PlaybackForward "playback_forward" Right "Step forward through the recording while held",
+    SaveRecording "save_recording" F6 "Save the recording",
+    LoadRecording "load_recording" F10 "Load a saved recording",
+    SaveEventLog "save_event_log" Backslash "Write the latest merges, spawns and removals to the event log file",
+    PresetSolarSystem "preset_solar_system" Key4 "Replace the particles with the solar system",
+    PresetEphemeris "preset_ephemeris" Key0 "Replace the particles with the bodies of the ephemeris file",
+    PresetGalaxy "preset_galaxy" Key5 "Replace the particles with a galaxy",
+    PresetGalaxyCollision "preset_galaxy_collision" Key6 "Replace the particles with two colliding galaxies",
+    PresetFigureEight "preset_figure_eight" Key7 "Replace the particles with the figure eight orbit",
+    PresetLagrangeTriangle "preset_lagrange_triangle" Key8 "Replace the particles with a Lagrange triangle",
+    PresetCircumbinary "preset_circumbinary" Key9 "Replace the particles with a circumbinary orbit",
+}
+
+macro_rules! keys {
+    ($($key:ident),* $(,)?) => {
+        /// Keys which can be bound to actions, by the name used in the key_bindings setting
+        const KEYS: &[(&str, KeyCode)] = &[$((stringify!($key), KeyCode::$key)),*];
+    };
+}
+
+keys! {
+    A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0,
+    F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+    Escape, Space, Tab, Return, Back, Insert, Delete, Home, End, PageUp, PageDown,
+    Left, Up, Right, Down,
+    Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+    Add, Subtract, Multiply, Divide, Decimal, NumpadEnter,
+    Apostrophe, Backslash, Comma, Equals, Grave, LBracket, RBracket, Minus, Period, Semicolon, Slash,
+}
+
+/// The key of every action, the defaults with the keys of the key_bindings setting in their place.
+#[derive(Debug, Clone)]
+pub struct KeyBindings {
+    /// Key of each action, in the order of [`Action::ALL`]
+    keys: Vec<KeyCode>,
+}
+
+impl KeyBindings {
+    /// Binds the keys of the key_bindings setting to their actions, keeping the default
+    /// keys of the other actions. Unknown actions and key names are an error listing the
+    /// names which can be used.
+    pub fn new(bindings: &[KeyBinding]) -> Result<Self, String> {
+        let mut keys: Vec<KeyCode> = Action::ALL.iter().map(|action| action.default_key()).collect();
+        for binding in bindings {
+            let Some(index) = Action::ALL.iter().position(|action| action.name() == binding.action) else {
+                let names: Vec<&str> = Action::ALL.iter().map(|action| action.name()).collect();
+                return Err(format!("Unknown action '{}', expected one of {}", binding.action, names.join(", ")));
+            };
+            let Some(&(_, key)) = KEYS.iter().find(|(name, _)| name.eq_ignore_ascii_case(&binding.key)) else {
+                let names: Vec<&str> = KEYS.iter().map(|(name, _)| *name).collect();
+                return Err(format!("Unknown key '{}' for action '{}', expected one of {}", binding.key, binding.action, names.join(", ")));
+            };
+            keys[index] = key;
+        }
+        Ok(KeyBindings { keys })
+    }
+
+    pub fn key(&self, action: Action) -> KeyCode {
+        self.keys[Action::ALL.iter().position(|&other| other == action).expect("every action is listed in ALL")]
+    }
+
+    pub fn is_pressed(&self, keyboard: &Keyboard, action: Action) -> bool {
+        keyboard.is_key_pressed(self.key(action))
+    }
+
+    /// Actions whose keys were released since the last interaction.
+    pub fn released(&self, keyboard: &Keyboard) -> Vec<Action> {
+        Action::ALL.iter().zip(&self.keys).filter(|(_, &key)| keyboard.was_key_released(key)).map(|(&action, _)| action).collect()
+    }
+
+    /// A line for each action naming its key and what it does.
+    pub fn lines(&self) -> impl Iterator<Item = String> + '_ {
+        Action::ALL.iter().zip(&self.keys).map(|(action, &key)| format!("{}: {} ({})", key_name(key), action.description(), action.name()))
+    }
+}
+
+fn key_name(key: KeyCode) -> &'static str {
+    KEYS.iter().find(|&&(_, other)| other == key).map_or("?", |(name, _)| name)
+}
@@ -0,0 +1,52 @@
+use coffee::graphics::{Color, Font, Point, Target, Text, VerticalAlignment};
+
+/// Font the labels are drawn with, the same font as the rest of the user interface
+pub const FONT: &[u8] = include_bytes!("../../../resources/Inconsolata-Regular.ttf");
+/// Size in pixels of the text of the labels
+const TEXT_SIZE: f32 = 16.;
+/// Pixels between a particle and the start of its label
+const OFFSET: f32 = 8.;
+/// Most named particles on screen whose spacing is checked, above which only the
+/// highlighted particles are labeled so the quadratic check doesn't slow drawing
+const MAX_SPACED: usize = 500;
+
+/// A named particle on screen which may be labeled.
+pub struct Label<'a> {
+    /// Position of the particle in the space of the target the labels are drawn on
+    pub point: Point,
+    pub name: &'a str,
+    /// Whether the particle is hovered, pinned or selected, which is always labeled
+    pub highlighted: bool,
+}
+
+/// Names of particles drawn as small text next to them.
+pub struct Labels {
+    font: Font,
+}
+
+impl Labels {
+    pub fn new(font: Font) -> Self {
+        Labels { font }
+    }
+
+    /// Draws the names of the highlighted particles, and of the other particles at
+    /// least spacing pixels from every other named particle on screen, so names
+    /// appear once the view is zoomed in far enough that they don't overlap.
+    pub fn draw(&mut self, target: &mut Target<'_>, labels: &[Label<'_>], spacing: f32) {
+        let spaced = labels.len() <= MAX_SPACED;
+        for (i, label) in labels.iter().enumerate() {
+            let apart = spaced && labels.iter().enumerate().all(|(j, other)| i == j || (label.point - other.point).norm() >= spacing);
+            if label.highlighted || apart {
+                self.font.add(Text {
+                    content: label.name,
+                    position: Point::new(label.point.x + OFFSET, label.point.y),
+                    size: TEXT_SIZE,
+                    color: if label.highlighted { Color::WHITE } else { Color::new(0.8, 0.8, 0.8, 1.) },
+                    vertical_alignment: VerticalAlignment::Center,
+                    ..Text::default()
+                });
+            }
+        }
+        self.font.draw(target);
+    }
+}
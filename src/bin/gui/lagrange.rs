@@ -0,0 +1,30 @@
+use coffee::graphics::{Color, Mesh, Point, Shape, Target};
+
+use nbody_simulation::lagrange::NAMES;
+
+use crate::labels::{Label, Labels};
+
+/// Half the width in pixels of the markers of the Lagrange points
+const MARKER_SIZE: f32 = 6.;
+/// Color of the markers of the Lagrange points
+const COLOR: Color = Color { r: 0.4, g: 0.9, b: 1., a: 1. };
+
+/// Draws a cross at each of the unstable points L1 to L3 and a diamond at each
+/// of the stable points L4 and L5, labeled with their names, at the points in the
+/// space of the target so the markers follow the camera as it zooms.
+pub fn draw(target: &mut Target<'_>, labels: &mut Labels, points: &[Point; 5]) {
+    let mut mesh = Mesh::new();
+    for (index, point) in points.iter().enumerate() {
+        let (x, y, size) = (point.x, point.y, MARKER_SIZE);
+        if index < 3 {
+            mesh.stroke(Shape::Polyline { points: vec![Point::new(x - size, y - size), Point::new(x + size, y + size)] }, COLOR, 1.5);
+            mesh.stroke(Shape::Polyline { points: vec![Point::new(x - size, y + size), Point::new(x + size, y - size)] }, COLOR, 1.5);
+        } else {
+            let corners = vec![Point::new(x, y - size), Point::new(x + size, y), Point::new(x, y + size), Point::new(x - size, y), Point::new(x, y - size)];
+            mesh.stroke(Shape::Polyline { points: corners }, COLOR, 1.5);
+        }
+    }
+    mesh.draw(target);
+    let names: Vec<Label<'_>> = points.iter().zip(NAMES).map(|(&point, name)| Label { point, name, highlighted: true }).collect();
+    labels.draw(target, &names, 0.);
+}
@@ -0,0 +1,39 @@
+// positions are cast to f32 for coffee, which is a no-op in single precision
+#![cfg_attr(feature = "single-precision", allow(clippy::unnecessary_cast))]
+
+mod application;
+mod axes;
+mod cached_text;
+mod fast_forward;
+mod heatmap;
+mod interpolation;
+mod keybindings;
+mod labels;
+mod lagrange;
+mod measure;
+mod minimap;
+mod ship;
+mod spawner;
+mod sprites;
+mod stats;
+mod time_ramp;
+mod trails;
+mod undo;
+
+use coffee::{graphics::WindowSettings, ui::UserInterface};
+
+use nbody_simulation::config::Config;
+
+use crate::application::Application;
+
+fn main() -> Result<(), coffee::Error> {
+    // a config which can't be loaded is reported once the application loads it
+    let size = Config::load().map_or((1920, 1080), |config| (config.render.screen_width, config.render.screen_height));
+    <Application as UserInterface>::run(WindowSettings {
+        title: String::from("Particle Physics Simulator"),
+        size,
+        resizable: true,
+        fullscreen: false,
+        maximized: false,
+    })
+}
@@ -0,0 +1,88 @@
+use coffee::graphics::{Color, Mesh, Point, Shape, Target};
+
+use nbody_simulation::particle::ParticleId;
+use nbody_simulation::vector::Vector;
+
+use crate::labels::{Label, Labels};
+
+/// Radius in pixels of the circles marking the ends of a measurement
+const MARKER_RADIUS: f32 = 6.;
+/// Color of the line and markers of a measurement
+const COLOR: Color = Color { r: 1., g: 0.8, b: 0.2, a: 1. };
+
+/// An end of a measurement, either a point fixed in the world or a particle it moves with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum End {
+    Position(Vector),
+    Particle(ParticleId),
+}
+
+/// Two points or particles picked with the mouse, which the distance between is
+/// shown for until the measurement is dismissed.
+#[derive(Debug, Default)]
+pub struct Measurement {
+    ends: Vec<End>,
+    /// Whether left clicks pick the ends instead of selecting and spawning particles
+    picking: bool,
+}
+
+impl Measurement {
+    /// Forgets the ends of the previous measurement and picks new ends.
+    pub fn start(&mut self) {
+        self.ends.clear();
+        self.picking = true;
+    }
+
+    pub fn dismiss(&mut self) {
+        self.ends.clear();
+        self.picking = false;
+    }
+
+    /// Whether ends are being picked or a measurement is shown.
+    pub fn is_active(&self) -> bool {
+        self.picking || !self.ends.is_empty()
+    }
+
+    pub fn is_picking(&self) -> bool {
+        self.picking
+    }
+
+    /// Adds an end, stopping picking once both ends are picked.
+    pub fn pick(&mut self, end: End) {
+        self.ends.push(end);
+        self.picking = self.ends.len() < 2;
+    }
+
+    pub fn ends(&self) -> &[End] {
+        &self.ends
+    }
+
+    /// Dismisses the measurement if one of its particles no longer exists, such as
+    /// after being removed or merged into another particle.
+    pub fn retain_particles(&mut self, exists: impl Fn(ParticleId) -> bool) {
+        if self.ends.iter().any(|&end| matches!(end, End::Particle(id) if !exists(id))) {
+            self.dismiss();
+        }
+    }
+
+    /// Draws a marker at each end and a line between them labeled with the distance,
+    /// at the points of the ends in the space of the target, so the measurement
+    /// follows the camera as it zooms.
+    pub fn draw(&self, target: &mut Target<'_>, labels: &mut Labels, points: &[Point], distance: Option<&str>) {
+        if points.is_empty() {
+            return;
+        }
+        let mut mesh = Mesh::new();
+        for &center in points {
+            mesh.stroke(Shape::Circle { center, radius: MARKER_RADIUS }, COLOR, 1.5);
+        }
+        if let [from, to] = *points {
+            mesh.stroke(Shape::Polyline { points: vec![from, to] }, COLOR, 1.);
+        }
+        mesh.draw(target);
+        if let (&[from, to], Some(distance)) = (points, distance) {
+            let middle = Point::new((from.x + to.x) / 2., (from.y + to.y) / 2.);
+            labels.draw(target, &[Label { point: middle, name: distance, highlighted: true }], 0.);
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use coffee::graphics::{Color, Mesh, Point, Rectangle, Shape, Target, Transformation, Vector as ScreenVector};
+use rayon::prelude::*;
+
+use nbody_simulation::vector::Vector;
+
+/// Frames between each measurement of the bounding box of the particles
+const BOUNDS_INTERVAL: usize = 10;
+/// Most particles drawn as dots, every nth particle is drawn when there are more
+const MAX_DOTS: usize = 2000;
+/// Pixels between the minimap and the edges of the window
+const MARGIN: f32 = 10.;
+/// Fraction of the bounding box added around it so particles on the edge aren't drawn on the border
+const PADDING: f32 = 0.05;
+
+/// Small map in the top right corner of the window showing where every particle
+/// is, with the area the camera sees outlined. Clicking the map moves the camera
+/// to the position clicked.
+pub struct Minimap {
+    /// Width and height of the minimap in pixels
+    size: f32,
+    /// Area of the world shown, the bounding box of the particles when it was last measured
+    bounds: Option<Rectangle<f32>>,
+    frames: usize,
+}
+
+impl Minimap {
+    pub fn new(size: f32) -> Self {
+        Minimap { size, bounds: None, frames: 0 }
+    }
+
+    pub fn set_size(&mut self, size: f32) {
+        self.size = size;
+    }
+
+    /// Area of the window the minimap covers.
+    pub fn area(&self, window_width: f32) -> Rectangle<f32> {
+        Rectangle { x: window_width - MARGIN - self.size, y: MARGIN, width: self.size, height: self.size }
+    }
+
+    /// Position in the world under a point of the window, or None if the point isn't on the minimap.
+    pub fn world_position(&self, point: Point, window_width: f32) -> Option<Point> {
+        let (scale, origin) = self.projection(window_width)?;
+        self.area(window_width).contains(point).then(|| Point::new((point.x - origin.x) / scale, (point.y - origin.y) / scale))
+    }
+
+    /// Measures the bounding box of the positions every BOUNDS_INTERVAL frames
+    /// and draws the minimap with the viewport, the area of the world the camera
+    /// sees. The target is offset by the given camera offset.
+    pub fn draw<T: Sync>(&mut self, target: &mut Target<'_>, camera_offset: ScreenVector, items: &[T], position: impl Fn(&T) -> Vector + Sync, viewport: Rectangle<f32>, window_width: f32) {
+        if self.bounds.is_none() || self.frames.is_multiple_of(BOUNDS_INTERVAL) {
+            self.bounds = bounding_box(items, &position);
+        }
+        self.frames += 1;
+
+        let area = self.area(window_width);
+        let mut mesh = Mesh::new();
+        mesh.fill(Shape::Rectangle(area), Color::new(0., 0., 0., 0.7));
+        mesh.stroke(Shape::Rectangle(area), Color::new(0.6, 0.6, 0.6, 1.), 1.);
+        if let Some((scale, origin)) = self.projection(window_width) {
+            let to_map = |x: f32, y: f32| Point::new(origin.x + x * scale, origin.y + y * scale);
+            for item in items.iter().step_by((items.len() / MAX_DOTS).max(1)) {
+                let position = position(item);
+                let dot = to_map(position.x as f32, position.y as f32);
+                if area.contains(dot) {
+                    mesh.fill(Shape::Rectangle(Rectangle { x: dot.x - 1., y: dot.y - 1., width: 2., height: 2. }), Color::WHITE);
+                }
+            }
+
+            // outline the part of the viewport over the minimap
+            let (min, max) = (to_map(viewport.x, viewport.y), to_map(viewport.x + viewport.width, viewport.y + viewport.height));
+            let (left, top) = (min.x.max(area.x), min.y.max(area.y));
+            let (right, bottom) = (max.x.min(area.x + area.width), max.y.min(area.y + area.height));
+            if left < right && top < bottom {
+                mesh.stroke(Shape::Rectangle(Rectangle { x: left, y: top, width: right - left, height: bottom - top }), Color::new(1., 0.8, 0.2, 1.), 1.);
+            }
+        }
+        mesh.draw(&mut target.transform(Transformation::translate(-camera_offset)));
+    }
+
+    /// Pixels of the minimap per meter and the point of the window the origin of
+    /// the world is drawn at, fitting the bounding box into the minimap.
+    fn projection(&self, window_width: f32) -> Option<(f32, Point)> {
+        let bounds = self.bounds?;
+        let area = self.area(window_width);
+        let extent = bounds.width.max(bounds.height).max(f32::EPSILON) * (1. + 2. * PADDING);
+        let scale = area.width / extent;
+        let center = Point::new(bounds.x + bounds.width / 2., bounds.y + bounds.height / 2.);
+        Some((scale, Point::new(area.x + area.width / 2. - center.x * scale, area.y + area.height / 2. - center.y * scale)))
+    }
+}
+
+/// Smallest rectangle containing the positions in the plane of the screen, or None if there are none.
+fn bounding_box<T: Sync>(items: &[T], position: &(impl Fn(&T) -> Vector + Sync)) -> Option<Rectangle<f32>> {
+    let (min, max) = items
+        .par_iter()
+        .map(|item| {
+            let position = position(item);
+            let point = [position.x as f32, position.y as f32];
+            (point, point)
+        })
+        .reduce(
+            || ([f32::INFINITY; 2], [f32::NEG_INFINITY; 2]),
+            |(min_a, max_a), (min_b, max_b)| ([min_a[0].min(min_b[0]), min_a[1].min(min_b[1])], [max_a[0].max(max_b[0]), max_a[1].max(max_b[1])]),
+        );
+    (min[0] <= max[0]).then(|| Rectangle { x: min[0], y: min[1], width: max[0] - min[0], height: max[1] - min[1] })
+}
@@ -0,0 +1,51 @@
+use nbody_simulation::force::Thrust;
+use nbody_simulation::particle::ParticleId;
+use nbody_simulation::scalar::Scalar;
+use nbody_simulation::vector::Vector;
+
+/// Particle steered from the keyboard. Its engine is a [`Thrust`] force of the
+/// world, which is only replaced when the thrust changes, and its fuel is the
+/// change of velocity the engine can still give, used up by each update it runs.
+#[derive(Debug, Clone, Copy)]
+pub struct Ship {
+    pub id: ParticleId,
+    /// Change of velocity in m/s left in the fuel
+    pub fuel: Scalar,
+    /// Acceleration of the engine in m/s^2, zero while it is off
+    pub thrust: Vector,
+}
+
+impl Ship {
+    pub fn new(id: ParticleId, fuel: Scalar) -> Self {
+        Ship { id, fuel, thrust: Vector::ZERO }
+    }
+
+    /// Points the engine along the direction with the given acceleration, or turns
+    /// it off if the direction is zero or the fuel has run out. Returns whether
+    /// the thrust changed.
+    pub fn steer(&mut self, direction: Vector, acceleration: Scalar) -> bool {
+        let thrust = if self.fuel > 0. { direction.normalize_or_zero() * acceleration } else { Vector::ZERO };
+        let changed = thrust != self.thrust;
+        self.thrust = thrust;
+        changed
+    }
+
+    /// Uses the fuel burnt by running the engine for dt seconds, turning it off
+    /// once the fuel runs out. Returns whether the engine was turned off.
+    pub fn burn(&mut self, dt: Scalar) -> bool {
+        self.fuel = (self.fuel - self.thrust.length() * dt).max(0.);
+        self.fuel == 0. && self.steer(Vector::ZERO, 0.)
+    }
+
+    /// Updates of dt seconds the engine can run for until the fuel runs out,
+    /// counting the update which burns the last of it, or None while it is off.
+    pub fn steps_of_fuel(&self, dt: Scalar) -> Option<usize> {
+        let burnt = self.thrust.length() * dt;
+        (burnt > 0.).then(|| (self.fuel / burnt).ceil() as usize)
+    }
+
+    /// Force of the engine while it is running.
+    pub fn force(&self) -> Option<Thrust> {
+        (self.thrust != Vector::ZERO).then_some(Thrust { id: self.id, acceleration: self.thrust })
+    }
+}
@@ -0,0 +1,84 @@
+use std::time::Instant;
+
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+use nbody_simulation::scalar::Scalar;
+use nbody_simulation::vector::{Vector, perpendicular};
+
+/// Share of the direction of the stream taken from the latest cursor motion,
+/// the rest is kept from the previous direction to smooth out jittery motion.
+const DIRECTION_SMOOTHING: Scalar = 0.3;
+
+/// Emits a stream of particles from the cursor while it is held. Particles are
+/// emitted at a rate in real time, so the stream doesn't depend on the frame rate,
+/// in the direction the cursor has recently been moving.
+pub struct Spawner {
+    /// Particles emitted per second
+    pub rate: Scalar,
+    /// Speed of the emitted particles in meters per second
+    pub speed: Scalar,
+    /// Angle in degrees of the cone the particles are emitted in
+    pub spread: Scalar,
+    /// Unit vector in the plane of the screen the stream is emitted in
+    direction: Vector,
+    /// Cursor position and time of the previous call to emit while the stream is running
+    last: Option<(Vector, Instant)>,
+    /// Fraction of a particle owed from time which hasn't added up to a whole particle
+    pending: Scalar,
+    rng: StdRng,
+}
+
+impl Spawner {
+    pub fn new(rate: Scalar, speed: Scalar, spread: Scalar, seed: u64) -> Self {
+        Spawner {
+            rate,
+            speed,
+            spread,
+            direction: Vector::X,
+            last: None,
+            pending: 0.,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Angle in degrees of the direction the stream is emitted in from the x axis.
+    pub fn direction_degrees(&self) -> Scalar {
+        self.direction.y.atan2(self.direction.x).to_degrees()
+    }
+
+    /// Advances the stream to the cursor position at the given time, returning
+    /// the position and velocity of each particle to create. The first call after
+    /// the stream starts emits a particle straight away. The particles are spread
+    /// along the path of the cursor since the last call, and at most a second's
+    /// worth of particles are emitted after a long frame.
+    pub fn emit(&mut self, position: Vector, now: Instant) -> Vec<(Vector, Vector)> {
+        let (last_position, count) = match self.last {
+            Some((last_position, last_time)) => {
+                self.pending = (self.pending + now.duration_since(last_time).as_secs_f64() as Scalar * self.rate).min(self.rate.max(1.));
+                let motion = position - last_position;
+                if motion != Vector::ZERO {
+                    self.direction = self.direction.lerp(motion.normalize(), DIRECTION_SMOOTHING).try_normalize().unwrap_or(self.direction);
+                }
+                (last_position, self.pending.floor())
+            }
+            None => (position, 1.),
+        };
+        self.last = Some((position, now));
+        self.pending -= count.min(self.pending);
+
+        let half_spread = self.spread.to_radians() / 2.;
+        (0..count as usize)
+            .map(|i| {
+                let angle = if half_spread > 0. { self.rng.gen_range(-half_spread..=half_spread) } else { 0. };
+                let velocity = (self.direction * angle.cos() + perpendicular(self.direction) * angle.sin()) * self.speed;
+                (last_position.lerp(position, (i + 1) as Scalar / count), velocity)
+            })
+            .collect()
+    }
+
+    /// Stops the stream, so the next call to emit starts a new stream.
+    pub fn stop(&mut self) {
+        self.last = None;
+        self.pending = 0.;
+    }
+}
@@ -0,0 +1,87 @@
+use coffee::graphics::{Image, Rectangle};
+use coffee::load::Task;
+use image::{imageops, DynamicImage, FilterType, GenericImage, GenericImageView, RgbaImage};
+
+use nbody_simulation::config::{RenderConfig, SpriteSource};
+
+/// Size in pixels of the faded copies of the sprites. Blended sprites are drawn
+/// where many particles overlap, which are small enough on screen that they don't
+/// need the full resolution of the sprite strip.
+const FADED_SPRITE_SIZE: u16 = 128;
+/// Levels of opacity the faded copies of the sprites are made at
+pub const FADE_LEVELS: usize = 4;
+
+/// How the sprites of overlapping particles are combined. Coffee blends every
+/// sprite by its alpha and can't tint the sprites of a batch, so the blended modes
+/// draw faded copies of the sprites which are added below the sprite strip when it
+/// is loaded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlendMode {
+    /// Sprites are drawn as they are in the sprite strip, covering the sprites behind them
+    Opaque,
+    /// Faint sprites which are more opaque the heavier the particle, so overlapping
+    /// sprites add up to the brightness of the mass under them until they saturate
+    Additive,
+    /// Every sprite has the opacity of the blend alpha, so dense regions are brighter
+    Alpha,
+}
+
+impl BlendMode {
+    pub fn next(self) -> Self {
+        match self {
+            BlendMode::Opaque => BlendMode::Additive,
+            BlendMode::Additive => BlendMode::Alpha,
+            BlendMode::Alpha => BlendMode::Opaque,
+        }
+    }
+}
+
+/// Loads the sprite strip with a row of faded copies of its sprites below it for
+/// each level of opacity.
+pub fn load_sheet(render: &RenderConfig) -> Task<Image> {
+    let (path, width, height, blend_alpha) = (render.sprite_file.clone(), render.sprite_width as u32, render.sprite_height as u32, render.blend_alpha);
+    Task::using_gpu(move |gpu| {
+        let strip = image::open(&path)?.to_rgba();
+        Image::from_image(gpu, &DynamicImage::ImageRgba8(faded_sheet(&strip, width, height, blend_alpha)))
+    })
+}
+
+/// Area of the faded copy of a sprite at a level of opacity, and the factor the
+/// scale of the sprite is multiplied by so the copy is drawn at the same size.
+pub fn faded_source(source: SpriteSource, level: usize, render: &RenderConfig) -> (Rectangle<u16>, f32) {
+    let sprite = source.x / render.sprite_width as u16;
+    let rectangle = Rectangle {
+        x: sprite * FADED_SPRITE_SIZE,
+        y: render.sprite_height as u16 + level as u16 * FADED_SPRITE_SIZE,
+        width: FADED_SPRITE_SIZE,
+        height: FADED_SPRITE_SIZE,
+    };
+    (rectangle, render.sprite_width / FADED_SPRITE_SIZE as f32)
+}
+
+/// Opacity of the faded sprites at a level, from the blend alpha at level 0 towards opaque.
+fn opacity(level: usize, blend_alpha: f32) -> f32 {
+    blend_alpha + (1. - blend_alpha) * level as f32 / FADE_LEVELS as f32
+}
+
+/// The strip with the faded copies of its sprites added below it.
+fn faded_sheet(strip: &RgbaImage, sprite_width: u32, sprite_height: u32, blend_alpha: f32) -> RgbaImage {
+    let size = FADED_SPRITE_SIZE as u32;
+    let sprites = strip.width() / sprite_width.max(1);
+    let mut sheet = RgbaImage::new(strip.width().max(sprites * size), strip.height().max(sprite_height + FADE_LEVELS as u32 * size));
+    sheet.copy_from(strip, 0, 0);
+    for sprite in 0..sprites {
+        let cell = strip.view(sprite * sprite_width, 0, sprite_width, sprite_height.min(strip.height())).to_image();
+        let small = imageops::resize(&cell, size, size, FilterType::Triangle);
+        for level in 0..FADE_LEVELS {
+            let opacity = opacity(level, blend_alpha.clamp(0., 1.));
+            let (x, y) = (sprite * size, sprite_height + level as u32 * size);
+            for (dx, dy, pixel) in small.enumerate_pixels() {
+                let mut pixel = *pixel;
+                pixel.data[3] = (pixel.data[3] as f32 * opacity).round() as u8;
+                sheet.put_pixel(x + dx, y + dy, pixel);
+            }
+        }
+    }
+    sheet
+}
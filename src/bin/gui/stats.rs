@@ -0,0 +1,140 @@
+use std::time::{Duration, Instant};
+
+use nbody_simulation::worker_pool::PoolStats;
+
+/// Number of samples the rolling averages are taken over.
+const SAMPLES: usize = 120;
+
+/// The latest samples of a time, overwriting the oldest sample once full.
+struct Samples {
+    /// Times in seconds
+    values: [f64; SAMPLES],
+    len: usize,
+    /// Index the next sample is written to
+    next: usize,
+}
+
+impl Samples {
+    fn new() -> Self {
+        Samples { values: [0.; SAMPLES], len: 0, next: 0 }
+    }
+
+    fn push(&mut self, value: f64) {
+        self.values[self.next] = value;
+        self.next = (self.next + 1) % SAMPLES;
+        self.len = (self.len + 1).min(SAMPLES);
+    }
+
+    fn latest(&self) -> Option<f64> {
+        (self.len > 0).then(|| self.values[(self.next + SAMPLES - 1) % SAMPLES])
+    }
+
+    fn mean(&self) -> Option<f64> {
+        (self.len > 0).then(|| self.values[..self.len].iter().sum::<f64>() / self.len as f64)
+    }
+}
+
+/// Times of recent frames and updates, shown in the performance overlay. Nothing
+/// is recorded while the overlay is hidden, and the samples are cleared when it is
+/// shown so the overlay starts from fresh samples.
+pub struct PerformanceStats {
+    visible: bool,
+    /// Seconds between the starts of consecutive frames and updates
+    frame_intervals: Samples,
+    tick_intervals: Samples,
+    last_frame: Option<Instant>,
+    last_tick: Option<Instant>,
+    /// Seconds taken by the physics of each update and the rendering of each frame
+    physics_times: Samples,
+    render_times: Samples,
+    /// Fraction of the time the threads of the worker pool were busy during each update which used them
+    pool_utilization: Samples,
+    /// Stats of the worker pool after the previous update
+    last_pool: Option<PoolStats>,
+}
+
+impl PerformanceStats {
+    pub fn new() -> Self {
+        PerformanceStats {
+            visible: false,
+            frame_intervals: Samples::new(),
+            tick_intervals: Samples::new(),
+            last_frame: None,
+            last_tick: None,
+            physics_times: Samples::new(),
+            render_times: Samples::new(),
+            pool_utilization: Samples::new(),
+            last_pool: None,
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Shows or hides the overlay, clearing the samples when it is shown.
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+        if self.visible {
+            *self = PerformanceStats { visible: true, ..PerformanceStats::new() };
+        }
+    }
+
+    /// Records a frame which took the duration to render, starting at the given time.
+    pub fn record_frame(&mut self, start: Instant, duration: Duration) {
+        if !self.visible {
+            return;
+        }
+        if let Some(last) = self.last_frame.replace(start) {
+            self.frame_intervals.push(start.duration_since(last).as_secs_f64());
+        }
+        self.render_times.push(duration.as_secs_f64());
+    }
+
+    /// Records an update whose physics took the duration, starting at the given time.
+    pub fn record_tick(&mut self, start: Instant, duration: Duration) {
+        if !self.visible {
+            return;
+        }
+        if let Some(last) = self.last_tick.replace(start) {
+            self.tick_intervals.push(start.duration_since(last).as_secs_f64());
+        }
+        self.physics_times.push(duration.as_secs_f64());
+    }
+
+    /// Records the stats of the worker pool after an update.
+    pub fn record_pool(&mut self, stats: PoolStats) {
+        if !self.visible {
+            return;
+        }
+        if let Some(utilization) = self.last_pool.replace(stats).and_then(|last| stats.utilization_since(&last)) {
+            self.pool_utilization.push(utilization);
+        }
+    }
+
+    /// Frames per second of the latest frame and averaged over the recent frames.
+    pub fn fps(&self) -> Option<(f64, f64)> {
+        Some((1. / self.frame_intervals.latest()?, 1. / self.frame_intervals.mean()?))
+    }
+
+    /// Updates per second averaged over the recent updates.
+    pub fn tick_rate(&self) -> Option<f64> {
+        Some(1. / self.tick_intervals.mean()?)
+    }
+
+    /// Milliseconds taken by the physics of the latest update and the average over the recent updates.
+    pub fn physics_ms(&self) -> Option<(f64, f64)> {
+        Some((self.physics_times.latest()? * 1e3, self.physics_times.mean()? * 1e3))
+    }
+
+    /// Milliseconds taken to render the latest frame and the average over the recent frames.
+    pub fn render_ms(&self) -> Option<(f64, f64)> {
+        Some((self.render_times.latest()? * 1e3, self.render_times.mean()? * 1e3))
+    }
+
+    /// Fraction of the time the threads of the worker pool were busy during the
+    /// latest update which used them and the average over the recent updates.
+    pub fn pool_utilization(&self) -> Option<(f64, f64)> {
+        Some((self.pool_utilization.latest()?, self.pool_utilization.mean()?))
+    }
+}
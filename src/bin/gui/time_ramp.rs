@@ -0,0 +1,54 @@
+use std::time::{Duration, Instant};
+
+use nbody_simulation::scalar::Scalar;
+
+/// Updates a change of the time scale is spread over
+const RAMP_UPDATES: u32 = 10;
+/// How long the time scale is shown on screen after it is changed from the keyboard
+const DISPLAY_TIME: Duration = Duration::from_secs(1);
+
+/// Change of the time scale from the keyboard, spread over [`RAMP_UPDATES`]
+/// updates by the same factor each update, so the time step doesn't jump and
+/// kick the energy of integrators like Euler.
+#[derive(Debug, Default)]
+pub struct TimeScaleRamp {
+    /// Time scale before the change, the time scale it changes to, and the updates of the change run so far
+    ramp: Option<(Scalar, Scalar, u32)>,
+    /// When the time scale was last changed from the keyboard
+    changed_at: Option<Instant>,
+}
+
+impl TimeScaleRamp {
+    /// Starts changing from the current time scale to the target.
+    pub fn start(&mut self, current: Scalar, target: Scalar) {
+        self.ramp = Some((current, target, 0));
+        self.changed_at = Some(Instant::now());
+    }
+
+    /// Time scale being changed to, or else the current time scale, which halving
+    /// and doubling start from so quick presses add up.
+    pub fn target(&self, current: Scalar) -> Scalar {
+        self.ramp.map_or(current, |(_, target, _)| target)
+    }
+
+    /// Time scale of the next update while changing, or None once the change is finished.
+    pub fn next(&mut self) -> Option<Scalar> {
+        let (from, to, update) = self.ramp.as_mut()?;
+        *update += 1;
+        let time_scale = if *update >= RAMP_UPDATES { *to } else { *from * (*to / *from).powf(*update as Scalar / RAMP_UPDATES as Scalar) };
+        if *update >= RAMP_UPDATES {
+            self.ramp = None;
+        }
+        Some(time_scale)
+    }
+
+    /// Stops the change where it is, such as when the time scale is set another way.
+    pub fn cancel(&mut self) {
+        self.ramp = None;
+    }
+
+    /// Whether the time scale was changed recently enough to still be shown.
+    pub fn is_displayed(&self) -> bool {
+        self.changed_at.is_some_and(|changed_at| changed_at.elapsed() < DISPLAY_TIME)
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::{HashMap, VecDeque};
+
+use nbody_simulation::particle::Particle;
+use nbody_simulation::vector::Vector;
+
+/// Recent positions of each particle, kept by the renderer and keyed by particle id
+/// so the trails don't need to be stored or cloned with the particles.
+pub struct Trails {
+    /// Number of positions kept for each particle
+    length: usize,
+    /// Number of calls to record between each recorded position
+    interval: usize,
+    /// Above this many particles trails are not recorded, limiting the memory they use
+    max_particles: usize,
+    ticks: usize,
+    /// Recorded positions of each particle, oldest first, with the tick they were last recorded
+    positions: HashMap<usize, (usize, VecDeque<Vector>)>,
+}
+
+impl Trails {
+    pub fn new(length: usize, interval: usize, max_particles: usize) -> Self {
+        Trails {
+            length,
+            interval: interval.max(1),
+            max_particles,
+            ticks: 0,
+            positions: HashMap::new(),
+        }
+    }
+
+    /// Records the positions of the particles every interval calls. The trails of
+    /// particles which no longer exist are removed, and every trail is cleared if
+    /// there are more particles than the limit.
+    pub fn record(&mut self, particles: &[Particle]) {
+        if particles.len() > self.max_particles {
+            self.clear();
+            return;
+        }
+
+        self.ticks += 1;
+        if !self.ticks.is_multiple_of(self.interval) {
+            return;
+        }
+
+        let (ticks, length) = (self.ticks, self.length);
+        for particle in particles {
+            let (recorded, trail) = self.positions.entry(particle.id).or_insert_with(|| (ticks, VecDeque::with_capacity(length)));
+            *recorded = ticks;
+            if trail.len() == length {
+                trail.pop_front();
+            }
+            trail.push_back(particle.position);
+        }
+        self.positions.retain(|_, (recorded, _)| *recorded == ticks);
+    }
+
+    pub fn clear(&mut self) {
+        self.positions.clear();
+    }
+
+    /// Moves every recorded position by the offset, used when the particles are moved.
+    pub fn translate(&mut self, offset: Vector) {
+        for (_, trail) in self.positions.values_mut() {
+            trail.iter_mut().for_each(|position| *position += offset);
+        }
+    }
+
+    /// Positions of every trail, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &VecDeque<Vector>> {
+        self.positions.values().map(|(_, trail)| trail)
+    }
+
+    pub fn length(&self) -> usize {
+        self.length
+    }
+}
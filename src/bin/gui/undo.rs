@@ -0,0 +1,69 @@
+use std::collections::VecDeque;
+
+use nbody_simulation::particle::{Particle, ParticleId};
+use nbody_simulation::world::World;
+
+/// Ids of the particles spawned by the user, most recent last, which can be
+/// removed again by undoing, and the particles removed by undoing which can
+/// be restored by redoing. Particles added by presets, scenarios or loading a
+/// save are not recorded, so they aren't undone one particle at a time.
+pub struct UndoHistory {
+    /// Most spawned particles which can be undone
+    levels: usize,
+    spawned: VecDeque<ParticleId>,
+    undone: Vec<Particle>,
+}
+
+impl UndoHistory {
+    pub fn new(levels: usize) -> Self {
+        UndoHistory {
+            levels,
+            spawned: VecDeque::new(),
+            undone: Vec::new(),
+        }
+    }
+
+    /// Records a particle spawned by the user, forgetting the oldest spawn if
+    /// there are too many. Particles which were undone can no longer be redone.
+    pub fn spawned(&mut self, id: ParticleId) {
+        self.push_spawned(id);
+        self.undone.clear();
+    }
+
+    /// Removes the most recently spawned particle which still exists, returning
+    /// its id. Particles which were merged into another particle or removed are skipped.
+    pub fn undo(&mut self, world: &mut dyn World) -> Option<ParticleId> {
+        while let Some(id) = self.spawned.pop_back() {
+            let particle = world.particles().iter().find(|particle| particle.id == id).cloned();
+            if let Some(particle) = particle {
+                world.remove_particle(id);
+                self.undone.push(particle);
+                return Some(id);
+            }
+        }
+        None
+    }
+
+    /// Restores the most recently undone particle with the state it was removed in, returning its id.
+    pub fn redo(&mut self, world: &mut dyn World) -> Option<ParticleId> {
+        let particle = self.undone.pop()?;
+        let id = particle.id;
+        world.restore_particle(particle);
+        self.push_spawned(id);
+        Some(id)
+    }
+
+    fn push_spawned(&mut self, id: ParticleId) {
+        if self.spawned.len() == self.levels {
+            self.spawned.pop_front();
+        }
+        self.spawned.push_back(id);
+    }
+
+    /// Forgets every spawned and undone particle, used when the particles are
+    /// replaced and the ids may be reused.
+    pub fn clear(&mut self) {
+        self.spawned.clear();
+        self.undone.clear();
+    }
+}
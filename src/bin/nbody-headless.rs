@@ -0,0 +1,477 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::process;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use nbody_simulation::benchmark::{Statistics, load_report};
+use nbody_simulation::boundary::Boundary;
+use nbody_simulation::clock::{format_time, parse_time};
+use nbody_simulation::collision::{BounceParams, CollisionMode};
+use nbody_simulation::diagnostics::Diagnostics;
+use nbody_simulation::ephemeris::{parse_ephemeris, SOLAR_SYSTEM_J2000};
+use nbody_simulation::force::GravitySolver;
+use nbody_simulation::integrator::{Integrator, EulerIntegrator, VerletIntegrator, Rk4Integrator};
+use nbody_simulation::particle::{Particle, ParticleId, G};
+use nbody_simulation::presets;
+use nbody_simulation::scalar::{PRECISION, Scalar, to_f64};
+use nbody_simulation::trajectory::TrajectoryLogger;
+use nbody_simulation::vector;
+use nbody_simulation::world::{World, Physics, ThreadsWorld, RayonWorld, SequentialWorld};
+
+const USAGE: &str = "Usage: nbody-headless [options]
+
+Options:
+    --particles <count>      Number of randomly placed particles (default 1000)
+    --algorithm <name>       sequential, rayon or threads (default rayon)
+    --integrator <name>      euler, verlet or rk4 (default verlet)
+    --steps <count>          Number of updates to run (default 100)
+    --until <time>           Run until this much time is simulated instead of a number of steps, in
+                             seconds or followed by h, d or y for hours, days or years, such as 10y
+    --warm-start <steps>     Run this many updates before the run starts, without timing or logging
+                             them, so presets can settle first (default 0)
+    --batch <steps>          Run the updates in batches of this many with a single call to the world,
+                             timing each batch instead of each update (default 1)
+    --threads <count>        Threads used by the threads and rayon algorithms, 0 for all cores (default 0)
+    --dt <seconds>           Simulated time of each update (default 1)
+    --softening <meters>     Softening length (default 5)
+    --solver <name>          Gravity solver, direct, barnes-hut, particle-mesh or pairwise (default direct)
+    --theta <value>          Accuracy of the Barnes-Hut approximation of gravity from 0 to 1, lower
+                             values are slower but more accurate (default 0.5)
+    --mesh-cells <count>     Grid points along each axis of the particle mesh, rounded up to a power
+                             of two (default 64)
+    --seed <number>          Seed for placing the particles (default 0)
+    --preset <name>          Start from solar, ephemeris, two-body, galaxy, collision, figure-eight, lagrange
+                             or circumbinary instead of random particles. The galaxies use --particles and
+                             --seed, and ephemeris is the solar system and Moon at J2000
+    --output <file>          Write the final particles to a CSV file
+    --trajectory <file>      Log the positions and velocities of the particles to a CSV file
+    --trajectory-interval <steps>
+                             Steps between each logged step of the trajectories (default 1)
+    --trajectory-ids <ids>   Comma separated ids of the particles to log (default every particle)
+    --sweep <counts>         Run every algorithm with each of a comma separated list of particle counts
+    --report <file>          CSV file the results of a sweep are written to (default benchmark.csv)
+    --show-report <file>     Print a benchmark report saved by the simulation and exit";
+
+/// Counts the allocations made by the program, so the number of allocations
+/// made by each step can be reported.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Radius and mass of the galaxy presets, the same as the defaults of the simulation.
+const GALAXY_RADIUS: Scalar = 1000.;
+const GALAXY_MASS: Scalar = 1e14;
+
+/// Above this many particles the energy isn't measured, as it takes O(n²).
+const ENERGY_MAX_PARTICLES: usize = 10_000;
+
+/// Algorithms run by a sweep, in order.
+const ALGORITHMS: [&str; 3] = ["sequential", "rayon", "threads"];
+
+/// When a run ends, either after a number of steps or once the clock of the world
+/// reaches a simulated time.
+#[derive(Clone, Copy)]
+enum Stop {
+    Steps(usize),
+    Time(f64),
+}
+
+impl Stop {
+    /// Whether a run which has taken the given number of steps of dt is over. A run
+    /// until a time ends on the first step reaching it, allowing for the rounding of
+    /// the clock so a whole number of steps isn't followed by one more.
+    fn reached(self, steps: usize, world: &dyn World, dt: Scalar) -> bool {
+        match self {
+            Stop::Steps(count) => steps >= count,
+            Stop::Time(time) => world.time() >= time - to_f64(dt) * 1e-6,
+        }
+    }
+
+    /// Number of steps of dt left in a run which has taken the given number of steps,
+    /// at least one if the run isn't over.
+    fn remaining(self, steps: usize, world: &dyn World, dt: Scalar) -> usize {
+        match self {
+            Stop::Steps(count) => count.saturating_sub(steps),
+            Stop::Time(time) => ((time - world.time()) / to_f64(dt) - 1e-6).ceil().max(1.) as usize,
+        }
+    }
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stop::Steps(count) => write!(f, "{} steps", count),
+            Stop::Time(time) => write!(f, "{} of simulated time", format_time(*time)),
+        }
+    }
+}
+
+/// Options for a headless run, read from the command line arguments.
+struct Options {
+    particles: usize,
+    algorithm: String,
+    integrator: String,
+    steps: usize,
+    until: Option<f64>,
+    warm_start: usize,
+    batch: usize,
+    threads: usize,
+    dt: Scalar,
+    softening: Scalar,
+    solver: GravitySolver,
+    theta: Scalar,
+    mesh_cells: usize,
+    seed: u64,
+    preset: Option<String>,
+    output: Option<String>,
+    trajectory: Option<String>,
+    trajectory_interval: usize,
+    trajectory_ids: Option<Vec<ParticleId>>,
+    sweep: Option<Vec<usize>>,
+    report: String,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+        let mut options = Options {
+            particles: 1000,
+            algorithm: String::from("rayon"),
+            integrator: String::from("verlet"),
+            steps: 100,
+            until: None,
+            warm_start: 0,
+            batch: 1,
+            threads: 0,
+            dt: 1.,
+            softening: 5.,
+            solver: GravitySolver::Direct,
+            theta: 0.5,
+            mesh_cells: 64,
+            seed: 0,
+            preset: None,
+            output: None,
+            trajectory: None,
+            trajectory_interval: 1,
+            trajectory_ids: None,
+            sweep: None,
+            report: String::from("benchmark.csv"),
+        };
+
+        while let Some(arg) = args.next() {
+            if arg == "--help" || arg == "-h" {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            let value = args.next().ok_or(format!("Missing value for '{}'", arg))?;
+            match arg.as_str() {
+                "--particles" => options.particles = parse_value(&arg, &value)?,
+                "--algorithm" => options.algorithm = value.to_lowercase(),
+                "--integrator" => options.integrator = value.to_lowercase(),
+                "--steps" => options.steps = parse_value(&arg, &value)?,
+                "--until" => options.until = Some(parse_time(&value).ok_or(format!("Invalid value '{}' for '{}'", value, arg))?),
+                "--warm-start" => options.warm_start = parse_value(&arg, &value)?,
+                "--batch" => options.batch = parse_value(&arg, &value)?,
+                "--threads" => options.threads = parse_value(&arg, &value)?,
+                "--dt" => options.dt = parse_value(&arg, &value)?,
+                "--softening" => options.softening = parse_value(&arg, &value)?,
+                "--solver" => options.solver = value.parse()?,
+                "--theta" => options.theta = parse_value(&arg, &value)?,
+                "--mesh-cells" => options.mesh_cells = parse_value(&arg, &value)?,
+                "--seed" => options.seed = parse_value(&arg, &value)?,
+                "--preset" => options.preset = Some(value.to_lowercase()),
+                "--output" => options.output = Some(value),
+                "--trajectory" => options.trajectory = Some(value),
+                "--trajectory-interval" => options.trajectory_interval = parse_value(&arg, &value)?,
+                "--trajectory-ids" => {
+                    let ids = value.split(',').map(|id| parse_value(&arg, id.trim())).collect::<Result<Vec<ParticleId>, _>>()?;
+                    options.trajectory_ids = Some(ids);
+                }
+                "--sweep" => {
+                    let counts = value.split(',').map(|count| parse_value(&arg, count.trim())).collect::<Result<Vec<usize>, _>>()?;
+                    options.sweep = Some(counts);
+                }
+                "--report" => options.report = value,
+                "--show-report" => {
+                    let report = load_report(&value).map_err(|error| format!("Failed to read report '{}': {}", value, error))?;
+                    print!("{}", report);
+                    process::exit(0);
+                }
+                _ => return Err(format!("Unknown option '{}'", arg)),
+            }
+        }
+        if options.until.is_some() && options.dt <= 0. {
+            return Err(String::from("--until needs a positive --dt"));
+        }
+        if options.batch == 0 {
+            return Err(String::from("--batch needs at least one step"));
+        }
+        if options.batch > 1 && options.trajectory.is_some() {
+            return Err(String::from("--trajectory logs every update, so it can't be used with --batch"));
+        }
+        Ok(options)
+    }
+
+    fn stop(&self) -> Stop {
+        match self.until {
+            Some(time) => Stop::Time(time),
+            None => Stop::Steps(self.steps),
+        }
+    }
+
+    /// Particles of the chosen preset, or None if the particles should be placed randomly.
+    fn preset_particles(&self) -> Result<Option<Vec<Particle>>, String> {
+        let Some(preset) = &self.preset else {
+            return Ok(None);
+        };
+        Ok(Some(match preset.as_str() {
+            "solar" => presets::solar_system(),
+            "ephemeris" => parse_ephemeris(SOLAR_SYSTEM_J2000)?.particles(false).0,
+            "two-body" => presets::two_body(),
+            "galaxy" => presets::galaxy(self.particles, GALAXY_RADIUS, GALAXY_MASS, self.seed),
+            "collision" => presets::galaxy_collision(self.particles, GALAXY_RADIUS, GALAXY_MASS, self.seed),
+            "figure-eight" => presets::figure_eight(),
+            "lagrange" => presets::lagrange_triangle(),
+            "circumbinary" => presets::circumbinary(),
+            name => return Err(format!("Unknown preset '{}'", name)),
+        }))
+    }
+
+    fn create_world(&self, algorithm: &str) -> Result<Box<dyn World>, String> {
+        let integrator: Box<dyn Integrator> = match self.integrator.as_str() {
+            "euler" => Box::new(EulerIntegrator),
+            "verlet" => Box::new(VerletIntegrator),
+            "rk4" => Box::new(Rk4Integrator),
+            name => return Err(format!("Unknown integrator '{}'", name)),
+        };
+        Ok(match algorithm {
+            "sequential" => Box::new(SequentialWorld::new(Vec::new(), integrator, self.physics())),
+            "rayon" => Box::new(RayonWorld::with_threads(Vec::new(), integrator, self.physics(), self.threads)),
+            "threads" => Box::new(ThreadsWorld::new(self.threads, Vec::new(), integrator, self.physics())),
+            name => return Err(format!("Unknown algorithm '{}'", name)),
+        })
+    }
+
+    fn physics(&self) -> Physics {
+        Physics {
+            softening: self.softening,
+            gravitational_constant: G,
+            gravity_multiplier: 1.,
+            density: 1000.,
+            collision_mode: CollisionMode::None,
+            bounce: BounceParams::default(),
+            boundary: Boundary::default(),
+            cell_size: 10.,
+            adaptive_timestep: false,
+            max_substeps: 1,
+            timestep_accuracy: 0.1,
+            gravity_solver: self.solver,
+            barnes_hut_theta: self.theta,
+            mesh_cells: self.mesh_cells,
+        }
+    }
+}
+
+fn parse_value<T: FromStr>(arg: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("Invalid value '{}' for '{}'", value, arg))
+}
+
+fn main() {
+    let options = Options::parse(std::env::args().skip(1)).unwrap_or_else(|error| {
+        eprintln!("{}\n\n{}", error, USAGE);
+        process::exit(2);
+    });
+    if let Some(counts) = &options.sweep {
+        sweep(&options, counts);
+        return;
+    }
+    let mut world = options.create_world(&options.algorithm).unwrap_or_else(|error| {
+        eprintln!("{}\n\n{}", error, USAGE);
+        process::exit(2);
+    });
+    match options.preset_particles() {
+        Ok(Some(particles)) => world.set_particles(particles),
+        Ok(None) => world.set_particles(presets::random(options.particles, options.seed)),
+        Err(error) => {
+            eprintln!("{}\n\n{}", error, USAGE);
+            process::exit(2);
+        }
+    }
+
+    if options.warm_start > 0 {
+        let start = Instant::now();
+        world.advance(options.warm_start, options.dt);
+        println!("Warm started with {} steps in {:.3?}, {} simulated", options.warm_start, start.elapsed(), format_time(world.time()));
+    }
+
+    println!(
+        "Running {} particles for {} with the {} algorithm ({} thread(s)) and {} integrator in {}",
+        world.len(), options.stop(), options.algorithm, world.num_threads(), options.integrator, PRECISION,
+    );
+
+    let mut trajectory = options.trajectory.as_ref().map(|path| {
+        TrajectoryLogger::new(path, options.trajectory_interval, options.trajectory_ids.clone(), usize::MAX).unwrap_or_else(|error| {
+            eprintln!("Failed to create trajectory file '{}': {}", path, error);
+            process::exit(1);
+        })
+    });
+    if let Some(logger) = &mut trajectory {
+        logger.record(0, &world.particles());
+    }
+
+    let force = options.physics().force_params();
+    let initial = Diagnostics::measure(&world.particles(), force, ENERGY_MAX_PARTICLES);
+    let start = Instant::now();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let step_times = run_steps(world.as_mut(), options.stop(), options.dt, options.batch, trajectory.as_mut());
+    // other than the vector of step times and any trajectory buffers, every allocation was made by the world
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations - 1;
+    let total = start.elapsed();
+
+    if let Some(stats) = Statistics::from_samples(&step_times) {
+        println!("Total time: {:.3?}", total);
+        println!("Step time: mean {:.3?}, min {:.3?}, max {:.3?}, p95 {:.3?}", stats.mean, stats.min, stats.max, stats.p95);
+        println!("Steps per second: {:.2}", step_times.len() as f64 / total.as_secs_f64());
+        println!("Allocations per step: {:.1}", allocations as f64 / step_times.len() as f64);
+    }
+    println!("Simulated time: {} in {} steps", format_time(world.time()), step_times.len());
+    let last = Diagnostics::measure(&world.particles(), force, ENERGY_MAX_PARTICLES);
+    if let (Some(initial), Some(last)) = (initial.total_energy(), last.total_energy()) {
+        // without particles or with a single one at rest there is no energy to compare the change with
+        if initial == 0. {
+            println!("Energy change: {:.3e} J (relative change n/a)", last - initial);
+        } else {
+            println!("Relative energy change: {:.3e}", (last - initial) / initial.abs());
+        }
+    }
+
+    if let (Some(logger), Some(path)) = (trajectory, &options.trajectory) {
+        if let Err(error) = logger.finish() {
+            eprintln!("Failed to write trajectories to '{}': {}", path, error);
+            process::exit(1);
+        }
+        println!("Wrote trajectories to {}", path);
+    }
+
+    if let Some(path) = &options.output {
+        if let Err(error) = write_particles(world.as_ref(), path) {
+            eprintln!("Failed to write particles to '{}': {}", path, error);
+            process::exit(1);
+        }
+        println!("Wrote final particles to {}", path);
+    }
+}
+
+/// Runs the simulation until it stops, timing each step and logging the trajectories
+/// after each step if there is a trajectory logger. Logging isn't included in the step times.
+/// With a batch of more than one step the steps are run by advancing the world a batch at
+/// a time, and each step of a batch is given an equal share of its time.
+fn run_steps(world: &mut dyn World, stop: Stop, dt: Scalar, batch: usize, mut trajectory: Option<&mut TrajectoryLogger>) -> Vec<Duration> {
+    let mut step_times = Vec::with_capacity(match stop {
+        Stop::Steps(count) => count,
+        Stop::Time(time) => ((time - world.time()) / to_f64(dt)).ceil().max(0.) as usize,
+    });
+    while !stop.reached(step_times.len(), world, dt) {
+        let step_start = Instant::now();
+        if batch > 1 {
+            let steps = stop.remaining(step_times.len(), world, dt).min(batch);
+            world.advance(steps, dt);
+            let step_time = step_start.elapsed() / steps as u32;
+            step_times.extend(std::iter::repeat_n(step_time, steps));
+            continue;
+        }
+        world.update(dt);
+        step_times.push(step_start.elapsed());
+        if let Some(logger) = &mut trajectory {
+            logger.record(step_times.len() as u64, &world.particles());
+        }
+    }
+    step_times
+}
+
+/// Runs every algorithm with each particle count, printing a table of the
+/// step times and writing it to the report file. Each run uses the same
+/// particles for a given count, and its world is dropped before the next run
+/// so the threads of the threads algorithm are stopped.
+fn sweep(options: &Options, counts: &[usize]) {
+    println!("Running each algorithm for {} with the {} integrator", options.stop(), options.integrator);
+    println!("{:<12}{:>10}{:>9}{:>14}{:>14}{:>14}{:>14}", "algorithm", "particles", "threads", "mean", "min", "max", "p95");
+
+    let mut rows = Vec::new();
+    for algorithm in ALGORITHMS {
+        for &count in counts {
+            let mut world = options.create_world(algorithm).unwrap_or_else(|error| {
+                eprintln!("{}\n\n{}", error, USAGE);
+                process::exit(2);
+            });
+            world.set_particles(presets::random(count, options.seed));
+            let threads = world.num_threads();
+            let step_times = run_steps(world.as_mut(), options.stop(), options.dt, options.batch, None);
+            drop(world);
+
+            if let Some(stats) = Statistics::from_samples(&step_times) {
+                println!(
+                    "{:<12}{:>10}{:>9}{:>14.3?}{:>14.3?}{:>14.3?}{:>14.3?}",
+                    algorithm, count, threads, stats.mean, stats.min, stats.max, stats.p95,
+                );
+                rows.push((algorithm, count, threads, stats));
+            }
+        }
+    }
+
+    if let Err(error) = write_report(&options.report, &rows) {
+        eprintln!("Failed to write report to '{}': {}", options.report, error);
+        process::exit(1);
+    }
+    println!("Wrote report to {}", options.report);
+}
+
+/// Writes the step times of each run of a sweep to a CSV file, in milliseconds.
+fn write_report(path: &str, rows: &[(&str, usize, usize, Statistics)]) -> std::io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    writeln!(file, "algorithm,particles,threads,mean_ms,min_ms,max_ms,p95_ms")?;
+    for (algorithm, count, threads, stats) in rows {
+        writeln!(
+            file,
+            "{},{},{},{},{},{},{}",
+            algorithm, count, threads,
+            stats.mean.as_secs_f64() * 1e3, stats.min.as_secs_f64() * 1e3, stats.max.as_secs_f64() * 1e3, stats.p95.as_secs_f64() * 1e3,
+        )?;
+    }
+    file.flush()
+}
+
+/// Writes the particles of the world to a CSV file.
+fn write_particles(world: &dyn World, path: &str) -> std::io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    writeln!(file, "id,{},{},mass", vector::csv_header(""), vector::csv_header("v"))?;
+    for particle in world.particles().iter() {
+        write!(file, "{}", particle.id)?;
+        vector::write_csv(&mut file, particle.position.to_array())?;
+        vector::write_csv(&mut file, particle.velocity.to_array())?;
+        writeln!(file, ",{}", particle.mass)?;
+    }
+    file.flush()
+}
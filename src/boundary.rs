@@ -0,0 +1,183 @@
+use std::str::FromStr;
+
+use crate::particle::Particle;
+use crate::scalar::Scalar;
+use crate::vector::{self, DIMENSIONS, Vector};
+
+/// What happens to particles reaching the edge of the world.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BoundaryMode {
+    /// The world has no edge
+    None,
+    /// Particles leaving one side of the box enter from the opposite side
+    Periodic,
+    /// Particles bounce off the walls of the box
+    Reflective,
+}
+
+impl BoundaryMode {
+    /// The mode that follows this one when cycling through the modes.
+    pub fn next(self) -> Self {
+        match self {
+            BoundaryMode::None => BoundaryMode::Periodic,
+            BoundaryMode::Periodic => BoundaryMode::Reflective,
+            BoundaryMode::Reflective => BoundaryMode::None,
+        }
+    }
+}
+
+impl FromStr for BoundaryMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(BoundaryMode::None),
+            "periodic" => Ok(BoundaryMode::Periodic),
+            "reflective" => Ok(BoundaryMode::Reflective),
+            _ => Err(format!("Unknown boundary mode '{}', expected none, periodic or reflective", s)),
+        }
+    }
+}
+
+/// Box centered on the origin which the particles are kept inside of.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Boundary {
+    pub mode: BoundaryMode,
+    /// Width, height and in three dimensions depth of the box in meters
+    pub size: Vector,
+    /// Fraction of the speed into a wall kept when bouncing off it with reflective walls
+    pub restitution: Scalar,
+    /// Whether forces in a periodic box act across the edges along the shortest
+    /// separation between the particles and their nearest images
+    pub minimum_image: bool,
+}
+
+impl Default for Boundary {
+    fn default() -> Self {
+        Boundary { mode: BoundaryMode::None, size: Vector::splat(1.0e4), restitution: 1., minimum_image: true }
+    }
+}
+
+impl Boundary {
+    /// Corner of the box with the smallest coordinates.
+    pub fn min(&self) -> Vector {
+        -self.size / 2.
+    }
+
+    /// Corner of the box with the largest coordinates.
+    pub fn max(&self) -> Vector {
+        self.size / 2.
+    }
+
+    /// Size of the box when forces use the minimum image convention, used to
+    /// wrap the separation between particles.
+    pub fn period(&self) -> Option<Vector> {
+        (self.mode == BoundaryMode::Periodic && self.minimum_image).then_some(self.size)
+    }
+
+    /// Moves particles outside the box back inside, wrapping them to the
+    /// opposite side or reflecting them off the walls. Fixed particles are left in place.
+    pub fn apply(&self, particles: &mut [Particle]) {
+        let (min, max) = (self.min(), self.max());
+        let moving = particles.iter_mut().filter(|particle| !particle.fixed);
+        match self.mode {
+            BoundaryMode::None => (),
+            BoundaryMode::Periodic => {
+                for particle in moving {
+                    let offset = particle.position - min;
+                    particle.position = min + vector::map(offset, |axis, offset| offset.rem_euclid(self.size[axis]));
+                }
+            }
+            BoundaryMode::Reflective => {
+                for particle in moving {
+                    for axis in 0..DIMENSIONS {
+                        let (position, velocity) = (&mut particle.position[axis], &mut particle.velocity[axis]);
+                        if *position < min[axis] {
+                            *position = (2. * min[axis] - *position).min(max[axis]);
+                            *velocity = velocity.abs() * self.restitution;
+                        } else if *position > max[axis] {
+                            *position = (2. * max[axis] - *position).max(min[axis]);
+                            *velocity = -velocity.abs() * self.restitution;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integrator::VerletIntegrator;
+    use crate::particle::{ForceParams, ParticleId};
+    use crate::world::{Physics, RayonWorld, SequentialWorld, ThreadsWorld, World};
+
+    fn boundary(mode: BoundaryMode, restitution: Scalar) -> Boundary {
+        Boundary { mode, size: vector::with_depth(100., 50., 20.), restitution, minimum_image: true }
+    }
+
+    fn particle(id: usize, position: Vector, velocity: Vector) -> Particle {
+        Particle::new(ParticleId::new(id), position, velocity, 1., 1.)
+    }
+
+    #[test]
+    fn periodic_boundary_wraps_positions_into_the_box() {
+        let boundary = boundary(BoundaryMode::Periodic, 1.);
+        let velocity = vector::planar(3., -2.);
+        let mut particles = vec![
+            particle(0, vector::with_depth(260., -80., 35.), velocity),
+            particle(1, vector::with_depth(-10., 5., 1.), velocity),
+            particle(2, vector::planar(500., 0.), velocity),
+        ];
+        particles[2].fixed = true;
+        boundary.apply(&mut particles);
+        // 260 - 3 * 100, -80 + 2 * 50 and 35 - 2 * 20 are the same places in the box
+        assert!((particles[0].position - vector::with_depth(-40., 20., -5.)).length() <= 1e3 * Scalar::EPSILON, "{:?}", particles[0].position);
+        assert_eq!(particles[1].position, vector::with_depth(-10., 5., 1.));
+        assert_eq!(particles[2].position, vector::planar(500., 0.));
+        assert!(particles.iter().all(|particle| particle.velocity == velocity));
+    }
+
+    #[test]
+    fn reflective_boundary_bounces_particles_off_the_walls() {
+        let boundary = boundary(BoundaryMode::Reflective, 0.5);
+        let mut particles = vec![particle(0, vector::planar(55., -30.), vector::planar(4., -6.)), particle(1, vector::planar(10., 0.), vector::planar(4., -6.))];
+        boundary.apply(&mut particles);
+        assert_eq!((particles[0].position, particles[0].velocity), (vector::planar(45., -20.), vector::planar(-2., 3.)));
+        assert_eq!((particles[1].position, particles[1].velocity), (vector::planar(10., 0.), vector::planar(4., -6.)));
+    }
+
+    #[test]
+    fn minimum_image_is_only_used_in_a_periodic_box() {
+        let periodic = boundary(BoundaryMode::Periodic, 1.);
+        assert_eq!(periodic.period(), Some(periodic.size));
+        assert_eq!(Boundary { minimum_image: false, ..periodic }.period(), None);
+        assert_eq!(boundary(BoundaryMode::Reflective, 1.).period(), None);
+        // the nearest image of a particle 90 m away across the box is 10 m away on the other side
+        let params = ForceParams { period: periodic.period(), ..ForceParams::softened(0.) };
+        assert_eq!(params.separation(vector::planar(45., 0.), vector::planar(-45., 0.)), vector::planar(-10., 0.));
+        assert_eq!(ForceParams::softened(0.).separation(vector::planar(45., 0.), vector::planar(-45., 0.)), vector::planar(90., 0.));
+    }
+
+    #[test]
+    fn every_world_keeps_particles_inside_the_box() {
+        for mode in [BoundaryMode::Periodic, BoundaryMode::Reflective] {
+            let boundary = boundary(mode, 1.);
+            let physics = Physics { gravitational_constant: 0., boundary, ..Physics::default() };
+            let particles: Vec<Particle> = (0..20).map(|id| particle(id, Vector::ZERO, vector::planar(id as Scalar * 7. - 70., 30. - id as Scalar * 3.))).collect();
+            let worlds: Vec<Box<dyn World>> = vec![
+                Box::new(SequentialWorld::new(particles.clone(), Box::new(VerletIntegrator), physics)),
+                Box::new(RayonWorld::new(particles.clone(), Box::new(VerletIntegrator), physics)),
+                Box::new(ThreadsWorld::new(3, particles, Box::new(VerletIntegrator), physics)),
+            ];
+            for mut world in worlds {
+                for _ in 0..100 {
+                    world.update(0.1);
+                    let inside = |particle: &Particle| (0..DIMENSIONS).all(|axis| (boundary.min()[axis]..=boundary.max()[axis]).contains(&particle.position[axis]));
+                    assert!(world.particles().iter().all(inside), "{:?} boundary let a particle out", mode);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use std::time::{Duration, Instant};
+
+/// Seconds in an hour
+pub const HOUR: f64 = 3600.;
+/// Seconds in a day
+pub const DAY: f64 = 24. * HOUR;
+/// Seconds in a Julian year of 365.25 days
+pub const YEAR: f64 = 365.25 * DAY;
+
+/// Units a simulated time can be written in, with the number of seconds in each
+const UNITS: [(&str, f64); 4] = [("s", 1.), ("h", HOUR), ("d", DAY), ("y", YEAR)];
+
+/// Simulated time in the largest unit of seconds, hours, days or years it is
+/// at least one of, so both short and long runs are readable.
+pub fn format_time(seconds: f64) -> String {
+    match UNITS.iter().rev().find(|(_, length)| seconds.abs() >= *length) {
+        Some(&(unit, length)) if length > 1. => format!("{:.2} {}", seconds / length, unit),
+        _ => format!("{:.1} s", seconds),
+    }
+}
+
+/// Parses a simulated time in seconds, optionally followed by one of the units
+/// s, h, d or y, such as `3600`, `12h` or `1.5y`.
+pub fn parse_time(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let (number, length) = UNITS
+        .iter()
+        .find_map(|&(unit, length)| text.strip_suffix(unit).map(|number| (number, length)))
+        .unwrap_or((text, 1.));
+    number.trim().parse::<f64>().ok().filter(|seconds| seconds.is_finite()).map(|seconds| seconds * length)
+}
+
+/// Decides which ticks of a game loop running at a fixed rate update the
+/// simulation, so a loop which fell behind real time, such as after a slow
+/// load, a stall or while the window was hidden, catches up on at most the
+/// given amount of real time instead of running every missed update back to
+/// back. Real time fills a budget of at most that much time, which each update
+/// spends a tick of.
+#[derive(Debug)]
+pub struct TickPacer {
+    tick: Duration,
+    max_catch_up: Duration,
+    /// Real time not yet spent on updates
+    budget: Duration,
+    /// When the previous tick was, or None before the first tick
+    last_tick: Option<Instant>,
+}
+
+impl TickPacer {
+    /// Creates a pacer of ticks of the given length, catching up on at most
+    /// max_catch_up of real time, which is at least one tick. The budget starts
+    /// full so the ticks of a loop running on time are never dropped.
+    pub fn new(tick: Duration, max_catch_up: Duration) -> Self {
+        let max_catch_up = max_catch_up.max(tick);
+        TickPacer { tick, max_catch_up, budget: max_catch_up, last_tick: None }
+    }
+
+    /// Whether the tick at the given time should update the simulation.
+    pub fn tick(&mut self, now: Instant) -> bool {
+        if let Some(last_tick) = self.last_tick {
+            self.budget = (self.budget + now.saturating_duration_since(last_tick)).min(self.max_catch_up);
+        }
+        self.last_tick = Some(now);
+        match self.budget.checked_sub(self.tick) {
+            Some(budget) => {
+                self.budget = budget;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Changes the most real time caught up on, keeping at most that much of the budget.
+    pub fn set_max_catch_up(&mut self, max_catch_up: Duration) {
+        self.max_catch_up = max_catch_up.max(self.tick);
+        self.budget = self.budget.min(self.max_catch_up);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+    use crate::integrator::VerletIntegrator;
+    use crate::presets;
+    use crate::vector::{self, Vector};
+    use crate::world::{Physics, SequentialWorld, World};
+
+    #[test]
+    fn times_are_written_in_the_largest_unit_they_reach() {
+        assert_eq!(format_time(42.), "42.0 s");
+        assert_eq!(format_time(36. * 3600.), "1.50 d");
+        assert_eq!(format_time(2.5 * YEAR), "2.50 y");
+    }
+
+    #[test]
+    fn variable_frame_times_update_like_a_fixed_dt_run() {
+        let (tick, dt) = (Duration::from_secs(1) / 60, 3600.);
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut pacer = TickPacer::new(tick, Duration::from_millis(250));
+        let mut world = SequentialWorld::new(presets::solar_system(), Box::new(VerletIntegrator), Physics::default());
+
+        // frames from 5 to 100 ms apart, each running the ticks which fell due since the previous frame
+        let start = Instant::now();
+        let (mut now, mut ticks, mut updates) = (start, 0, 0);
+        for _ in 0..500 {
+            now += Duration::from_micros(rng.gen_range(5_000..100_000));
+            while start + tick * (ticks + 1) <= now {
+                ticks += 1;
+                if pacer.tick(now) {
+                    world.update(dt);
+                    updates += 1;
+                }
+            }
+        }
+        assert_eq!(updates, ticks, "frames closer together than the catch up limit drop no ticks");
+
+        let mut headless = SequentialWorld::new(presets::solar_system(), Box::new(VerletIntegrator), Physics::default());
+        for _ in 0..updates {
+            headless.update(dt);
+        }
+        assert_eq!(world.time(), headless.time());
+        for (particle, expected) in world.get_particles().iter().zip(headless.get_particles().iter()) {
+            assert_eq!((particle.position, particle.velocity), (expected.position, expected.velocity), "particle {}", particle.id);
+        }
+    }
+
+    #[test]
+    fn stalls_catch_up_on_at_most_the_limit() {
+        let tick = Duration::from_secs(1) / 60;
+        let start = Instant::now();
+        // ticks run at the given time until one is dropped
+        let burst = |pacer: &mut TickPacer, now: Instant| (0..1000).take_while(|_| pacer.tick(now)).count();
+        for stall in [Duration::from_millis(500), Duration::from_secs(5), Duration::from_secs(3600)] {
+            let mut pacer = TickPacer::new(tick, Duration::from_millis(100));
+            assert!(pacer.tick(start));
+            assert_eq!(burst(&mut pacer, start + stall), 6, "stall of {:?}", stall);
+            // the next tick on time runs again, with nothing left over from the stall
+            assert_eq!(burst(&mut pacer, start + stall + tick), 1, "stall of {:?}", stall);
+        }
+
+        // a limit below one tick still lets one update through after a stall
+        let mut pacer = TickPacer::new(tick, Duration::ZERO);
+        assert!(pacer.tick(start));
+        assert_eq!(burst(&mut pacer, start + Duration::from_secs(5)), 1);
+
+        // lowering the limit drops the budget already built up beyond it
+        let mut pacer = TickPacer::new(tick, Duration::from_secs(1));
+        assert!(pacer.tick(start));
+        assert!(pacer.tick(start + Duration::from_secs(5)));
+        pacer.set_max_catch_up(tick * 2);
+        assert_eq!(burst(&mut pacer, start + Duration::from_secs(5)), 2);
+        assert_eq!(burst(&mut pacer, start + Duration::from_secs(10)), 2);
+    }
+
+    #[test]
+    fn world_resumes_from_where_it_stalled() {
+        let tick = Duration::from_secs(1) / 60;
+        let mut pacer = TickPacer::new(tick, Duration::from_millis(100));
+        let mut world = SequentialWorld::new(Vec::new(), Box::new(VerletIntegrator), Physics::default());
+        world.create_particle(Vector::ZERO, Vector::ZERO, 1.0e12, true);
+        world.create_particle(vector::planar(100., 0.), Vector::ZERO, 1., false);
+
+        let (start, dt) = (Instant::now(), 1.);
+        for step in 0..60 {
+            assert!(pacer.tick(start + tick * step));
+            world.update(dt);
+        }
+        // the loop stalls for 5 seconds, then runs every missed tick at once
+        let (speed, resumed) = (world.particles()[1].velocity.length(), start + tick * 60 + Duration::from_secs(5));
+        let updates = (0..300).filter(|_| pacer.tick(resumed)).count();
+        for _ in 0..updates {
+            world.update(dt);
+        }
+        assert_eq!(updates, 6);
+        // the particle falls towards the fixed particle for no more than 6 updates
+        let acceleration = world.particles()[1].acceleration.length();
+        assert!(world.particles()[1].velocity.length() - speed <= acceleration * 6. * dt);
+    }
+}
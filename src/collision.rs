@@ -0,0 +1,325 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::particle::{Particle, ParticleId};
+use crate::scalar::Scalar;
+use crate::scalar::consts::PI;
+use crate::vector::{self, Vector};
+
+/// How overlapping particles are handled after each update.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CollisionMode {
+    /// Particles pass through each other
+    None,
+    /// Overlapping particles are merged into a single particle
+    Merge,
+    /// Overlapping particles bounce off each other, keeping the share of their
+    /// speed given by the [`BounceParams`] of the physics
+    Bounce,
+}
+
+impl CollisionMode {
+    /// The mode that follows this one when cycling through the modes.
+    pub fn next(self) -> Self {
+        match self {
+            CollisionMode::None => CollisionMode::Merge,
+            CollisionMode::Merge => CollisionMode::Bounce,
+            CollisionMode::Bounce => CollisionMode::None,
+        }
+    }
+}
+
+impl FromStr for CollisionMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(CollisionMode::None),
+            "merge" => Ok(CollisionMode::Merge),
+            "bounce" => Ok(CollisionMode::Bounce),
+            _ => Err(format!("Unknown collision mode '{}', expected none, merge or bounce", s)),
+        }
+    }
+}
+
+/// Fraction of the radius of the smaller particle a bounce has to rise for the
+/// particles to bounce apart, slower collisions leave the particles resting
+/// against each other instead of jittering through ever smaller bounces.
+const RESTING_HEIGHT: Scalar = 0.01;
+
+/// How bouncing particles lose speed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BounceParams {
+    /// Fraction of the speed the particles approach each other with kept as they separate,
+    /// 1 is perfectly elastic and 0 leaves the particles touching
+    pub restitution: Scalar,
+    /// Coefficient of friction limiting how much of the speed the particles slide past each
+    /// other with is lost, as a multiple of the impulse pushing them apart
+    pub friction: Scalar,
+}
+
+impl Default for BounceParams {
+    /// Perfectly elastic collisions without friction.
+    fn default() -> Self {
+        BounceParams { restitution: 1., friction: 0. }
+    }
+}
+
+/// Two particles merged into one, keeping the id of the survivor.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Merge {
+    pub survivor: ParticleId,
+    pub absorbed: ParticleId,
+    /// Mass of the merged particle
+    pub mass: Scalar,
+}
+
+/// Handles the given pairs of overlapping particles according to the collision
+/// mode, returning the merges it made.
+pub fn resolve_collisions(particles: &mut Vec<Particle>, pairs: &[(usize, usize)], mode: CollisionMode, density: Scalar, bounce: BounceParams) -> Vec<Merge> {
+    match mode {
+        CollisionMode::None => Vec::new(),
+        CollisionMode::Merge => merge_pairs(particles, pairs, density),
+        CollisionMode::Bounce => {
+            bounce_pairs(particles, pairs, bounce);
+            Vec::new()
+        }
+    }
+}
+
+/// Merges the given pairs of overlapping particles, returning the merges in
+/// the order they were made. Particles that have already been absorbed are
+/// skipped, any overlaps that remain will be merged on the next update.
+pub fn merge_pairs(particles: &mut Vec<Particle>, pairs: &[(usize, usize)], density: Scalar) -> Vec<Merge> {
+    let mut merges = Vec::new();
+    if pairs.is_empty() {
+        return merges;
+    }
+
+    let mut absorbed = vec![false; particles.len()];
+    for &(i, j) in pairs {
+        if absorbed[i] || absorbed[j] {
+            continue;
+        }
+        let other = particles[j].clone();
+        let before = particles[i].id;
+        particles[i].merge(&other, density);
+        absorbed[j] = true;
+        // the merged particle keeps the id of the heavier particle
+        let survivor = particles[i].id;
+        merges.push(Merge { survivor, absorbed: if survivor == before { other.id } else { before }, mass: particles[i].mass });
+    }
+
+    // remove the absorbed particles
+    let mut index = 0;
+    particles.retain(|_| {
+        index += 1;
+        !absorbed[index - 1]
+    });
+    merges
+}
+
+/// Updates after a split during which its fragments neither merge with nor
+/// bounce off each other, so they can fly apart before they are handled as
+/// overlapping again
+pub const SPLIT_GRACE_UPDATES: usize = 60;
+
+/// Splits the particle into fragments, the opposite of merging. The fragments
+/// share the mass of the particle equally and are spread over a disk the size
+/// of the particle in the plane of the screen, centered on the particle. The
+/// explosion energy in joules is added to the kinetic energy as velocities
+/// pointing away from the center in proportion to the distance from it, so the
+/// fragments keep the momentum and center of mass of the particle. The fragments
+/// keep the acceleration of the particle and are numbered from first_id. Fixed
+/// particles, and splits into fewer than two fragments, give no fragments.
+pub fn split(particle: &Particle, fragments: usize, energy: Scalar, density: Scalar, first_id: ParticleId) -> Vec<Particle> {
+    if particle.fixed || fragments < 2 {
+        return Vec::new();
+    }
+
+    // a sunflower spiral spreads the fragments evenly over the disk, moved so its center of mass is the particle
+    let golden_angle = PI * (3. - Scalar::sqrt(5.));
+    let mut offsets: Vec<Vector> = (0..fragments)
+        .map(|index| {
+            let (radius, angle) = (particle.radius * ((index as Scalar + 0.5) / fragments as Scalar).sqrt(), golden_angle * index as Scalar);
+            vector::planar(radius * angle.cos(), radius * angle.sin())
+        })
+        .collect();
+    let center = offsets.iter().copied().sum::<Vector>() / fragments as Scalar;
+    offsets.iter_mut().for_each(|offset| *offset -= center);
+
+    // 1/2 m s² Σ|offset|² = energy, for velocities s * offset relative to the particle
+    let mass = particle.mass / fragments as Scalar;
+    let spread: Scalar = offsets.iter().map(|offset| offset.length_squared()).sum();
+    let speed = if spread > 0. { (2. * energy.max(0.) / (mass * spread)).sqrt() } else { 0. };
+    offsets
+        .into_iter()
+        .zip(std::iter::successors(Some(first_id), |id| Some(id.next())))
+        .map(|(offset, id)| Particle {
+            acceleration: particle.acceleration,
+            ..Particle::new(id, particle.position + offset, particle.velocity + offset * speed, mass, density)
+        })
+        .collect()
+}
+
+/// Fragments of recent splits, which don't merge with or bounce off the other
+/// fragments of the same split until [`SPLIT_GRACE_UPDATES`] updates have passed.
+#[derive(Clone, Debug, Default)]
+pub struct SplitGrace {
+    /// First fragment of the split each fragment came from and the updates left
+    /// in its grace period, by fragment id
+    fragments: HashMap<ParticleId, (ParticleId, usize)>,
+}
+
+impl SplitGrace {
+    /// Starts the grace period of the fragments of a split.
+    pub fn add(&mut self, fragments: &[ParticleId]) {
+        if let Some(&first) = fragments.first() {
+            self.fragments.extend(fragments.iter().map(|&id| (id, (first, SPLIT_GRACE_UPDATES))));
+        }
+    }
+
+    /// Whether the particles are fragments of the same split still in its grace period.
+    pub fn excludes(&self, a: ParticleId, b: ParticleId) -> bool {
+        matches!((self.fragments.get(&a), self.fragments.get(&b)), (Some((split_a, _)), Some((split_b, _))) if split_a == split_b)
+    }
+
+    /// Removes the pairs of particles at the indices which are in a grace period.
+    pub fn retain_pairs(&self, particles: &[Particle], pairs: &mut Vec<(usize, usize)>) {
+        if !self.fragments.is_empty() {
+            pairs.retain(|&(i, j)| !self.excludes(particles[i].id, particles[j].id));
+        }
+    }
+
+    /// Counts down the grace periods by an update, ending those that are over.
+    pub fn tick(&mut self) {
+        self.fragments.retain(|_, (_, updates)| {
+            *updates = updates.saturating_sub(1);
+            *updates > 0
+        });
+    }
+}
+
+/// Resolves the given pairs of overlapping particles with collisions that
+/// conserve momentum, losing kinetic energy unless they are perfectly elastic.
+pub fn bounce_pairs(particles: &mut [Particle], pairs: &[(usize, usize)], params: BounceParams) {
+    for &(i, j) in pairs {
+        let (left, right) = particles.split_at_mut(j);
+        bounce(&mut left[i], &mut right[0], params);
+    }
+}
+
+/// Applies a collision impulse along the line between the centers of the
+/// particles and a friction impulse against their sliding, and pushes them
+/// apart so they no longer overlap, even when they don't bounce. Fixed
+/// particles act as if they had infinite mass.
+fn bounce(a: &mut Particle, b: &mut Particle, params: BounceParams) {
+    // particles spawned on top of each other have no separation, so pick an arbitrary normal
+    let normal = (b.position - a.position).try_normalize().unwrap_or(Vector::X);
+
+    // share of the collision each particle takes, the lighter particle takes more
+    let total_mass = a.mass + b.mass;
+    let (share_a, share_b) = match (a.fixed, b.fixed) {
+        (false, false) => (b.mass / total_mass, a.mass / total_mass),
+        (true, false) => (0., 1.),
+        (false, true) => (1., 0.),
+        (true, true) => (0., 0.),
+    };
+
+    // push the particles apart
+    let overlap = a.radius + b.radius - a.position.distance(b.position);
+    a.position -= normal * overlap * share_a;
+    b.position += normal * overlap * share_b;
+
+    // only exchange momentum if the particles are moving towards each other
+    let relative_velocity = a.velocity - b.velocity;
+    let closing_speed = relative_velocity.dot(normal);
+    if closing_speed <= 0. {
+        return;
+    }
+
+    // pushing the particles apart lifts them against the acceleration pulling them together, so the speed
+    // gained falling into the overlap is taken back instead of feeding every bounce. Particles which would
+    // then only bounce a tiny height come to rest
+    let acceleration = |particle: &Particle| if particle.fixed { Vector::ZERO } else { particle.acceleration };
+    let closing_acceleration = (acceleration(a) - acceleration(b)).dot(normal).max(0.);
+    let bounce_speed_squared = (closing_speed * params.restitution).powi(2) - 2. * closing_acceleration * overlap.max(0.);
+    let resting_speed_squared = 2. * closing_acceleration * RESTING_HEIGHT * a.radius.min(b.radius);
+    let separating_speed = if bounce_speed_squared > resting_speed_squared { bounce_speed_squared.sqrt() } else { 0. };
+    let normal_impulse = closing_speed + separating_speed;
+    a.velocity -= normal * normal_impulse * share_a;
+    b.velocity += normal * normal_impulse * share_b;
+
+    // friction slows the sliding by at most the coefficient times the normal impulse, without reversing it
+    let sliding = relative_velocity - normal * closing_speed;
+    if let Some(direction) = sliding.try_normalize() {
+        let friction_impulse = (params.friction * normal_impulse).min(sliding.length());
+        a.velocity -= direction * friction_impulse * share_a;
+        b.velocity += direction * friction_impulse * share_b;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integrator::VerletIntegrator;
+    use crate::scalar::to_f64;
+    use crate::world::{Physics, SequentialWorld, World};
+
+    #[test]
+    fn fragments_keep_the_mass_momentum_and_center_of_their_particle() {
+        let particle = Particle::new(ParticleId::new(3), vector::planar(10., -5.), vector::planar(2., 1.), 8e6, 1.);
+        let fragments = split(&particle, 7, 1e6, 1., ParticleId::new(10));
+        assert_eq!(fragments.iter().map(|fragment| fragment.id.get()).collect::<Vec<_>>(), (10..17).collect::<Vec<_>>());
+
+        let mass: f64 = fragments.iter().map(|fragment| to_f64(fragment.mass)).sum();
+        let momentum: Vector = fragments.iter().map(|fragment| fragment.velocity * fragment.mass).sum();
+        let center: Vector = fragments.iter().map(|fragment| fragment.position * fragment.mass).sum::<Vector>() / particle.mass;
+        assert!((mass / to_f64(particle.mass) - 1.).abs() < 1e-6);
+        assert!((momentum - particle.velocity * particle.mass).length() < 1e-4 * (particle.velocity * particle.mass).length());
+        assert!(center.distance(particle.position) < 1e-4);
+
+        // the kinetic energy gained is the explosion energy
+        let energy = |particles: &[Particle]| particles.iter().map(|p| 0.5 * to_f64(p.mass) * to_f64(p.velocity.length_squared())).sum::<f64>();
+        assert!((energy(&fragments) - energy(&[particle]) - 1e6).abs() < 1.);
+    }
+
+    #[test]
+    fn dropped_particle_bounces_to_restitution_squared_of_its_height() {
+        let restitution = 0.8;
+        // a surface gravity of about 10 m/s^2
+        let physics = Physics {
+            gravitational_constant: 4e-4,
+            softening: 0.,
+            collision_mode: CollisionMode::Bounce,
+            bounce: BounceParams { restitution, friction: 0. },
+            ..Physics::default()
+        };
+        let mut world = SequentialWorld::new(Vec::new(), Box::new(VerletIntegrator), physics);
+        world.create_particle(Vector::ZERO, Vector::ZERO, 1e6, true);
+        let planet = world.particles()[0].radius;
+        world.create_particle(vector::planar(0., planet + 0.1), Vector::ZERO, 1e-3, false);
+        let height = |world: &SequentialWorld| {
+            let particles = world.particles();
+            particles[1].position.length() - planet - particles[1].radius
+        };
+
+        // the highest point between each pair of bounces
+        let (mut peaks, mut previous, mut rising) = (Vec::new(), height(&world), false);
+        for _ in 0..10_000 {
+            world.update(5e-4);
+            let current = height(&world);
+            if rising && current < previous {
+                peaks.push(previous);
+            }
+            rising = current > previous;
+            previous = current;
+        }
+        for pair in peaks[..6].windows(2) {
+            assert!((pair[1] / pair[0] - restitution * restitution).abs() < 0.01, "{:?}", peaks);
+        }
+        // it comes to rest on the surface
+        assert!(height(&world).abs() < 1e-4);
+        assert!(world.particles()[1].velocity.length() < 1e-6);
+    }
+}
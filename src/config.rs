@@ -1,51 +1,853 @@
-use coffee::graphics::Rectangle;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::RangeBounds;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime};
+
 use dotenv::dotenv;
+use toml_edit::{Document, Item, Value};
 
-#[derive(Clone, Debug)]
-pub struct Config {
+use crate::boundary::Boundary;
+use crate::collision::{BounceParams, CollisionMode};
+use crate::force::GravitySolver;
+use crate::particle::ParticleId;
+use crate::scalar::{Scalar, to_f64};
+use crate::vector::{self, Vector};
+use crate::world::Physics;
+
+/// Area of the sprite strip a sprite is drawn from, in pixels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpriteSource {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+/// Kind of particle drawn with its own sprite, chosen by the mass of the
+/// particle unless the preset it came from chooses it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SpriteKind {
+    Debris,
+    Planet,
+    Star,
+}
+
+impl FromStr for SpriteKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "debris" => Ok(SpriteKind::Debris),
+            "planet" => Ok(SpriteKind::Planet),
+            "star" => Ok(SpriteKind::Star),
+            _ => Err(format!("Invalid sprite kind '{}', expected star, planet or debris", s)),
+        }
+    }
+}
+
+/// Sprite of the sprite strip drawn for a kind of particle, written as
+/// `kind=sprite` where the sprites of the strip are counted from 0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpriteKindCell {
+    pub kind: SpriteKind,
+    pub sprite: u16,
+}
+
+impl FromStr for SpriteKindCell {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, sprite) = s.split_once('=').ok_or_else(|| format!("Invalid sprite kind '{}', expected kind=sprite", s))?;
+        let sprite = sprite.trim().parse().map_err(|_| format!("Invalid sprite '{}' of kind {}, expected the number of a sprite of the strip", sprite.trim(), kind.trim()))?;
+        Ok(SpriteKindCell { kind: kind.parse()?, sprite })
+    }
+}
+
+/// Color written as `#rrggbb`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RgbColor {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl FromStr for RgbColor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = s.strip_prefix('#').filter(|digits| digits.len() == 6 && digits.is_ascii());
+        let channel = |i: usize| digits.and_then(|digits| u8::from_str_radix(&digits[i..i + 2], 16).ok());
+        match (channel(0), channel(2), channel(4)) {
+            (Some(r), Some(g), Some(b)) => Ok(RgbColor { r, g, b }),
+            _ => Err(format!("Invalid color '{}', expected #rrggbb", s)),
+        }
+    }
+}
+
+/// Key bound to an action of the graphical front end, written as `action=key`. The
+/// names are checked by the front end, which knows the actions and the keys.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyBinding {
+    pub action: String,
+    pub key: String,
+}
+
+impl FromStr for KeyBinding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=').map(|(action, key)| (action.trim(), key.trim())) {
+            Some((action, key)) if !action.is_empty() && !key.is_empty() => Ok(KeyBinding { action: action.to_string(), key: key.to_string() }),
+            _ => Err(format!("Invalid key binding '{}', expected action=key", s)),
+        }
+    }
+}
+
+/// Settings of how the particles are drawn, only used by the graphical front end.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderConfig {
     // sprite parameters
     pub sprite_file: String,
     pub sprite_width: f32,
     pub sprite_height: f32,
     pub sprite_scale: f32,
-    pub sprite_source: Rectangle<u16>,
+    pub sprite_source: SpriteSource,
+    /// Sprites of each color band, placed after the uncolored sprite in the sprite strip
+    pub band_sources: Vec<SpriteSource>,
+    /// Sprite of fixed particles, placed after the color bands in the sprite strip
+    pub fixed_source: SpriteSource,
+    /// Sprites of debris, planets and stars drawn while the particles aren't colored
+    pub kind_sources: [SpriteSource; 3],
+    /// Smallest masses of the particles drawn as planets and as stars
+    pub planet_sprite_mass: Scalar,
+    pub star_sprite_mass: Scalar,
+    pub min_sprite_scale: f32,
+    pub max_sprite_scale: f32,
+    pub sprite_reference_mass: Scalar,
+    /// Opacity of the faintest sprites when overlapping sprites are blended, from 0 to 1
+    pub blend_alpha: f32,
+    /// Size in pixels of the cells of the heatmap
+    pub heatmap_cell_size: f32,
+    /// Colors of the heatmap from the sparsest to the densest cells
+    pub heatmap_colors: Vec<RgbColor>,
+    /// Width and height in pixels of the minimap
+    pub minimap_size: f32,
+    /// Pixels a named particle must be from the other named particles on screen for its name to be shown
+    pub label_spacing: f32,
+    /// Keys replacing the default keys of actions
+    pub key_bindings: Vec<KeyBinding>,
     pub horizontal_offset: f32,
     pub vertical_offset: f32,
-    // rendering and processing parameters
-    pub num_threads: usize,
+    // window parameters
     pub screen_height: u32,
     pub screen_width: u32,
-    // world parameters
-    pub time_scale: f64,
+    // meters per pixel of the camera at startup
     pub world_scale: f32,
 }
 
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub render: RenderConfig,
+    // processing parameters
+    pub num_threads: usize,
+    // world parameters
+    pub time_scale: Scalar,
+    // whether every update simulates exactly time_scale seconds, ignoring changes to the time scale
+    pub fixed_timestep: bool,
+    pub softening: Scalar,
+    pub gravitational_constant: Scalar,
+    // multiplier of the gravitational constant, negative values make gravity repulsive
+    pub gravity_multiplier: Scalar,
+    pub density: Scalar,
+    // acceleration per meter per second of velocity caused by drag and the acceleration of the uniform field
+    pub drag_coefficient: Scalar,
+    pub uniform_field: Vector,
+    pub collision_mode: CollisionMode,
+    pub bounce: BounceParams,
+    pub boundary: Boundary,
+    pub cell_size: Scalar,
+    pub adaptive_timestep: bool,
+    pub max_substeps: usize,
+    pub timestep_accuracy: Scalar,
+    pub gravity_solver: GravitySolver,
+    pub barnes_hut_theta: Scalar,
+    pub mesh_cells: usize,
+    // velocity of spawned particles for each meter the mouse is dragged
+    pub drag_velocity_scale: Scalar,
+    // change in velocity of the selected particle each time a nudge key is pressed
+    pub nudge_speed: Scalar,
+    // acceleration in m/s^2 of the engine of the ship steered with the arrow keys, and the change in speed in m/s its fuel gives
+    pub ship_thrust: Scalar,
+    pub ship_fuel: Scalar,
+    // particles, radius in pixels at the current zoom, mass as a fraction of the mass of the body it is launched at,
+    // spring strength, breaking stretch of the springs and periapsis in Roche limits of rubble piles
+    pub rubble_particles: usize,
+    pub rubble_radius: f32,
+    pub rubble_mass_ratio: Scalar,
+    pub rubble_strength: Scalar,
+    pub rubble_breaking_stretch: Scalar,
+    pub rubble_periapsis: Scalar,
+    // fragments a selected particle is split into and the energy in joules of the explosion
+    pub split_fragments: usize,
+    pub split_energy: Scalar,
+    // speed in m/s the travel time between measured points is given for
+    pub measure_speed: Scalar,
+    // updates run without drawing them when fast-forwarding
+    pub fast_forward_steps: usize,
+    // seconds of real time the updates catch up on after falling behind
+    pub max_catch_up: f64,
+    // particles per second, speed and spread angle in degrees of the particle stream
+    pub stream_rate: Scalar,
+    pub stream_speed: Scalar,
+    pub stream_spread: Scalar,
+    // whether loaded particles are moved into their center of mass frame, and whether the camera moves with the center of mass
+    pub auto_recenter: bool,
+    pub center_of_mass_frame: bool,
+    // whether particles are drawn between their positions of the last two updates
+    pub interpolate_rendering: bool,
+    // trail parameters
+    pub trail_length: usize,
+    pub trail_interval: usize,
+    pub max_trail_particles: usize,
+    // galaxy preset parameters
+    pub galaxy_particles: usize,
+    pub galaxy_radius: Scalar,
+    pub galaxy_mass: Scalar,
+    pub preset_seed: u64,
+    // diagnostics parameters
+    pub diagnostics_interval: usize,
+    pub max_potential_particles: usize,
+    // number of updates recorded by a benchmark
+    pub benchmark_updates: usize,
+    // optional file the diagnostics are logged to
+    pub diagnostics_file: Option<String>,
+    // file the simulation state is saved to and loaded from
+    pub save_file: String,
+    // number of updates between recorded frames, the most frames kept and the file recordings are saved to
+    pub record_interval: usize,
+    pub max_recorded_frames: usize,
+    pub recording_file: String,
+    // file the event log of merges, spawns and removals is written to
+    pub event_log_file: String,
+    // optional file of initial particles loaded at startup
+    pub scenario_file: Option<String>,
+    // optional ephemeris file loaded by the ephemeris preset instead of the bundled one, and whether its optional bodies are loaded
+    pub ephemeris_file: Option<String>,
+    pub ephemeris_optional_bodies: bool,
+    // file trajectories are logged to, updates between logged steps, the particles logged or every
+    // particle if unset, and the most particles logged when logging every particle
+    pub trajectory_file: String,
+    pub trajectory_interval: usize,
+    pub trajectory_ids: Option<Vec<ParticleId>>,
+    pub trajectory_max_particles: usize,
+}
+
+/// File the settings are read from. It is created with the default settings if it doesn't exist.
+pub const CONFIG_FILE: &str = "config.toml";
+
+/// A setting of the config file. Every setting can also be set with an environment variable named
+/// after its key in upper case, which takes priority over the config file.
+struct Setting {
+    key: &'static str,
+    /// Default value as a TOML value, or an example value if the setting is optional
+    value: &'static str,
+    /// Optional settings are left out of new config files and are unset by default
+    optional: bool,
+    /// Comment written above the setting in new config files
+    description: &'static str,
+}
+
+const fn setting(key: &'static str, value: &'static str, description: &'static str) -> Setting {
+    Setting { key, value, optional: false, description }
+}
+
+const fn optional(key: &'static str, value: &'static str, description: &'static str) -> Setting {
+    Setting { key, value, optional: true, description }
+}
+
+/// Every setting, in the order they are written to new config files.
+const SETTINGS: &[Setting] = &[
+    setting("sprite_file", "\"resources/star_bands.png\"", "Image with the particle sprites side by side"),
+    setting("sprite_width", "512", "Size of each sprite in pixels"),
+    setting("sprite_height", "512", ""),
+    setting("sprite_scale", "0.05", "Scale of the sprite of a particle of the reference mass"),
+    setting("color_bands", "6", "Number of colored sprites after the uncolored sprite"),
+    optional("sprite_kinds", "\"star=0, planet=1, debris=2\"", "Comma separated kind=sprite pairs choosing the sprite of the strip, counted from 0, drawn for star, planet and debris particles while the particles aren't colored. Kinds left out are drawn with the uncolored sprite"),
+    setting("planet_sprite_mass", "1e20", "Smallest mass in kg of a particle drawn as a planet rather than debris, unless its preset chooses its kind"),
+    setting("star_sprite_mass", "1e28", "Smallest mass in kg of a particle drawn as a star"),
+    setting("min_sprite_scale", "0.02", ""),
+    setting("max_sprite_scale", "0.5", ""),
+    setting("sprite_reference_mass", "100", ""),
+    setting("blend_alpha", "0.15", "Opacity of the faintest sprites when overlapping sprites are blended, from 0 to 1"),
+    setting("heatmap_cell_size", "8", "Size in pixels of the cells of the heatmap"),
+    setting("heatmap_colors", "\"#1a0b40, #6a1b7a, #c33b54, #f57d15, #fad13f, #fcffa4\"", "Comma separated colors of the heatmap from the sparsest to the densest cells"),
+    setting("minimap_size", "200", "Width and height in pixels of the minimap"),
+    setting("label_spacing", "60", "Pixels a named particle must be from the other named particles on screen for its name to be shown, so names appear as the view is zoomed in"),
+    optional("key_bindings", "\"toggle_pause=P, pan_up=Up\"", "Comma separated action=key pairs replacing the default keys of actions, which are listed with F1"),
+    setting("num_threads", "20", "Threads used by the threads and rayon worlds"),
+    setting("screen_height", "1080", ""),
+    setting("screen_width", "1920", ""),
+    setting("default_time_scale", "50", "Seconds simulated for each second of real time"),
+    setting("fixed_timestep", "false", "Whether every update simulates exactly the default time scale"),
+    setting("default_world_scale", "1", ""),
+    setting("default_softening", "5", "Distance added to close encounters to stop the forces blowing up"),
+    setting("gravitational_constant", "6.6743e-11", "Gravitational constant in m^3 / (kg s^2)"),
+    setting("gravity_multiplier", "1", "Multiplier of the gravitational constant, negative values make gravity repulsive"),
+    setting("drag_coefficient", "0.1", "Acceleration in m/s^2 for each m/s of velocity caused by linear drag"),
+    setting("uniform_field_x", "0", "Acceleration in m/s^2 of the uniform field, positive y points down the screen"),
+    setting("uniform_field_y", "9.81", ""),
+    setting("particle_density", "1000", "Density in kg/m^3 used for the radius of particles"),
+    setting("collision_mode", "\"none\"", "Either none, merge or bounce"),
+    setting("bounce_restitution", "1", "Fraction of the speed colliding particles approach with kept as they bounce apart, from 1 for elastic to 0 for inelastic"),
+    setting("bounce_friction", "0", "Coefficient of friction slowing bouncing particles sliding past each other"),
+    setting("boundary_mode", "\"none\"", "Either none, periodic or reflective"),
+    setting("boundary_width", "10000", "Size in meters of the box centered on the origin the particles are kept inside of"),
+    setting("boundary_height", "10000", ""),
+    setting("boundary_depth", "10000", "Only used when simulating in three dimensions"),
+    setting("restitution", "1", "Fraction of the speed into a reflective wall kept when bouncing off it"),
+    setting("minimum_image", "true", "Whether forces in a periodic box act on the nearest image of each particle"),
+    setting("grid_cell_size", "10", "Size of the cells of the grid used to find collisions"),
+    setting("adaptive_timestep", "false", ""),
+    setting("max_substeps", "64", ""),
+    setting("timestep_accuracy", "0.1", "Lower values use more, smaller steps with the adaptive time step"),
+    setting("gravity_solver", "\"direct\"", "Either direct, barnes-hut, particle-mesh or pairwise"),
+    setting("barnes_hut_theta", "0.5", "Accuracy of the Barnes-Hut approximation of gravity from 0 to 1, lower values are slower but more accurate"),
+    setting("mesh_cells", "64", "Grid points along each axis of the particle mesh, rounded up to a power of two"),
+    setting("drag_velocity_scale", "0.01", "Velocity of spawned particles for each meter the mouse is dragged"),
+    setting("nudge_speed", "1", "Change in speed in m/s of the selected particle each time a nudge key is pressed"),
+    setting("ship_thrust", "1", "Acceleration in m/s^2 of the engine of the ship steered with the arrow keys"),
+    setting("ship_fuel", "1000", "Change in speed in m/s the fuel of a ship gives before the engine stops"),
+    setting("rubble_particles", "200", "Particles in a launched rubble pile"),
+    setting("rubble_radius", "20", "Radius in pixels at the current zoom of a launched rubble pile"),
+    setting("rubble_mass_ratio", "0.001", "Mass of a launched rubble pile as a fraction of the mass of the particle it is launched at"),
+    setting("rubble_strength", "300", "Stiffness of the springs between neighbors in a rubble pile, in units of the pile's own gravity G m / R^3"),
+    setting("rubble_breaking_stretch", "1.5", "Multiple of its rest length beyond which a spring of a rubble pile breaks"),
+    setting("rubble_periapsis", "0.5", "Closest approach of a launched rubble pile in Roche limits of the particle it is launched at"),
+    setting("split_fragments", "8", "Fragments the selected particle is split into"),
+    setting("split_energy", "1e12", "Energy in joules of the explosion splitting the selected particle, which sends the fragments flying apart"),
+    setting("measure_speed", "299792458", "Speed in m/s the straight line travel time between measured points is shown for, the speed of light by default"),
+    setting("fast_forward_steps", "2000", "Updates run back to back without drawing them when fast-forwarding, such as to let a galaxy settle"),
+    setting("max_catch_up", "0.1", "Seconds of real time the updates catch up on after falling behind, such as after a slow load or while the window was hidden, with the missed updates beyond it skipped"),
+    setting("stream_rate", "50", "Particles per second emitted by the particle stream"),
+    setting("stream_speed", "10", "Speed in m/s of particles emitted by the particle stream"),
+    setting("stream_spread", "30", "Angle in degrees of the cone the particle stream is emitted in"),
+    setting("auto_recenter", "false", "Whether presets and scenarios are moved so their center of mass is at rest at the origin"),
+    setting("center_of_mass_frame", "false", "Whether the camera moves with the center of mass of the particles"),
+    setting("interpolate_rendering", "true", "Whether particles are drawn moving smoothly between updates when frames are drawn faster than the updates"),
+    setting("trail_length", "100", ""),
+    setting("trail_interval", "5", ""),
+    setting("max_trail_particles", "2000", ""),
+    setting("galaxy_particles", "2000", ""),
+    setting("galaxy_radius", "1000", ""),
+    setting("galaxy_mass", "1e14", ""),
+    setting("preset_seed", "0", ""),
+    setting("diagnostics_interval", "30", ""),
+    setting("max_potential_particles", "5000", ""),
+    setting("benchmark_updates", "600", ""),
+    setting("save_file", "\"save.json\"", ""),
+    setting("record_interval", "5", ""),
+    setting("max_recorded_frames", "1000", ""),
+    setting("recording_file", "\"recording.json\"", ""),
+    setting("event_log_file", "\"events.txt\"", "Text file the latest merges, spawns and removals are written to"),
+    setting("trajectory_file", "\"trajectory.csv\"", "CSV file the positions and velocities of particles are logged to"),
+    setting("trajectory_interval", "10", "Updates between each logged step of the trajectories"),
+    setting("trajectory_max_particles", "1000", "Above this many particles nothing is logged unless trajectory_ids is set"),
+    setting("ephemeris_optional_bodies", "false", "Whether the ephemeris preset loads the bodies marked optional, such as asteroids"),
+    optional("trajectory_ids", "\"0, 1, 2\"", "Comma separated ids of the particles whose trajectories are logged, every particle if unset"),
+    optional("scenario_file", "\"resources/two_galaxies.csv\"", "CSV file of particles loaded at startup"),
+    optional("ephemeris_file", "\"horizons.json\"", "JSON file of state vectors loaded by the ephemeris preset, the bundled solar system at J2000 if unset"),
+    optional("diagnostics_file", "\"diagnostics.csv\"", "CSV file the diagnostics are logged to"),
+];
+
+/// Why the config couldn't be loaded.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// The config file couldn't be read, or the default config file couldn't be written
+    Io { path: String, error: io::Error },
+    /// The config file isn't valid TOML
+    Syntax { path: String, message: String },
+    /// The config file has a key which isn't a setting
+    UnknownField { path: String, field: String },
+    /// A setting has a value which can't be used, set in the given source
+    InvalidValue { field: String, value: String, source: String, message: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io { path, error } => write!(f, "Couldn't access config file '{}': {}", path, error),
+            ConfigError::Syntax { path, message } => write!(f, "Config file '{}' isn't valid TOML: {}", path, message),
+            ConfigError::UnknownField { path, field } => write!(f, "Config file '{}' has an unknown setting '{}'", path, field),
+            ConfigError::InvalidValue { field, value, source, message } => {
+                write!(f, "Invalid value '{}' for setting '{}' in {}: {}", value, field, source, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Values of the settings, looked up in the environment, then the config file and then the defaults.
+struct Settings {
+    path: String,
+    document: Document,
+    /// Whether environment variables override the config file
+    environment: bool,
+}
+
+impl Settings {
+    /// The value of the setting as a string along with where it was set, or None for an unset optional setting.
+    fn value(&self, setting: &Setting) -> Result<Option<(String, String)>, ConfigError> {
+        let variable = setting.key.to_uppercase();
+        if let Some(value) = std::env::var(&variable).ok().filter(|_| self.environment) {
+            return Ok(Some((value, format!("environment variable '{}'", variable))));
+        }
+        let source = format!("config file '{}'", self.path);
+        match self.document.get(setting.key) {
+            Some(Item::Value(value)) => value_to_string(value)
+                .map(|value| Some((value, source.clone())))
+                .ok_or_else(|| ConfigError::InvalidValue {
+                    field: setting.key.to_string(),
+                    value: value.to_string().trim().to_string(),
+                    source,
+                    message: String::from("expected a string, number or boolean"),
+                }),
+            Some(item) => Err(ConfigError::InvalidValue {
+                field: setting.key.to_string(),
+                value: item.to_string().trim().to_string(),
+                source,
+                message: String::from("expected a string, number or boolean"),
+            }),
+            None if setting.optional => Ok(None),
+            None => {
+                let default = setting.value.parse::<Value>().ok().as_ref().and_then(value_to_string);
+                Ok(Some((default.expect("default settings are valid TOML"), String::from("the defaults"))))
+            }
+        }
+    }
+
+    /// Parses the value of a required setting.
+    fn get<T: FromStr>(&self, key: &str) -> Result<T, ConfigError>
+    where
+        T::Err: fmt::Display,
+    {
+        let setting = SETTINGS.iter().find(|setting| setting.key == key).expect("setting is listed in SETTINGS");
+        let (value, source) = self.value(setting)?.expect("required settings have a default");
+        value.parse().map_err(|error: T::Err| ConfigError::InvalidValue {
+            field: key.to_string(),
+            value,
+            source,
+            message: error.to_string(),
+        })
+    }
+
+    /// Parses the value of a required setting which is only valid within the range,
+    /// described by the message of the error for values outside it.
+    fn get_in_range<T: FromStr + PartialOrd>(&self, key: &str, range: impl RangeBounds<T>, expected: &str) -> Result<T, ConfigError>
+    where
+        T::Err: fmt::Display,
+    {
+        let value = self.get(key)?;
+        if range.contains(&value) {
+            return Ok(value);
+        }
+        let setting = SETTINGS.iter().find(|setting| setting.key == key).expect("setting is listed in SETTINGS");
+        let (value, source) = self.value(setting)?.expect("required settings have a default");
+        Err(ConfigError::InvalidValue { field: key.to_string(), value, source, message: expected.to_string() })
+    }
+
+    /// Value of an optional setting, with an empty value leaving it unset.
+    fn get_optional(&self, key: &str) -> Result<Option<String>, ConfigError> {
+        let setting = SETTINGS.iter().find(|setting| setting.key == key).expect("setting is listed in SETTINGS");
+        Ok(self.value(setting)?.map(|(value, _)| value).filter(|file| !file.is_empty()))
+    }
+
+    /// Values of an optional comma separated list setting, with an empty value leaving it unset.
+    fn get_list<T: FromStr>(&self, key: &str) -> Result<Option<Vec<T>>, ConfigError>
+    where
+        T::Err: fmt::Display,
+    {
+        let setting = SETTINGS.iter().find(|setting| setting.key == key).expect("setting is listed in SETTINGS");
+        let Some((value, source)) = self.value(setting)?.filter(|(value, _)| !value.trim().is_empty()) else {
+            return Ok(None);
+        };
+        let items = value.split(',').map(|item| item.trim().parse()).collect::<Result<Vec<T>, T::Err>>();
+        items.map(Some).map_err(|error| ConfigError::InvalidValue {
+            field: key.to_string(),
+            value,
+            source,
+            message: error.to_string(),
+        })
+    }
+}
+
+/// Contents of a TOML value as a string, or None if it is a table or array.
+fn value_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value.value().clone()),
+        Value::Integer(value) => Some(value.value().to_string()),
+        Value::Float(value) => Some(value.value().to_string()),
+        Value::Boolean(value) => Some(value.value().to_string()),
+        _ => None,
+    }
+}
+
+/// Contents of a new config file with every setting at its default value.
+pub fn default_config_file() -> String {
+    let mut file = String::from("# Settings of the simulation. Any of them can be overridden with an environment\n# variable named after the setting in upper case, such as NUM_THREADS=8.\n");
+    for setting in SETTINGS {
+        file.push('\n');
+        if !setting.description.is_empty() {
+            file.push_str(&format!("# {}\n", setting.description));
+        }
+        let comment = if setting.optional { "# " } else { "" };
+        file.push_str(&format!("{}{} = {}\n", comment, setting.key, setting.value));
+    }
+    file
+}
+
 impl Config {
-    pub fn new() -> Config {
+    /// Loads the settings from config.toml, creating it with the default settings if it doesn't
+    /// exist, with environment variables and a .env file overriding the settings in the file.
+    pub fn load() -> Result<Config, ConfigError> {
         dotenv().ok();
-        let sprite_file = std::env::var("SPRITE_FILE").expect("Environment variable 'SPRITE_FILE' missing").parse().unwrap();
-        let sprite_width = std::env::var("SPRITE_WIDTH").expect("Environment variable 'SPRITE_WIDTH' missing").parse().unwrap();
-        let sprite_height = std::env::var("SPRITE_HEIGHT").expect("Environment variable 'SPRITE_HEIGHT' missing").parse().unwrap();
-        let sprite_scale = std::env::var("SPRITE_SCALE").expect("Environment variable 'SPRITE_SCALE' missing").parse().unwrap();
-        let num_threads = std::env::var("NUM_THREADS").expect("Environment variable 'NUM_THREADS' missing").parse().unwrap();
-        let screen_height = std::env::var("SCREEN_HEIGHT").expect("Environment variable 'SCREEN_HEIGHT' missing").parse().unwrap();
-        let screen_width = std::env::var("SCREEN_WIDTH").expect("Environment variable 'SCREEN_WIDTH' missing").parse().unwrap();
-        let default_time_scale: f64 = std::env::var("DEFAULT_TIME_SCALE").expect("Environment variable 'DEFAULT_TIME_SCALE' missing").parse().unwrap();
-        let default_world_scale = std::env::var("DEFAULT_WORLD_SCALE").expect("Environment variable 'DEFAULT_WORLD_SCALE' missing").parse().unwrap();
-        
-        Config { 
-            sprite_file,
-            sprite_width,
-            sprite_height,
-            sprite_scale,
-            sprite_source: Rectangle { x: 0, y: 0, width: sprite_height as u16, height: sprite_width as u16 }, 
-            horizontal_offset: sprite_width * sprite_scale / 2., 
-            vertical_offset: sprite_height * sprite_scale / 2.,
-            num_threads,
-            screen_height,
-            screen_width,
+        Config::load_from(CONFIG_FILE)
+    }
+
+    /// Loads the settings from the config file at the path, creating it with the default settings
+    /// if it doesn't exist, with environment variables overriding the settings in the file.
+    pub fn load_from(path: &str) -> Result<Config, ConfigError> {
+        let io_error = |error| ConfigError::Io { path: path.to_string(), error };
+        if !Path::new(path).exists() {
+            fs::write(path, default_config_file()).map_err(io_error)?;
+        }
+        let contents = fs::read_to_string(path).map_err(io_error)?;
+        let document = contents.parse::<Document>().map_err(|error| ConfigError::Syntax {
+            path: path.to_string(),
+            message: error.to_string(),
+        })?;
+        if let Some((field, _)) = document.iter().find(|(key, _)| SETTINGS.iter().all(|setting| setting.key != *key)) {
+            return Err(ConfigError::UnknownField { path: path.to_string(), field: field.to_string() });
+        }
+        Config::from_settings(&Settings { path: path.to_string(), document, environment: true })
+    }
+
+    fn from_settings(settings: &Settings) -> Result<Config, ConfigError> {
+        let sprite_width: f32 = settings.get("sprite_width")?;
+        let sprite_height: f32 = settings.get("sprite_height")?;
+        let sprite_scale: f32 = settings.get("sprite_scale")?;
+        let color_bands: u16 = settings.get("color_bands")?;
+        let default_time_scale: Scalar = settings.get("default_time_scale")?;
+        let cell = |sprite: u16| SpriteSource { x: sprite * sprite_width as u16, y: 0, width: sprite_width as u16, height: sprite_height as u16 };
+        let mut kind_sources = [cell(0); 3];
+        for SpriteKindCell { kind, sprite } in settings.get_list::<SpriteKindCell>("sprite_kinds")?.unwrap_or_default() {
+            kind_sources[kind as usize] = cell(sprite);
+        }
+
+        Ok(Config {
+            render: RenderConfig {
+                sprite_file: settings.get("sprite_file")?,
+                sprite_width,
+                sprite_height,
+                sprite_scale,
+                sprite_source: SpriteSource { x: 0, y: 0, width: sprite_height as u16, height: sprite_width as u16 },
+                band_sources: (1..=color_bands)
+                    .map(|band| SpriteSource { x: band * sprite_width as u16, y: 0, width: sprite_width as u16, height: sprite_height as u16 })
+                    .collect(),
+                fixed_source: SpriteSource { x: (color_bands + 1) * sprite_width as u16, y: 0, width: sprite_width as u16, height: sprite_height as u16 },
+                kind_sources,
+                planet_sprite_mass: settings.get("planet_sprite_mass")?,
+                star_sprite_mass: settings.get("star_sprite_mass")?,
+                min_sprite_scale: settings.get("min_sprite_scale")?,
+                max_sprite_scale: settings.get("max_sprite_scale")?,
+                sprite_reference_mass: settings.get("sprite_reference_mass")?,
+                blend_alpha: settings.get("blend_alpha")?,
+                heatmap_cell_size: settings.get("heatmap_cell_size")?,
+                heatmap_colors: settings.get_list("heatmap_colors")?.unwrap_or_default(),
+                minimap_size: settings.get("minimap_size")?,
+                label_spacing: settings.get("label_spacing")?,
+                key_bindings: settings.get_list("key_bindings")?.unwrap_or_default(),
+                horizontal_offset: sprite_width * sprite_scale / 2.,
+                vertical_offset: sprite_height * sprite_scale / 2.,
+                screen_height: settings.get("screen_height")?,
+                screen_width: settings.get("screen_width")?,
+                world_scale: settings.get("default_world_scale")?,
+            },
+            num_threads: settings.get("num_threads")?,
             time_scale: 1. / 60. * default_time_scale,
-            world_scale: default_world_scale, 
-        }   
+            softening: settings.get("default_softening")?,
+            gravitational_constant: settings.get("gravitational_constant")?,
+            gravity_multiplier: settings.get("gravity_multiplier")?,
+            density: settings.get("particle_density")?,
+            drag_coefficient: settings.get("drag_coefficient")?,
+            uniform_field: vector::planar(settings.get("uniform_field_x")?, settings.get("uniform_field_y")?),
+            collision_mode: settings.get("collision_mode")?,
+            bounce: BounceParams {
+                restitution: settings.get_in_range("bounce_restitution", 0.0..=1., "expected a fraction from 0 to 1")?,
+                friction: settings.get_in_range("bounce_friction", 0.0.., "expected a coefficient of at least 0")?,
+            },
+            boundary: Boundary {
+                mode: settings.get("boundary_mode")?,
+                size: vector::with_depth(settings.get("boundary_width")?, settings.get("boundary_height")?, settings.get("boundary_depth")?),
+                restitution: settings.get("restitution")?,
+                minimum_image: settings.get("minimum_image")?,
+            },
+            cell_size: settings.get("grid_cell_size")?,
+            fixed_timestep: settings.get("fixed_timestep")?,
+            adaptive_timestep: settings.get("adaptive_timestep")?,
+            max_substeps: settings.get("max_substeps")?,
+            timestep_accuracy: settings.get("timestep_accuracy")?,
+            gravity_solver: settings.get("gravity_solver")?,
+            barnes_hut_theta: settings.get("barnes_hut_theta")?,
+            mesh_cells: settings.get("mesh_cells")?,
+            drag_velocity_scale: settings.get("drag_velocity_scale")?,
+            nudge_speed: settings.get("nudge_speed")?,
+            ship_thrust: settings.get("ship_thrust")?,
+            ship_fuel: settings.get("ship_fuel")?,
+            rubble_particles: settings.get("rubble_particles")?,
+            rubble_radius: settings.get("rubble_radius")?,
+            rubble_mass_ratio: settings.get("rubble_mass_ratio")?,
+            rubble_strength: settings.get("rubble_strength")?,
+            rubble_breaking_stretch: settings.get("rubble_breaking_stretch")?,
+            rubble_periapsis: settings.get("rubble_periapsis")?,
+            split_fragments: settings.get("split_fragments")?,
+            split_energy: settings.get("split_energy")?,
+            measure_speed: settings.get("measure_speed")?,
+            fast_forward_steps: settings.get("fast_forward_steps")?,
+            max_catch_up: settings.get("max_catch_up")?,
+            stream_rate: settings.get("stream_rate")?,
+            stream_speed: settings.get("stream_speed")?,
+            stream_spread: settings.get("stream_spread")?,
+            auto_recenter: settings.get("auto_recenter")?,
+            center_of_mass_frame: settings.get("center_of_mass_frame")?,
+            interpolate_rendering: settings.get("interpolate_rendering")?,
+            trail_length: settings.get("trail_length")?,
+            trail_interval: settings.get("trail_interval")?,
+            max_trail_particles: settings.get("max_trail_particles")?,
+            galaxy_particles: settings.get("galaxy_particles")?,
+            galaxy_radius: settings.get("galaxy_radius")?,
+            galaxy_mass: settings.get("galaxy_mass")?,
+            preset_seed: settings.get("preset_seed")?,
+            diagnostics_interval: settings.get("diagnostics_interval")?,
+            max_potential_particles: settings.get("max_potential_particles")?,
+            benchmark_updates: settings.get("benchmark_updates")?,
+            diagnostics_file: settings.get_optional("diagnostics_file")?,
+            save_file: settings.get("save_file")?,
+            record_interval: settings.get("record_interval")?,
+            max_recorded_frames: settings.get("max_recorded_frames")?,
+            recording_file: settings.get("recording_file")?,
+            event_log_file: settings.get("event_log_file")?,
+            scenario_file: settings.get_optional("scenario_file")?,
+            ephemeris_file: settings.get_optional("ephemeris_file")?,
+            ephemeris_optional_bodies: settings.get("ephemeris_optional_bodies")?,
+            trajectory_file: settings.get("trajectory_file")?,
+            trajectory_interval: settings.get("trajectory_interval")?,
+            trajectory_ids: settings.get_list("trajectory_ids")?,
+            trajectory_max_particles: settings.get("trajectory_max_particles")?,
+        })
+    }
+}
+
+impl Config {
+    /// Physics of a world using the settings.
+    pub fn physics(&self) -> Physics {
+        Physics {
+            softening: self.softening,
+            gravitational_constant: self.gravitational_constant,
+            gravity_multiplier: self.gravity_multiplier,
+            density: self.density,
+            collision_mode: self.collision_mode,
+            bounce: self.bounce,
+            boundary: self.boundary,
+            cell_size: self.cell_size,
+            adaptive_timestep: self.adaptive_timestep,
+            max_substeps: self.max_substeps,
+            timestep_accuracy: self.timestep_accuracy,
+            gravity_solver: self.gravity_solver,
+            barnes_hut_theta: self.barnes_hut_theta,
+            mesh_cells: self.mesh_cells,
+        }
+    }
+}
+
+impl RenderConfig {
+    /// Kind of sprite of a particle with the given mass.
+    pub fn sprite_kind_of(&self, mass: Scalar) -> SpriteKind {
+        if mass >= self.star_sprite_mass {
+            SpriteKind::Star
+        } else if mass >= self.planet_sprite_mass {
+            SpriteKind::Planet
+        } else {
+            SpriteKind::Debris
+        }
+    }
+
+    /// Area of the sprite strip the sprite of a kind of particle is drawn from.
+    pub fn kind_source(&self, kind: SpriteKind) -> SpriteSource {
+        self.kind_sources[kind as usize]
+    }
+
+    /// Scale of the sprite of a particle with the given mass. The scale grows with the cube root of
+    /// the mass like the radius of a sphere, with a particle of the reference mass using the sprite scale.
+    pub fn sprite_scale_of(&self, mass: Scalar) -> f32 {
+        (self.sprite_scale * to_f64((mass / self.sprite_reference_mass).cbrt()) as f32).clamp(self.min_sprite_scale, self.max_sprite_scale)
+    }
+}
+
+impl Config {
+    /// Names of the fields with different values in the other config.
+    pub fn changed_fields(&self, other: &Config) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        // fields of the render config are named without the render prefix
+        macro_rules! compare {
+            (render: $($field:ident),*) => {
+                $(if self.render.$field != other.render.$field {
+                    changed.push(stringify!($field));
+                })*
+            };
+            ($($field:ident),*) => {
+                $(if self.$field != other.$field {
+                    changed.push(stringify!($field));
+                })*
+            };
+        }
+        compare!(
+            render: sprite_file, sprite_width, sprite_height, sprite_scale, sprite_source, band_sources, fixed_source,
+            kind_sources, planet_sprite_mass, star_sprite_mass,
+            min_sprite_scale, max_sprite_scale, sprite_reference_mass, blend_alpha, heatmap_cell_size, heatmap_colors,
+            minimap_size, label_spacing, key_bindings, horizontal_offset, vertical_offset,
+            screen_height, screen_width, world_scale
+        );
+        compare!(
+            num_threads, time_scale, fixed_timestep, softening,
+            gravitational_constant, gravity_multiplier, density, drag_coefficient, uniform_field, collision_mode,
+            bounce, boundary, cell_size, adaptive_timestep, max_substeps, timestep_accuracy, gravity_solver, barnes_hut_theta, mesh_cells,
+            drag_velocity_scale, nudge_speed, ship_thrust, ship_fuel, rubble_particles, rubble_radius, rubble_mass_ratio, rubble_strength,
+            rubble_breaking_stretch, rubble_periapsis, split_fragments, split_energy, measure_speed, fast_forward_steps, max_catch_up,
+            stream_rate, stream_speed, stream_spread, auto_recenter, center_of_mass_frame,
+            interpolate_rendering,
+            trail_length, trail_interval, max_trail_particles, galaxy_particles, galaxy_radius, galaxy_mass,
+            preset_seed, diagnostics_interval, max_potential_particles, benchmark_updates, diagnostics_file,
+            save_file, record_interval, max_recorded_frames, recording_file, event_log_file, scenario_file,
+            ephemeris_file, ephemeris_optional_bodies,
+            trajectory_file, trajectory_interval, trajectory_ids, trajectory_max_particles
+        );
+        changed
+    }
+}
+
+/// Time between checks of the config file for changes.
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Reloads a config file when its modification time changes, checking it at most once a second.
+pub struct ConfigWatcher {
+    path: String,
+    /// Modification time of the file when it was last loaded
+    modified: Option<SystemTime>,
+    last_check: Instant,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: &str) -> Self {
+        ConfigWatcher {
+            path: path.to_string(),
+            modified: fs::metadata(path).and_then(|metadata| metadata.modified()).ok(),
+            last_check: Instant::now(),
+        }
+    }
+
+    /// Loads the config again if the file has been modified since it was last loaded. Returns None
+    /// if it hasn't been modified, or the file can't be checked, such as while it is being replaced.
+    pub fn poll(&mut self) -> Option<Result<Config, ConfigError>> {
+        if self.last_check.elapsed() < WATCH_INTERVAL {
+            return None;
+        }
+        self.last_check = Instant::now();
+        let modified = fs::metadata(&self.path).and_then(|metadata| metadata.modified()).ok()?;
+        if self.modified == Some(modified) {
+            return None;
+        }
+        // a bad edit is only reported once, until the file is modified again
+        self.modified = Some(modified);
+        Some(Config::load_from(&self.path))
+    }
+}
+
+impl Default for Config {
+    /// The default settings, ignoring the config file and environment variables.
+    fn default() -> Self {
+        let settings = Settings { path: String::new(), document: Document::new(), environment: false };
+        Config::from_settings(&settings).expect("default settings are valid")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Settings of a config file with the contents, ignoring environment variables.
+    fn config(contents: &str) -> Result<Config, ConfigError> {
+        let document = contents.parse::<Document>().expect("test config is valid TOML");
+        Config::from_settings(&Settings { path: String::from("test.toml"), document, environment: false })
+    }
+
+    #[test]
+    fn bounce_settings_outside_their_range_are_invalid() {
+        for contents in ["bounce_restitution = 1.5", "bounce_restitution = -0.1", "bounce_restitution = nan", "bounce_friction = -1"] {
+            match config(contents) {
+                Err(ConfigError::InvalidValue { field, .. }) => assert!(contents.starts_with(&field), "{}", contents),
+                other => panic!("{} gave {:?}", contents, other.map(|_| ())),
+            }
+        }
+        let config = config("bounce_restitution = 0\nbounce_friction = 2.5").unwrap();
+        assert_eq!((config.bounce.restitution, config.bounce.friction), (0., 2.5));
+    }
+
+    /// Loads the config file with the contents, or a missing file if None, from a temporary directory.
+    fn load(name: &str, contents: Option<&str>) -> (Result<Config, ConfigError>, String) {
+        let path = std::env::temp_dir().join(format!("nbody-config-{}-{}.toml", name, std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        match contents {
+            Some(contents) => fs::write(&path, contents).unwrap(),
+            None => fs::remove_file(&path).unwrap_or(()),
+        }
+        let config = Config::load_from(&path);
+        let written = fs::read_to_string(&path).unwrap_or_default();
+        fs::remove_file(&path).unwrap_or(());
+        (config, written)
+    }
+
+    #[test]
+    fn missing_config_file_is_created_with_the_defaults() {
+        let (config, written) = load("missing", None);
+        let (config, defaults) = (config.unwrap(), Config::default());
+        assert_eq!(written, default_config_file());
+        assert_eq!((config.num_threads, config.softening, config.time_scale), (defaults.num_threads, defaults.softening, defaults.time_scale));
+    }
+
+    #[test]
+    fn partial_config_file_falls_back_to_the_defaults() {
+        let (config, written) = load("partial", Some("default_softening = 2.5\nnum_threads = 3\n"));
+        let (config, defaults) = (config.unwrap(), Config::default());
+        assert_eq!(written, "default_softening = 2.5\nnum_threads = 3\n");
+        assert_eq!((config.softening, config.num_threads), (2.5, 3));
+        assert_eq!((config.gravitational_constant, config.collision_mode), (defaults.gravitational_constant, defaults.collision_mode));
+    }
+
+    #[test]
+    fn invalid_config_files_give_errors() {
+        assert!(matches!(load("syntax", Some("num_threads = [")).0, Err(ConfigError::Syntax { .. })));
+        match load("unknown", Some("num_threads = 3\nnot_a_setting = 1")).0 {
+            Err(ConfigError::UnknownField { field, .. }) => assert_eq!(field, "not_a_setting"),
+            other => panic!("unknown setting gave {:?}", other.map(|_| ())),
+        }
+        match load("invalid", Some("num_threads = \"many\"")).0 {
+            Err(ConfigError::InvalidValue { field, value, .. }) => assert_eq!((field.as_str(), value.as_str()), ("num_threads", "many")),
+            other => panic!("invalid value gave {:?}", other.map(|_| ())),
+        }
     }
 }
@@ -0,0 +1,209 @@
+use glam::DVec3;
+use rayon::prelude::*;
+
+use crate::particle::{ForceParams, Particle};
+use crate::scalar::to_f64;
+use crate::summation::CompensatedSum;
+use crate::vector::{self, DoubleVector, Vector};
+
+/// Conserved quantities of a set of particles, used to see how well an
+/// integrator conserves energy and momentum. They are summed in double precision
+/// whatever the precision of the simulation, as the sums can overflow `f32`, and
+/// the energies are compensated sums so the energy of small bodies isn't lost
+/// next to the energy of a star.
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct Diagnostics {
+    pub kinetic_energy: f64,
+    /// Not calculated when there are too many particles, as it is O(n²)
+    pub potential_energy: Option<f64>,
+    pub momentum: DoubleVector,
+    pub center_of_mass: Vector,
+    pub total_mass: f64,
+    pub center_of_mass_velocity: Vector,
+    /// Angular momentum about the center of mass, which merging particles
+    /// doesn't conserve even though it conserves the momentum
+    pub angular_momentum: DVec3,
+}
+
+impl Diagnostics {
+    /// Measures the particles, skipping the potential energy if there are more
+    /// than max_potential_particles.
+    pub fn measure(particles: &[Particle], force: ForceParams, max_potential_particles: usize) -> Self {
+        Diagnostics {
+            kinetic_energy: kinetic_energy(particles),
+            potential_energy: (particles.len() <= max_potential_particles).then(|| potential_energy(particles, force)),
+            momentum: momentum(particles),
+            center_of_mass: center_of_mass(particles),
+            total_mass: total_mass(particles),
+            center_of_mass_velocity: center_of_mass_velocity(particles),
+            angular_momentum: angular_momentum(particles),
+        }
+    }
+
+    pub fn total_energy(&self) -> Option<f64> {
+        self.potential_energy.map(|potential_energy| self.kinetic_energy + potential_energy)
+    }
+}
+
+/// Sum of 1/2 m v² over the particles.
+pub fn kinetic_energy(particles: &[Particle]) -> f64 {
+    particles
+        .par_iter()
+        .map(|particle| 0.5 * to_f64(particle.mass) * vector::to_f64(particle.velocity).length_squared())
+        .fold(CompensatedSum::default, |mut sum, energy| {
+            sum.add(energy);
+            sum
+        })
+        .reduce(CompensatedSum::default, CompensatedSum::combine)
+        .value()
+}
+
+/// Sum of the potential energy of every pair of particles. The potential is
+/// softened the same way as the acceleration so the total energy is conserved.
+pub fn potential_energy(particles: &[Particle], force: ForceParams) -> f64 {
+    particles
+        .par_iter()
+        .enumerate()
+        .map(|(i, particle)| {
+            particles[i + 1..]
+                .iter()
+                .map(|other| {
+                    let distance_squared = vector::to_f64(force.separation(particle.position, other.position)).length_squared() + to_f64(force.softening).powi(2);
+                    -to_f64(force.g) * to_f64(particle.mass) * to_f64(other.mass) / distance_squared.sqrt() // U = -Gm1m2 / (|r|^2 + e^2)^(1/2)
+                })
+                .collect::<CompensatedSum<f64>>()
+        })
+        .reduce(CompensatedSum::default, CompensatedSum::combine)
+        .value()
+}
+
+/// Sum of m v over the particles.
+pub fn momentum(particles: &[Particle]) -> DoubleVector {
+    particles.par_iter().map(|particle| vector::to_f64(particle.velocity) * to_f64(particle.mass)).sum()
+}
+
+/// Mass weighted average position of the particles, or the origin if there are none.
+pub fn center_of_mass(particles: &[Particle]) -> Vector {
+    let total_mass = total_mass(particles);
+    if total_mass <= 0. {
+        return Vector::ZERO;
+    }
+    vector::from_f64(particles.par_iter().map(|particle| vector::to_f64(particle.position) * to_f64(particle.mass)).sum::<DoubleVector>() / total_mass)
+}
+
+/// Mass weighted average velocity of the particles, or zero if there are none.
+pub fn center_of_mass_velocity(particles: &[Particle]) -> Vector {
+    let total_mass = total_mass(particles);
+    if total_mass <= 0. {
+        return Vector::ZERO;
+    }
+    vector::from_f64(momentum(particles) / total_mass)
+}
+
+/// Sum of the masses of the particles.
+pub fn total_mass(particles: &[Particle]) -> f64 {
+    particles.par_iter().map(|particle| to_f64(particle.mass)).sum()
+}
+
+/// Sum of m (r - R) × (v - V) over the particles, their angular momentum about
+/// their center of mass R moving at V, or zero if there are none. In two
+/// dimensions only the component out of the screen can be nonzero.
+pub fn angular_momentum(particles: &[Particle]) -> DVec3 {
+    let total_mass = total_mass(particles);
+    if total_mass <= 0. {
+        return DVec3::ZERO;
+    }
+    let center = particles.par_iter().map(|particle| vector::to_f64(particle.position) * to_f64(particle.mass)).sum::<DoubleVector>() / total_mass;
+    let velocity = momentum(particles) / total_mass;
+    particles
+        .par_iter()
+        .map(|particle| vector::cross(vector::to_f64(particle.position) - center, (vector::to_f64(particle.velocity) - velocity) * to_f64(particle.mass)))
+        .sum()
+}
+
+/// Moves the particles into the frame of their center of mass, translating every
+/// particle so the center of mass is at the origin and subtracting the velocity of
+/// the center of mass from the moving particles. Fixed particles stay at rest, so
+/// the momentum is only zeroed when there are none. Returns the translation.
+pub fn recenter(particles: &mut [Particle]) -> Vector {
+    let offset = -center_of_mass(particles);
+    let velocity = center_of_mass_velocity(particles);
+    particles.par_iter_mut().for_each(|particle| {
+        particle.position += offset;
+        if !particle.fixed {
+            particle.velocity -= velocity;
+        }
+    });
+    offset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integrator::VerletIntegrator;
+    use crate::particle::ParticleId;
+    use crate::presets;
+    use crate::world::{Physics, SequentialWorld, World};
+
+    #[test]
+    fn measures_the_energy_and_momentum_of_a_pair() {
+        let particles = [
+            Particle::new(ParticleId::new(0), Vector::ZERO, vector::planar(1., 0.), 2., 1.),
+            Particle::new(ParticleId::new(1), vector::planar(4., 0.), vector::planar(0., 2.), 3., 1.),
+        ];
+        // a softening of 3 m makes the distance 5 m
+        let force = ForceParams { g: 1., softening: 3., period: None };
+        let diagnostics = Diagnostics::measure(&particles, force, 2);
+        assert_eq!(diagnostics.kinetic_energy, 7.);
+        assert_eq!(diagnostics.potential_energy, Some(-1.2));
+        assert!((diagnostics.total_energy().unwrap() - 5.8).abs() < 1e-12);
+        assert_eq!(diagnostics.momentum, vector::to_f64(vector::planar(2., 6.)));
+        assert_eq!(diagnostics.total_mass, 5.);
+        assert_eq!(Diagnostics::measure(&particles, force, 1).total_energy(), None);
+    }
+
+    #[test]
+    fn totals_the_mass_and_averages_the_velocity_by_mass() {
+        let particles = [
+            Particle::new(ParticleId::new(0), Vector::ZERO, vector::planar(3., 0.), 1., 1.),
+            Particle::new(ParticleId::new(1), vector::planar(1., 0.), vector::planar(0., 6.), 2., 1.),
+        ];
+        assert_eq!(total_mass(&particles), 3.);
+        assert_eq!(center_of_mass_velocity(&particles), vector::planar(1., 4.));
+        assert_eq!(total_mass(&[]), 0.);
+    }
+
+    #[test]
+    fn angular_momentum_is_about_the_moving_center_of_mass() {
+        // two equal masses circling their center of mass anticlockwise, both drifting along x
+        let drift = vector::planar(5., 0.);
+        let particles = [
+            Particle::new(ParticleId::new(0), vector::planar(1., 0.), vector::planar(0., 2.) + drift, 3., 1.),
+            Particle::new(ParticleId::new(1), vector::planar(-1., 0.), vector::planar(0., -2.) + drift, 3., 1.),
+        ];
+        // each has m r v = 3 * 1 * 2, and the drift adds nothing about the center of mass
+        let momentum = angular_momentum(&particles);
+        assert_eq!(momentum.z, 12.);
+        assert_eq!(momentum.truncate(), glam::DVec2::ZERO);
+        assert_eq!(angular_momentum(&particles[..1]).z, 0.);
+    }
+
+    #[test]
+    fn circular_orbit_keeps_its_energy_with_half_its_potential_as_kinetic_energy() {
+        let force = Physics::default().force_params();
+        let particles = presets::two_body();
+        let initial = Diagnostics::measure(&particles, force, 2);
+        let potential = initial.potential_energy.unwrap();
+        assert!((initial.kinetic_energy + potential / 2.).abs() <= 1e-6 * potential.abs());
+
+        let separation = to_f64(particles[0].position.distance(particles[1].position));
+        let mut world = SequentialWorld::new(particles, Box::new(VerletIntegrator), Physics::default());
+        world.advance(1000, presets::two_body_period() / 1000.);
+        let last = Diagnostics::measure(&world.get_particles(), force, 2);
+        let drift = vector::to_f64(last.center_of_mass - initial.center_of_mass).length();
+        assert!(drift <= 1e-6 * separation, "center of mass moved {} m", drift);
+        let (initial, last) = (initial.total_energy().unwrap(), last.total_energy().unwrap());
+        assert!((last - initial).abs() <= 1e-5 * initial.abs(), "energy changed from {:e} to {:e}", initial, last);
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::HashSet;
+
+use serde::Deserialize;
+
+use crate::diagnostics::recenter;
+use crate::particle::{Particle, ParticleId, ParticleNames};
+use crate::scalar::Scalar;
+use crate::vector::{self, Vector};
+
+/// Heliocentric state vectors of the Sun, planets and Moon at J2000, bundled
+/// with the simulation.
+pub const SOLAR_SYSTEM_J2000: &str = include_str!("../resources/solar_system_j2000.json");
+
+/// Meters in each distance unit an ephemeris file can use.
+const DISTANCE_UNITS: [(&str, f64); 3] = [("m", 1.), ("km", 1.0e3), ("au", 1.495_978_707e11)];
+/// Meters per second in each velocity unit an ephemeris file can use.
+const VELOCITY_UNITS: [(&str, f64); 3] = [("m/s", 1.), ("km/s", 1.0e3), ("au/d", 1.495_978_707e11 / 86_400.)];
+/// Kilograms in each mass unit an ephemeris file can use.
+const MASS_UNITS: [(&str, f64); 1] = [("kg", 1.)];
+
+/// Positions and velocities of named bodies at an epoch, such as state vectors
+/// exported from JPL Horizons, converted to meters, meters per second and
+/// kilograms.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ephemeris {
+    /// When the state vectors were taken, as written in the file
+    pub epoch: String,
+    /// Origin and axes of the state vectors, as written in the file
+    pub frame: Option<String>,
+    /// Where the state vectors came from, as written in the file
+    pub source: Option<String>,
+    pub bodies: Vec<EphemerisBody>,
+}
+
+/// A body of an [`Ephemeris`]. Positions and velocities are always in three
+/// dimensions, and are projected onto the plane of the file's frame when
+/// simulating in two.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EphemerisBody {
+    pub name: String,
+    pub mass: f64,
+    pub position: [f64; 3],
+    pub velocity: [f64; 3],
+    /// Whether the body is only included when asked for, such as the largest asteroids
+    pub optional: bool,
+}
+
+/// Layout of an ephemeris file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EphemerisFile {
+    epoch: String,
+    #[serde(default)]
+    frame: Option<String>,
+    #[serde(default)]
+    source: Option<String>,
+    units: Units,
+    bodies: Vec<BodyRecord>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Units {
+    distance: String,
+    velocity: String,
+    mass: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BodyRecord {
+    name: String,
+    mass: f64,
+    position: [f64; 3],
+    velocity: [f64; 3],
+    #[serde(default)]
+    optional: bool,
+}
+
+/// Reads an ephemeris file.
+pub fn load_ephemeris(path: &str) -> Result<Ephemeris, String> {
+    let contents = std::fs::read_to_string(path).map_err(|error| format!("Failed to read '{}': {}", path, error))?;
+    parse_ephemeris(&contents)
+}
+
+/// Parses a JSON ephemeris of the form
+///
+/// ```json
+/// {
+///   "epoch": "2000-01-01 12:00 TDB",
+///   "units": { "distance": "km", "velocity": "km/s", "mass": "kg" },
+///   "bodies": [
+///     { "name": "Sun", "mass": 1.989e30, "position": [0, 0, 0], "velocity": [0, 0, 0] },
+///     { "name": "Ceres", "mass": 9.38e20, "position": [...], "velocity": [...], "optional": true }
+///   ]
+/// }
+/// ```
+///
+/// with distances in m, km or au, velocities in m/s, km/s or au/d and masses
+/// in kg. Optional `frame` and `source` fields describe the file. Missing or
+/// unknown fields, unknown units, bodies without a name or with the name of
+/// another body, masses which aren't positive and values which aren't finite
+/// are reported as errors naming the body.
+pub fn parse_ephemeris(contents: &str) -> Result<Ephemeris, String> {
+    let file: EphemerisFile = serde_json::from_str(contents).map_err(|error| format!("Invalid ephemeris: {}", error))?;
+    let distance = unit(&DISTANCE_UNITS, "distance", &file.units.distance)?;
+    let velocity = unit(&VELOCITY_UNITS, "velocity", &file.units.velocity)?;
+    let mass = unit(&MASS_UNITS, "mass", &file.units.mass)?;
+    if file.bodies.is_empty() {
+        return Err(String::from("Invalid ephemeris: no bodies"));
+    }
+
+    let mut names = HashSet::new();
+    let mut bodies = Vec::with_capacity(file.bodies.len());
+    for (index, body) in file.bodies.into_iter().enumerate() {
+        let name = body.name.trim();
+        if name.is_empty() {
+            return Err(format!("Body {}: missing name", index + 1));
+        }
+        if !names.insert(name.to_string()) {
+            return Err(format!("Body {} ({}): another body has the same name", index + 1, name));
+        }
+        if !(body.mass.is_finite() && body.mass > 0.) {
+            return Err(format!("Body {} ({}): mass must be greater than 0 but was {}", index + 1, name, body.mass));
+        }
+        if !body.position.iter().chain(&body.velocity).all(|value| value.is_finite()) {
+            return Err(format!("Body {} ({}): position and velocity must be finite", index + 1, name));
+        }
+        bodies.push(EphemerisBody {
+            name: name.to_string(),
+            mass: body.mass * mass,
+            position: body.position.map(|value| value * distance),
+            velocity: body.velocity.map(|value| value * velocity),
+            optional: body.optional,
+        });
+    }
+    Ok(Ephemeris { epoch: file.epoch, frame: file.frame, source: file.source, bodies })
+}
+
+/// Size in SI units of a unit from the list, ignoring case.
+fn unit(units: &[(&str, f64)], quantity: &str, name: &str) -> Result<f64, String> {
+    let lowercase = name.trim().to_lowercase();
+    units.iter().find(|(unit, _)| *unit == lowercase).map(|&(_, size)| size).ok_or_else(|| {
+        let names: Vec<&str> = units.iter().map(|(unit, _)| *unit).collect();
+        format!("Invalid ephemeris: unknown {} unit '{}', expected {}", quantity, name, names.join(", "))
+    })
+}
+
+impl Ephemeris {
+    /// The bodies as particles with ids in the order of the file, and their
+    /// names. Optional bodies are left out unless include_optional is true.
+    /// The particles are moved into the frame of their center of mass, so the
+    /// system stays on screen rather than drifting with the Sun's wobble. Their
+    /// radii are set by the world they are added to.
+    pub fn particles(&self, include_optional: bool) -> (Vec<Particle>, ParticleNames) {
+        let included = self.bodies.iter().filter(|body| include_optional || !body.optional);
+        let (mut particles, mut names) = (Vec::new(), ParticleNames::new());
+        for (index, body) in included.enumerate() {
+            let id = ParticleId::new(index);
+            let [x, y, z] = body.position.map(|value| value as Scalar);
+            let [vx, vy, vz] = body.velocity.map(|value| value as Scalar);
+            particles.push(Particle {
+                id,
+                velocity: vector::with_depth(vx, vy, vz),
+                position: vector::with_depth(x, y, z),
+                mass: body.mass as Scalar,
+                radius: 0.,
+                acceleration: Vector::ZERO,
+                fixed: false,
+            });
+            names.insert(id, body.name.clone());
+        }
+        recenter(&mut particles);
+        (particles, names)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_earth_moves_near_its_mean_orbital_speed() {
+        // the Earth is near perihelion, within 2% of its mean orbital speed of 29.8 km/s
+        let ephemeris = parse_ephemeris(SOLAR_SYSTEM_J2000).unwrap();
+        let earth = ephemeris.bodies.iter().find(|body| body.name == "Earth").unwrap();
+        let speed = earth.velocity.iter().map(|v| v * v).sum::<f64>().sqrt();
+        assert!((speed / 29.8e3 - 1.).abs() < 0.02, "{} m/s", speed);
+    }
+
+    #[test]
+    fn unknown_units_are_errors() {
+        let error = parse_ephemeris(&SOLAR_SYSTEM_J2000.replace("\"km/s\"", "\"mph\"")).unwrap_err();
+        assert!(error.contains("mph"), "{}", error);
+    }
+
+    #[test]
+    fn particles_are_named_in_the_order_of_the_file() {
+        let (particles, names) = parse_ephemeris(SOLAR_SYSTEM_J2000).unwrap().particles(false);
+        assert_eq!(particles.len(), 10);
+        let (sun, earth, moon) = (&particles[0], &particles[3], &particles[4]);
+        let name = |id| names[&ParticleId::new(id)].as_str();
+        assert_eq!((name(0), name(3), name(4)), ("Sun", "Earth", "Moon"));
+        // the Moon is about 384,000 km from the Earth, which orbits the Sun at about 30 km/s
+        assert!((moon.position.distance(earth.position) / 3.84e8 - 1.).abs() < 0.1);
+        assert!((earth.velocity.distance(sun.velocity) / 29.8e3 - 1.).abs() < 0.02);
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use crate::clock::format_time;
+use crate::collision::Merge;
+use crate::particle::ParticleId;
+use crate::scalar::Scalar;
+
+/// Events kept by a log unless it was created with another capacity
+pub const EVENT_LOG_CAPACITY: usize = 100;
+
+/// Something that happened to the particles during the simulation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EventKind {
+    /// Two overlapping particles were merged into one
+    Merge(Merge),
+    /// A particle was split into the given number of fragments
+    Split { id: ParticleId, fragments: usize },
+    /// A particle was taken out of the world
+    Removal { id: ParticleId },
+    /// A particle was added to the world
+    Spawn { id: ParticleId, mass: Scalar },
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EventKind::Merge(merge) => write!(f, "particle {} absorbed {}, new mass {:.1e}", merge.survivor, merge.absorbed, merge.mass),
+            EventKind::Split { id, fragments } => write!(f, "particle {} split into {} fragments", id, fragments),
+            EventKind::Removal { id } => write!(f, "particle {} removed", id),
+            EventKind::Spawn { id, mass } => write!(f, "particle {} spawned with mass {:.1e}", id, mass),
+        }
+    }
+}
+
+/// An event and the simulated time it happened at.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Event {
+    /// Seconds simulated when the event happened
+    pub time: f64,
+    pub kind: EventKind,
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", format_time(self.time), self.kind)
+    }
+}
+
+/// The most recent events, oldest first. Once the log is full every new event
+/// drops the oldest, so a log nobody reads stays the same size.
+#[derive(Clone, Debug)]
+pub struct EventLog {
+    events: VecDeque<Event>,
+    capacity: usize,
+}
+
+impl Default for EventLog {
+    /// An empty log of [`EVENT_LOG_CAPACITY`] events.
+    fn default() -> Self {
+        EventLog::with_capacity(EVENT_LOG_CAPACITY)
+    }
+}
+
+impl EventLog {
+    /// Creates an empty log keeping at most capacity events.
+    pub fn with_capacity(capacity: usize) -> Self {
+        EventLog { events: VecDeque::with_capacity(capacity), capacity }
+    }
+
+    /// Adds an event, dropping the oldest if the log is full.
+    pub fn push(&mut self, event: Event) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+
+    /// Takes every event out of the log, oldest first.
+    pub fn drain(&mut self) -> Vec<Event> {
+        self.events.drain(..).collect()
+    }
+
+    /// The events, oldest first.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Event> {
+        self.events.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+}
+
+impl Extend<Event> for EventLog {
+    fn extend<T: IntoIterator<Item = Event>>(&mut self, events: T) {
+        for event in events {
+            self.push(event);
+        }
+    }
+}
+
+/// Writes the events to a text file one per line, oldest first, replacing the
+/// file if it already exists.
+pub fn save_event_log(path: &str, log: &EventLog) -> std::io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    for event in log.iter() {
+        writeln!(file, "{}", event)?;
+    }
+    file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_log_drops_the_oldest_events() {
+        let mut log = EventLog::with_capacity(2);
+        log.extend((0..3).map(|id| Event { time: id as f64, kind: EventKind::Removal { id: ParticleId::new(id) } }));
+        let lines: Vec<String> = log.iter().map(|event| event.to_string()).collect();
+        assert_eq!(lines, ["1.0 s: particle 1 removed", "2.0 s: particle 2 removed"]);
+    }
+}
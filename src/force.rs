@@ -0,0 +1,445 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use rayon::prelude::*;
+
+use crate::particle::{ForceParams, Particle, ParticleId};
+use crate::scalar::Scalar;
+use crate::summation::compensated_sum;
+use crate::vector::Vector;
+
+/// A force acting on the particles. The accelerations caused by every force
+/// of a world are summed to find the acceleration of each particle.
+pub trait Force: Send + Sync {
+    /// Name of the force shown in the user interface.
+    fn name(&self) -> &'static str;
+    /// Acceleration of the target caused by the force. The sources are every
+    /// particle of the state being integrated, including the target.
+    fn acceleration(&self, target: &Particle, sources: &[Particle], params: ForceParams) -> Vector;
+    /// Acceleration of the source at the index, which forces depending on the
+    /// other particles can use to skip the target without comparing ids.
+    fn acceleration_at(&self, index: usize, sources: &[Particle], params: ForceParams) -> Vector {
+        self.acceleration(&sources[index], sources, params)
+    }
+    /// Acceleration of the source at the index given a gravity field built from
+    /// the sources, which gravity uses instead of summing the gravity of the
+    /// particle directly. Other forces ignore the field.
+    fn field_acceleration(&self, index: usize, sources: &[Particle], _field: &dyn GravityField, params: ForceParams) -> Vector {
+        self.acceleration_at(index, sources, params)
+    }
+}
+
+/// Gravity of a set of particles calculated ahead of time for the whole set,
+/// which takes less time than summing the gravity of each particle separately.
+pub trait GravityField: Sync {
+    /// Gravitational acceleration of the source at the index caused by the
+    /// other sources, which must be the particles the field was built from.
+    fn acceleration(&self, index: usize, sources: &[Particle], force: ForceParams) -> Vector;
+}
+
+/// How the gravity between the particles is calculated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GravitySolver {
+    /// The gravity of every pair of particles is summed, which is exact but takes O(n²) time
+    Direct,
+    /// Distant groups of particles are approximated by a [`BarnesHutTree`](crate::barnes_hut::BarnesHutTree)
+    BarnesHut,
+    /// Gravity is interpolated from a [`ParticleMesh`](crate::particle_mesh::ParticleMesh)
+    ParticleMesh,
+    /// The gravity of every pair of particles is calculated once by [`PairwiseGravity`]
+    /// and applied to both, which halves the work of the direct sum
+    Pairwise,
+}
+
+impl GravitySolver {
+    /// The solver that follows this one when cycling through the solvers.
+    pub fn next(self) -> Self {
+        match self {
+            GravitySolver::Direct => GravitySolver::BarnesHut,
+            GravitySolver::BarnesHut => GravitySolver::ParticleMesh,
+            GravitySolver::ParticleMesh => GravitySolver::Pairwise,
+            GravitySolver::Pairwise => GravitySolver::Direct,
+        }
+    }
+}
+
+impl FromStr for GravitySolver {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "direct" => Ok(GravitySolver::Direct),
+            "barnes-hut" => Ok(GravitySolver::BarnesHut),
+            "particle-mesh" => Ok(GravitySolver::ParticleMesh),
+            "pairwise" => Ok(GravitySolver::Pairwise),
+            _ => Err(format!("Unknown gravity solver '{}', expected direct, barnes-hut, particle-mesh or pairwise", s)),
+        }
+    }
+}
+
+/// Newtonian gravity between every pair of particles, using the gravitational
+/// constant and softening length of the physics.
+pub struct Gravity;
+
+impl Force for Gravity {
+    fn name(&self) -> &'static str {
+        "Gravity"
+    }
+
+    fn acceleration(&self, target: &Particle, sources: &[Particle], params: ForceParams) -> Vector {
+        target.net_acceleration(sources, params)
+    }
+
+    fn acceleration_at(&self, index: usize, sources: &[Particle], params: ForceParams) -> Vector {
+        Particle::net_acceleration_at(index, sources, params)
+    }
+
+    fn field_acceleration(&self, index: usize, sources: &[Particle], field: &dyn GravityField, params: ForceParams) -> Vector {
+        field.acceleration(index, sources, params)
+    }
+}
+
+/// Gravity between every pair of particles summed ahead of time, calculating
+/// the gravity of each pair once and applying it to both particles in opposite
+/// directions. This halves the work of the direct sum, but the accelerations
+/// are rounded differently from summing the gravity of each particle on its own.
+#[derive(Default)]
+pub struct PairwiseGravity {
+    accelerations: Vec<Vector>,
+}
+
+impl PairwiseGravity {
+    /// Sums the gravity of every pair of the particles.
+    pub fn build(&mut self, particles: &[Particle], force: ForceParams) {
+        self.accelerations.clear();
+        self.accelerations.resize(particles.len(), Vector::ZERO);
+        for row in 0..particles.len() {
+            add_pairs(row, particles, force, &mut self.accelerations);
+        }
+    }
+
+    /// Sums the gravity of every pair of the particles using parallel iterators
+    /// from the rayon library. Each thread adds the pairs it calculates to a
+    /// buffer of its own, and the buffers are added together afterwards. Rows
+    /// are taken from both ends at once so every item has the same number of pairs.
+    pub fn par_build(&mut self, particles: &[Particle], force: ForceParams) {
+        let len = particles.len();
+        self.accelerations = (0..len.div_ceil(2))
+            .into_par_iter()
+            .fold(
+                || vec![Vector::ZERO; len],
+                |mut accelerations, row| {
+                    add_pairs(row, particles, force, &mut accelerations);
+                    if len - 1 - row != row {
+                        add_pairs(len - 1 - row, particles, force, &mut accelerations);
+                    }
+                    accelerations
+                },
+            )
+            .reduce_with(|mut sum, accelerations| {
+                for (sum, acceleration) in sum.iter_mut().zip(accelerations) {
+                    *sum += acceleration;
+                }
+                sum
+            })
+            .unwrap_or_default();
+    }
+}
+
+impl GravityField for PairwiseGravity {
+    fn acceleration(&self, index: usize, _sources: &[Particle], _force: ForceParams) -> Vector {
+        self.accelerations[index]
+    }
+}
+
+/// Adds the gravity between the particle at the row and each particle after it
+/// to the accelerations of both.
+fn add_pairs(row: usize, particles: &[Particle], force: ForceParams, accelerations: &mut [Vector]) {
+    let particle = &particles[row];
+    let mut acceleration = Vector::ZERO;
+    for (other, other_acceleration) in particles[row + 1..].iter().zip(&mut accelerations[row + 1..]) {
+        let r = force.separation(particle.position, other.position);
+        let distance_squared = r.length_squared() + force.softening * force.softening;
+        // acceleration per kilogram of the other particle, divided in steps like ForceParams::acceleration
+        let pull = force.g / distance_squared * r / distance_squared.sqrt();
+        acceleration -= pull * other.mass;
+        *other_acceleration += pull * particle.mass;
+    }
+    accelerations[row] += acceleration;
+}
+
+/// Damping proportional to the velocity of a particle, which slows every
+/// particle down exponentially without other forces.
+pub struct LinearDrag {
+    /// Acceleration per meter per second of velocity, in 1/s
+    pub coefficient: Scalar,
+}
+
+impl Force for LinearDrag {
+    fn name(&self) -> &'static str {
+        "Linear drag"
+    }
+
+    fn acceleration(&self, target: &Particle, _sources: &[Particle], _params: ForceParams) -> Vector {
+        -self.coefficient * target.velocity // a = -kv
+    }
+}
+
+/// The same acceleration for every particle, like gravity near the surface of a planet or a steady wind.
+pub struct UniformField {
+    /// Acceleration in m/s^2
+    pub acceleration: Vector,
+}
+
+impl Force for UniformField {
+    fn name(&self) -> &'static str {
+        "Uniform field"
+    }
+
+    fn acceleration(&self, _target: &Particle, _sources: &[Particle], _params: ForceParams) -> Vector {
+        self.acceleration
+    }
+}
+
+/// Acceleration of a single particle by its own engine, such as a spacecraft
+/// steered from the keyboard. As a force it is integrated with the rest of the
+/// physics, so a burn changes the orbit the same way at any time step.
+#[derive(Clone, Copy, Debug)]
+pub struct Thrust {
+    /// Id of the particle pushed by the engine
+    pub id: ParticleId,
+    /// Acceleration in m/s^2
+    pub acceleration: Vector,
+}
+
+impl Force for Thrust {
+    fn name(&self) -> &'static str {
+        "Thrust"
+    }
+
+    fn acceleration(&self, target: &Particle, _sources: &[Particle], _params: ForceParams) -> Vector {
+        if target.id == self.id { self.acceleration } else { Vector::ZERO }
+    }
+}
+
+/// Springs between recorded pairs of particles, such as the neighbors of a rubble
+/// pile, pulling or pushing each pair towards the length the spring rests at.
+/// A spring stretched beyond the breaking stretch times its rest length lets go,
+/// so the pair can be torn apart, and springs to particles which no longer exist
+/// are skipped.
+#[derive(Clone, Debug, Default)]
+pub struct Springs {
+    /// Multiple of the rest length beyond which a spring exerts no force
+    pub breaking_stretch: Scalar,
+    /// Springs of each particle, with every spring recorded for both of its particles
+    springs: HashMap<ParticleId, Vec<Spring>>,
+}
+
+/// A spring seen from one of its particles.
+#[derive(Clone, Copy, Debug)]
+struct Spring {
+    /// Id of the particle at the other end
+    other: ParticleId,
+    rest_length: Scalar,
+    /// Acceleration in m/s^2 of each of two equal particles for each meter the
+    /// spring is stretched. Particles of different masses share the force so the
+    /// springs conserve momentum.
+    stiffness: Scalar,
+}
+
+impl Springs {
+    pub fn new(breaking_stretch: Scalar) -> Self {
+        Springs { breaking_stretch, springs: HashMap::new() }
+    }
+
+    /// Adds a spring between two particles which rests at the given length, with
+    /// the stiffness in m/s^2 per meter of stretch for two equal particles.
+    pub fn connect(&mut self, a: ParticleId, b: ParticleId, rest_length: Scalar, stiffness: Scalar) {
+        self.springs.entry(a).or_default().push(Spring { other: b, rest_length, stiffness });
+        self.springs.entry(b).or_default().push(Spring { other: a, rest_length, stiffness });
+    }
+
+    /// Number of springs.
+    pub fn len(&self) -> usize {
+        self.springs.values().map(Vec::len).sum::<usize>() / 2
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.springs.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.springs.clear();
+    }
+}
+
+impl Force for Springs {
+    fn name(&self) -> &'static str {
+        "Springs"
+    }
+
+    fn acceleration(&self, target: &Particle, sources: &[Particle], params: ForceParams) -> Vector {
+        let Some(springs) = self.springs.get(&target.id) else {
+            return Vector::ZERO;
+        };
+        let mut acceleration = Vector::ZERO;
+        for spring in springs {
+            // particles are usually kept in order of their ids, so they are searched for by id before checking every particle
+            let other = match sources.binary_search_by_key(&spring.other, |particle| particle.id) {
+                Ok(index) => &sources[index],
+                Err(_) => match sources.iter().find(|particle| particle.id == spring.other) {
+                    Some(other) => other,
+                    None => continue,
+                },
+            };
+            let r = params.separation(target.position, other.position);
+            let distance = r.length();
+            if distance == 0. || distance > spring.rest_length * self.breaking_stretch {
+                continue;
+            }
+            let share = 2. * other.mass / (target.mass + other.mass);
+            acceleration -= r / distance * spring.stiffness * (distance - spring.rest_length) * share;
+        }
+        acceleration
+    }
+}
+
+/// Forces used by new worlds.
+pub fn default_forces() -> Vec<Box<dyn Force>> {
+    vec![Box::new(Gravity)]
+}
+
+/// Sum of the accelerations of the target caused by each of the forces.
+pub fn net_acceleration(forces: &[Box<dyn Force>], target: &Particle, sources: &[Particle], params: ForceParams) -> Vector {
+    compensated_sum(forces.iter().map(|force| force.acceleration(target, sources, params)))
+}
+
+/// Sum of the accelerations of the source at the index caused by each of the forces.
+pub fn net_acceleration_at(forces: &[Box<dyn Force>], index: usize, sources: &[Particle], params: ForceParams) -> Vector {
+    compensated_sum(forces.iter().map(|force| force.acceleration_at(index, sources, params)))
+}
+
+/// Sum of the accelerations of the source at the index caused by each of the
+/// forces, using a gravity field built from the sources instead of summing
+/// gravity directly if there is one.
+pub fn field_net_acceleration_at(forces: &[Box<dyn Force>], index: usize, sources: &[Particle], field: Option<&dyn GravityField>, params: ForceParams) -> Vector {
+    match field {
+        Some(field) => compensated_sum(forces.iter().map(|force| force.field_acceleration(index, sources, field, params))),
+        None => net_acceleration_at(forces, index, sources, params),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integrator::{Rk4Integrator, VerletIntegrator};
+    use crate::particle::G;
+    use crate::presets;
+    use crate::vector;
+    use crate::world::{Physics, SequentialWorld, World};
+
+    fn particle(id: usize, position: Vector, velocity: Vector, mass: Scalar) -> Particle {
+        Particle::new(ParticleId::new(id), position, velocity, mass, 1.)
+    }
+
+    fn close(actual: Vector, expected: Vector, tolerance: Scalar) -> bool {
+        (actual - expected).length() <= tolerance * expected.length()
+    }
+
+    #[test]
+    fn gravity_of_a_pair_follows_newtons_law() {
+        let particles = [particle(0, Vector::ZERO, Vector::ZERO, 1e10), particle(1, vector::planar(100., 0.), Vector::ZERO, 3e10)];
+        let params = ForceParams::softened(0.);
+        let expected = vector::planar(G * 3e10 / 1e4, 0.);
+        assert!(close(Gravity.acceleration(&particles[0], &particles, params), expected, 1e3 * Scalar::EPSILON));
+        assert!(close(Gravity.acceleration_at(0, &particles, params), expected, 1e3 * Scalar::EPSILON));
+        assert!(close(Gravity.acceleration_at(1, &particles, params), -expected / 3., 1e3 * Scalar::EPSILON));
+    }
+
+    #[test]
+    fn pairwise_gravity_matches_the_direct_sum() {
+        let force = ForceParams::softened(1.);
+        for len in [0, 1, 2, 3, 64, 65, 257] {
+            let particles = presets::random(len, len as u64);
+            let (mut gravity, mut par_gravity) = (PairwiseGravity::default(), PairwiseGravity::default());
+            gravity.build(&particles, force);
+            par_gravity.par_build(&particles, force);
+            let exact: Vec<Vector> = (0..len).map(|index| Particle::net_acceleration_at(index, &particles, force)).collect();
+            // the pairs are summed in a different order, so the roundings grow with the number of particles
+            let largest = exact.iter().map(|acceleration| acceleration.length()).fold(0., Scalar::max);
+            let tolerance = 4. * len as Scalar * Scalar::EPSILON * largest;
+            for (index, exact) in exact.iter().enumerate() {
+                for (name, gravity) in [("build", &gravity), ("par_build", &par_gravity)] {
+                    let acceleration = gravity.acceleration(index, &particles, force);
+                    assert!((acceleration - *exact).length() <= tolerance, "{} of {} particles, particle {}: {:?} instead of {:?}", name, len, index, acceleration, exact);
+                }
+            }
+            assert_eq!((gravity.accelerations.len(), par_gravity.accelerations.len()), (len, len));
+        }
+    }
+
+    #[test]
+    fn linear_drag_slows_particles_exponentially() {
+        const COEFFICIENT: Scalar = 0.5;
+        let (position, velocity) = (vector::planar(1., 2.), vector::planar(10., -4.));
+        let mut world = SequentialWorld::new(vec![particle(0, position, velocity, 1.)], Box::new(Rk4Integrator), Physics::default());
+        world.set_forces(vec![Box::new(LinearDrag { coefficient: COEFFICIENT })]);
+        world.advance(100, 0.02);
+        // v = v0 e^-kt and x = x0 + v0 (1 - e^-kt) / k
+        let decay = (-COEFFICIENT * 2.).exp();
+        let particle = world.particles()[0].clone();
+        let tolerance = (1e-6 as Scalar).max(1e3 * Scalar::EPSILON);
+        assert!(close(particle.velocity, velocity * decay, tolerance), "{:?}", particle.velocity);
+        assert!(close(particle.position, position + velocity * (1. - decay) / COEFFICIENT, tolerance), "{:?}", particle.position);
+    }
+
+    #[test]
+    fn uniform_field_throws_particles_along_a_parabola() {
+        let field = vector::planar(0., -9.81);
+        let (position, velocity) = (vector::planar(1., 2.), vector::planar(3., 20.));
+        let mut world = SequentialWorld::new(vec![particle(0, position, velocity, 1.)], Box::new(Rk4Integrator), Physics::default());
+        world.set_forces(vec![Box::new(UniformField { acceleration: field })]);
+        world.advance(50, 0.1);
+        // Runge-Kutta integrates a constant acceleration exactly
+        let particle = world.particles()[0].clone();
+        assert!(close(particle.velocity, velocity + field * 5., 1e3 * Scalar::EPSILON), "{:?}", particle.velocity);
+        assert!(close(particle.position, position + velocity * 5. + field * 12.5, 1e3 * Scalar::EPSILON), "{:?}", particle.position);
+    }
+
+    #[test]
+    fn prograde_burn_at_periapsis_raises_the_apoapsis() {
+        let physics = Physics { gravitational_constant: 1., softening: 0., ..Physics::default() };
+        // furthest distance over an orbit starting at periapsis, after burning for 0.2 s
+        let apoapsis = |burn: Scalar| {
+            let mut world = SequentialWorld::new(Vec::new(), Box::new(VerletIntegrator), physics);
+            world.create_particle(Vector::ZERO, Vector::ZERO, 1., true);
+            let ship = world.create_particle(vector::planar(1., 0.), vector::planar(0., 1.2), 1e-9, false);
+            world.set_forces(vec![Box::new(Gravity), Box::new(Thrust { id: ship, acceleration: vector::planar(0., burn) })]);
+            for _ in 0..200 {
+                world.update(1e-3);
+            }
+            world.set_forces(vec![Box::new(Gravity)]);
+            let mut apoapsis: Scalar = 0.;
+            for _ in 0..20_000 {
+                world.update(1e-3);
+                apoapsis = apoapsis.max(world.particles()[1].position.length());
+            }
+            apoapsis
+        };
+
+        // a = 1 / (2 / r - v²) and the apoapsis is 2a - r
+        let coasting = apoapsis(0.);
+        assert!((coasting - 2.571).abs() < 0.01, "{}", coasting);
+        assert!(apoapsis(0.1) > coasting + 0.2);
+    }
+
+    #[test]
+    fn net_acceleration_sums_every_force() {
+        let particles = [particle(0, Vector::ZERO, vector::planar(2., 1.), 1e10), particle(1, vector::planar(0., 50.), Vector::ZERO, 1e11)];
+        let forces: Vec<Box<dyn Force>> = vec![Box::new(Gravity), Box::new(LinearDrag { coefficient: 0.1 }), Box::new(UniformField { acceleration: vector::planar(0., -1.) })];
+        let params = ForceParams::softened(0.);
+        let expected = vector::planar(-0.2, G * 1e11 / 2500. - 0.1 - 1.);
+        assert!(close(net_acceleration(&forces, &particles[0], &particles, params), expected, 1e3 * Scalar::EPSILON));
+        assert!(close(net_acceleration_at(&forces, 0, &particles, params), expected, 1e3 * Scalar::EPSILON));
+    }
+}
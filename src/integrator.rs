@@ -0,0 +1,171 @@
+use crate::particle::Particle;
+use crate::scalar::Scalar;
+use crate::vector::Vector;
+
+/// Calculates the acceleration of every particle in the given state into the
+/// buffer, replacing its contents. The buffer is indexed the same as the slice
+/// passed in.
+pub type ForceFn<'a> = dyn FnMut(&[Particle], &mut Vec<Vector>) + 'a;
+
+/// Buffers kept by a world between steps, so the integrators can reuse their
+/// allocations instead of allocating new buffers every step.
+#[derive(Default)]
+pub struct Scratch {
+    /// Accelerations of each stage of a step
+    accelerations: [Vec<Vector>; 4],
+    /// Velocities of each stage of a step
+    velocities: [Vec<Vector>; 4],
+    /// Trial state the accelerations of the later stages are evaluated at
+    trial: Vec<Particle>,
+}
+
+pub trait Integrator: Send + Sync {
+    /// Advances the positions and velocities of the particles by dt, using
+    /// force_fn to calculate accelerations for any intermediate states. Fixed
+    /// particles are not moved but are still included when calculating accelerations.
+    fn step(&self, particles: &mut [Particle], dt: Scalar, force_fn: &mut ForceFn, scratch: &mut Scratch);
+}
+
+/// Semi-implicit Euler integration. The velocity is updated with the current
+/// acceleration and the position is then updated with the new velocity.
+pub struct EulerIntegrator;
+
+impl Integrator for EulerIntegrator {
+    fn step(&self, particles: &mut [Particle], dt: Scalar, force_fn: &mut ForceFn, scratch: &mut Scratch) {
+        let accelerations = &mut scratch.accelerations[0];
+        force_fn(particles, accelerations);
+        for (particle, &acceleration) in particles.iter_mut().zip(accelerations.iter()).filter(|(particle, _)| !particle.fixed) {
+            particle.velocity += acceleration * dt;
+            particle.position += particle.velocity * dt;
+            particle.acceleration = acceleration;
+        }
+    }
+}
+
+/// Velocity Verlet integration. Positions are moved using the acceleration
+/// stored from the previous step, then the accelerations are recalculated and
+/// the velocities are updated with the average of the old and new accelerations.
+pub struct VerletIntegrator;
+
+impl Integrator for VerletIntegrator {
+    fn step(&self, particles: &mut [Particle], dt: Scalar, force_fn: &mut ForceFn, scratch: &mut Scratch) {
+        // move particles using the acceleration from the previous step
+        for particle in particles.iter_mut().filter(|particle| !particle.fixed) {
+            particle.position += particle.velocity * dt + 0.5 * particle.acceleration * dt * dt;
+        }
+
+        // recalculate accelerations at the new positions and update velocities with the average acceleration
+        let accelerations = &mut scratch.accelerations[0];
+        force_fn(particles, accelerations);
+        for (particle, &acceleration) in particles.iter_mut().zip(accelerations.iter()).filter(|(particle, _)| !particle.fixed) {
+            particle.velocity += 0.5 * (particle.acceleration + acceleration) * dt;
+            particle.acceleration = acceleration;
+        }
+    }
+}
+
+/// Classic fourth order Runge-Kutta integration. The accelerations are
+/// evaluated at four trial states per step, which makes it the most accurate
+/// and the most expensive of the integrators.
+pub struct Rk4Integrator;
+
+impl Integrator for Rk4Integrator {
+    fn step(&self, particles: &mut [Particle], dt: Scalar, force_fn: &mut ForceFn, scratch: &mut Scratch) {
+        let Scratch { accelerations: [a1, a2, a3, a4], velocities: [v1, v2, v3, v4], trial } = scratch;
+
+        // k1 is evaluated at the current state
+        v1.clear();
+        v1.extend(particles.iter().map(|particle| particle.velocity));
+        force_fn(particles, a1);
+
+        // k2, k3 and k4 are evaluated at trial states stepped from the current state
+        trial.clear();
+        trial.extend_from_slice(particles);
+        let mut trial_step = |velocities: &[Vector], accelerations: &[Vector], h: Scalar, trial_velocities: &mut Vec<Vector>, trial_accelerations: &mut Vec<Vector>| {
+            // fixed particles stay at their current position in every trial state
+            for ((trial_particle, particle), velocity) in trial.iter_mut().zip(particles.iter()).zip(velocities).filter(|((_, particle), _)| !particle.fixed) {
+                trial_particle.position = particle.position + *velocity * h;
+            }
+            trial_velocities.clear();
+            trial_velocities.extend(
+                particles
+                    .iter()
+                    .zip(accelerations)
+                    .map(|(particle, acceleration)| particle.velocity + *acceleration * h),
+            );
+            // forces depending on velocity, like drag, see the velocity of the trial state
+            for (trial_particle, velocity) in trial.iter_mut().zip(trial_velocities.iter()).filter(|(trial_particle, _)| !trial_particle.fixed) {
+                trial_particle.velocity = *velocity;
+            }
+            force_fn(trial, trial_accelerations);
+        };
+        trial_step(v1, a1, dt / 2., v2, a2);
+        trial_step(v2, a2, dt / 2., v3, a3);
+        trial_step(v3, a3, dt, v4, a4);
+
+        // combine the weighted slopes
+        for (i, particle) in particles.iter_mut().enumerate().filter(|(_, particle)| !particle.fixed) {
+            particle.position += (v1[i] + 2. * v2[i] + 2. * v3[i] + v4[i]) * dt / 6.;
+            particle.velocity += (a1[i] + 2. * a2[i] + 2. * a3[i] + a4[i]) * dt / 6.;
+            particle.acceleration = a1[i];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::ForceParams;
+    use crate::presets;
+    use crate::scalar;
+
+    /// Largest change of the distance between the Sun and the Earth of the two body
+    /// preset relative to its starting value, while the integrator steps through the
+    /// orbits with the given number of steps per orbit.
+    fn radius_error(integrator: &dyn Integrator, steps_per_orbit: usize, orbits: usize) -> f64 {
+        let force = ForceParams::softened(1.);
+        let mut force_fn = |state: &[Particle], accelerations: &mut Vec<Vector>| {
+            *accelerations = (0..state.len()).map(|index| Particle::net_acceleration_at(index, state, force)).collect();
+        };
+        let mut particles = presets::two_body();
+        let mut accelerations = Vec::new();
+        force_fn(&particles, &mut accelerations);
+        for (particle, acceleration) in particles.iter_mut().zip(accelerations) {
+            particle.acceleration = acceleration;
+        }
+
+        let distance = |particles: &[Particle]| scalar::to_f64(particles[0].position.distance(particles[1].position));
+        let initial = distance(&particles);
+        let dt = presets::two_body_period() / steps_per_orbit as Scalar;
+        let mut scratch = Scratch::default();
+        let mut error: f64 = 0.;
+        for _ in 0..steps_per_orbit * orbits {
+            integrator.step(&mut particles, dt, &mut force_fn, &mut scratch);
+            error = error.max((distance(&particles) - initial).abs() / initial);
+        }
+        error
+    }
+
+    #[test]
+    fn integrators_keep_a_circular_orbit() {
+        // the error of rk4 in single precision is the rounding of the positions rather than of the integration
+        let rk4_bound = scalar::to_f64(100. * Scalar::EPSILON).max(1e-9);
+        for (name, integrator, bound) in [
+            ("euler", &EulerIntegrator as &dyn Integrator, 5e-3),
+            ("verlet", &VerletIntegrator, 5e-5),
+            ("rk4", &Rk4Integrator, rk4_bound),
+        ] {
+            let error = radius_error(integrator, 1000, 10);
+            assert!(error <= bound, "{} orbit radius changed by {:e}", name, error);
+        }
+    }
+
+    #[test]
+    fn finer_steps_shrink_the_orbit_error_by_the_order_of_the_integrator() {
+        // halving the step of a first order method halves its error, and quarters it for a second order method
+        for (name, integrator, factor) in [("euler", &EulerIntegrator as &dyn Integrator, 1.8), ("verlet", &VerletIntegrator, 3.5)] {
+            let (coarse, fine) = (radius_error(integrator, 500, 2), radius_error(integrator, 1000, 2));
+            assert!(coarse >= factor * fine, "{} orbit error went from {:e} to {:e}", name, coarse, fine);
+        }
+    }
+}
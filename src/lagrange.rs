@@ -0,0 +1,96 @@
+use crate::scalar::{self, Scalar};
+use crate::vector::{self, Vector};
+
+/// Halvings of the interval around each collinear point, enough to narrow it to
+/// the precision of an f64.
+const BISECTION_STEPS: usize = 100;
+
+/// Distances of L1, L2 and L3 from the center of mass along the line from the
+/// primary to the secondary, in units of the distance between them, where the
+/// mass ratio is the mass of the secondary over the total mass. The primary is
+/// at minus the mass ratio and the secondary at one minus the mass ratio.
+///
+/// The points are where gravity and the centrifugal force balance in the frame
+/// rotating with the bodies, found by bisection between the bodies for L1,
+/// beyond the secondary for L2 and beyond the primary for L3.
+pub fn collinear_points(mass_ratio: f64) -> [f64; 3] {
+    let mu = mass_ratio.clamp(0., 1.);
+    // acceleration along the line in the rotating frame, increasing through each point
+    let acceleration = |x: f64| {
+        let (to_primary, to_secondary) = (x + mu, x - 1. + mu);
+        x - (1. - mu) * to_primary / to_primary.abs().powi(3) - mu * to_secondary / to_secondary.abs().powi(3)
+    };
+    let bisect = |mut low: f64, mut high: f64| {
+        for _ in 0..BISECTION_STEPS {
+            let middle = (low + high) / 2.;
+            if acceleration(middle) < 0. {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+        (low + high) / 2.
+    };
+    let (primary, secondary) = (-mu, 1. - mu);
+    [bisect(primary, secondary), bisect(secondary, secondary + 1.), bisect(primary - 1., primary)]
+}
+
+/// Positions of the Lagrange points L1 to L5 of a secondary body orbiting a
+/// primary, from the positions and masses of the bodies and the velocity of the
+/// secondary relative to the primary. L1 is between the bodies, L2 beyond the
+/// secondary and L3 beyond the primary. L4 leads the secondary along its orbit
+/// and L5 trails it, each making an equilateral triangle with the bodies. The
+/// orbit is taken to be circular at the current distance between the bodies.
+/// Returns None if the bodies are at the same position or have no mass.
+pub fn lagrange_points(primary: Vector, primary_mass: Scalar, secondary: Vector, secondary_mass: Scalar, relative_velocity: Vector) -> Option<[Vector; 5]> {
+    let (primary, secondary) = (vector::to_f64(primary), vector::to_f64(secondary));
+    let total_mass = scalar::to_f64(primary_mass) + scalar::to_f64(secondary_mass);
+    let separation = primary.distance(secondary);
+    if !(total_mass > 0. && separation > 0.) {
+        return None;
+    }
+    let mass_ratio = scalar::to_f64(secondary_mass) / total_mass;
+    let axis = (secondary - primary) / separation;
+    let center_of_mass = primary + axis * mass_ratio * separation;
+    let [l1, l2, l3] = collinear_points(mass_ratio).map(|x| center_of_mass + axis * x * separation);
+
+    // direction of the orbit in its plane, around the axis out of the screen if the bodies aren't moving apart sideways
+    let velocity = vector::to_f64(relative_velocity);
+    let forward = (velocity - axis * velocity.dot(axis))
+        .try_normalize()
+        .unwrap_or_else(|| vector::to_f64(vector::perpendicular(vector::from_f64(axis))).normalize());
+    let (along, across) = (axis * 0.5 * separation, forward * 3f64.sqrt() / 2. * separation);
+    let (l4, l5) = (primary + along + across, primary + along - across);
+    Some([l1, l2, l3, l4, l5].map(vector::from_f64))
+}
+
+/// Names of the Lagrange points in the order they are returned by [`lagrange_points`].
+pub const NAMES: [&str; 5] = ["L1", "L2", "L3", "L4", "L5"];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sun_earth_l1_and_l2_are_about_1_5_million_km_from_the_earth() {
+        let mass_ratio = 5.9722e24 / (1.98847e30 + 5.9722e24);
+        let [l1, l2, l3] = collinear_points(mass_ratio);
+        let earth = 1. - mass_ratio;
+        let au = 1.495_978_707e11;
+        assert!(((earth - l1) * au / 1.4916e9 - 1.).abs() < 1e-3);
+        assert!(((l2 - earth) * au / 1.5015e9 - 1.).abs() < 1e-3);
+        // L3 is just beyond the orbit on the far side of the Sun
+        assert!((l3 + 1. + 5. / 12. * mass_ratio).abs() < 1e-9);
+    }
+
+    #[test]
+    fn equal_masses_have_symmetric_lagrange_points() {
+        // equal masses moving anticlockwise around each other
+        let points = lagrange_points(Vector::ZERO, 1., vector::planar(2., 0.), 1., vector::planar(0., 1.)).unwrap();
+        assert!(points[0].distance(vector::planar(1., 0.)) < 1e-5);
+        // L2 and L3 are the same distance beyond either body
+        assert!((points[1].x - 1. + points[2].x - 1.).abs() < 1e-5);
+        assert!(points[3].distance(vector::planar(1., 3f64.sqrt() as Scalar)) < 1e-5);
+        assert!(points[4].distance(vector::planar(1., -3f64.sqrt() as Scalar)) < 1e-5);
+    }
+}
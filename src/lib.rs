@@ -0,0 +1,83 @@
+//! Core of the n-body simulation, without any graphics. The coffee front end in
+//! `src/bin/gui` and the `nbody-headless` binary are both built on this library,
+//! and it can be used on its own with `default-features = false`, which leaves out
+//! the `gui` feature and coffee.
+//!
+//! A [`world::World`] holds the particles and advances them with an
+//! [`integrator::Integrator`] using the [`world::Physics`] it was created with:
+//!
+//! ```
+//! use nbody_simulation::integrator::VerletIntegrator;
+//! use nbody_simulation::particle::Particle;
+//! use nbody_simulation::vector::{self, Vector};
+//! use nbody_simulation::world::{Physics, RayonWorld, World};
+//!
+//! let mut world = RayonWorld::new(Vec::new(), Box::new(VerletIntegrator), Physics::default());
+//! world.create_particle(Vector::ZERO, Vector::ZERO, 1.0e12, false);
+//! world.create_particle(vector::planar(100., 0.), vector::planar(0., 0.8), 1., false);
+//! for _ in 0..100 {
+//!     world.update(1.);
+//! }
+//!
+//! let particles: Vec<Particle> = world.get_particles();
+//! assert_eq!(particles.len(), 2);
+//! assert!(particles[1].position.distance(particles[0].position) > 50.);
+//! ```
+
+/// Tree approximating the gravity of distant groups of particles
+pub mod barnes_hut;
+/// Timing of updates and frames, and the reports saved from them
+pub mod benchmark;
+/// Boxes the particles are kept inside of
+pub mod boundary;
+/// Formatting and parsing of simulated time, and pacing updates against real time
+pub mod clock;
+/// Handling of overlapping particles
+pub mod collision;
+/// Settings read from `config.toml` and environment variables
+pub mod config;
+/// Energy, momentum and center of mass of the particles
+pub mod diagnostics;
+/// Merges, splits, spawns and removals of particles, and the log keeping the latest of them
+pub mod events;
+/// State vectors of real bodies loaded from JSON files
+pub mod ephemeris;
+/// Forces acting on the particles
+pub mod force;
+/// Methods of advancing the particles by a time step
+pub mod integrator;
+/// Points where a small body keeps its place relative to two orbiting bodies
+pub mod lagrange;
+/// Distances, travel times and closing rates between points and particles
+pub mod measurement;
+/// Osculating orbits of bodies around a central body
+pub mod orbital;
+/// Particles and the gravitational force between them
+pub mod particle;
+/// Grid approximating the gravity of many particles using Fourier transforms
+pub mod particle_mesh;
+/// Ready-made sets of particles
+pub mod presets;
+/// Snapshots of the particles kept for playback
+pub mod recording;
+/// Floating point precision of the simulation, chosen with the `single-precision` feature
+pub mod scalar;
+/// Particles loaded from CSV files
+pub mod scenario;
+/// Grid used to find nearby particles
+pub mod spatial_grid;
+/// Saving and loading the whole simulation
+pub mod state;
+/// Compensated summation, keeping the small terms of sums spanning many orders of magnitude
+pub mod summation;
+/// Logging the positions and velocities of particles to CSV files
+pub mod trajectory;
+/// Vectors of the simulation, in two or three dimensions chosen with the `three-dimensions` feature
+pub mod vector;
+/// Threads kept between worlds which run jobs together with the calling thread
+pub mod worker_pool;
+/// Worlds holding the particles, one for each way of parallelising the updates
+pub mod world;
+
+/// The vector types used by the public API, re-exported so users get the same version.
+pub use glam;
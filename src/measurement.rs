@@ -0,0 +1,98 @@
+use crate::scalar::Scalar;
+use crate::vector::Vector;
+
+/// Meters in an astronomical unit
+pub const AU: f64 = 1.495_978_707e11;
+/// Meters in a kilometer
+pub const KM: f64 = 1.0e3;
+
+/// Value with four significant figures, written out from a thousandth up to a
+/// million and in scientific notation outside of that, so tiny values such as
+/// the pull of a distant body don't show as zero.
+pub fn format_magnitude(value: f64) -> String {
+    if value == 0. || !value.is_finite() {
+        return value.to_string();
+    }
+    let exponent = value.abs().log10().floor();
+    if (-3. ..6.).contains(&exponent) {
+        format!("{:.*}", (3. - exponent).max(0.) as usize, value)
+    } else {
+        format!("{:.3e}", value)
+    }
+}
+
+/// Distance in meters, with kilometers once it reaches a kilometer and
+/// astronomical units once it reaches a hundredth of one, so distances from
+/// a spawned cluster to the outer planets are readable. Distances under a
+/// centimeter are written with [`format_magnitude`].
+pub fn format_distance(meters: f64) -> String {
+    if meters.abs() >= 0.01 * AU {
+        format!("{:.3} AU ({:.3e} km)", meters / AU, meters / KM)
+    } else if meters.abs() >= KM {
+        format!("{:.2} km", meters / KM)
+    } else if meters.abs() >= 0.01 {
+        format!("{:.2} m", meters)
+    } else {
+        format!("{} m", format_magnitude(meters))
+    }
+}
+
+/// Seconds to travel the distance in a straight line at the speed, or None
+/// if the speed isn't positive.
+pub fn travel_time(distance: f64, speed: f64) -> Option<f64> {
+    (speed > 0.).then(|| distance / speed)
+}
+
+/// Speed of b as seen from a.
+pub fn relative_speed(velocity_a: Vector, velocity_b: Vector) -> Scalar {
+    (velocity_b - velocity_a).length()
+}
+
+/// Rate the distance between a and b shrinks in m/s, negative while they move
+/// apart. Points at the same position have no direction between them and are
+/// given a rate of zero.
+pub fn closing_rate(position_a: Vector, velocity_a: Vector, position_b: Vector, velocity_b: Vector) -> Scalar {
+    let Some(direction) = (position_b - position_a).try_normalize() else {
+        return 0.;
+    };
+    -(velocity_b - velocity_a).dot(direction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector;
+
+    #[test]
+    fn magnitudes_keep_four_significant_figures() {
+        assert_eq!(format_magnitude(9.81), "9.810");
+        assert_eq!(format_magnitude(-0.0123), "-0.01230");
+        assert_eq!(format_magnitude(123456.7), "123457");
+        assert_eq!(format_magnitude(2.5e-9), "2.500e-9");
+        assert_eq!(format_magnitude(0.), "0");
+    }
+    #[test]
+    fn distances_switch_to_kilometers_and_astronomical_units() {
+        assert_eq!(format_distance(12.5), "12.50 m");
+        assert_eq!(format_distance(3e-5), "3.000e-5 m");
+        assert_eq!(format_distance(3.2e6), "3200.00 km");
+        assert_eq!(format_distance(5.2 * AU), "5.200 AU (7.779e8 km)");
+    }
+
+    #[test]
+    fn sunlight_takes_a_little_over_eight_minutes_to_reach_the_earth() {
+        let seconds = travel_time(AU, 299_792_458.).unwrap();
+        assert!((seconds - 499.0).abs() < 0.1);
+        assert_eq!(travel_time(AU, 0.), None);
+    }
+
+    #[test]
+    fn closing_rate_ignores_sideways_motion() {
+        // b heads straight for a at 3 m/s while sliding past it at 4 m/s
+        let (a, b) = (Vector::ZERO, vector::planar(10., 0.));
+        let rate = closing_rate(a, Vector::ZERO, b, vector::planar(-3., 4.));
+        assert!((rate - 3.).abs() < 1e-6);
+        assert!(closing_rate(a, Vector::ZERO, b, vector::planar(3., 0.)) < 0.);
+        assert_eq!(closing_rate(a, Vector::ZERO, a, vector::planar(3., 0.)), 0.);
+    }
+}
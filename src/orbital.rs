@@ -0,0 +1,136 @@
+use std::f64::consts::PI;
+
+use crate::vector::{self, DoubleVector, Vector};
+
+/// Osculating orbit of a body around a central body, the two-body orbit the
+/// body would follow from its current position and velocity if nothing else
+/// pulled on it. Distances are in meters and times in seconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OrbitalElements {
+    /// Half the longest width of the orbit, negative for escape trajectories
+    /// and infinite for a parabola
+    pub semi_major_axis: f64,
+    /// 0 for a circle, between 0 and 1 for an ellipse and at least 1 for an escape trajectory
+    pub eccentricity: f64,
+    /// Closest distance to the central body
+    pub periapsis: f64,
+    /// Furthest distance from the central body, None on escape trajectories
+    pub apoapsis: Option<f64>,
+    /// Time to go around the orbit once, None on escape trajectories
+    pub period: Option<f64>,
+    /// Direction from the central body to the periapsis, or to the body if the orbit is circular
+    pub periapsis_direction: DoubleVector,
+    /// Direction the body moves in at the periapsis
+    pub prograde_direction: DoubleVector,
+}
+
+impl OrbitalElements {
+    /// Whether the body escapes the central body, with an eccentricity of at least 1.
+    pub fn is_escape(&self) -> bool {
+        self.eccentricity >= 1.
+    }
+
+    /// Semi-latus rectum, the distance from the central body to the orbit at right
+    /// angles to the periapsis.
+    fn semi_latus_rectum(&self) -> f64 {
+        self.periapsis * (1. + self.eccentricity)
+    }
+
+    /// Points of the orbit relative to the central body, evenly spaced in angle
+    /// around it starting from the periapsis and ending back at it. Escape
+    /// trajectories don't close, so they have no points.
+    pub fn points(&self, count: usize) -> Vec<DoubleVector> {
+        if self.is_escape() || count < 2 {
+            return Vec::new();
+        }
+        (0..count)
+            .map(|index| {
+                let anomaly = 2. * PI * index as f64 / (count - 1) as f64;
+                let distance = self.semi_latus_rectum() / (1. + self.eccentricity * anomaly.cos());
+                (self.periapsis_direction * anomaly.cos() + self.prograde_direction * anomaly.sin()) * distance
+            })
+            .collect()
+    }
+}
+
+/// Osculating orbital elements of a body at the position and velocity relative
+/// to a central body, where the gravitational parameter is G times the masses of
+/// both bodies. Returns None without gravity, if the body is at the center, or
+/// if it moves straight towards or away from it.
+pub fn orbital_elements(relative_position: Vector, relative_velocity: Vector, gravitational_parameter: f64) -> Option<OrbitalElements> {
+    let (r, v, mu) = (vector::to_f64(relative_position), vector::to_f64(relative_velocity), gravitational_parameter);
+    let distance = r.length();
+    // squared specific angular momentum, |r x v|²
+    let h_squared = r.length_squared() * v.length_squared() - r.dot(v).powi(2);
+    if !(mu > 0. && distance > 0. && h_squared > 0.) {
+        return None;
+    }
+
+    let energy = v.length_squared() / 2. - mu / distance;
+    let semi_major_axis = -mu / (2. * energy);
+    let eccentricity_vector = (r * (v.length_squared() - mu / distance) - v * r.dot(v)) / mu;
+    let eccentricity = eccentricity_vector.length();
+    let periapsis = h_squared / (mu * (1. + eccentricity));
+    let bound = eccentricity < 1.;
+
+    // the direction of motion at the periapsis is h x p / |h| = (v (r.p) - r (v.p)) / |h|, which stays in the plane of the orbit in both two and three dimensions
+    let periapsis_direction = eccentricity_vector.try_normalize().filter(|_| eccentricity > 1e-9).unwrap_or(r / distance);
+    let prograde_direction = (v * r.dot(periapsis_direction) - r * v.dot(periapsis_direction)) / h_squared.sqrt();
+    Some(OrbitalElements {
+        semi_major_axis,
+        eccentricity,
+        periapsis,
+        apoapsis: bound.then_some(semi_major_axis * (1. + eccentricity)),
+        period: bound.then(|| 2. * PI * (semi_major_axis.powi(3) / mu).sqrt()),
+        periapsis_direction,
+        prograde_direction,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scalar::Scalar;
+
+    #[test]
+    fn circular_orbit_has_no_eccentricity() {
+        let orbit = orbital_elements(vector::planar(2., 0.), vector::planar(0., 0.5f64.sqrt() as Scalar), 1.).unwrap();
+        assert!(orbit.eccentricity < 1e-6);
+        assert!((orbit.semi_major_axis - 2.).abs() < 1e-6);
+        assert!((orbit.periapsis - 2.).abs() < 1e-6 && (orbit.apoapsis.unwrap() - 2.).abs() < 1e-6);
+        assert!((orbit.period.unwrap() - 2. * PI * 8f64.sqrt()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn faster_than_circular_orbit_is_an_ellipse_with_its_periapsis_at_the_body() {
+        // r = 1, v² = 1.5 so a = 1 / (2 - 1.5) = 2 and e = 1 - r / a
+        let orbit = orbital_elements(vector::planar(0., 1.), vector::planar(-(1.5f64.sqrt()) as Scalar, 0.), 1.).unwrap();
+        assert!((orbit.semi_major_axis - 2.).abs() < 1e-6);
+        assert!((orbit.eccentricity - 0.5).abs() < 1e-6);
+        assert!((orbit.periapsis - 1.).abs() < 1e-6 && (orbit.apoapsis.unwrap() - 3.).abs() < 1e-6);
+        assert!(orbit.periapsis_direction.x.abs() < 1e-6 && (orbit.periapsis_direction.y - 1.).abs() < 1e-6);
+        // a quarter of the way around it is the semi-latus rectum a (1 - e²) away, and halfway the apoapsis
+        let points = orbit.points(5);
+        assert!((points[1].x + 1.5).abs() < 1e-6 && points[1].y.abs() < 1e-6);
+        assert!((points[2].y + 3.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn earths_orbit_takes_a_year() {
+        let (au, gm_sun) = (1.495_978_707e11, 1.327_124_4e20);
+        let earth = orbital_elements(vector::planar(au as Scalar, 0.), vector::planar(0., 29_784.7), gm_sun).unwrap();
+        assert!((earth.period.unwrap() / (365.25 * 86_400.) - 1.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn orbit_above_the_escape_speed_is_hyperbolic() {
+        // r = 1, v² = 3 so a = 1 / (2 - 3) = -1 and e = 1 - r / a = 2
+        let orbit = orbital_elements(vector::planar(1., 0.), vector::planar(0., 3f64.sqrt() as Scalar), 1.).unwrap();
+        assert!(orbit.is_escape());
+        assert!((orbit.eccentricity - 2.).abs() < 1e-6);
+        assert!((orbit.semi_major_axis + 1.).abs() < 1e-6);
+        assert!((orbit.periapsis - 1.).abs() < 1e-6);
+        assert_eq!((orbit.apoapsis, orbit.period), (None, None));
+        assert!(orbit.points(100).is_empty());
+    }
+}
@@ -1,27 +1,489 @@
-use glam::DVec2;
+use std::collections::HashMap;
+use std::fmt;
+use std::num::ParseIntError;
+use std::str::FromStr;
 
-const NEG_G: f64 = -6.67430e-11;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug)]
+use crate::scalar::consts::PI;
+use crate::scalar::Scalar;
+use crate::summation::compensated_sum;
+use crate::vector::{Vector, perpendicular};
+
+/// Gravitational constant in m^3 / (kg s^2)
+pub const G: Scalar = 6.67430e-11;
+
+/// Parameters of the gravitational force between particles.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ForceParams {
+    /// Gravitational constant of the force, negative values make gravity repulsive
+    pub g: Scalar,
+    /// Softening length, which keeps the force bounded when particles are very close
+    pub softening: Scalar,
+    /// Size of the periodic box the separations between particles are wrapped
+    /// to, so particles are attracted by the nearest image of each other
+    pub period: Option<Vector>,
+}
+
+impl ForceParams {
+    /// Real gravity with the given softening length.
+    pub fn softened(softening: Scalar) -> Self {
+        ForceParams { g: G, softening, period: None }
+    }
+
+    /// Vector from b to a, or to the nearest image of b in a periodic box.
+    pub fn separation(&self, a: Vector, b: Vector) -> Vector {
+        let r = a - b;
+        match self.period {
+            Some(period) => r - period * (r / period).round(),
+            None => r,
+        }
+    }
+
+    /// Acceleration at a position caused by a mass at the source position.
+    pub fn acceleration(&self, position: Vector, source: Vector, mass: Scalar) -> Vector {
+        let r = self.separation(position, source);
+        let distance_squared = r.length_squared() + self.softening * self.softening;
+        // a = (-GMr) / (|r|^2 + e^2)^(3/2), divided in steps so the powers of the
+        // distance between planets don't overflow in single precision
+        -self.g * mass / distance_squared * r / distance_squared.sqrt()
+    }
+}
+
+/// Identifier of a particle, unique within a world. Worlds give new particles
+/// increasing ids from a counter, so ids are never reused after removals. It is
+/// a type of its own so ids can't be mixed up with indices into the particles.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ParticleId(usize);
+
+impl ParticleId {
+    pub const fn new(id: usize) -> Self {
+        ParticleId(id)
+    }
+
+    /// The id as a number, such as to write it to a file.
+    pub const fn get(self) -> usize {
+        self.0
+    }
+
+    /// The id after this one, panicking rather than wrapping around to ids
+    /// which may still be in use.
+    pub fn next(self) -> Self {
+        ParticleId(self.0.checked_add(1).expect("ran out of particle ids"))
+    }
+}
+
+impl From<usize> for ParticleId {
+    fn from(id: usize) -> Self {
+        ParticleId(id)
+    }
+}
+
+impl fmt::Display for ParticleId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl FromStr for ParticleId {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(ParticleId)
+    }
+}
+
+/// Names of particles by id, such as the planets of the solar system preset. The
+/// names are kept apart from the particles so the particles stay cheap to copy
+/// between buffers and worlds, and only the few named particles carry a name.
+pub type ParticleNames = HashMap<ParticleId, String>;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Particle {
-    pub id: usize,
-    pub velocity: DVec2,
-    pub position: DVec2,
-    pub mass: f64,
+    pub id: ParticleId,
+    pub velocity: Vector,
+    pub position: Vector,
+    pub mass: Scalar,
+    /// Radius of the particle, derived from its mass and the density of the world
+    pub radius: Scalar,
+    /// Acceleration from the previous step, used by the velocity Verlet integration
+    pub acceleration: Vector,
+    /// Fixed particles attract other particles but never move
+    #[serde(default)]
+    pub fixed: bool,
 }
 
 impl Particle {
-    pub fn acceleration(&self, rhs: &Particle) -> DVec2 {
-        let r = self.position - rhs.position;
-        let acceleration = NEG_G * rhs.mass * r / r.length().powi(3); // a = (-GM/|r|^2) * (r / |r|) = (-GMr) / |r|^3
-        if acceleration.is_nan() { DVec2::ZERO } else { acceleration }
-    }
-
-    pub fn net_acceleration(&self, particles: &[Particle]) -> DVec2 {
-        particles
-            .iter()
-            .filter(|other| self.id != other.id)
-            .map(|other| self.acceleration(other))
-            .sum()
+    /// A particle with the radius of its mass at the density and no acceleration
+    /// yet. Worlds set the acceleration when the particle is added, and particles
+    /// made on their own can be advanced by an integrator without a world:
+    ///
+    /// ```
+    /// use nbody_simulation::integrator::{EulerIntegrator, Integrator, Scratch};
+    /// use nbody_simulation::particle::{ForceParams, Particle, ParticleId};
+    /// use nbody_simulation::vector::{self, Vector};
+    ///
+    /// let force = ForceParams::softened(1.);
+    /// let mut particles = vec![
+    ///     Particle::new(ParticleId::new(0), Vector::ZERO, Vector::ZERO, 1.0e12, 1.),
+    ///     Particle::new(ParticleId::new(1), vector::planar(100., 0.), vector::planar(0., 0.8), 1.0e6, 1.),
+    /// ];
+    /// EulerIntegrator.step(&mut particles, 1., &mut |state, accelerations| {
+    ///     *accelerations = (0..state.len()).map(|index| Particle::net_acceleration_at(index, state, force)).collect();
+    /// }, &mut Scratch::default());
+    /// ```
+    pub fn new(id: ParticleId, position: Vector, velocity: Vector, mass: Scalar, density: Scalar) -> Self {
+        Particle { id, velocity, position, mass, radius: Particle::radius_from_mass(mass, density), acceleration: Vector::ZERO, fixed: false }
+    }
+
+    /// Acceleration caused by rhs. The softening length keeps the acceleration
+    /// bounded when the particles are very close and must be greater than 0.
+    pub fn acceleration(&self, rhs: &Particle, force: ForceParams) -> Vector {
+        force.acceleration(self.position, rhs.position, rhs.mass)
+    }
+
+    /// Acceleration caused by every particle other than this one, which is
+    /// found among the particles by its id. Use [`Particle::net_acceleration_at`]
+    /// when the index of the particle is already known.
+    pub fn net_acceleration(&self, particles: &[Particle], force: ForceParams) -> Vector {
+        let index = particles.iter().position(|other| other.id == self.id).unwrap_or(particles.len());
+        self.net_acceleration_skipping(index, particles, force)
+    }
+
+    /// Acceleration of the particle at the index caused by every other particle.
+    /// The particles before and after the index are summed separately, so the
+    /// loop over them has no comparison to exclude the particle itself.
+    ///
+    /// The accelerations are summed with a [`CompensatedSum`], so the pull of
+    /// small bodies still counts next to the pull of a star.
+    pub fn net_acceleration_at(index: usize, particles: &[Particle], force: ForceParams) -> Vector {
+        particles[index].net_acceleration_skipping(index, particles, force)
+    }
+
+    fn net_acceleration_skipping(&self, index: usize, particles: &[Particle], force: ForceParams) -> Vector {
+        let (before, after) = particles.split_at(index);
+        compensated_sum(before.iter().chain(after.iter().skip(1)).map(|other| self.acceleration(other, force)))
+    }
+
+    /// Velocity of a circular orbit around this particle at the given position,
+    /// using the same force as the acceleration. There are no orbits when gravity
+    /// is repulsive, so the velocity of this particle is returned instead.
+    pub fn orbital_velocity(&self, position: Vector, force: ForceParams) -> Vector {
+        let r = force.separation(position, self.position);
+        let distance_squared = r.length_squared();
+        if distance_squared == 0. || force.g <= 0. {
+            return self.velocity;
+        }
+        let softened_squared = distance_squared + force.softening * force.softening;
+        let speed = (force.g * self.mass / softened_squared * distance_squared / softened_squared.sqrt()).sqrt(); // v^2 = |a| * |r|
+        self.velocity + perpendicular(r).normalize() * speed
+    }
+
+    /// Velocity of an orbit around this particle with the given eccentricity
+    /// which is closest to this particle at the given position, so an eccentricity
+    /// of zero gives a circular orbit. The speed at periapsis is the circular
+    /// speed scaled by sqrt(1 + e), from the vis-viva equation.
+    pub fn insertion_velocity(&self, position: Vector, eccentricity: Scalar, force: ForceParams) -> Vector {
+        self.velocity + (self.orbital_velocity(position, force) - self.velocity) * (1. + eccentricity).sqrt()
+    }
+
+    /// Velocity of a parabolic orbit around this particle from the given position
+    /// which comes closest to this particle at the periapsis distance, passing it
+    /// anticlockwise. The speed is the escape speed, with the tangential part set
+    /// by the angular momentum of the orbit, sqrt(2GMq), and the rest pointing
+    /// inwards. A periapsis beyond the position gives a purely tangential velocity.
+    /// There are no orbits when gravity is repulsive, so the velocity of this
+    /// particle is returned instead.
+    pub fn approach_velocity(&self, position: Vector, periapsis: Scalar, force: ForceParams) -> Vector {
+        let r = force.separation(position, self.position);
+        let distance = r.length();
+        if distance == 0. || force.g <= 0. {
+            return self.velocity;
+        }
+        let speed_squared = 2. * force.g * self.mass / distance;
+        let tangential = ((2. * force.g * self.mass * periapsis).sqrt() / distance).min(speed_squared.sqrt());
+        let radial = (speed_squared - tangential * tangential).max(0.).sqrt();
+        self.velocity + perpendicular(r).normalize() * tangential - r / distance * radial
+    }
+
+    /// Radius of a sphere with the given mass and density.
+    pub fn radius_from_mass(mass: Scalar, density: Scalar) -> Scalar {
+        (3. * mass / (4. * PI * density)).cbrt() // m = 4/3 * pi * r^3 * density
+    }
+
+    pub fn overlaps(&self, other: &Particle) -> bool {
+        self.position.distance_squared(other.position) < (self.radius + other.radius).powi(2)
+    }
+
+    /// Absorbs other into this particle, conserving mass and momentum. The
+    /// merged particle is placed at the center of mass and keeps the id of
+    /// the more massive of the two particles. A moving particle merging with
+    /// a fixed particle is absorbed into the fixed particle, which stays fixed.
+    pub fn merge(&mut self, other: &Particle, density: Scalar) {
+        let mass = self.mass + other.mass;
+        if self.fixed || other.fixed {
+            if !self.fixed || (other.fixed && other.mass > self.mass) {
+                self.id = other.id;
+                self.position = other.position;
+            }
+            self.velocity = Vector::ZERO;
+            self.fixed = true;
+        } else {
+            if other.mass > self.mass {
+                self.id = other.id;
+            }
+            self.position = (self.position * self.mass + other.position * other.mass) / mass;
+            self.velocity = (self.velocity * self.mass + other.velocity * other.mass) / mass;
+        }
+        self.acceleration = (self.acceleration * self.mass + other.acceleration * other.mass) / mass;
+        self.mass = mass;
+        self.radius = Particle::radius_from_mass(mass, density);
+    }
+}
+
+/// Particle causing the strongest gravitational acceleration at the position,
+/// the largest Gm/r^2 using the softening of the force.
+pub fn dominant_attractor(particles: &[Particle], position: Vector, force: ForceParams) -> Option<&Particle> {
+    let pull = |particle: &Particle| particle.mass / (force.separation(position, particle.position).length_squared() + force.softening * force.softening);
+    particles
+        .iter()
+        .filter(|particle| particle.position != position)
+        .max_by(|a, b| pull(a).total_cmp(&pull(b)))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+    use crate::integrator::{EulerIntegrator, Integrator, Scratch, VerletIntegrator};
+    use crate::measurement::AU;
+    use crate::presets;
+    use crate::scalar;
+    use crate::vector::{self, DoubleVector};
+    use crate::world::{Physics, SequentialWorld, World};
+
+    /// Particles of random masses and velocities spread through a box 2 km across.
+    fn random_particles(rng: &mut StdRng, count: usize) -> Vec<Particle> {
+        (0..count)
+            .map(|id| {
+                let position = vector::map(Vector::ZERO, |_, _| rng.gen_range(-1e3..1e3));
+                let velocity = vector::map(Vector::ZERO, |_, _| rng.gen_range(-1.0..1.));
+                Particle::new(ParticleId::new(id), position, velocity, rng.gen_range(1e3..1e9), 1.)
+            })
+            .collect()
+    }
+
+    fn momentum(particles: &[Particle]) -> Vector {
+        particles.iter().map(|particle| particle.velocity * particle.mass).sum()
+    }
+
+    #[test]
+    fn particles_pull_on_each_other_equally() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let force = ForceParams::softened(1.);
+        for pair in random_particles(&mut rng, 200).chunks(2) {
+            let [a, b] = pair else { unreachable!() };
+            let (pull, reaction) = (a.acceleration(b, force) * a.mass, b.acceleration(a, force) * b.mass);
+            assert!((pull + reaction).length() <= 4. * Scalar::EPSILON * pull.length(), "{:?} and {:?}", pull, reaction);
+        }
+    }
+
+    #[test]
+    fn acceleration_falls_with_the_square_of_the_distance() {
+        let mut rng = StdRng::seed_from_u64(1);
+        // the softening is too small next to the distances to change the accelerations
+        let force = ForceParams::softened(1e-6);
+        for pair in random_particles(&mut rng, 200).chunks(2) {
+            let [a, b] = pair else { unreachable!() };
+            let mut far = b.clone();
+            far.position = a.position + (b.position - a.position) * 2.;
+            let (near, far) = (a.acceleration(b, force), a.acceleration(&far, force));
+            assert!((near / 4. - far).length() <= 8. * Scalar::EPSILON * far.length(), "{:?} and {:?}", near, far);
+        }
+    }
+
+    #[test]
+    fn symmetric_ring_pulls_its_center_nowhere() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let force = ForceParams::softened(1.);
+        for _ in 0..20 {
+            let count = rng.gen_range(3..40);
+            let (center, radius, mass) = (vector::planar(rng.gen_range(-1e3..1e3), rng.gen_range(-1e3..1e3)), rng.gen_range(10.0..1e3), rng.gen_range(1e3..1e9));
+            let rotation = rng.gen_range(0.0..2. * PI);
+            let mut particles = vec![Particle::new(ParticleId::new(0), center, Vector::ZERO, 1., 1.)];
+            for i in 0..count {
+                let angle = rotation + 2. * PI * i as Scalar / count as Scalar;
+                let position = center + vector::planar(angle.cos(), angle.sin()) * radius;
+                particles.push(Particle::new(ParticleId::new(i + 1), position, Vector::ZERO, mass, 1.));
+            }
+            let single = particles[0].acceleration(&particles[1], force).length();
+            let net = Particle::net_acceleration_at(0, &particles, force);
+            assert!(net.length() <= 16. * Scalar::EPSILON * single * count as Scalar, "{} particles pull by {:?}", count, net);
+        }
+    }
+
+    #[test]
+    fn softening_bounds_the_pull_of_close_particles() {
+        let mut rng = StdRng::seed_from_u64(4);
+        for softening in [0.1, 1., 5.] {
+            let force = ForceParams::softened(softening);
+            let source = Particle::new(ParticleId::new(0), Vector::ZERO, Vector::ZERO, 1e12, 1.);
+            // G m r / (r^2 + e^2)^(3/2) is largest at r = e / sqrt(2)
+            let bound = 2. * G * source.mass / (3. * (3. as Scalar).sqrt() * softening * softening);
+            let coincident = Particle::new(ParticleId::new(1), Vector::ZERO, Vector::ZERO, 1., 1.);
+            assert_eq!(coincident.acceleration(&source, force), Vector::ZERO);
+            for _ in 0..100 {
+                let distance = softening * (10 as Scalar).powf(rng.gen_range(-30.0..1.));
+                let angle = rng.gen_range(0.0..2. * PI);
+                let target = Particle::new(ParticleId::new(1), vector::planar(angle.cos(), angle.sin()) * distance, Vector::ZERO, 1., 1.);
+                let acceleration = target.acceleration(&source, force);
+                assert!(acceleration.is_finite() && acceleration.length() <= bound * (1. + 4. * Scalar::EPSILON), "{:?} at {} m", acceleration, distance);
+            }
+            let stack = vec![source.clone(), coincident.clone(), coincident];
+            assert!((0..stack.len()).all(|index| Particle::net_acceleration_at(index, &stack, force).is_finite()));
+        }
+    }
+
+    #[test]
+    fn pull_of_small_bodies_counts_next_to_a_star() {
+        // a thousand 100 kg bodies each pulling a quarter of the rounding error of the pull of the Sun
+        let force = ForceParams::softened(1e-3);
+        let mut particles = vec![
+            Particle::new(ParticleId::new(0), Vector::ZERO, Vector::ZERO, 100., 1.),
+            Particle::new(ParticleId::new(1), vector::planar(scalar::from_f64(AU), 0.), Vector::ZERO, 2e30, 1.),
+        ];
+        let sun = particles[0].acceleration(&particles[1], force).x;
+        let distance = (G * 100. / (sun * Scalar::EPSILON / 4.)).sqrt();
+        for id in 2..1002 {
+            particles.push(Particle::new(ParticleId::new(id), vector::planar(-distance, 0.), Vector::ZERO, 100., 1.));
+        }
+        let tiny = particles[0].acceleration(&particles[2], force).x;
+
+        // the identical tiny pulls are summed by one multiplication, which only rounds once
+        let reference = scalar::from_f64(scalar::to_f64(sun) + 1000. * scalar::to_f64(tiny));
+        let naive: Vector = particles[1..].iter().map(|other| particles[0].acceleration(other, force)).sum();
+        let compensated = Particle::net_acceleration_at(0, &particles, force);
+        assert!((naive.x - reference).abs() > 100. * Scalar::EPSILON * reference);
+        assert!((compensated.x - reference).abs() <= Scalar::EPSILON * reference);
+    }
+
+    #[test]
+    fn approach_velocity_keeps_the_angular_momentum_of_its_periapsis() {
+        let force = ForceParams::softened(0.);
+        let planet = Particle::new(ParticleId::new(0), Vector::ZERO, Vector::ZERO, 6.0e24, 5.5e3);
+        let (position, periapsis) = (vector::planar(1.0e8, 0.), 1.0e7);
+        let velocity = planet.approach_velocity(position, periapsis, force);
+
+        // the specific angular momentum is kept all the way to periapsis, sqrt(2GMq)
+        let momentum = scalar::to_f64(position.x * velocity.y - position.y * velocity.x);
+        let expected = (2. * 6.67430e-11 * 6.0e24 * 1.0e7_f64).sqrt();
+        assert!((momentum - expected).abs() < 1e-4 * expected);
+        assert!(velocity.x < 0.);
+    }
+
+    #[test]
+    fn euler_step_conserves_momentum() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let force = ForceParams::softened(1.);
+        let mut particles = random_particles(&mut rng, 50);
+        let before = momentum(&particles);
+        let dt = 10.;
+        EulerIntegrator.step(&mut particles, dt, &mut |state, accelerations| {
+            *accelerations = (0..state.len()).map(|index| Particle::net_acceleration_at(index, state, force)).collect();
+        }, &mut Scratch::default());
+        // the momentum only changes by rounding, relative to the largest momenta added up
+        let scale: Scalar = particles.iter().map(|particle| particle.mass * (particle.velocity.length() + particle.acceleration.length() * dt)).sum();
+        assert!((momentum(&particles) - before).length() <= 64. * Scalar::EPSILON * scale, "{:?} became {:?}", before, momentum(&particles));
+    }
+
+    /// Positions in meters of the Sun and the planets of the solar system preset after
+    /// 100 daily updates of a sequential world with the default physics, recorded in
+    /// double precision.
+    #[cfg(not(feature = "three-dimensions"))]
+    const SOLAR_SYSTEM_AFTER_100_DAYS: [[f64; 2]; 9] = [
+        [-38870775.4163778, 85520897.92502536],
+        [31848885042.24217, 49882619341.74038],
+        [45345401061.89429, -98821387368.64749],
+        [147304924390.1251, 32575379308.959442],
+        [-57847962582.4653, 220836427375.1512],
+        [-739077254673.3909, -245124266991.5785],
+        [1252526501745.9153, -697376306647.5359],
+        [-802890828798.0906, 2757974893860.3135],
+        [-2029026285363.605, -4011063706490.6875],
+    ];
+    #[cfg(feature = "three-dimensions")]
+    const SOLAR_SYSTEM_AFTER_100_DAYS: [[f64; 3]; 9] = [
+        [-38851546.54784772, 85528796.93362004, 1018221.6718126273],
+        [31901360097.43396, 49836076587.69599, 1134593824.4214463],
+        [45231017549.63924, -98794430551.7028, -3963023535.5628924],
+        [147304921885.31543, 32575386352.302597, 477886.33855058183],
+        [-57773675076.62125, 220773109987.78326, 6045668100.447973],
+        [-738881127656.5886, -245088044477.18384, 17539222991.090862],
+        [1251779501161.4277, -697703672830.526, -37603402641.44569],
+        [-802756825533.4915, 2757936484203.8174, 20664611744.910988],
+        [-2027537128758.9377, -4009732994913.72, 129283506856.59889],
+    ];
+
+    /// Smallest and largest distance of a planet inserted into an orbit around a
+    /// star with the eccentricity, over a number of orbits of the given period.
+    fn inserted_orbit_range(eccentricity: Scalar, period: Scalar, orbits: usize) -> (Scalar, Scalar) {
+        const STEPS_PER_ORBIT: usize = 2000;
+        let physics = Physics { softening: 0., gravity_multiplier: 1., gravitational_constant: G, ..Physics::default() };
+        let star = Particle::new(ParticleId::new(0), Vector::ZERO, Vector::ZERO, 2e30, 1.4e3);
+        let position = vector::planar(1.5e11, 0.);
+        let velocity = star.insertion_velocity(position, eccentricity, physics.force_params());
+        let planet = Particle::new(ParticleId::new(1), position, velocity, 6e24, 5.5e3);
+        let mut world = SequentialWorld::new(vec![star, planet], Box::new(VerletIntegrator), physics);
+        let (mut min, mut max) = (Scalar::INFINITY, 0. as Scalar);
+        for _ in 0..orbits * STEPS_PER_ORBIT {
+            world.update(period / STEPS_PER_ORBIT as Scalar);
+            let particles = world.particles();
+            let distance = particles[0].position.distance(particles[1].position);
+            (min, max) = (min.min(distance), max.max(distance));
+        }
+        (min / 1.5e11, max / 1.5e11)
+    }
+
+    #[test]
+    fn inserted_circular_orbit_keeps_its_radius() {
+        let period = scalar::consts::TAU * ((1.5e11 as Scalar).powi(3) / (G * (2e30 + 6e24))).sqrt();
+        let (min, max) = inserted_orbit_range(0., period, 20);
+        assert!(min >= 0.99 && max <= 1.01, "orbit ranged from {} to {} of its radius", min, max);
+    }
+
+    #[test]
+    fn inserted_elliptical_orbit_reaches_its_apoapsis() {
+        // the periapsis is the insertion point, so the semi-major axis is r / (1 - e) and the apoapsis r (1 + e) / (1 - e)
+        let period = scalar::consts::TAU * ((3e11 as Scalar).powi(3) / (G * (2e30 + 6e24))).sqrt();
+        let (min, max) = inserted_orbit_range(0.5, period, 5);
+        assert!(min >= 0.99 && (max - 3.).abs() <= 0.03, "orbit ranged from {} to {} of its periapsis", min, max);
+    }
+
+    #[test]
+    fn dominant_attractor_has_the_strongest_pull() {
+        let star = Particle::new(ParticleId::new(0), Vector::ZERO, Vector::ZERO, 2e30, 1.4e3);
+        let planet = Particle::new(ParticleId::new(1), vector::planar(1.5e11, 0.), Vector::ZERO, 6e24, 5.5e3);
+        let particles = [star, planet];
+        let force = ForceParams::softened(0.);
+        // the pulls are equal about 2.6e8 m from the planet
+        assert_eq!(dominant_attractor(&particles, vector::planar(1.5e11 - 1e8, 0.), force).map(|particle| particle.id), Some(ParticleId::new(1)));
+        assert_eq!(dominant_attractor(&particles, vector::planar(1.5e11 - 1e9, 0.), force).map(|particle| particle.id), Some(ParticleId::new(0)));
+        assert_eq!(dominant_attractor(&particles[..1], Vector::ZERO, force).map(|particle| particle.id), None);
+    }
+
+    #[test]
+    fn solar_system_matches_golden_positions_after_100_days() {
+        let mut world = SequentialWorld::new(presets::solar_system(), Box::new(VerletIntegrator), Physics::default());
+        world.advance(100, 86_400.);
+        assert_eq!(world.len(), SOLAR_SYSTEM_AFTER_100_DAYS.len());
+        // loose enough for single precision, which drifts from the recording by a few parts in a million
+        let tolerance = scalar::to_f64(Scalar::EPSILON.sqrt());
+        for (particle, golden) in world.get_particles().iter().zip(SOLAR_SYSTEM_AFTER_100_DAYS) {
+            let (position, golden) = (vector::to_f64(particle.position), DoubleVector::from_array(golden));
+            assert!((position - golden).length() <= tolerance * golden.length(), "particle {} is at {:?} instead of {:?}", particle.id, position, golden);
+        }
     }
 }
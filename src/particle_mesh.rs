@@ -0,0 +1,383 @@
+use std::ops::{Add, Mul, Sub};
+
+use rayon::prelude::*;
+
+use crate::force::GravityField;
+use crate::particle::{ForceParams, Particle};
+use crate::scalar;
+use crate::vector::{self, DIMENSIONS, DoubleVector, Vector};
+
+/// Corners of a cell of the grid, which share the mass of a particle in the cell
+const CORNERS: usize = 1 << DIMENSIONS;
+/// Fewest grid points along each axis
+const MIN_CELLS: usize = 4;
+/// Rows transposed together between the transforms of each axis
+const TRANSPOSE_BLOCK: usize = 16;
+
+/// Complex number, the values of the Fourier transforms.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn conj(self) -> Self {
+        Complex { re: self.re, im: -self.im }
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, rhs: Complex) -> Complex {
+        Complex { re: self.re + rhs.re, im: self.im + rhs.im }
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, rhs: Complex) -> Complex {
+        Complex { re: self.re - rhs.re, im: self.im - rhs.im }
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, rhs: Complex) -> Complex {
+        Complex { re: self.re * rhs.re - self.im * rhs.im, im: self.re * rhs.im + self.im * rhs.re }
+    }
+}
+
+/// Particle-mesh approximation of gravity, which spreads the mass of the
+/// particles over a grid and finds the gravity at every grid point at once by
+/// convolving the masses with the gravity of a single particle using fast
+/// Fourier transforms. Building it takes O(n + m log m) time for n particles
+/// and m grid points, and the acceleration of each particle is interpolated
+/// from the grid points around it.
+///
+/// The mass of each particle is shared between the corners of the cell it is
+/// in, weighted by how close it is to each corner (cloud-in-cell), and the
+/// accelerations are interpolated with the same weights. Gravity is softened
+/// to at least the width of a cell, so it is only accurate for particles which
+/// are several cells apart, which suits large numbers of evenly spread particles.
+///
+/// The grid is stretched over the particles every time it is built, so no
+/// particle is ever outside of it, and padded with empty grid points so the
+/// convolution doesn't wrap around. In a periodic box the grid covers the box
+/// instead and wraps around it, giving the nearest image of every particle.
+/// The buffers are kept between builds so they don't need to be reallocated
+/// every update.
+#[derive(Default)]
+pub struct ParticleMesh {
+    /// Grid points along each axis of the region containing the particles
+    cells: usize,
+    /// Grid points along each axis of the Fourier transforms, twice the cells unless the grid wraps around
+    len: usize,
+    periodic: bool,
+    /// Position of the first grid point and the distance between grid points along each axis
+    origin: DoubleVector,
+    spacing: DoubleVector,
+    /// Roots of unity used by the Fourier transforms of lines of length len
+    twiddles: Vec<Complex>,
+    /// Mass at each grid point and its Fourier transform
+    density: Vec<Complex>,
+    /// Gravity of a single particle and its convolution with the density
+    kernel: Vec<Complex>,
+    /// Buffer the grids are transposed into between the transforms of each axis
+    scratch: Vec<Complex>,
+    /// Acceleration at each grid point of the region containing the particles
+    field: Vec<DoubleVector>,
+}
+
+impl ParticleMesh {
+    /// Builds the grid of the accelerations caused by the particles, with the
+    /// given number of grid points along each axis rounded up to a power of two.
+    pub fn build(&mut self, particles: &[Particle], force: ForceParams, cells: usize) {
+        self.build_grid(particles, force, cells, false);
+    }
+
+    /// Builds the grid using parallel iterators from the rayon library.
+    pub fn par_build(&mut self, particles: &[Particle], force: ForceParams, cells: usize) {
+        self.build_grid(particles, force, cells, true);
+    }
+
+    fn build_grid(&mut self, particles: &[Particle], force: ForceParams, cells: usize, parallel: bool) {
+        self.cells = cells.max(MIN_CELLS).next_power_of_two();
+        self.periodic = force.period.is_some();
+        self.len = if self.periodic { self.cells } else { 2 * self.cells };
+        (self.origin, self.spacing) = match force.period {
+            Some(period) => {
+                let period = vector::to_f64(period);
+                (-period / 2., period / self.cells as f64)
+            }
+            None => {
+                let (min, max) = particles
+                    .iter()
+                    .map(|particle| vector::to_f64(particle.position))
+                    .fold((DoubleVector::splat(f64::INFINITY), DoubleVector::splat(f64::NEG_INFINITY)), |(min, max), position| (min.min(position), max.max(position)));
+                let side = (max - min).max_element();
+                let side = if side > 0. { side } else { 1. };
+                (min, DoubleVector::splat(side / (self.cells - 1) as f64))
+            }
+        };
+
+        let points = self.len.pow(DIMENSIONS as u32);
+        for buffer in [&mut self.density, &mut self.kernel, &mut self.scratch] {
+            buffer.clear();
+            buffer.resize(points, Complex::default());
+        }
+        self.field.clear();
+        self.field.resize(self.cells.pow(DIMENSIONS as u32), DoubleVector::ZERO);
+        if self.twiddles.len() != self.len / 2 {
+            let len = self.len as f64;
+            self.twiddles.clear();
+            self.twiddles.extend((0..self.len / 2).map(|k| {
+                let angle = -2. * std::f64::consts::PI * k as f64 / len;
+                Complex { re: angle.cos(), im: angle.sin() }
+            }));
+        }
+
+        // share the mass of each particle between the corners of its cell
+        for particle in particles {
+            for (point, weight) in self.corners(vector::to_f64(particle.position)) {
+                let index = self.padded_index(point);
+                self.density[index].re += scalar::to_f64(particle.mass) * weight;
+            }
+        }
+        transform(&mut self.density, &mut self.scratch, &self.twiddles, false, parallel);
+
+        // convolve the density with the acceleration caused by a particle at each offset. The kernels of two
+        // axes are transformed together as the real and imaginary parts, as both convolutions are real
+        let softening = scalar::to_f64(force.softening).max(self.spacing.min_element());
+        let (g, len, cells, spacing, density) = (scalar::to_f64(force.g), self.len, self.cells, self.spacing, &self.density);
+        let strides: [usize; DIMENSIONS] = std::array::from_fn(|i| len.pow((DIMENSIONS - 1 - i) as u32));
+        let scale = 1. / points as f64;
+        for first in (0..DIMENSIONS).step_by(2) {
+            let second = (first + 1 < DIMENSIONS).then_some(first + 1);
+            for_each(&mut self.kernel, parallel, |index, value| {
+                // offsets past the middle of an axis are negative offsets wrapped around
+                let offset = DoubleVector::from_array(std::array::from_fn(|i| {
+                    let point = index / strides[i] % len;
+                    (if point < len / 2 { point as f64 } else { point as f64 - len as f64 }) * spacing[i]
+                }));
+                let distance_squared = offset.length_squared() + softening * softening;
+                let magnitude = -g / (distance_squared * distance_squared.sqrt());
+                *value = Complex { re: magnitude * offset[first], im: second.map_or(0., |second| magnitude * offset[second]) };
+            });
+            transform(&mut self.kernel, &mut self.scratch, &self.twiddles, false, parallel);
+            for_each(&mut self.kernel, parallel, |index, value| *value = *value * density[index]);
+            transform(&mut self.kernel, &mut self.scratch, &self.twiddles, true, parallel);
+
+            let kernel = &self.kernel;
+            for_each(&mut self.field, parallel, |index, acceleration| {
+                let point: [usize; DIMENSIONS] = std::array::from_fn(|i| index / cells.pow((DIMENSIONS - 1 - i) as u32) % cells);
+                let value = kernel[padded_index(point, len)];
+                acceleration[first] = value.re * scale;
+                if let Some(second) = second {
+                    acceleration[second] = value.im * scale;
+                }
+            });
+        }
+    }
+
+    /// Grid points at the corners of the cell containing the position and the
+    /// weight of each, from how close the position is to the corner.
+    fn corners(&self, position: DoubleVector) -> [([usize; DIMENSIONS], f64); CORNERS] {
+        let cells = self.cells;
+        let grid_position = (position - self.origin) / self.spacing;
+        let (first, fraction): ([usize; DIMENSIONS], [f64; DIMENSIONS]) = {
+            let mut first = [0; DIMENSIONS];
+            let mut fraction = [0.; DIMENSIONS];
+            for axis in 0..DIMENSIONS {
+                let point = grid_position[axis];
+                if self.periodic {
+                    let floor = point.floor();
+                    first[axis] = (floor as i64).rem_euclid(cells as i64) as usize;
+                    fraction[axis] = point - floor;
+                } else {
+                    let point = point.clamp(0., (cells - 1) as f64);
+                    first[axis] = (point as usize).min(cells - 2);
+                    fraction[axis] = point - first[axis] as f64;
+                }
+            }
+            (first, fraction)
+        };
+        std::array::from_fn(|corner| {
+            let mut weight = 1.;
+            let point = std::array::from_fn(|axis| {
+                if corner >> axis & 1 == 1 {
+                    weight *= fraction[axis];
+                    (first[axis] + 1) % cells
+                } else {
+                    weight *= 1. - fraction[axis];
+                    first[axis]
+                }
+            });
+            (point, weight)
+        })
+    }
+
+    /// Index of a grid point in the padded grids of the Fourier transforms.
+    fn padded_index(&self, point: [usize; DIMENSIONS]) -> usize {
+        padded_index(point, self.len)
+    }
+}
+
+impl GravityField for ParticleMesh {
+    fn acceleration(&self, index: usize, sources: &[Particle], _force: ForceParams) -> Vector {
+        if self.field.is_empty() {
+            return Vector::ZERO;
+        }
+        let acceleration = self.corners(vector::to_f64(sources[index].position)).into_iter().fold(DoubleVector::ZERO, |acceleration, (point, weight)| {
+            let index = point.iter().fold(0, |index, &coordinate| index * self.cells + coordinate);
+            acceleration + self.field[index] * weight
+        });
+        vector::from_f64(acceleration)
+    }
+}
+
+/// Index of a grid point in a grid with len points along each axis, with the last axis changing fastest.
+fn padded_index(point: [usize; DIMENSIONS], len: usize) -> usize {
+    point.iter().fold(0, |index, &coordinate| index * len + coordinate)
+}
+
+/// Calls the function with the index of each value, in parallel or not.
+fn for_each<T: Send>(values: &mut [T], parallel: bool, f: impl Fn(usize, &mut T) + Sync + Send) {
+    if parallel {
+        values.par_iter_mut().enumerate().for_each(|(index, value)| f(index, value));
+    } else {
+        values.iter_mut().enumerate().for_each(|(index, value)| f(index, value));
+    }
+}
+
+/// Fourier transform of a grid with the same number of points along each axis.
+/// The lines along the last axis are transformed and the grid is then transposed
+/// so the next axis is last, which returns the grid to its original layout once
+/// every axis has been transformed. The inverse transform is scaled by the
+/// number of points, which the caller divides out.
+fn transform(grid: &mut Vec<Complex>, scratch: &mut Vec<Complex>, twiddles: &[Complex], inverse: bool, parallel: bool) {
+    let len = twiddles.len() * 2;
+    for _ in 0..DIMENSIONS {
+        if parallel {
+            grid.par_chunks_mut(len).for_each(|line| transform_line(line, twiddles, inverse));
+        } else {
+            grid.chunks_mut(len).for_each(|line| transform_line(line, twiddles, inverse));
+        }
+
+        // the first axis becomes the last, as a transpose of a len by stride matrix. Blocks of rows
+        // are transposed together so the values read from each column are next to each other
+        let stride = grid.len() / len;
+        let block = TRANSPOSE_BLOCK.min(stride);
+        let source = &*grid;
+        let transpose = |(index, rows): (usize, &mut [Complex])| {
+            let first_row = index * block;
+            for column in 0..len {
+                let values = &source[column * stride + first_row..][..block];
+                for (row, &value) in values.iter().enumerate() {
+                    rows[row * len + column] = value;
+                }
+            }
+        };
+        if parallel {
+            scratch.par_chunks_mut(len * block).enumerate().for_each(transpose);
+        } else {
+            scratch.chunks_mut(len * block).enumerate().for_each(transpose);
+        }
+        std::mem::swap(grid, scratch);
+    }
+}
+
+/// In place radix-2 fast Fourier transform of a line whose length is a power of two.
+fn transform_line(line: &mut [Complex], twiddles: &[Complex], inverse: bool) {
+    let len = line.len();
+    let bits = len.trailing_zeros();
+    for i in 0..len {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if i < j {
+            line.swap(i, j);
+        }
+    }
+
+    let mut size = 2;
+    while size <= len {
+        let (half, step) = (size / 2, len / size);
+        for chunk in line.chunks_exact_mut(size) {
+            let (evens, odds) = chunk.split_at_mut(half);
+            for ((even, odd), &twiddle) in evens.iter_mut().zip(odds).zip(twiddles.iter().step_by(step)) {
+                let twiddle = if inverse { twiddle.conj() } else { twiddle };
+                let (a, b) = (*even, *odd * twiddle);
+                *even = a + b;
+                *odd = a - b;
+            }
+        }
+        size *= 2;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::particle::ParticleId;
+    use crate::presets;
+    use crate::scalar::Scalar;
+
+    /// Grid points along each axis, fewer in three dimensions to keep the grid small
+    const CELLS: usize = if DIMENSIONS == 2 { 128 } else { 32 };
+
+    /// Root mean square error of the accelerations of the mesh relative to the
+    /// root mean square of the direct sum, over the particles at the indices.
+    fn relative_error(mesh: &ParticleMesh, particles: &[Particle], force: ForceParams, indices: impl Iterator<Item = usize>) -> f64 {
+        let (mut error, mut total) = (0., 0.);
+        for index in indices {
+            let exact = vector::to_f64(Particle::net_acceleration_at(index, particles, force));
+            error += (vector::to_f64(mesh.acceleration(index, particles, force)) - exact).length_squared();
+            total += exact.length_squared();
+        }
+        (error / total).sqrt()
+    }
+
+    /// Softening of a few cells for particles spread over the given width, so
+    /// the direct sum is softened like the mesh.
+    fn softening(width: Scalar) -> Scalar {
+        4. * width / CELLS as Scalar
+    }
+
+    #[test]
+    fn isolated_grid_matches_the_direct_sum() {
+        let particles = presets::random(2000, 21);
+        let force = ForceParams::softened(softening(900.));
+        let mut mesh = ParticleMesh::default();
+        mesh.build(&particles, force, CELLS);
+        let error = relative_error(&mesh, &particles, force, 0..particles.len());
+        assert!(error <= 0.03, "error {:e} against the direct sum", error);
+    }
+
+    #[test]
+    fn periodic_grid_matches_the_nearest_images() {
+        // the cluster is split between the corners of the box, and narrower than half the box so every pair is
+        // nearer than the edge of its nearest images
+        let size = Vector::splat(2000.);
+        let particles: Vec<Particle> = presets::random(2000, 22).into_iter().map(|particle| Particle { position: particle.position + size / 2., ..particle }).collect();
+        let force = ForceParams { period: Some(size), ..ForceParams::softened(softening(2000.)) };
+        let mut mesh = ParticleMesh::default();
+        mesh.par_build(&particles, force, CELLS);
+        let error = relative_error(&mesh, &particles, force, 0..particles.len());
+        assert!(error <= 0.03, "error {:e} against the direct sum", error);
+    }
+
+    #[test]
+    fn grid_stretches_to_a_distant_particle() {
+        let mut particles = presets::random(500, 23);
+        let far = particles.len();
+        // far enough away that the cluster pulls it like a single particle, once the grid covers it
+        particles.push(Particle::new(ParticleId::new(far), vector::planar(2e4, -1e4), Vector::ZERO, 1e6, 1.));
+        let force = ForceParams::softened(softening(900.));
+        let mut mesh = ParticleMesh::default();
+        mesh.build(&particles, force, CELLS);
+        let error = relative_error(&mesh, &particles, force, far..far + 1);
+        assert!(error <= 0.01, "error {:e} against the direct sum", error);
+    }
+}
@@ -0,0 +1,446 @@
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+use crate::particle::{ForceParams, Particle, ParticleId, ParticleNames, G};
+use crate::scalar::consts::TAU;
+use crate::scalar::Scalar;
+use crate::vector::{self, Vector, perpendicular};
+
+/// Mass of the Sun in kilograms
+const SUN_MASS: Scalar = 1.989e30;
+/// Fraction of the mass of a galaxy in its central body
+const GALAXY_CORE_MASS: Scalar = 0.9;
+/// Length in meters and mass in kilograms that the unit lengths and masses of the
+/// choreographies are scaled to
+const CHOREOGRAPHY_LENGTH: Scalar = 1000.;
+const CHOREOGRAPHY_MASS: Scalar = 1.0e14;
+/// Largest fraction the speed of a particle in a galaxy differs from a circular orbit
+const GALAXY_DISPERSION: Scalar = 0.05;
+/// Largest distance of a particle in a galaxy from the plane of the disk, as a
+/// fraction of the radius of the galaxy
+#[cfg(feature = "three-dimensions")]
+const GALAXY_THICKNESS: Scalar = 0.02;
+/// Largest distance between neighbors of a rubble pile joined by springs, as a
+/// multiple of the spacing of its lattice. This joins the six nearest neighbors on
+/// a triangular lattice, and the nearest and face diagonal neighbors on a cubic one.
+const RUBBLE_NEIGHBOR_DISTANCE: Scalar = 1.5;
+/// Factor the stiffness of the springs of a rubble pile is divided by. A particle
+/// of the cubic lattice has 18 springs to the 6 of the triangular lattice, which
+/// hold the pile together far more strongly, so they are made weaker. The factor
+/// was chosen by launching piles past a massive body, so in either number of
+/// dimensions a pile breaks up passing within about its Roche limit.
+#[cfg(not(feature = "three-dimensions"))]
+const RUBBLE_SPRING_SHARE: Scalar = 1.;
+#[cfg(feature = "three-dimensions")]
+const RUBBLE_SPRING_SHARE: Scalar = 18.;
+
+/// Mass in kilograms, distance from the Sun in meters and mean orbital speed in
+/// meters per second of each planet, from Mercury to Neptune.
+const PLANETS: [(Scalar, Scalar, Scalar); 8] = [
+    (3.301e23, 5.791e10, 47_360.),
+    (4.867e24, 1.0821e11, 35_020.),
+    (5.972e24, 1.496e11, 29_780.),
+    (6.417e23, 2.2794e11, 24_070.),
+    (1.898e27, 7.7857e11, 13_070.),
+    (5.683e26, 1.43353e12, 9_680.),
+    (8.681e25, 2.87246e12, 6_800.),
+    (1.024e26, 4.49506e12, 5_430.),
+];
+
+/// Name of each planet, from Mercury to Neptune.
+const PLANET_NAMES: [&str; 8] = ["Mercury", "Venus", "Earth", "Mars", "Jupiter", "Saturn", "Uranus", "Neptune"];
+
+/// Inclination to the plane of the Earth's orbit and longitude of the ascending
+/// node in degrees of the orbit of each planet, from Mercury to Neptune.
+#[cfg(feature = "three-dimensions")]
+const ORBITAL_PLANES: [(Scalar, Scalar); 8] = [
+    (7.005, 48.331),
+    (3.3946, 76.680),
+    (0., 0.),
+    (1.850, 49.558),
+    (1.303, 100.464),
+    (2.485, 113.665),
+    (0.773, 74.006),
+    (1.770, 131.784),
+];
+
+/// The Sun and the eight planets on circular orbits. The planets are spread
+/// around the Sun and the Sun is given the velocity that cancels the momentum
+/// of the planets, so the system doesn't drift. In three dimensions the screen
+/// is the plane of the Earth's orbit and the other orbits are tilted by their
+/// inclinations to it.
+pub fn solar_system() -> Vec<Particle> {
+    let mut particles = vec![body(0, Vector::ZERO, Vector::ZERO, SUN_MASS)];
+    for (i, &(mass, distance, speed)) in PLANETS.iter().enumerate() {
+        let direction = direction(i as Scalar * 2.4); // spread the planets by roughly the golden angle
+        let (position, velocity) = (direction * distance, perpendicular(direction) * speed);
+        #[cfg(feature = "three-dimensions")]
+        let (position, velocity) = {
+            let (inclination, ascending_node) = ORBITAL_PLANES[i];
+            (incline(position, inclination, ascending_node), incline(velocity, inclination, ascending_node))
+        };
+        particles.push(body(i + 1, position, velocity, mass));
+    }
+
+    let momentum: Vector = particles.iter().map(|particle| particle.velocity * particle.mass).sum();
+    particles[0].velocity = -momentum / SUN_MASS;
+    particles
+}
+
+/// Names of the bodies of the solar system preset.
+pub fn solar_system_names() -> ParticleNames {
+    std::iter::once("Sun").chain(PLANET_NAMES).enumerate().map(|(id, name)| (ParticleId::new(id), name.to_string())).collect()
+}
+
+/// The Sun and the Earth on a circular orbit around their center of mass, which
+/// stays at the origin.
+pub fn two_body() -> Vec<Particle> {
+    let (mass, distance, _) = PLANETS[2];
+    let speed = (G * (SUN_MASS + mass) / distance).sqrt(); // relative speed of a circular orbit
+    let velocity = vector::planar(0., speed * SUN_MASS / (SUN_MASS + mass));
+    vec![
+        body(0, Vector::ZERO, -velocity * mass / SUN_MASS, SUN_MASS),
+        body(1, vector::planar(distance, 0.), velocity, mass),
+    ]
+}
+
+/// Period of the orbit of the two body preset in seconds.
+pub fn two_body_period() -> Scalar {
+    let (mass, distance, _) = PLANETS[2];
+    TAU * (distance.powi(3) / (G * (SUN_MASS + mass))).sqrt()
+}
+
+/// Particles of random masses from 100 kg to 1000 t at rest, placed uniformly in
+/// a disk, or a ball in three dimensions, whose radius grows with the square root
+/// of the number of particles. The same seed always gives the same particles, so
+/// runs and benchmarks can be compared on them.
+pub fn random(particles: usize, seed: u64) -> Vec<Particle> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let radius = (particles as Scalar).sqrt() * 10.;
+    (0..particles)
+        .map(|id| {
+            let position = loop {
+                let position = vector::map(Vector::ZERO, |_, _| rng.gen_range(-radius..=radius));
+                if position.length() <= radius {
+                    break position;
+                }
+            };
+            body(id, position, Vector::ZERO, rng.gen_range(1.0e2..=1.0e6))
+        })
+        .collect()
+}
+
+/// A disk galaxy of a massive central body and particles on near circular orbits
+/// out to the radius. Half of the total mass is in the central body.
+pub fn galaxy(particles: usize, radius: Scalar, mass: Scalar, seed: u64) -> Vec<Particle> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    disk(&mut rng, 0, particles, radius, mass)
+}
+
+/// Two disk galaxies, each with the given number of particles, radius and total
+/// mass, falling towards each other off center so they collide.
+pub fn galaxy_collision(particles: usize, radius: Scalar, mass: Scalar, seed: u64) -> Vec<Particle> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let offset = vector::planar(1.5 * radius, 0.3 * radius);
+    // a fraction of the velocity of a circular orbit around the other galaxy, so they fall together
+    let velocity = 0.3 * body(0, -offset, Vector::ZERO, 2. * mass).orbital_velocity(offset, ForceParams::softened(0.));
+
+    let mut galaxies = disk(&mut rng, 0, particles, radius, mass);
+    for particle in galaxies.iter_mut() {
+        particle.position -= offset;
+        particle.velocity -= velocity;
+    }
+    // the second galaxy is mirrored so it rotates the other way
+    let first_id = galaxies.len();
+    for mut particle in disk(&mut rng, first_id, particles, radius, mass) {
+        particle.position.y = -particle.position.y;
+        particle.velocity.y = -particle.velocity.y;
+        particle.position += offset;
+        particle.velocity += velocity;
+        galaxies.push(particle);
+    }
+    galaxies
+}
+
+/// Generates a disk galaxy around the origin with ids starting from first_id. The
+/// speed of each particle is the circular orbit speed around the mass enclosed by
+/// its orbit, which keeps the disk from collapsing, varied slightly so the orbits
+/// aren't perfectly circular.
+fn disk(rng: &mut StdRng, first_id: usize, particles: usize, radius: Scalar, mass: Scalar) -> Vec<Particle> {
+    let core_mass = if particles == 0 { mass } else { mass * GALAXY_CORE_MASS };
+    let particle_mass = (mass - core_mass) / particles.max(1) as Scalar;
+
+    // place the particles evenly over the area of the disk, leaving a gap around the center
+    let mut offsets: Vec<Vector> = (0..particles)
+        .map(|_| {
+            let offset = direction(rng.gen_range(0.0..TAU)) * radius * rng.gen_range(0.01..=1.0 as Scalar).sqrt();
+            // a flat disk would stay flat, so in three dimensions it is given some thickness
+            #[cfg(feature = "three-dimensions")]
+            let offset = offset + Vector::Z * radius * GALAXY_THICKNESS * rng.gen_range(-1.0..=1.0);
+            offset
+        })
+        .collect();
+    offsets.sort_by(|a, b| a.length_squared().total_cmp(&b.length_squared()));
+
+    let mut disk = vec![body(first_id, Vector::ZERO, Vector::ZERO, core_mass)];
+    for (i, offset) in offsets.into_iter().enumerate() {
+        // particles are sorted by radius, so the enclosed mass is the core and the particles before this one
+        let enclosed = body(0, Vector::ZERO, Vector::ZERO, core_mass + i as Scalar * particle_mass);
+        let velocity = enclosed.orbital_velocity(offset, ForceParams::softened(0.)) * (1. + rng.gen_range(-GALAXY_DISPERSION..=GALAXY_DISPERSION));
+        disk.push(body(first_id + i + 1, offset, velocity, particle_mass));
+    }
+    disk
+}
+
+/// A rubble pile of equal particles at rest around the origin, with the given
+/// total mass, filling a disk of the radius on a triangular lattice, or a ball on
+/// a cubic lattice in three dimensions. Returned with the indices of the pairs of
+/// neighbors and the distances between them, which springs resting at those lengths can hold
+/// together, so the pile starts out in equilibrium with its springs. The pile is
+/// held together by its own gravity and the springs until the tides of a massive
+/// body pull it apart, within about its [`roche_limit`].
+pub fn rubble_pile(particles: usize, radius: Scalar, mass: Scalar) -> (Vec<Particle>, Vec<(usize, usize, Scalar)>) {
+    if particles == 0 {
+        return (Vec::new(), Vec::new());
+    }
+    let spacing = lattice_spacing(particles, radius);
+    // the lattice reaches twice the radius so the rows of the sheared triangular lattice still cover the disk
+    let mut offsets = lattice(2 * (radius / spacing).ceil() as i32 + 2, spacing);
+    offsets.sort_by(|a, b| a.length_squared().total_cmp(&b.length_squared()));
+    offsets.truncate(particles);
+    let center = offsets.iter().sum::<Vector>() / offsets.len() as Scalar;
+
+    let pile: Vec<Particle> = offsets
+        .into_iter()
+        .enumerate()
+        .map(|(id, offset)| body(id, offset - center, Vector::ZERO, mass / particles as Scalar))
+        .collect();
+    let mut neighbors = Vec::new();
+    for (i, a) in pile.iter().enumerate() {
+        for (j, b) in pile.iter().enumerate().skip(i + 1) {
+            let distance = a.position.distance(b.position);
+            if distance < RUBBLE_NEIGHBOR_DISTANCE * spacing {
+                neighbors.push((i, j, distance));
+            }
+        }
+    }
+    (pile, neighbors)
+}
+
+/// Distance from a body of the primary mass within which the tides of the body
+/// pull apart a rubble pile of the radius and mass, R (2M / m)^(1/3).
+pub fn roche_limit(radius: Scalar, mass: Scalar, primary_mass: Scalar) -> Scalar {
+    radius * (2. * primary_mass / mass).cbrt()
+}
+
+/// Stiffness in m/s^2 per meter of the springs of a rubble pile of the radius and
+/// mass, the strength times G m / R^3, so piles of every size hold together the
+/// same way. A strength of a few hundred keeps the pile from collapsing under its
+/// own gravity while its springs break within about its Roche limit.
+pub fn rubble_stiffness(strength: Scalar, radius: Scalar, mass: Scalar, g: Scalar) -> Scalar {
+    strength * g.abs() * mass / radius.powi(3) / RUBBLE_SPRING_SHARE
+}
+
+/// Spacing of a triangular lattice with the number of points in a disk of the radius.
+#[cfg(not(feature = "three-dimensions"))]
+fn lattice_spacing(points: usize, radius: Scalar) -> Scalar {
+    // each point of a triangular lattice takes up sqrt(3)/2 s^2 of the area
+    (TAU / 2. * radius * radius / (points as Scalar * (3. as Scalar).sqrt() / 2.)).sqrt()
+}
+
+/// Spacing of a cubic lattice with the number of points in a ball of the radius.
+#[cfg(feature = "three-dimensions")]
+fn lattice_spacing(points: usize, radius: Scalar) -> Scalar {
+    (2. / 3. * TAU * radius.powi(3) / points as Scalar).cbrt()
+}
+
+/// Points of a triangular lattice with the spacing, up to the extent in rows and columns from the origin.
+#[cfg(not(feature = "three-dimensions"))]
+fn lattice(extent: i32, spacing: Scalar) -> Vec<Vector> {
+    // each row is shifted by half a spacing from the row below and sqrt(3)/2 spacings above it
+    let row_height = spacing * (3. as Scalar).sqrt() / 2.;
+    (-extent..=extent)
+        .flat_map(|row| (-extent..=extent).map(move |column| vector::planar((column as Scalar + row as Scalar / 2.) * spacing, row as Scalar * row_height)))
+        .collect()
+}
+
+/// Points of a cubic lattice with the spacing, up to the extent along each axis from the origin.
+#[cfg(feature = "three-dimensions")]
+fn lattice(extent: i32, spacing: Scalar) -> Vec<Vector> {
+    let range = -extent..=extent;
+    range.clone()
+        .flat_map(|x| range.clone().flat_map(move |y| (-extent..=extent).map(move |z| Vector::new(x as Scalar, y as Scalar, z as Scalar) * spacing)))
+        .collect()
+}
+
+/// Period of the figure-eight orbit in units where G = 1
+#[cfg_attr(feature = "single-precision", allow(clippy::excessive_precision))] // published to more digits than f32 keeps
+const FIGURE_EIGHT_PERIOD: Scalar = 6.32591398;
+
+/// Three equal masses chasing each other around a figure-eight, using the initial
+/// conditions found by Chenciner and Montgomery scaled to simulator units.
+#[cfg_attr(feature = "single-precision", allow(clippy::excessive_precision))]
+pub fn figure_eight() -> Vec<Particle> {
+    let position = vector::planar(0.97000436, -0.24308753);
+    let velocity = vector::planar(-0.93240737, -0.86473146);
+    choreography(&[
+        (position, -velocity / 2.),
+        (-position, -velocity / 2.),
+        (Vector::ZERO, velocity),
+    ])
+}
+
+/// Period of the figure-eight orbit in seconds once scaled to simulator units.
+pub fn figure_eight_period() -> Scalar {
+    FIGURE_EIGHT_PERIOD * time_unit()
+}
+
+/// Three equal masses at the corners of an equilateral triangle, rotating rigidly
+/// around their center of mass as in Lagrange's solution.
+pub fn lagrange_triangle() -> Vec<Particle> {
+    // with unit masses at unit distance from the center the side is sqrt(3), so w^2 = 3 / sqrt(3)^3
+    let speed = (3. as Scalar).powf(-0.25);
+    let bodies: Vec<(Vector, Vector)> = (0..3)
+        .map(|i| {
+            let direction = direction(i as Scalar * TAU / 3.);
+            (direction, perpendicular(direction) * speed)
+        })
+        .collect();
+    choreography(&bodies)
+}
+
+/// Two equal mass stars on a circular orbit with a light planet on a circular orbit
+/// around both of them, far enough away to be stable.
+pub fn circumbinary() -> Vec<Particle> {
+    // stars at unit separation orbit the center at half the separation with v^2 = m / (2d)
+    let star_speed = (0.5 as Scalar).sqrt();
+    // the planet orbits the total mass of both stars at four times the separation
+    let planet_distance: Scalar = 4.;
+    let planet_speed = (2. / planet_distance).sqrt();
+    let mut particles = choreography(&[
+        (vector::planar(0.5, 0.), vector::planar(0., star_speed)),
+        (vector::planar(-0.5, 0.), vector::planar(0., -star_speed)),
+    ]);
+    particles.push(body(
+        2,
+        vector::planar(planet_distance, 0.) * CHOREOGRAPHY_LENGTH,
+        vector::planar(0., planet_speed) * CHOREOGRAPHY_LENGTH / time_unit(),
+        CHOREOGRAPHY_MASS * 1.0e-6,
+    ));
+    particles
+}
+
+/// Creates unit mass bodies from positions and velocities in units where G = 1.
+fn choreography(bodies: &[(Vector, Vector)]) -> Vec<Particle> {
+    bodies
+        .iter()
+        .enumerate()
+        .map(|(id, &(position, velocity))| {
+            body(id, position * CHOREOGRAPHY_LENGTH, velocity * CHOREOGRAPHY_LENGTH / time_unit(), CHOREOGRAPHY_MASS)
+        })
+        .collect()
+}
+
+/// Seconds in the unit of time of the choreographies, the time unit that makes G = 1.
+fn time_unit() -> Scalar {
+    (CHOREOGRAPHY_LENGTH.powi(3) / (G * CHOREOGRAPHY_MASS)).sqrt()
+}
+
+/// Unit vector in the plane of the screen at the given angle from the x axis.
+fn direction(angle: Scalar) -> Vector {
+    vector::planar(angle.cos(), angle.sin())
+}
+
+/// Rotates a vector in the plane of the screen by the inclination in degrees around
+/// the line of nodes, which is at the longitude of the ascending node in degrees.
+#[cfg(feature = "three-dimensions")]
+fn incline(vector: Vector, inclination: Scalar, ascending_node: Scalar) -> Vector {
+    let (axis, angle) = (direction(ascending_node.to_radians()), inclination.to_radians());
+    // Rodrigues' rotation formula
+    vector * angle.cos() + axis.cross(vector) * angle.sin() + axis * axis.dot(vector) * (1. - angle.cos())
+}
+
+/// A particle whose radius and acceleration are set when it is added to a world,
+/// with the id of its place among the particles of the preset.
+fn body(id: usize, position: Vector, velocity: Vector, mass: Scalar) -> Particle {
+    Particle {
+        id: ParticleId::new(id),
+        velocity,
+        position,
+        mass,
+        radius: 0.,
+        acceleration: Vector::ZERO,
+        fixed: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integrator::Rk4Integrator;
+    use crate::scalar;
+    use crate::world::{Physics, SequentialWorld, World};
+
+    fn momentum(particles: &[Particle]) -> Vector {
+        particles.iter().map(|particle| particle.velocity * particle.mass).sum()
+    }
+
+    #[test]
+    fn solar_system_has_the_planets_on_near_circular_orbits_without_drifting() {
+        let particles = solar_system();
+        let names = solar_system_names();
+        assert_eq!(particles.len(), 9);
+        assert_eq!(names[&particles[0].id], "Sun");
+        let sun = &particles[0];
+        for (planet, &(mass, distance, speed)) in particles[1..].iter().zip(&PLANETS) {
+            // the Sun starts at the origin, and only moves slowly enough to cancel the momentum of the planets
+            let (offset, velocity) = (planet.position - sun.position, planet.velocity);
+            assert_eq!(planet.mass, mass, "{}", names[&planet.id]);
+            assert!((offset.length() - distance).abs() <= 1e-6 * distance, "{} is {} m from the Sun", names[&planet.id], offset.length());
+            assert!((velocity.length() - speed).abs() <= 1e-3 * speed, "{} moves at {} m/s", names[&planet.id], velocity.length());
+            // the mean speeds of the planets are within 2% of the speed of a circular orbit at their distance
+            let circular = (G * SUN_MASS / distance).sqrt();
+            assert!((velocity.length() - circular).abs() <= 0.02 * circular, "{} is {} m/s from a circular orbit", names[&planet.id], velocity.length() - circular);
+            assert!(offset.dot(velocity).abs() <= 1e-3 * offset.length() * velocity.length(), "{} isn't moving around the Sun", names[&planet.id]);
+        }
+        // in double precision, as the square of the momentum overflows single precision
+        let drift = vector::to_f64(momentum(&particles)).length();
+        let largest = particles[1..].iter().map(|planet| scalar::to_f64(planet.mass * planet.velocity.length())).fold(0., f64::max);
+        assert!(drift <= 32. * scalar::to_f64(Scalar::EPSILON) * largest, "the system drifts with {:e} kg m/s", drift);
+    }
+
+    /// Runs the particles in a sequential world with the fourth order integrator
+    /// and a tiny softening length for the time in the given number of updates.
+    fn run(particles: Vec<Particle>, time: Scalar, updates: usize) -> Vec<Particle> {
+        let physics = Physics { softening: 1e-3, ..Physics::default() };
+        let mut world = SequentialWorld::new(particles, Box::new(Rk4Integrator), physics);
+        world.advance(updates, time / updates as Scalar);
+        world.get_particles().to_vec()
+    }
+
+    #[test]
+    fn figure_eight_returns_to_its_start_after_a_period() {
+        let start = figure_eight();
+        let end = run(start.clone(), figure_eight_period(), 2000);
+        for (particle, start) in end.iter().zip(&start) {
+            let (distance, speed) = (particle.position.distance(start.position), particle.velocity.distance(start.velocity));
+            assert!(distance <= 1e-3 * CHOREOGRAPHY_LENGTH, "particle {} ended {} m from its start", particle.id, distance);
+            assert!(speed <= 1e-3 * start.velocity.length(), "particle {} ended {} m/s from its start velocity", particle.id, speed);
+        }
+    }
+
+    #[test]
+    fn lagrange_triangle_stays_equilateral_and_circumbinary_planet_keeps_its_distance() {
+        let sides = |particles: &[Particle]| [(0, 1), (1, 2), (2, 0)].map(|(a, b)| particles[a].position.distance(particles[b].position));
+        let side = sides(&lagrange_triangle())[0];
+        // a third of a turn of the triangle, which is unstable for equal masses so drifts apart over many turns
+        let period = TAU / (3. as Scalar).powf(-0.25) * time_unit();
+        for length in sides(&run(lagrange_triangle(), period / 3., 500)) {
+            assert!((length - side).abs() <= 1e-3 * side, "side of {} m instead of {} m", length, side);
+        }
+
+        let planet_distance = 4. * CHOREOGRAPHY_LENGTH;
+        let end = run(circumbinary(), 50. * time_unit(), 5000);
+        let distance = end[2].position.length();
+        assert!((distance - planet_distance).abs() <= 0.05 * planet_distance, "planet ended {} m from the stars", distance);
+    }
+}
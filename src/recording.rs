@@ -0,0 +1,99 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::particle::{Particle, ParticleId};
+use crate::scalar::Scalar;
+use crate::vector::Vector;
+
+/// The parts of a particle needed to draw it.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ParticleSnapshot {
+    pub id: ParticleId,
+    pub position: Vector,
+    pub mass: Scalar,
+    pub fixed: bool,
+}
+
+impl From<&Particle> for ParticleSnapshot {
+    fn from(particle: &Particle) -> Self {
+        ParticleSnapshot {
+            id: particle.id,
+            position: particle.position,
+            mass: particle.mass,
+            fixed: particle.fixed,
+        }
+    }
+}
+
+/// The particles of the world after an update.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Number of updates the world had run when the snapshot was taken
+    pub step: u64,
+    pub particles: Vec<ParticleSnapshot>,
+}
+
+/// Snapshots of the world taken every interval updates. Only the most recent
+/// max_frames snapshots are kept, the oldest are dropped to make room.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Recording {
+    interval: usize,
+    max_frames: usize,
+    frames: VecDeque<Snapshot>,
+}
+
+impl Recording {
+    pub fn new(interval: usize, max_frames: usize) -> Self {
+        Recording {
+            interval: interval.max(1),
+            max_frames: max_frames.max(1),
+            frames: VecDeque::new(),
+        }
+    }
+
+    /// Stores a snapshot if the step is a multiple of the interval, dropping
+    /// the oldest frame if the recording is full. The snapshot is only taken
+    /// when it will be stored.
+    pub fn record(&mut self, step: u64, snapshot: impl FnOnce() -> Vec<ParticleSnapshot>) {
+        if !step.is_multiple_of(self.interval as u64) {
+            return;
+        }
+        if self.frames.len() == self.max_frames {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(Snapshot { step, particles: snapshot() });
+    }
+
+    /// Frame at the index, with 0 being the oldest frame.
+    pub fn frame(&self, index: usize) -> Option<&Snapshot> {
+        self.frames.get(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+}
+
+/// Writes the recording to a JSON file, replacing the file if it already exists.
+pub fn save_recording(path: &str, recording: &Recording) -> std::io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    serde_json::to_writer(&mut file, recording)?;
+    file.flush()
+}
+
+/// Reads a recording previously written by save_recording.
+pub fn load_recording(path: &str) -> std::io::Result<Recording> {
+    let file = BufReader::new(File::open(path)?);
+    Ok(serde_json::from_reader(file)?)
+}
@@ -0,0 +1,50 @@
+//! The floating point type the simulation is computed in. Positions, velocities, masses and
+//! forces are `f64` by default, and `f32` with the `single-precision` feature, which halves
+//! the memory used by the particles and fits twice as many numbers in each SIMD register.
+//!
+//! Sums over every particle, such as the energy or the center of mass of the solar system,
+//! can overflow `f32`, so the diagnostics are always computed in `f64` using [`to_f64`].
+
+#[cfg(not(feature = "single-precision"))]
+mod precision {
+    pub use std::f64::consts;
+
+    /// Name of the floating point type, for reports
+    pub const PRECISION: &str = "f64";
+
+    /// Floating point type of the simulation
+    pub type Scalar = f64;
+
+    /// Converts a number to double precision.
+    pub fn to_f64(value: Scalar) -> f64 {
+        value
+    }
+
+    /// Converts a double precision number to the precision of the simulation.
+    pub fn from_f64(value: f64) -> Scalar {
+        value
+    }
+}
+
+#[cfg(feature = "single-precision")]
+mod precision {
+    pub use std::f32::consts;
+
+    /// Name of the floating point type, for reports
+    pub const PRECISION: &str = "f32";
+
+    /// Floating point type of the simulation
+    pub type Scalar = f32;
+
+    /// Converts a number to double precision.
+    pub fn to_f64(value: Scalar) -> f64 {
+        f64::from(value)
+    }
+
+    /// Converts a double precision number to the precision of the simulation.
+    pub fn from_f64(value: f64) -> Scalar {
+        value as f32
+    }
+}
+
+pub use precision::*;
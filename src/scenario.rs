@@ -0,0 +1,126 @@
+use crate::scalar::Scalar;
+use crate::vector::{DIMENSIONS, Vector};
+
+/// Names of the columns of a scenario file, in order.
+#[cfg(not(feature = "three-dimensions"))]
+const COLUMNS: [&str; 5] = ["x", "y", "vx", "vy", "mass"];
+#[cfg(feature = "three-dimensions")]
+const COLUMNS: [&str; 7] = ["x", "y", "z", "vx", "vy", "vz", "mass"];
+/// Name of the optional column marking particles as fixed.
+const FIXED_COLUMN: &str = "fixed";
+/// Name of the optional last column naming particles, after the fixed column.
+const NAME_COLUMN: &str = "name";
+
+/// Initial conditions of a particle read from a scenario file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScenarioParticle {
+    pub position: Vector,
+    pub velocity: Vector,
+    pub mass: Scalar,
+    pub fixed: bool,
+    pub name: Option<String>,
+}
+
+/// Reads the particles of a scenario file.
+pub fn load_scenario(path: &str) -> Result<Vec<ScenarioParticle>, String> {
+    let contents = std::fs::read_to_string(path).map_err(|error| format!("Failed to read '{}': {}", path, error))?;
+    parse_scenario(&contents)
+}
+
+/// Parses rows of `x,y,vx,vy,mass`, or `x,y,z,vx,vy,vz,mass` in three
+/// dimensions, with an optional `fixed` column of `true`, `false`, `1` or `0`
+/// followed by an optional `name` column. An empty fixed column is false, so rows
+/// like `0,0,0,0,1e30,,Sun` name particles without fixing them, and an empty name
+/// is no name. Blank lines are skipped, as is the first row if none of
+/// its fields are numbers. Malformed rows are reported with their line number.
+pub fn parse_scenario(contents: &str) -> Result<Vec<ScenarioParticle>, String> {
+    let mut particles = Vec::new();
+    let mut first_row = true;
+
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let line_number = index + 1;
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+
+        // skip the header
+        if std::mem::replace(&mut first_row, false) && fields.iter().all(|field| field.parse::<Scalar>().is_err()) {
+            continue;
+        }
+
+        if fields.len() < COLUMNS.len() || fields.len() > COLUMNS.len() + 2 {
+            return Err(format!(
+                "Line {}: expected {} to {} columns ({}[,{}[,{}]]) but found {}",
+                line_number, COLUMNS.len(), COLUMNS.len() + 2, COLUMNS.join(","), FIXED_COLUMN, NAME_COLUMN, fields.len(),
+            ));
+        }
+        let mut values = [0.; COLUMNS.len()];
+        for ((value, field), column) in values.iter_mut().zip(&fields).zip(COLUMNS) {
+            *value = match field.parse::<Scalar>() {
+                Ok(number) if number.is_finite() => number,
+                _ => return Err(format!("Line {}: invalid {} '{}'", line_number, column, field)),
+            };
+        }
+
+        let position = Vector::from_slice(&values[..DIMENSIONS]);
+        let velocity = Vector::from_slice(&values[DIMENSIONS..2 * DIMENSIONS]);
+        let mass = values[2 * DIMENSIONS];
+        if mass <= 0. {
+            return Err(format!("Line {}: mass must be greater than 0 but was {}", line_number, mass));
+        }
+        let fixed = match fields.get(COLUMNS.len()).map(|field| field.to_lowercase()) {
+            None => false,
+            Some(field) if field.is_empty() => false,
+            Some(field) if field == "true" || field == "1" => true,
+            Some(field) if field == "false" || field == "0" => false,
+            Some(field) => return Err(format!("Line {}: invalid {} '{}'", line_number, FIXED_COLUMN, field)),
+        };
+        particles.push(ScenarioParticle {
+            position,
+            velocity,
+            mass,
+            fixed,
+            name: fields.get(COLUMNS.len() + 1).filter(|name| !name.is_empty()).map(|name| name.to_string()),
+        });
+    }
+    Ok(particles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Row of a particle at rest at the origin with the mass, followed by the extra columns.
+    fn row(mass: &str, extra: &[&str]) -> String {
+        let mut fields = vec!["0"; COLUMNS.len() - 1];
+        fields.push(mass);
+        fields.extend(extra);
+        fields.join(",")
+    }
+
+    fn error(contents: &str) -> String {
+        parse_scenario(contents).expect_err(contents)
+    }
+
+    #[test]
+    fn reads_the_header_fixed_and_name_columns() {
+        let contents = [COLUMNS.join(","), row("1", &[]), String::new(), row("2", &["true"]), row("3", &["", "Sun"]), row("4", &["0", ""])].join("\n");
+        let particles = parse_scenario(&contents).unwrap();
+        let read: Vec<(Scalar, bool, Option<&str>)> = particles.iter().map(|particle| (particle.mass, particle.fixed, particle.name.as_deref())).collect();
+        assert_eq!(read, [(1., false, None), (2., true, None), (3., false, Some("Sun")), (4., false, None)]);
+    }
+
+    #[test]
+    fn reports_malformed_rows_with_their_line_number() {
+        assert!(error("0,0").starts_with("Line 1: expected"));
+        assert!(error(&row("1", &["true", "Sun", "extra"])).starts_with("Line 1: expected"));
+        assert_eq!(error(&format!("{}\n\n{}", row("1", &[]), row("heavy", &[]))), "Line 3: invalid mass 'heavy'");
+        assert_eq!(error(&row("inf", &[])), "Line 1: invalid mass 'inf'");
+        assert_eq!(error(&row("NaN", &[])), "Line 1: invalid mass 'NaN'");
+        assert_eq!(error(&row("0", &[])), "Line 1: mass must be greater than 0 but was 0");
+        assert_eq!(error(&row("-5", &[])), "Line 1: mass must be greater than 0 but was -5");
+        assert_eq!(error(&row("1", &["yes"])), "Line 1: invalid fixed 'yes'");
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use rayon::prelude::*;
+
+use crate::particle::Particle;
+use crate::scalar::Scalar;
+use crate::vector::{DIMENSIONS, Vector};
+
+/// Coordinates of a cell in the grid
+type Cell = [i64; DIMENSIONS];
+
+/// Uniform grid which bins particles by the cell their position is in, so
+/// particles near a point can be found without checking every particle.
+///
+/// The particle indices are sorted by cell and each occupied cell stores the
+/// range of the sorted indices it contains. The buffers are kept between
+/// rebuilds so they don't need to be reallocated every update.
+pub struct SpatialGrid {
+    cell_size: Scalar,
+    /// Particle indices sorted by the cell they are in
+    entries: Vec<(Cell, usize)>,
+    /// Range of entries in each occupied cell
+    cells: HashMap<Cell, Range<usize>>,
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: Scalar) -> Self {
+        SpatialGrid {
+            cell_size,
+            entries: Vec::new(),
+            cells: HashMap::new(),
+        }
+    }
+
+    /// Bins the particles into cells.
+    pub fn rebuild(&mut self, particles: &[Particle]) {
+        let cell_size = self.cell_size;
+        self.entries.clear();
+        self.entries.extend(particles.iter().enumerate().map(|(i, particle)| (cell(particle.position, cell_size), i)));
+        self.entries.sort_unstable();
+        self.index_cells();
+    }
+
+    /// Bins the particles into cells using parallel iterators from the rayon library.
+    pub fn par_rebuild(&mut self, particles: &[Particle]) {
+        let cell_size = self.cell_size;
+        self.entries.clear();
+        self.entries.par_extend(particles.par_iter().enumerate().map(|(i, particle)| (cell(particle.position, cell_size), i)));
+        self.entries.par_sort_unstable();
+        self.index_cells();
+    }
+
+    /// Indices of the particles in the cell of the given particle and the cells around it.
+    pub fn neighbors_of<'a>(&'a self, particle: &Particle) -> impl Iterator<Item = usize> + 'a {
+        self.within(particle.position, self.cell_size)
+    }
+
+    /// Every pair of overlapping particles, with the first index less than the
+    /// second. The grid must have been rebuilt with the same particles.
+    pub fn overlapping_pairs(&self, particles: &[Particle]) -> Vec<(usize, usize)> {
+        let mut pairs: Vec<(usize, usize)> = (0..particles.len())
+            .flat_map(|i| self.overlaps_of(particles, i))
+            .collect();
+        pairs.sort_unstable();
+        pairs
+    }
+
+    /// Every pair of overlapping particles found using a parallel iterator from the rayon library.
+    pub fn par_overlapping_pairs(&self, particles: &[Particle]) -> Vec<(usize, usize)> {
+        let mut pairs: Vec<(usize, usize)> = (0..particles.len())
+            .into_par_iter()
+            .flat_map_iter(|i| self.overlaps_of(particles, i))
+            .collect();
+        pairs.par_sort_unstable();
+        pairs
+    }
+
+    /// Pairs of particle i with the overlapping particles that are smaller than it.
+    /// Smaller particles are always within twice the radius of particle i, and
+    /// each pair is only found from the side of the larger particle.
+    fn overlaps_of<'a>(&'a self, particles: &'a [Particle], i: usize) -> impl Iterator<Item = (usize, usize)> + 'a {
+        let particle = &particles[i];
+        self.within(particle.position, 2. * particle.radius)
+            .filter(move |&j| {
+                let other = &particles[j];
+                let smaller = other.radius < particle.radius || (other.radius == particle.radius && j > i);
+                smaller && particle.overlaps(other)
+            })
+            .map(move |j| (i.min(j), i.max(j)))
+    }
+
+    /// Indices of the particles in every cell touching the square, or cube in three
+    /// dimensions, of the given half width around a position.
+    fn within(&self, position: Vector, distance: Scalar) -> impl Iterator<Item = usize> + '_ {
+        let min = self.cell_of(position - distance);
+        let max = self.cell_of(position + distance);
+
+        // very large particles can cover more cells than are occupied, so check the occupied cells instead
+        let volume: Scalar = (0..DIMENSIONS).map(|axis| (max[axis] - min[axis] + 1) as Scalar).product();
+        let check_occupied = volume > self.cells.len() as Scalar;
+
+        let nearby_cells = (!check_occupied).then(|| cells_between(min, max).filter_map(|cell| self.cells.get(&cell)));
+        let occupied_cells = check_occupied.then(|| {
+            self.cells
+                .iter()
+                .filter(move |(cell, _)| (0..DIMENSIONS).all(|axis| (min[axis]..=max[axis]).contains(&cell[axis])))
+                .map(|(_, range)| range)
+        });
+
+        nearby_cells.into_iter().flatten()
+            .chain(occupied_cells.into_iter().flatten())
+            .flat_map(|range| self.entries[range.clone()].iter().map(|&(_, i)| i))
+    }
+
+    fn cell_of(&self, position: Vector) -> Cell {
+        cell(position, self.cell_size)
+    }
+
+    /// Stores the range of sorted entries belonging to each cell.
+    fn index_cells(&mut self) {
+        self.cells.clear();
+        let mut start = 0;
+        for end in 1..=self.entries.len() {
+            if end == self.entries.len() || self.entries[end].0 != self.entries[start].0 {
+                self.cells.insert(self.entries[start].0, start..end);
+                start = end;
+            }
+        }
+    }
+}
+
+fn cell(position: Vector, cell_size: Scalar) -> Cell {
+    std::array::from_fn(|axis| (position[axis] / cell_size).floor() as i64)
+}
+
+/// Every cell in the box between the min and max cells inclusive, with the last
+/// coordinate changing fastest.
+fn cells_between(min: Cell, max: Cell) -> impl Iterator<Item = Cell> {
+    let sizes: [i64; DIMENSIONS] = std::array::from_fn(|axis| max[axis] - min[axis] + 1);
+    let count = sizes.iter().product::<i64>().max(0);
+    (0..count).map(move |mut index| {
+        let mut cell = min;
+        for axis in (0..DIMENSIONS).rev() {
+            cell[axis] += index % sizes[axis];
+            index /= sizes[axis];
+        }
+        cell
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+    use crate::particle::ParticleId;
+    use crate::vector;
+
+    #[test]
+    fn grid_finds_the_same_pairs_as_checking_every_pair() {
+        let mut rng = StdRng::seed_from_u64(0);
+        // radii from about 0.6 to 13 m, so particles span from a fraction of a cell to several cells
+        let particles: Vec<Particle> = (0..500)
+            .map(|id| {
+                let position = vector::map(Vector::ZERO, |_, _| rng.gen_range(-150.0..150.));
+                Particle::new(ParticleId::new(id), position, Vector::ZERO, rng.gen_range(1.0..1e4), 1.)
+            })
+            .collect();
+        let mut expected: Vec<(usize, usize)> = (0..particles.len())
+            .flat_map(|i| (i + 1..particles.len()).map(move |j| (i, j)))
+            .filter(|&(i, j)| particles[i].overlaps(&particles[j]))
+            .collect();
+        expected.sort_unstable();
+        assert!(expected.len() > 10, "too few overlaps to compare");
+
+        for cell_size in [1., 5., 20., 500.] {
+            let mut grid = SpatialGrid::new(cell_size);
+            grid.rebuild(&particles);
+            let mut pairs = grid.overlapping_pairs(&particles);
+            pairs.sort_unstable();
+            assert_eq!(pairs, expected, "cells of {} m", cell_size);
+
+            grid.par_rebuild(&particles);
+            let mut pairs = grid.par_overlapping_pairs(&particles);
+            pairs.sort_unstable();
+            assert_eq!(pairs, expected, "cells of {} m built in parallel", cell_size);
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::particle::{Particle, ParticleNames};
+use crate::scalar::Scalar;
+
+/// Snapshot of a simulation which can be saved to and loaded from a file.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SimulationState {
+    pub particles: Vec<Particle>,
+    pub world_scale: f32,
+    pub time_scale: Scalar,
+    /// Seconds simulated before the state was saved, zero for states saved without a clock
+    #[serde(default)]
+    pub time: f64,
+    /// Names of the named particles, by id
+    #[serde(default)]
+    pub names: ParticleNames,
+}
+
+/// Writes the state to a JSON file, replacing the file if it already exists.
+pub fn save_state(path: &str, state: &SimulationState) -> std::io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    serde_json::to_writer(&mut file, state)?;
+    file.flush()
+}
+
+/// Reads a state previously written by save_state.
+pub fn load_state(path: &str) -> std::io::Result<SimulationState> {
+    let file = BufReader::new(File::open(path)?);
+    Ok(serde_json::from_reader(file)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::particle::ParticleId;
+    use crate::presets;
+    use crate::vector::Vector;
+
+    #[test]
+    fn saved_state_loads_back_unchanged() {
+        let path = std::env::temp_dir().join(format!("nbody-state-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut particles = presets::solar_system();
+        particles[3].fixed = true;
+        particles[4].acceleration = particles[4].position * 1e-3;
+        let state = SimulationState { particles, world_scale: 1.5e-9, time_scale: 3600., time: 1234.5, names: presets::solar_system_names() };
+        save_state(path, &state).unwrap();
+        let loaded = load_state(path);
+        fs::remove_file(path).unwrap();
+
+        let loaded = loaded.unwrap();
+        assert_eq!((loaded.world_scale, loaded.time_scale, loaded.time), (state.world_scale, state.time_scale, state.time));
+        assert_eq!(loaded.names, state.names);
+        assert_eq!(loaded.particles.len(), state.particles.len());
+        for (particle, expected) in loaded.particles.iter().zip(&state.particles) {
+            assert_eq!(
+                (particle.id, particle.position, particle.velocity, particle.acceleration, particle.mass, particle.radius, particle.fixed),
+                (expected.id, expected.position, expected.velocity, expected.acceleration, expected.mass, expected.radius, expected.fixed),
+            );
+        }
+    }
+
+    #[test]
+    fn states_saved_before_the_clock_and_names_load_with_defaults() {
+        let path = std::env::temp_dir().join(format!("nbody-old-state-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        let particle = serde_json::to_value(Particle::new(ParticleId::new(7), Vector::ZERO, Vector::ZERO, 1., 1.)).unwrap();
+        let mut particle = particle.as_object().unwrap().clone();
+        particle.remove("fixed");
+        fs::write(path, serde_json::json!({ "particles": [particle], "world_scale": 1.0, "time_scale": 1.0 }).to_string()).unwrap();
+        let loaded = load_state(path);
+        fs::remove_file(path).unwrap();
+
+        let loaded = loaded.unwrap();
+        assert_eq!((loaded.time, loaded.names.len(), loaded.particles[0].id, loaded.particles[0].fixed), (0., 0, ParticleId::new(7), false));
+        assert!(load_state(path).is_err(), "a missing file loads");
+    }
+}
@@ -0,0 +1,111 @@
+use std::ops::{Add, Sub};
+
+use glam::{DVec2, DVec3, Vec2, Vec3};
+
+/// Numbers which can be summed with [`CompensatedSum`].
+pub trait Summand: Copy + Add<Output = Self> + Sub<Output = Self> {
+    const ZERO: Self;
+
+    /// What rounding a + b to the sum lost, a + b - sum, found exactly by
+    /// subtracting the larger of a and b from the sum first. Vectors are
+    /// compared component by component.
+    fn rounding_error(a: Self, b: Self, sum: Self) -> Self;
+}
+
+macro_rules! impl_float_summand {
+    ($($float:ty),*) => {$(
+        impl Summand for $float {
+            const ZERO: Self = 0.;
+
+            fn rounding_error(a: Self, b: Self, sum: Self) -> Self {
+                if a.abs() >= b.abs() { (a - sum) + b } else { (b - sum) + a }
+            }
+        }
+    )*};
+}
+
+macro_rules! impl_vector_summand {
+    ($($vector:ty),*) => {$(
+        impl Summand for $vector {
+            const ZERO: Self = <$vector>::ZERO;
+
+            fn rounding_error(a: Self, b: Self, sum: Self) -> Self {
+                <$vector>::select(a.abs().cmpge(b.abs()), (a - sum) + b, (b - sum) + a)
+            }
+        }
+    )*};
+}
+
+impl_float_summand!(f32, f64);
+impl_vector_summand!(Vec2, Vec3, DVec2, DVec3);
+
+/// Sum which keeps track of what rounding each addition lost and adds it back
+/// at the end (Kahan-Babuška or Neumaier summation), so terms many orders of
+/// magnitude smaller than the sum still count.
+#[derive(Clone, Copy, Debug)]
+pub struct CompensatedSum<T> {
+    sum: T,
+    /// Sum of what rounding lost from each addition
+    compensation: T,
+}
+
+impl<T: Summand> Default for CompensatedSum<T> {
+    fn default() -> Self {
+        CompensatedSum { sum: T::ZERO, compensation: T::ZERO }
+    }
+}
+
+impl<T: Summand> CompensatedSum<T> {
+    pub fn add(&mut self, value: T) {
+        let sum = self.sum + value;
+        self.compensation = self.compensation + T::rounding_error(self.sum, value, sum);
+        self.sum = sum;
+    }
+
+    /// Adds the terms of another sum, such as when the sums of parallel chunks are combined.
+    pub fn combine(mut self, other: Self) -> Self {
+        self.add(other.sum);
+        self.add(other.compensation);
+        self
+    }
+
+    /// The sum with what rounding lost added back.
+    pub fn value(&self) -> T {
+        self.sum + self.compensation
+    }
+}
+
+impl<T: Summand> Extend<T> for CompensatedSum<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, values: I) {
+        for value in values {
+            self.add(value);
+        }
+    }
+}
+
+impl<T: Summand> FromIterator<T> for CompensatedSum<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(values: I) -> Self {
+        let mut sum = CompensatedSum::default();
+        sum.extend(values);
+        sum
+    }
+}
+
+/// Compensated sum of the values, see [`CompensatedSum`].
+pub fn compensated_sum<T: Summand>(values: impl IntoIterator<Item = T>) -> T {
+    values.into_iter().collect::<CompensatedSum<T>>().value()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn terms_too_small_to_change_the_sum_still_count() {
+        // summed naively, a million terms each too small to change the sum on their own are all lost
+        let terms = || std::iter::once(1.).chain(std::iter::repeat_n(1e-17, 1_000_000));
+        assert_eq!(terms().sum::<f64>(), 1.);
+        let sum: CompensatedSum<f64> = terms().collect();
+        assert!((sum.value() - (1. + 1e-11)).abs() <= f64::EPSILON);
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+use crate::particle::{Particle, ParticleId};
+use crate::vector;
+
+/// Size in bytes a buffer of rows grows to before it is sent to be written.
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Logs the positions and velocities of particles every interval steps to a CSV file with
+/// the columns step, id, x, y, vx and vy, or x, y, z, vx, vy and vz in three dimensions. Rows are formatted into a buffer which is written
+/// by a background thread once full, and written buffers are sent back to be reused, so
+/// logging doesn't allocate once the buffers have grown.
+pub struct TrajectoryLogger {
+    interval: u64,
+    /// Ids of the particles logged, or None to log every particle
+    ids: Option<HashSet<ParticleId>>,
+    /// Above this many particles nothing is logged when every particle is logged
+    max_particles: usize,
+    buffer: Vec<u8>,
+    /// Full buffers sent to the writer thread, and emptied buffers sent back from it
+    full: Option<Sender<Vec<u8>>>,
+    empty: Receiver<Vec<u8>>,
+    writer: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl TrajectoryLogger {
+    /// Creates the file, writes the header and starts the writer thread.
+    pub fn new(path: &str, interval: usize, ids: Option<Vec<ParticleId>>, max_particles: usize) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(file, "step,id,{},{}", vector::csv_header(""), vector::csv_header("v"))?;
+        let (full, full_receiver) = mpsc::channel::<Vec<u8>>();
+        let (empty_sender, empty) = mpsc::channel();
+        let writer = thread::spawn(move || {
+            for mut buffer in full_receiver {
+                file.write_all(&buffer)?;
+                buffer.clear();
+                // the logger may have been dropped already, in which case the buffer is freed
+                let _ = empty_sender.send(buffer);
+            }
+            file.flush()
+        });
+        Ok(TrajectoryLogger {
+            interval: interval.max(1) as u64,
+            ids: ids.map(|ids| ids.into_iter().collect()),
+            max_particles,
+            buffer: Vec::with_capacity(BUFFER_SIZE),
+            full: Some(full),
+            empty,
+            writer: Some(writer),
+        })
+    }
+
+    /// Logs the particles if the step is a multiple of the interval. Logged ids which no
+    /// longer exist are skipped, so particles removed or merged during the log just stop
+    /// appearing in it.
+    pub fn record(&mut self, step: u64, particles: &[Particle]) {
+        if !step.is_multiple_of(self.interval) {
+            return;
+        }
+        if self.ids.is_none() && particles.len() > self.max_particles {
+            return;
+        }
+        for particle in particles {
+            if self.ids.as_ref().is_some_and(|ids| !ids.contains(&particle.id)) {
+                continue;
+            }
+            // writing into a Vec can't fail
+            let _ = write!(self.buffer, "{},{}", step, particle.id);
+            let _ = vector::write_csv(&mut self.buffer, particle.position.to_array());
+            let _ = vector::write_csv(&mut self.buffer, particle.velocity.to_array());
+            let _ = writeln!(self.buffer);
+        }
+        if self.buffer.len() >= BUFFER_SIZE {
+            self.send();
+        }
+    }
+
+    /// Sends the buffer to the writer thread, replacing it with a buffer the thread has
+    /// finished writing if there is one.
+    fn send(&mut self) {
+        let replacement = self.empty.try_recv().unwrap_or_else(|_| Vec::with_capacity(BUFFER_SIZE));
+        let buffer = std::mem::replace(&mut self.buffer, replacement);
+        if let Some(full) = &self.full {
+            // if the writer thread failed, the error is returned by finish
+            let _ = full.send(buffer);
+        }
+    }
+
+    /// Writes the remaining rows and waits for the file to be written, returning any
+    /// error the writer thread ran into.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.stop()
+    }
+
+    fn stop(&mut self) -> io::Result<()> {
+        if !self.buffer.is_empty() {
+            self.send();
+        }
+        // closing the channel ends the writer thread once it has written every buffer
+        self.full = None;
+        match self.writer.take() {
+            Some(writer) => writer.join().unwrap_or_else(|_| Err(io::Error::other("trajectory writer thread panicked"))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for TrajectoryLogger {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integrator::VerletIntegrator;
+    use crate::presets;
+    use crate::scalar::Scalar;
+    use crate::vector::{DIMENSIONS, Vector};
+    use crate::world::{Physics, SequentialWorld, World};
+
+    /// Rows of the logged file, parsed into numbers, and removes the file.
+    fn read_log(path: &std::path::Path) -> Vec<Vec<f64>> {
+        let contents = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let mut lines = contents.lines();
+        assert_eq!(lines.next().unwrap().split(',').count(), 2 + 2 * DIMENSIONS);
+        lines.map(|line| line.split(',').map(|value| value.parse().unwrap_or_else(|_| panic!("'{}' isn't a number", line))).collect()).collect()
+    }
+
+    #[test]
+    fn logged_two_body_orbit_parses_with_increasing_steps() {
+        let path = std::env::temp_dir().join(format!("nbody-trajectory-{}.csv", std::process::id()));
+        let mut logger = TrajectoryLogger::new(path.to_str().unwrap(), 3, None, 10).unwrap();
+        let mut world = SequentialWorld::new(presets::two_body(), Box::new(VerletIntegrator), Physics::default());
+        let dt = presets::two_body_period() / 3000.;
+        let mut last = Vector::ZERO;
+        for step in 0..3000 {
+            world.update(dt);
+            logger.record(step, &world.particles());
+            if step % 3 == 0 {
+                last = world.particles()[1].position;
+            }
+        }
+        logger.finish().unwrap();
+
+        // enough rows to fill several buffers, which must all be written in order, ending with the last positions
+        let rows = read_log(&path);
+        assert_eq!(rows.len(), 2 * 1000);
+        for (index, row) in rows.iter().enumerate() {
+            assert_eq!((row[0], row[1]), ((index / 2 * 3) as f64, (index % 2) as f64), "row {}", index);
+            assert_eq!(row.len(), 2 + 2 * DIMENSIONS);
+        }
+        let logged = &rows[rows.len() - 1][2..2 + DIMENSIONS];
+        assert!((0..DIMENSIONS).all(|axis| logged[axis] as Scalar == last[axis]), "logged {:?} instead of {:?}", logged, last);
+    }
+
+    #[test]
+    fn removed_and_unselected_particles_are_skipped() {
+        let path = std::env::temp_dir().join(format!("nbody-trajectory-ids-{}.csv", std::process::id()));
+        let mut particles = presets::random(5, 3);
+        let mut logger = TrajectoryLogger::new(path.to_str().unwrap(), 1, Some(vec![ParticleId::new(1), ParticleId::new(3)]), 0).unwrap();
+        logger.record(0, &particles);
+        particles.retain(|particle| particle.id != ParticleId::new(3));
+        logger.record(1, &particles);
+        logger.finish().unwrap();
+        let ids: Vec<(f64, f64)> = read_log(&path).iter().map(|row| (row[0], row[1])).collect();
+        assert_eq!(ids, [(0., 1.), (0., 3.), (1., 1.)]);
+
+        // every particle is logged up to the limit, and none above it
+        let path = std::env::temp_dir().join(format!("nbody-trajectory-all-{}.csv", std::process::id()));
+        let mut logger = TrajectoryLogger::new(path.to_str().unwrap(), 1, None, 4).unwrap();
+        logger.record(0, &particles);
+        particles.pop();
+        logger.record(1, &particles[..0]);
+        logger.record(2, &presets::random(5, 3));
+        logger.record(3, &particles);
+        logger.finish().unwrap();
+        let steps: Vec<f64> = read_log(&path).iter().map(|row| row[0]).collect();
+        assert_eq!(steps, [0., 0., 0., 0., 3., 3., 3.]);
+    }
+}
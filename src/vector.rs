@@ -0,0 +1,133 @@
+//! The vector type of the positions, velocities and forces of the particles. The
+//! simulation is two dimensional by default, and three dimensional with the
+//! `three-dimensions` feature, where the screen is the plane of the x and y axes
+//! and the z axis points out of it. Code which only works in the plane, like
+//! placing particles from the cursor, goes through [`planar`] so it builds in
+//! either mode.
+
+use std::fmt::Display;
+use std::io::{self, Write};
+
+use crate::scalar::{self, Scalar};
+
+#[cfg(not(feature = "three-dimensions"))]
+mod dimension {
+    use crate::scalar::Scalar;
+
+    /// Number of components of a [`Vector`]
+    pub const DIMENSIONS: usize = 2;
+    /// Names of the components, used for the columns of CSV files
+    pub const AXES: [&str; DIMENSIONS] = ["x", "y"];
+
+    /// Vector of [`Scalar`]s
+    #[cfg(not(feature = "single-precision"))]
+    pub type Vector = glam::DVec2;
+    /// Vector of [`Scalar`]s
+    #[cfg(feature = "single-precision")]
+    pub type Vector = glam::Vec2;
+    /// Double precision vector with the same number of dimensions
+    pub type DoubleVector = glam::DVec2;
+
+    /// Vector in the plane of the screen.
+    pub fn planar(x: Scalar, y: Scalar) -> Vector {
+        Vector::new(x, y)
+    }
+
+    /// Vector with the given depth out of the screen, which is ignored in two dimensions.
+    pub fn with_depth(x: Scalar, y: Scalar, _depth: Scalar) -> Vector {
+        Vector::new(x, y)
+    }
+
+    /// Distance of the vector out of the screen, always zero in two dimensions.
+    pub fn depth(_vector: Vector) -> Scalar {
+        0.
+    }
+
+    /// The vector rotated a quarter turn anticlockwise around the axis out of the screen.
+    pub fn perpendicular(vector: Vector) -> Vector {
+        vector.perp()
+    }
+
+    /// Cross product of two double precision vectors, which in the plane only has
+    /// a component along the z axis out of the screen.
+    pub fn cross(a: DoubleVector, b: DoubleVector) -> glam::DVec3 {
+        glam::DVec3::new(0., 0., a.perp_dot(b))
+    }
+}
+
+#[cfg(feature = "three-dimensions")]
+mod dimension {
+    use crate::scalar::Scalar;
+
+    /// Number of components of a [`Vector`]
+    pub const DIMENSIONS: usize = 3;
+    /// Names of the components, used for the columns of CSV files
+    pub const AXES: [&str; DIMENSIONS] = ["x", "y", "z"];
+
+    /// Vector of [`Scalar`]s
+    #[cfg(not(feature = "single-precision"))]
+    pub type Vector = glam::DVec3;
+    /// Vector of [`Scalar`]s
+    #[cfg(feature = "single-precision")]
+    pub type Vector = glam::Vec3;
+    /// Double precision vector with the same number of dimensions
+    pub type DoubleVector = glam::DVec3;
+
+    /// Vector in the plane of the screen.
+    pub fn planar(x: Scalar, y: Scalar) -> Vector {
+        Vector::new(x, y, 0.)
+    }
+
+    /// Vector with the given depth out of the screen.
+    pub fn with_depth(x: Scalar, y: Scalar, depth: Scalar) -> Vector {
+        Vector::new(x, y, depth)
+    }
+
+    /// Distance of the vector out of the screen.
+    pub fn depth(vector: Vector) -> Scalar {
+        vector.z
+    }
+
+    /// The vector rotated a quarter turn anticlockwise around the axis out of the
+    /// screen and flattened onto the screen, so it is perpendicular to the vector.
+    /// A vector along the axis has no such rotation, so the x axis is used instead.
+    pub fn perpendicular(vector: Vector) -> Vector {
+        Vector::Z.cross(vector).try_normalize().unwrap_or(Vector::X) * vector.length()
+    }
+
+    /// Cross product of two double precision vectors.
+    pub fn cross(a: DoubleVector, b: DoubleVector) -> glam::DVec3 {
+        a.cross(b)
+    }
+}
+
+pub use dimension::*;
+
+/// Converts a vector to double precision.
+pub fn to_f64(vector: Vector) -> DoubleVector {
+    DoubleVector::from_array(vector.to_array().map(scalar::to_f64))
+}
+
+/// Converts a double precision vector to the precision of the simulation.
+pub fn from_f64(vector: DoubleVector) -> Vector {
+    Vector::from_array(vector.to_array().map(scalar::from_f64))
+}
+
+/// Applies a function to each component of the vector, in order.
+pub fn map(vector: Vector, mut f: impl FnMut(usize, Scalar) -> Scalar) -> Vector {
+    Vector::from_array(std::array::from_fn(|axis| f(axis, vector[axis])))
+}
+
+/// Comma separated names of the components with a prefix, such as `vx,vy`, for
+/// the header of a CSV file.
+pub fn csv_header(prefix: &str) -> String {
+    AXES.map(|axis| format!("{}{}", prefix, axis)).join(",")
+}
+
+/// Writes each component of a vector preceded by a comma, for a row of a CSV file.
+pub fn write_csv<T: Display>(writer: &mut impl Write, components: impl IntoIterator<Item = T>) -> io::Result<()> {
+    for component in components {
+        write!(writer, ",{}", component)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,232 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use parking_lot::{Condvar, Mutex};
+
+/// Threads which run jobs together with the thread calling [`WorkerPool::run`].
+///
+/// Cloning the pool gives another handle to the same threads, so worlds can be
+/// created and dropped without spawning new threads each time. The threads
+/// wait on a condition variable between jobs and exit once the last handle is
+/// dropped, or when the pool is shrunk.
+#[derive(Clone)]
+pub struct WorkerPool {
+    inner: Arc<Inner>,
+}
+
+/// Totals of the jobs run by a pool since it was created.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PoolStats {
+    /// Threads running each job, including the calling thread
+    pub threads: usize,
+    pub jobs: u64,
+    /// Time the threads spent running jobs, summed over every thread
+    pub busy: Duration,
+    /// Time each job took multiplied by the threads running it, summed over every job
+    pub capacity: Duration,
+}
+
+impl PoolStats {
+    /// Fraction of the time the threads were busy while jobs were running
+    /// between the earlier stats and these, or None if no job ran in between.
+    pub fn utilization_since(&self, earlier: &PoolStats) -> Option<f64> {
+        let capacity = self.capacity.saturating_sub(earlier.capacity).as_secs_f64();
+        (capacity > 0.).then(|| self.busy.saturating_sub(earlier.busy).as_secs_f64() / capacity)
+    }
+}
+
+/// Owned by the handles, so the threads are joined when the last handle is dropped.
+struct Inner {
+    shared: Arc<Shared>,
+    workers: Mutex<Vec<JoinHandle<()>>>,
+    /// Held while a job runs or the pool is resized, so only one happens at a time
+    running: Mutex<()>,
+}
+
+/// State shared with the worker threads.
+struct Shared {
+    state: Mutex<State>,
+    /// Notified when a job starts or the pool shrinks
+    start: Condvar,
+    /// Notified when the last worker thread finishes a job
+    done: Condvar,
+}
+
+struct State {
+    /// Increased for every job, so the worker threads know when a new job starts
+    generation: u64,
+    job: Option<Job>,
+    /// Threads running each job, including the calling thread. Worker threads
+    /// with an id of at least this exit.
+    threads: usize,
+    /// Worker threads still running the current job
+    remaining: usize,
+    /// Whether a worker thread panicked while running the current job
+    panicked: bool,
+    stats: PoolStats,
+}
+
+/// Job shared with the worker threads, which is only called while run is waiting for them.
+#[derive(Clone, Copy)]
+struct Job(*const (dyn Fn(usize, usize) + Sync));
+
+// the job is only called while run keeps it borrowed
+unsafe impl Send for Job {}
+
+impl WorkerPool {
+    /// Creates a pool where jobs run on the given number of threads, one of
+    /// which is the calling thread. If num_threads is 0 the available
+    /// parallelism of the machine is used instead.
+    pub fn new(num_threads: usize) -> Self {
+        let state = State { generation: 0, job: None, threads: 1, remaining: 0, panicked: false, stats: PoolStats { threads: 1, ..PoolStats::default() } };
+        let shared = Arc::new(Shared { state: Mutex::new(state), start: Condvar::new(), done: Condvar::new() });
+        let pool = WorkerPool { inner: Arc::new(Inner { shared, workers: Mutex::new(Vec::new()), running: Mutex::new(()) }) };
+        pool.resize(num_threads);
+        pool
+    }
+
+    /// Threads running each job, including the calling thread.
+    pub fn num_threads(&self) -> usize {
+        self.inner.shared.state.lock().threads
+    }
+
+    /// Spawns or joins worker threads so jobs run on the given number of
+    /// threads, or the available parallelism of the machine if it is 0. Waits
+    /// for a running job to finish first, so only idle threads are joined.
+    pub fn resize(&self, num_threads: usize) {
+        let num_threads = match num_threads {
+            0 => thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1),
+            num_threads => num_threads,
+        };
+        let _running = self.inner.running.lock();
+        let shared = &self.inner.shared;
+        let mut workers = self.inner.workers.lock();
+        let generation = {
+            let mut state = shared.state.lock();
+            state.threads = num_threads;
+            state.stats.threads = num_threads;
+            state.generation
+        };
+
+        // the worker thread with id i is at index i - 1, as the calling thread has id 0
+        if workers.len() + 1 > num_threads {
+            shared.start.notify_all();
+            for worker in workers.drain(num_threads - 1..) {
+                let _ = worker.join();
+            }
+        }
+        for id in workers.len() + 1..num_threads {
+            let shared = Arc::clone(shared);
+            workers.push(thread::spawn(move || work(&shared, id, generation)));
+        }
+    }
+
+    /// Calls the job on every thread of the pool with the id of the thread and
+    /// the number of threads, returning once every thread has finished. The
+    /// calling thread runs the job with id 0. Panics if the job panicked on
+    /// any of the threads.
+    pub fn run(&self, job: &(dyn Fn(usize, usize) + Sync)) {
+        let _running = self.inner.running.lock();
+        let shared = &self.inner.shared;
+        let threads = {
+            let mut state = shared.state.lock();
+            // SAFETY: only the lifetime is erased, and the worker threads stop using the job
+            // before run returns, as it waits for every worker thread below even if the job panics
+            let job = unsafe { std::mem::transmute::<*const (dyn Fn(usize, usize) + Sync + '_), *const (dyn Fn(usize, usize) + Sync)>(job) };
+            state.job = Some(Job(job));
+            state.generation += 1;
+            state.remaining = state.threads - 1;
+            shared.start.notify_all();
+            state.threads
+        };
+
+        let start = Instant::now();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| job(0, threads)));
+        let busy = start.elapsed();
+
+        let mut state = shared.state.lock();
+        while state.remaining > 0 {
+            shared.done.wait(&mut state);
+        }
+        state.job = None;
+        state.stats.jobs += 1;
+        state.stats.busy += busy;
+        state.stats.capacity += start.elapsed() * threads as u32;
+        let panicked = std::mem::take(&mut state.panicked);
+        drop(state);
+
+        if let Err(payload) = result {
+            panic::resume_unwind(payload);
+        }
+        if panicked {
+            panic!("A worker thread panicked while running a job");
+        }
+    }
+
+    /// Totals of the jobs run by the pool since it was created.
+    pub fn stats(&self) -> PoolStats {
+        self.inner.shared.state.lock().stats
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        // every worker thread has an id of at least 1, so they all exit
+        self.shared.state.lock().threads = 1;
+        self.shared.start.notify_all();
+        for worker in self.workers.get_mut().drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Loop of a worker thread, running each new job until its id is no longer in the pool.
+fn work(shared: &Shared, id: usize, mut generation: u64) {
+    loop {
+        let mut state = shared.state.lock();
+        while state.generation == generation && id < state.threads {
+            shared.start.wait(&mut state);
+        }
+        if id >= state.threads {
+            return;
+        }
+        generation = state.generation;
+        let (job, threads) = (state.job, state.threads);
+        drop(state);
+
+        let start = Instant::now();
+        // SAFETY: run keeps the job borrowed until this thread decreases the remaining threads below
+        let result = job.map(|job| panic::catch_unwind(AssertUnwindSafe(|| unsafe { (*job.0)(id, threads) })));
+        let busy = start.elapsed();
+
+        let mut state = shared.state.lock();
+        state.stats.busy += busy;
+        state.panicked |= matches!(result, Some(Err(_)));
+        state.remaining -= 1;
+        if state.remaining == 0 {
+            shared.done.notify_one();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integrator::VerletIntegrator;
+    use crate::presets;
+    use crate::world::{Physics, ThreadsWorld, World};
+
+    #[test]
+    fn dropping_the_world_joins_the_worker_threads() {
+        let pool = WorkerPool::new(4);
+        let shared = Arc::clone(&pool.inner.shared);
+        let mut world = ThreadsWorld::with_pool(pool, presets::random(100, 0), Box::new(VerletIntegrator), Physics::default());
+        world.update(1.);
+        // the pool, this test and each of the three worker threads hold the shared state
+        assert_eq!(Arc::strong_count(&shared), 5);
+        drop(world);
+        assert_eq!(Arc::strong_count(&shared), 1, "worker threads were still running after the world was dropped");
+    }
+}
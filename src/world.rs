@@ -1,201 +1,1698 @@
-use std::sync::{Arc, Barrier, atomic::Ordering};
-use std::thread::{self, JoinHandle};
+use std::collections::HashSet;
+use std::ops::{Deref, Range};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Barrier, OnceLock, mpsc};
 
 use rayon::prelude::*;
-use atomic_float::AtomicF64;
-use glam::DVec2;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock, RwLockReadGuard};
 
-use crate::particle::Particle;
+use crate::barnes_hut::BarnesHutTree;
+use crate::boundary::Boundary;
+use crate::collision::{BounceParams, CollisionMode, Merge, SplitGrace, resolve_collisions, split};
+use crate::config::Config;
+use crate::events::{Event, EventKind, EventLog};
+use crate::force::{Force, GravityField, GravitySolver, PairwiseGravity, default_forces, field_net_acceleration_at, net_acceleration, net_acceleration_at};
+use crate::integrator::{Integrator, Scratch};
+use crate::particle::{ForceParams, Particle, ParticleId};
+use crate::particle_mesh::ParticleMesh;
+use crate::recording::ParticleSnapshot;
+use crate::scalar::{Scalar, to_f64};
+use crate::vector::Vector;
+use crate::spatial_grid::SpatialGrid;
+use crate::worker_pool::WorkerPool;
 
 pub trait World {
     /// Updates the particles with a given delta time.
-    fn update(&mut self, dt: f64);
-    /// Add a new [`Particle`] to the world.
-    fn create_particle(&mut self, position: DVec2, velocity: DVec2, mass: f64);
+    fn update(&mut self, dt: Scalar);
+    /// Runs the given number of updates of dt back to back, such as to skip
+    /// ahead without drawing the particles in between. Observers are still
+    /// called after every update. Worlds can run the updates with less
+    /// overhead than calling update for each, but end with the same particles.
+    fn advance(&mut self, steps: usize, dt: Scalar) {
+        for _ in 0..steps {
+            self.update(dt);
+        }
+    }
+    /// Add a new [`Particle`] to the world, returning its id. Fixed particles are created at rest.
+    fn create_particle(&mut self, position: Vector, velocity: Vector, mass: Scalar, fixed: bool) -> ParticleId;
+    /// Reserves room for at least `additional` more particles, so creating many
+    /// particles one at a time doesn't reallocate the particles as it goes.
+    fn reserve(&mut self, additional: usize);
+    /// Queue other threads can request new particles through without waiting
+    /// for the world. The particles are created at the start of the next update.
+    fn spawn_queue(&self) -> SpawnQueue;
+    /// Registers an [`Observer`] called after every update, on the thread which
+    /// called update.
+    fn add_observer(&mut self, observer: Observer);
+    /// Adds a particle which was removed from the world back to it, keeping
+    /// its id and state. New particles are given ids after its id.
+    fn restore_particle(&mut self, particle: Particle);
+    /// Replaces every particle. The radius and acceleration of each particle
+    /// are recalculated using the physics of the world. New particles are given
+    /// ids after both the ids of the particles and the ids given out before, so
+    /// ids of replaced particles are not reused.
+    fn set_particles(&mut self, particles: Vec<Particle>);
+    /// Removes the particle with the given id, returning whether it was found.
+    /// Ids of removed particles are not reused.
+    fn remove_particle(&mut self, id: ParticleId) -> bool;
+    /// Replaces the particle with the given id with fragments made by
+    /// [`split`](crate::collision::split), with the explosion energy in joules,
+    /// returning the ids of the fragments. The ids are new, and the fragments
+    /// don't merge with or bounce off each other for the first
+    /// [`SPLIT_GRACE_UPDATES`](crate::collision::SPLIT_GRACE_UPDATES) updates.
+    /// Returns no ids if there is no such particle, or it can't be split.
+    fn split_particle(&mut self, id: ParticleId, fragments: usize, energy: Scalar) -> Vec<ParticleId>;
+    /// Adds dv to the velocity of the particle with the given id, returning whether
+    /// it was found. Fixed particles stay at rest. Other threads can apply impulses
+    /// through the [`SpawnQueue`] instead.
+    fn apply_impulse(&mut self, id: ParticleId, dv: Vector) -> bool;
+    /// Moves the particle with the given id and replaces its velocity and mass,
+    /// returning whether it was found. Its radius and acceleration are recalculated,
+    /// and fixed particles stay at rest.
+    fn set_particle(&mut self, id: ParticleId, position: Vector, velocity: Vector, mass: Scalar) -> bool;
+    /// Removes every particle and resets the clock.
+    fn clear(&mut self);
+    /// Seconds simulated by the updates of the world, the sum of the delta times
+    /// of every update. It is kept in f64 so long runs keep their precision.
+    fn time(&self) -> f64;
+    /// Sets the clock, such as when a saved simulation is loaded.
+    fn set_time(&mut self, time: f64);
+    /// Takes the events recorded since the last call, oldest first: merges made
+    /// by updates, and particles created, split or removed. Only the most recent
+    /// [`EVENT_LOG_CAPACITY`](crate::events::EVENT_LOG_CAPACITY) are kept
+    /// between calls.
+    fn drain_events(&mut self) -> Vec<Event>;
     /// Returns a copy of the Particles 
     fn get_particles(&mut self) -> Vec<Particle>;
+    /// Returns a view of the particles without copying them.
+    fn particles(&self) -> ParticleView<'_>;
+    /// Positions and masses of the particles, which are cheaper to store than the particles.
+    fn snapshot(&self) -> Vec<ParticleSnapshot> {
+        self.particles().iter().map(ParticleSnapshot::from).collect()
+    }
+    /// Number of particles in the world, which is cheap enough to call every
+    /// frame as it doesn't copy the particles or wait for a lock on them.
+    fn len(&self) -> usize;
+    /// Whether the world has no particles.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Replaces the [`Integrator`] used to update the particles.
+    fn set_integrator(&mut self, integrator: Box<dyn Integrator>);
+    /// Replaces the [`Physics`] parameters used to update the particles.
+    fn set_physics(&mut self, physics: Physics);
+    /// Replaces the [`Force`]s summed to find the accelerations of the particles.
+    fn set_forces(&mut self, forces: Vec<Box<dyn Force>>);
+    /// Names of the forces acting on the particles.
+    fn force_names(&self) -> Vec<&'static str>;
+    /// Number of threads used to update the particles.
+    fn num_threads(&self) -> usize;
+    /// Number of steps the last update was split into.
+    fn substeps(&self) -> usize;
+}
+
+/// Sends requests for new particles, and impulses on existing particles, to a
+/// world from any thread. Each world creates the requested particles, giving
+/// them their ids, and applies the impulses at the start of its next update, so
+/// requests never touch the particles while they are updated.
+#[derive(Clone)]
+pub struct SpawnQueue {
+    sender: mpsc::Sender<SpawnRequest>,
+}
+
+impl SpawnQueue {
+    /// Requests a new particle, returning false if the world has been dropped.
+    pub fn push(&self, position: Vector, velocity: Vector, mass: Scalar, fixed: bool) -> bool {
+        self.sender.send(SpawnRequest::Particle { position, velocity, mass, fixed }).is_ok()
+    }
+
+    /// Requests an impulse on the particle with the given id, returning false if
+    /// the world has been dropped. Impulses on particles which no longer exist are ignored.
+    pub fn push_impulse(&self, id: ParticleId, dv: Vector) -> bool {
+        self.sender.send(SpawnRequest::Impulse { id, dv }).is_ok()
+    }
+
+    /// Requests the removal of the particle with the given id, returning false if
+    /// the world has been dropped.
+    pub fn push_removal(&self, id: ParticleId) -> bool {
+        self.sender.send(SpawnRequest::Removal { id }).is_ok()
+    }
+}
+
+/// Change to the particles sent through a [`SpawnQueue`].
+enum SpawnRequest {
+    /// Arguments of create_particle
+    Particle { position: Vector, velocity: Vector, mass: Scalar, fixed: bool },
+    /// Arguments of apply_impulse
+    Impulse { id: ParticleId, dv: Vector },
+    /// Argument of remove_particle
+    Removal { id: ParticleId },
+}
+
+impl SpawnRequest {
+    fn apply(self, world: &mut dyn World) {
+        match self {
+            SpawnRequest::Particle { position, velocity, mass, fixed } => {
+                world.create_particle(position, velocity, mass, fixed);
+            }
+            SpawnRequest::Impulse { id, dv } => {
+                world.apply_impulse(id, dv);
+            }
+            SpawnRequest::Removal { id } => {
+                world.remove_particle(id);
+            }
+        }
+    }
+}
+
+/// Receiving end of the spawn queues of a world.
+struct SpawnRequests {
+    sender: mpsc::Sender<SpawnRequest>,
+    receiver: mpsc::Receiver<SpawnRequest>,
+}
+
+impl Default for SpawnRequests {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        SpawnRequests { sender, receiver }
+    }
+}
+
+impl SpawnRequests {
+    fn queue(&self) -> SpawnQueue {
+        SpawnQueue { sender: self.sender.clone() }
+    }
+
+    /// The oldest request which hasn't been created yet.
+    fn next(&self) -> Option<SpawnRequest> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+/// Function called after each update of a world with its particles and the
+/// delta time of the update, to watch or script the simulation. An observer
+/// changes the world through a [`SpawnQueue`], whose requests are handled at
+/// the start of the next update.
+pub type Observer = Box<dyn FnMut(&[Particle], Scalar) + Send>;
+
+/// Observers of a world, called in the order they were added.
+#[derive(Default)]
+struct Observers(Vec<Observer>);
+
+impl Observers {
+    fn notify(&mut self, particles: &[Particle], dt: Scalar) {
+        for observer in &mut self.0 {
+            observer(particles, dt);
+        }
+    }
+}
+
+/// Read only access to the particles of a world. Worlds which store their
+/// particles behind a lock hold the read lock until the view is dropped, so
+/// the view should not be kept across calls to update.
+pub enum ParticleView<'a> {
+    Slice(&'a [Particle]),
+    Guard(RwLockReadGuard<'a, Vec<Particle>>),
+}
+
+impl Deref for ParticleView<'_> {
+    type Target = [Particle];
+
+    fn deref(&self) -> &[Particle] {
+        match self {
+            ParticleView::Slice(particles) => particles,
+            ParticleView::Guard(particles) => particles,
+        }
+    }
+}
+
+/// Parameters of the simulation which can be changed while it is running.
+#[derive(Clone, Copy, Debug)]
+pub struct Physics {
+    /// Softening length used when calculating accelerations
+    pub softening: Scalar,
+    /// Gravitational constant in m^3 / (kg s^2)
+    pub gravitational_constant: Scalar,
+    /// Multiplier of the gravitational constant, negative values make gravity repulsive
+    pub gravity_multiplier: Scalar,
+    /// Density used to calculate the radius of a particle from its mass
+    pub density: Scalar,
+    /// How overlapping particles are handled
+    pub collision_mode: CollisionMode,
+    /// How much speed particles keep when they bounce off each other
+    pub bounce: BounceParams,
+    /// Box the particles are kept inside of
+    pub boundary: Boundary,
+    /// Size of the cells of the grid used to find collisions
+    pub cell_size: Scalar,
+    /// Whether each update is split into smaller steps when particles are accelerating quickly
+    pub adaptive_timestep: bool,
+    /// Most steps an update can be split into
+    pub max_substeps: usize,
+    /// Fraction of the time needed to cross the softening length at the largest acceleration
+    /// used as the largest step, smaller values are more accurate
+    pub timestep_accuracy: Scalar,
+    /// How the gravity between the particles is calculated
+    pub gravity_solver: GravitySolver,
+    /// Accuracy of the Barnes-Hut approximation of gravity from 0 to 1, larger values are
+    /// faster but less accurate
+    pub barnes_hut_theta: Scalar,
+    /// Grid points along each axis of the particle mesh, rounded up to a power of two
+    pub mesh_cells: usize,
+}
+
+impl Default for Physics {
+    /// The physics of the default settings, ignoring the config file and environment variables.
+    fn default() -> Self {
+        Config::default().physics()
+    }
+}
+
+impl Physics {
+    /// Parameters of the gravitational force between particles.
+    pub fn force_params(&self) -> ForceParams {
+        ForceParams {
+            g: self.gravitational_constant * self.gravity_multiplier,
+            softening: self.softening,
+            period: self.boundary.period(),
+        }
+    }
+
+    /// Number of steps to split an update of dt into. The step is limited to
+    /// accuracy * sqrt(softening / |a|) for the largest acceleration from the
+    /// previous step, so close encounters are integrated with smaller steps.
+    pub fn substeps(&self, particles: &[Particle], dt: Scalar) -> usize {
+        if !self.adaptive_timestep {
+            return 1;
+        }
+        let max_acceleration = particles.iter().map(|particle| particle.acceleration.length()).fold(0., Scalar::max);
+        if max_acceleration == 0. {
+            return 1;
+        }
+        let max_step = self.timestep_accuracy * (self.softening / max_acceleration).sqrt();
+        ((dt / max_step).ceil() as usize).clamp(1, self.max_substeps.max(1))
+    }
 }
 
 /// Stores the entities in the world as a vector of Particles and 
 /// handles updating velocities and positions of the particles.
 /// 
-/// The positions of the particles are calculated using a parallel
-/// iterator from the rayon library.
+/// The accelerations of the particles are calculated using a parallel
+/// iterator from the rayon library, on the global thread pool unless the
+/// world was created with its own number of threads.
 pub struct RayonWorld {
     pub particles: Vec<Particle>,
+    pub integrator: Box<dyn Integrator>,
+    pub physics: Physics,
+    /// Forces summed to find the accelerations
+    pub forces: Vec<Box<dyn Force>>,
+    grid: SpatialGrid,
+    /// Approximations of gravity, reused between steps
+    gravity: GravityFields,
+    /// Buffers reused by the integrator between steps
+    scratch: Scratch,
+    next_id: ParticleId,
+    substeps: usize,
+    /// Particles requested through spawn queues, created at the start of the next update
+    spawns: SpawnRequests,
+    observers: Observers,
+    /// Events recorded since they were last drained
+    events: EventLog,
+    /// Fragments of recent splits which don't collide with each other yet
+    split_grace: SplitGrace,
+    /// Seconds simulated by every update
+    time: f64,
+    /// Thread pool the updates run on, or None to use the global thread pool
+    pool: Option<Arc<rayon::ThreadPool>>,
 }
 
-impl World for RayonWorld {
-    fn update(&mut self, dt: f64) {
-        let particles_clone = self.particles.clone();
-        self.particles.par_iter_mut().for_each(|particle| {
-            let acceleration = particle.net_acceleration(&particles_clone) * dt;
-            particle.velocity += acceleration * dt;
-            particle.position += particle.velocity * dt;
-        });
+impl RayonWorld {
+    /// Creates a new [`World`] containing the given particles.
+    pub fn new(particles: Vec<Particle>, integrator: Box<dyn Integrator>, physics: Physics) -> Self {
+        let next_id = next_id(&particles);
+        RayonWorld { particles, integrator, physics, forces: default_forces(), grid: SpatialGrid::new(physics.cell_size), gravity: GravityFields::default(), scratch: Scratch::default(), next_id, substeps: 1, spawns: SpawnRequests::default(), observers: Observers::default(), events: EventLog::default(), split_grace: SplitGrace::default(), time: 0., pool: None }
     }
 
-    fn create_particle(&mut self, position: glam::DVec2, velocity: glam::DVec2, mass: f64) {
-        self.particles.push(Particle { 
-            id: self.particles.len(), 
-            velocity, 
-            position, 
-            mass 
+    /// Creates a new [`World`] containing the given particles which updates on
+    /// a thread pool of its own with the given number of threads. The global
+    /// thread pool is used if num_threads is 0.
+    pub fn with_threads(particles: Vec<Particle>, integrator: Box<dyn Integrator>, physics: Physics, num_threads: usize) -> Self {
+        let pool = (num_threads > 0).then(|| {
+            Arc::new(rayon::ThreadPoolBuilder::new().num_threads(num_threads).build().expect("failed to spawn the threads of the rayon world"))
         });
+        RayonWorld { pool, ..RayonWorld::new(particles, integrator, physics) }
+    }
+
+    /// Moves the particles forward by dt, on whichever thread pool the caller is in.
+    fn step(&mut self, dt: Scalar) {
+        let (forces, physics, params, gravity) = (&self.forces, &self.physics, self.physics.force_params(), &mut self.gravity);
+        self.substeps = self.physics.substeps(&self.particles, dt);
+        for _ in 0..self.substeps {
+            self.integrator.step(&mut self.particles, dt / self.substeps as Scalar, &mut |state, accelerations| {
+                gravity.build(state, physics, true);
+                let field = gravity.field(physics.gravity_solver);
+                (0..state.len()).into_par_iter().map(|index| field_net_acceleration_at(forces, index, state, field, params)).collect_into_vec(accelerations)
+            }, &mut self.scratch);
+            self.physics.boundary.apply(&mut self.particles);
+        }
+
+        if self.physics.collision_mode != CollisionMode::None {
+            self.grid.par_rebuild(&self.particles);
+            let mut pairs = self.grid.par_overlapping_pairs(&self.particles);
+            self.split_grace.retain_pairs(&self.particles, &mut pairs);
+            let merges = resolve_collisions(&mut self.particles, &pairs, self.physics.collision_mode, self.physics.density, self.physics.bounce);
+            record_merges(&mut self.events, merges, self.time + to_f64(dt));
+        }
+        self.split_grace.tick();
+    }
+}
+
+impl World for RayonWorld {
+    fn update(&mut self, dt: Scalar) {
+        while let Some(request) = self.spawns.next() {
+            request.apply(self);
+        }
+        match self.pool.clone() {
+            Some(pool) => pool.install(|| self.step(dt)),
+            None => self.step(dt),
+        }
+        self.time += to_f64(dt);
+        self.observers.notify(&self.particles, dt);
+    }
+
+    fn create_particle(&mut self, position: Vector, velocity: Vector, mass: Scalar, fixed: bool) -> ParticleId {
+        let mut particle = Particle { fixed, ..Particle::new(self.next_id, position, if fixed { Vector::ZERO } else { velocity }, mass, self.physics.density) };
+        // start with the correct acceleration so the first Verlet step is accurate
+        particle.acceleration = net_acceleration(&self.forces, &particle, &self.particles, self.physics.force_params());
+        self.particles.push(particle);
+        self.events.push(Event { time: self.time, kind: EventKind::Spawn { id: self.next_id, mass } });
+        take_id(&mut self.next_id)
+    }
+
+    fn spawn_queue(&self) -> SpawnQueue {
+        self.spawns.queue()
+    }
+
+    fn add_observer(&mut self, observer: Observer) {
+        self.observers.0.push(observer);
+    }
+
+    fn restore_particle(&mut self, particle: Particle) {
+        debug_assert!(self.particles.iter().all(|other| other.id != particle.id), "particle {} is already in the world", particle.id);
+        self.next_id = self.next_id.max(particle.id.next());
+        self.particles.push(particle);
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.particles.reserve(additional);
+    }
+
+    fn set_particles(&mut self, mut particles: Vec<Particle>) {
+        debug_assert!(has_unique_ids(&particles), "particles must have unique ids");
+        let (forces, params, density) = (&self.forces, self.physics.force_params(), self.physics.density);
+        let accelerations: Vec<Vector> = (0..particles.len()).into_par_iter().map(|index| net_acceleration_at(forces, index, &particles, params)).collect();
+        for (particle, acceleration) in particles.iter_mut().zip(accelerations) {
+            particle.radius = Particle::radius_from_mass(particle.mass, density);
+            particle.acceleration = acceleration;
+        }
+        self.next_id = self.next_id.max(next_id(&particles));
+        self.particles = particles;
+    }
+
+    fn remove_particle(&mut self, id: ParticleId) -> bool {
+        match self.particles.iter().position(|particle| particle.id == id) {
+            Some(index) => {
+                self.particles.remove(index);
+                self.events.push(Event { time: self.time, kind: EventKind::Removal { id } });
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn split_particle(&mut self, id: ParticleId, fragments: usize, energy: Scalar) -> Vec<ParticleId> {
+        let ids = split_particle(&mut self.particles, &mut self.next_id, self.physics.density, id, fragments, energy);
+        record_split(&mut self.split_grace, &mut self.events, self.time, id, &ids);
+        ids
+    }
+
+    fn apply_impulse(&mut self, id: ParticleId, dv: Vector) -> bool {
+        apply_impulse(&mut self.particles, id, dv)
+    }
+
+    fn set_particle(&mut self, id: ParticleId, position: Vector, velocity: Vector, mass: Scalar) -> bool {
+        set_particle(&mut self.particles, &self.forces, &self.physics, id, position, velocity, mass)
+    }
+
+    fn clear(&mut self) {
+        self.particles.clear();
+        self.time = 0.;
+    }
+
+    fn time(&self) -> f64 {
+        self.time
+    }
+
+    fn set_time(&mut self, time: f64) {
+        self.time = time;
+    }
+
+    fn drain_events(&mut self) -> Vec<Event> {
+        self.events.drain()
     }
 
     fn get_particles(&mut self) -> Vec<Particle> {
         self.particles.clone()
     }
+
+    fn particles(&self) -> ParticleView<'_> {
+        ParticleView::Slice(&self.particles)
+    }
+
+    fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    fn set_integrator(&mut self, integrator: Box<dyn Integrator>) {
+        self.integrator = integrator;
+    }
+
+    fn set_physics(&mut self, physics: Physics) {
+        self.physics = physics;
+        self.grid = SpatialGrid::new(physics.cell_size);
+    }
+
+    fn set_forces(&mut self, forces: Vec<Box<dyn Force>>) {
+        self.forces = forces;
+    }
+
+    fn force_names(&self) -> Vec<&'static str> {
+        self.forces.iter().map(|force| force.name()).collect()
+    }
+
+    fn num_threads(&self) -> usize {
+        self.pool.as_ref().map_or_else(rayon::current_num_threads, |pool| pool.current_num_threads())
+    }
+
+    fn substeps(&self) -> usize {
+        self.substeps
+    }
 }
 
 /// Stores the entities in the world as a vector of Particles and 
 /// handles updating velocities and positions of the particles.
 /// 
-/// The positions of the particles are calculated using a simple for loop.
+/// The accelerations of the particles are calculated using a simple iterator.
 pub struct SequentialWorld {
     pub particles: Vec<Particle>,
+    pub integrator: Box<dyn Integrator>,
+    pub physics: Physics,
+    /// Forces summed to find the accelerations
+    pub forces: Vec<Box<dyn Force>>,
+    grid: SpatialGrid,
+    /// Approximations of gravity, reused between steps
+    gravity: GravityFields,
+    /// Buffers reused by the integrator between steps
+    scratch: Scratch,
+    next_id: ParticleId,
+    substeps: usize,
+    /// Particles requested through spawn queues, created at the start of the next update
+    spawns: SpawnRequests,
+    observers: Observers,
+    /// Events recorded since they were last drained
+    events: EventLog,
+    /// Fragments of recent splits which don't collide with each other yet
+    split_grace: SplitGrace,
+    /// Seconds simulated by every update
+    time: f64,
+}
+
+impl SequentialWorld {
+    /// Creates a new [`World`] containing the given particles.
+    pub fn new(particles: Vec<Particle>, integrator: Box<dyn Integrator>, physics: Physics) -> Self {
+        let next_id = next_id(&particles);
+        SequentialWorld { particles, integrator, physics, forces: default_forces(), grid: SpatialGrid::new(physics.cell_size), gravity: GravityFields::default(), scratch: Scratch::default(), next_id, substeps: 1, spawns: SpawnRequests::default(), observers: Observers::default(), events: EventLog::default(), split_grace: SplitGrace::default(), time: 0. }
+    }
 }
 
 impl World for SequentialWorld {
-    fn update(&mut self, dt: f64) {
-        let particles_clone = self.particles.clone();
-        for particle in self.particles.iter_mut() {
-            let acceleration = particle.net_acceleration(&particles_clone) * dt;
-            particle.velocity += acceleration * dt;
-            particle.position += particle.velocity * dt;
+    fn update(&mut self, dt: Scalar) {
+        while let Some(request) = self.spawns.next() {
+            request.apply(self);
+        }
+        let (forces, physics, params, gravity) = (&self.forces, &self.physics, self.physics.force_params(), &mut self.gravity);
+        self.substeps = self.physics.substeps(&self.particles, dt);
+        for _ in 0..self.substeps {
+            self.integrator.step(&mut self.particles, dt / self.substeps as Scalar, &mut |state, accelerations| {
+                gravity.build(state, physics, false);
+                let field = gravity.field(physics.gravity_solver);
+                accelerations.clear();
+                accelerations.extend((0..state.len()).map(|index| field_net_acceleration_at(forces, index, state, field, params)));
+            }, &mut self.scratch);
+            self.physics.boundary.apply(&mut self.particles);
         }
+
+        if self.physics.collision_mode != CollisionMode::None {
+            self.grid.rebuild(&self.particles);
+            let mut pairs = self.grid.overlapping_pairs(&self.particles);
+            self.split_grace.retain_pairs(&self.particles, &mut pairs);
+            let merges = resolve_collisions(&mut self.particles, &pairs, self.physics.collision_mode, self.physics.density, self.physics.bounce);
+            record_merges(&mut self.events, merges, self.time + to_f64(dt));
+        }
+        self.split_grace.tick();
+        self.time += to_f64(dt);
+        self.observers.notify(&self.particles, dt);
     }
 
-    fn create_particle(&mut self, position: glam::DVec2, velocity: glam::DVec2, mass: f64) {
-        self.particles.push(Particle { 
-            id: self.particles.len(), 
-            velocity, 
-            position, 
-            mass 
-        });
+    fn create_particle(&mut self, position: Vector, velocity: Vector, mass: Scalar, fixed: bool) -> ParticleId {
+        let mut particle = Particle { fixed, ..Particle::new(self.next_id, position, if fixed { Vector::ZERO } else { velocity }, mass, self.physics.density) };
+        // start with the correct acceleration so the first Verlet step is accurate
+        particle.acceleration = net_acceleration(&self.forces, &particle, &self.particles, self.physics.force_params());
+        self.particles.push(particle);
+        self.events.push(Event { time: self.time, kind: EventKind::Spawn { id: self.next_id, mass } });
+        take_id(&mut self.next_id)
+    }
+
+    fn spawn_queue(&self) -> SpawnQueue {
+        self.spawns.queue()
+    }
+
+    fn add_observer(&mut self, observer: Observer) {
+        self.observers.0.push(observer);
+    }
+
+    fn restore_particle(&mut self, particle: Particle) {
+        debug_assert!(self.particles.iter().all(|other| other.id != particle.id), "particle {} is already in the world", particle.id);
+        self.next_id = self.next_id.max(particle.id.next());
+        self.particles.push(particle);
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.particles.reserve(additional);
+    }
+
+    fn set_particles(&mut self, mut particles: Vec<Particle>) {
+        debug_assert!(has_unique_ids(&particles), "particles must have unique ids");
+        let (forces, params, density) = (&self.forces, self.physics.force_params(), self.physics.density);
+        let accelerations: Vec<Vector> = (0..particles.len()).map(|index| net_acceleration_at(forces, index, &particles, params)).collect();
+        for (particle, acceleration) in particles.iter_mut().zip(accelerations) {
+            particle.radius = Particle::radius_from_mass(particle.mass, density);
+            particle.acceleration = acceleration;
+        }
+        self.next_id = self.next_id.max(next_id(&particles));
+        self.particles = particles;
+    }
+
+    fn remove_particle(&mut self, id: ParticleId) -> bool {
+        match self.particles.iter().position(|particle| particle.id == id) {
+            Some(index) => {
+                self.particles.remove(index);
+                self.events.push(Event { time: self.time, kind: EventKind::Removal { id } });
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn split_particle(&mut self, id: ParticleId, fragments: usize, energy: Scalar) -> Vec<ParticleId> {
+        let ids = split_particle(&mut self.particles, &mut self.next_id, self.physics.density, id, fragments, energy);
+        record_split(&mut self.split_grace, &mut self.events, self.time, id, &ids);
+        ids
+    }
+
+    fn apply_impulse(&mut self, id: ParticleId, dv: Vector) -> bool {
+        apply_impulse(&mut self.particles, id, dv)
+    }
+
+    fn set_particle(&mut self, id: ParticleId, position: Vector, velocity: Vector, mass: Scalar) -> bool {
+        set_particle(&mut self.particles, &self.forces, &self.physics, id, position, velocity, mass)
+    }
+
+    fn clear(&mut self) {
+        self.particles.clear();
+        self.time = 0.;
+    }
+
+    fn time(&self) -> f64 {
+        self.time
+    }
+
+    fn set_time(&mut self, time: f64) {
+        self.time = time;
+    }
+
+    fn drain_events(&mut self) -> Vec<Event> {
+        self.events.drain()
     }
 
     fn get_particles(&mut self) -> Vec<Particle> {
         self.particles.clone()
     }
+
+    fn particles(&self) -> ParticleView<'_> {
+        ParticleView::Slice(&self.particles)
+    }
+
+    fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    fn set_integrator(&mut self, integrator: Box<dyn Integrator>) {
+        self.integrator = integrator;
+    }
+
+    fn set_physics(&mut self, physics: Physics) {
+        self.physics = physics;
+        self.grid = SpatialGrid::new(physics.cell_size);
+    }
+
+    fn set_forces(&mut self, forces: Vec<Box<dyn Force>>) {
+        self.forces = forces;
+    }
+
+    fn force_names(&self) -> Vec<&'static str> {
+        self.forces.iter().map(|force| force.name()).collect()
+    }
+
+    fn num_threads(&self) -> usize {
+        1
+    }
+
+    fn substeps(&self) -> usize {
+        self.substeps
+    }
 }
 
-/// Uses the Rust standard library to calculate position and velocities.
-/// The worker threads are initalized to loop over the process_particles
-/// function along with a reference to the vector of particles, the dt,
-/// and a barrier. The worker threads will update the particles when the
-/// the update function is called.
-/// 
-/// The process_particle function starts by pausing 
-/// execution at the barrier. This execution will be continued once the 
-/// main thread executes the function. This will happen when the update
-/// function is called as the correct amount of threads will be waiting
-/// at the barrier. The particles positions and velocities are then
-/// calculated and updated. Finally, the barrier will stop execution once
-/// more to allow each thread to finish updating the particles.
+/// Uses the Rust standard library to calculate accelerations.
+/// The accelerations are calculated by the threads of a [`WorkerPool`],
+/// which the world borrows rather than spawning its own threads, so worlds
+/// can be rebuilt without starting new threads. The updates of a call to
+/// update or advance run as a single job on the pool. The main thread runs the
+/// integrator, and whenever it requests accelerations every thread, including
+/// the main thread, reads the state directly from the integrator and writes the
+/// accelerations of its own contiguous chunk of particles into the output
+/// buffer without locking. The threads meet at a barrier before and after each
+/// calculation, and the job only returns once every update has been integrated.
+///
+/// The particles are double buffered. Updates are integrated into the back
+/// buffer which is then swapped with the front buffer, so the lock on the
+/// particles is only held by an update for the swap.
 pub struct ThreadsWorld {
-    pub particles: Arc<RwLock<Vec<Particle>>>,
-    pub particle_count: usize,
-    dt: Arc<AtomicF64>,
-    barrier: Arc<Barrier>,
-    threads: Vec<JoinHandle<()>>,
-    num_threads: usize,
+    /// Front buffer read by get_particles and particles. It is only changed through
+    /// the methods of the world, which keep count in step with it.
+    particles: Arc<RwLock<Vec<Particle>>>,
+    pub integrator: Box<dyn Integrator>,
+    /// Back buffer the next update is integrated into
+    back: Vec<Particle>,
+    /// Number of particles in the front buffer, kept up to date by every method
+    /// which changes it so len doesn't wait for the lock.
+    count: AtomicUsize,
+    physics: Physics,
+    /// Forces summed to find the accelerations
+    forces: Vec<Box<dyn Force>>,
+    pool: WorkerPool,
+    grid: SpatialGrid,
+    /// Approximations of gravity, reused between steps
+    gravity: GravityFields,
+    /// Buffers reused by the integrator between steps
+    scratch: Scratch,
+    next_id: ParticleId,
+    substeps: usize,
+    /// Particles requested through spawn queues, created at the start of the next update
+    spawns: SpawnRequests,
+    observers: Observers,
+    /// Events recorded since they were last drained
+    events: EventLog,
+    /// Fragments of recent splits which don't collide with each other yet
+    split_grace: SplitGrace,
+    /// Seconds simulated by every update
+    time: f64,
 }
 
 impl World for ThreadsWorld {
-    fn update(&mut self, dt: f64) {
-        // update the delta time for threads to use
-        self.dt.store(dt, Ordering::Release);
-        
-        // main thread starts processing which starts worker threads also as barrier will be unlocked.
-        process_particles(
-            &self.barrier,
-            &self.particles,
-            &self.dt,
-            0,
-            self.num_threads,
-        );
-    }
-
-    fn create_particle(&mut self, position: DVec2, velocity: DVec2, mass: f64) {
-        self.particles.write().push(Particle {
-            id: self.particle_count,
-            velocity,
-            position,
-            mass,
-        });
-        self.particle_count += 1;
+    fn update(&mut self, dt: Scalar) {
+        self.advance(1, dt);
+    }
+
+    /// Integrates every update in the back buffer in a single job on the pool.
+    /// The back buffer is only copied from and swapped into the front buffer once
+    /// for all of the updates rather than once for each, so readers of the
+    /// particles see the last update. Observers are called with the back buffer.
+    /// Particles requested through spawn queues between updates are still
+    /// created before the next update, ending the job and copying the buffers
+    /// around the requests.
+    fn advance(&mut self, steps: usize, dt: Scalar) {
+        // copy the front buffer into the back buffer, reusing the allocation of the back buffer
+        let mut particles = std::mem::take(&mut self.back);
+        particles.clone_from(&self.particles.read());
+        let mut done = 0;
+        while done < steps {
+            let (batch, request) = self.batch(&mut particles, steps - done, dt);
+            done += batch;
+            if let Some(request) = request {
+                // requests change the front buffer, so it is brought up to date first
+                *self.count.get_mut() = particles.len();
+                std::mem::swap(&mut *self.particles.write(), &mut particles);
+                request.apply(self);
+                while let Some(request) = self.spawns.next() {
+                    request.apply(self);
+                }
+                particles.clone_from(&self.particles.read());
+            }
+        }
+
+        // swap the buffers so the updated particles are in the front buffer
+        *self.count.get_mut() = particles.len();
+        std::mem::swap(&mut *self.particles.write(), &mut particles);
+        self.back = particles;
+    }
+
+    fn create_particle(&mut self, position: Vector, velocity: Vector, mass: Scalar, fixed: bool) -> ParticleId {
+        let physics = self.physics;
+        let mut particle = Particle { fixed, ..Particle::new(self.next_id, position, if fixed { Vector::ZERO } else { velocity }, mass, physics.density) };
+        // start with the correct acceleration so the first Verlet step is accurate
+        let mut particles = self.particles.write();
+        particle.acceleration = net_acceleration(&self.forces, &particle, &particles, physics.force_params());
+        particles.push(particle);
+        *self.count.get_mut() = particles.len();
+        self.events.push(Event { time: self.time, kind: EventKind::Spawn { id: self.next_id, mass } });
+        take_id(&mut self.next_id)
+    }
+
+    fn spawn_queue(&self) -> SpawnQueue {
+        self.spawns.queue()
+    }
+
+    fn add_observer(&mut self, observer: Observer) {
+        self.observers.0.push(observer);
+    }
+
+    fn restore_particle(&mut self, particle: Particle) {
+        let mut particles = self.particles.write();
+        debug_assert!(particles.iter().all(|other| other.id != particle.id), "particle {} is already in the world", particle.id);
+        self.next_id = self.next_id.max(particle.id.next());
+        particles.push(particle);
+        *self.count.get_mut() = particles.len();
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.particles.write().reserve(additional);
+    }
+
+    fn set_particles(&mut self, mut particles: Vec<Particle>) {
+        debug_assert!(has_unique_ids(&particles), "particles must have unique ids");
+        let density = self.physics.density;
+        let mut accelerations = Vec::new();
+        self.calculate_accelerations(&particles, &mut accelerations, &self.gravity, GravitySolver::Direct);
+        for (particle, acceleration) in particles.iter_mut().zip(accelerations) {
+            particle.radius = Particle::radius_from_mass(particle.mass, density);
+            particle.acceleration = acceleration;
+        }
+        self.next_id = self.next_id.max(next_id(&particles));
+        *self.count.get_mut() = particles.len();
+        *self.particles.write() = particles;
+    }
+
+    fn remove_particle(&mut self, id: ParticleId) -> bool {
+        // the worker threads only process the state shared during update, so the
+        // particles can be changed without affecting how they are partitioned
+        let mut particles = self.particles.write();
+        match particles.iter().position(|particle| particle.id == id) {
+            Some(index) => {
+                particles.remove(index);
+                *self.count.get_mut() = particles.len();
+                self.events.push(Event { time: self.time, kind: EventKind::Removal { id } });
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn split_particle(&mut self, id: ParticleId, fragments: usize, energy: Scalar) -> Vec<ParticleId> {
+        let mut particles = self.particles.write();
+        let ids = split_particle(&mut particles, &mut self.next_id, self.physics.density, id, fragments, energy);
+        *self.count.get_mut() = particles.len();
+        record_split(&mut self.split_grace, &mut self.events, self.time, id, &ids);
+        ids
+    }
+
+    fn apply_impulse(&mut self, id: ParticleId, dv: Vector) -> bool {
+        apply_impulse(&mut self.particles.write(), id, dv)
+    }
+
+    fn set_particle(&mut self, id: ParticleId, position: Vector, velocity: Vector, mass: Scalar) -> bool {
+        set_particle(&mut self.particles.write(), &self.forces, &self.physics, id, position, velocity, mass)
+    }
+
+    fn clear(&mut self) {
+        self.particles.write().clear();
+        *self.count.get_mut() = 0;
+        self.time = 0.;
+    }
+
+    fn time(&self) -> f64 {
+        self.time
+    }
+
+    fn set_time(&mut self, time: f64) {
+        self.time = time;
+    }
+
+    fn drain_events(&mut self) -> Vec<Event> {
+        self.events.drain()
     }
 
     fn get_particles(&mut self) -> Vec<Particle> {
         self.particles.read().clone()
     }
+
+    fn particles(&self) -> ParticleView<'_> {
+        ParticleView::Guard(self.particles.read())
+    }
+
+    fn len(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    fn set_integrator(&mut self, integrator: Box<dyn Integrator>) {
+        self.integrator = integrator;
+    }
+
+    fn set_physics(&mut self, physics: Physics) {
+        self.physics = physics;
+        self.grid = SpatialGrid::new(physics.cell_size);
+    }
+
+    fn set_forces(&mut self, forces: Vec<Box<dyn Force>>) {
+        self.forces = forces;
+    }
+
+    fn force_names(&self) -> Vec<&'static str> {
+        self.forces.iter().map(|force| force.name()).collect()
+    }
+
+    fn num_threads(&self) -> usize {
+        self.pool.num_threads()
+    }
+
+    fn substeps(&self) -> usize {
+        self.substeps
+    }
 }
 
 impl ThreadsWorld {
-    /// Creates a new [`World`] with a given amount of worker threads.
-    pub fn new(num_threads: usize, particles: Vec<Particle>) -> Self {
-        let mut world = ThreadsWorld {
+    /// Creates a new [`World`] with a pool of the given amount of threads. The
+    /// main thread counts as one of the threads. If num_threads is 0 the
+    /// available parallelism of the machine is used instead.
+    pub fn new(num_threads: usize, particles: Vec<Particle>, integrator: Box<dyn Integrator>, physics: Physics) -> Self {
+        ThreadsWorld::with_pool(WorkerPool::new(num_threads), particles, integrator, physics)
+    }
+
+    /// Creates a new [`World`] which calculates accelerations using the threads
+    /// of the pool. The pool can be shared with other worlds and resized while
+    /// the world is using it.
+    pub fn with_pool(pool: WorkerPool, particles: Vec<Particle>, integrator: Box<dyn Integrator>, physics: Physics) -> Self {
+        let next_id = next_id(&particles);
+        ThreadsWorld {
+            count: AtomicUsize::new(particles.len()),
             particles: Arc::new(RwLock::new(particles)),
-            threads: Vec::new(),
-            dt: Arc::new(AtomicF64::new(0.)),
-            particle_count: 0,
-            barrier: Arc::new(Barrier::new(num_threads)),
-            num_threads,
-        };
-        world.init_worker_threads(num_threads);
-        world
-    }
-
-    /// Generates worker threads to calculate positions and velocities of particles
-    fn init_worker_threads(&mut self, num_threads: usize) {
-        for thread_id in 1..num_threads {
-            // clone pointers required for threads
-            let barrier = Arc::clone(&self.barrier);
-            let dt = Arc::clone(&self.dt);
-            let particles = Arc::clone(&self.particles);
-            // create worker threads which will just loop processing particles
-            self.threads.push(thread::spawn(move || loop {
-                process_particles(&barrier, &particles, &dt, thread_id, num_threads);
-            }))
-        }
-    }
-}
-
-fn process_particles(
-    barrier: &Arc<Barrier>,
-    particles: &Arc<RwLock<Vec<Particle>>>,
-    dt: &Arc<AtomicF64>,
-    thread_id: usize,
-    num_threads: usize,
-) {
-    // wait until all threads ready to process particles, this will be locked until the main thread calls this function which will happen when the update method is called
-    let _ = barrier.wait();
-
-    let dt_copy = dt.load(Ordering::Acquire); // get the dt to calculate new velocities and positions
-
-    // calculate accelerations of particles
-    let particles_read = particles.read().clone();
-    let velocities: Vec<DVec2> = particles_read
-        .iter()
-        .skip(thread_id)
-        .step_by(num_threads)
-        .map(|particle| particle.net_acceleration(&particles_read) * dt_copy)
-        .collect();
-
-    // update particle velocities and position with accelerations calculated
-    particles.write()
-        .iter_mut()
-        .skip(thread_id)
-        .step_by(num_threads)
-        .zip(velocities)
-        .for_each(|(particle, velocity)| {
-            particle.velocity += velocity;
-            particle.position += particle.velocity * dt_copy;
+            integrator,
+            back: Vec::new(),
+            physics,
+            forces: default_forces(),
+            pool,
+            grid: SpatialGrid::new(physics.cell_size),
+            gravity: GravityFields::default(),
+            scratch: Scratch::default(),
+            next_id,
+            substeps: 1,
+            spawns: SpawnRequests::default(),
+            observers: Observers::default(),
+            events: EventLog::default(),
+            split_grace: SplitGrace::default(),
+            time: 0.,
+        }
+    }
+
+    /// Integrates up to the given number of updates in a single job on the pool,
+    /// returning how many were integrated. A request arriving through a spawn
+    /// queue needs the world to itself, so the job stops before the next update
+    /// and returns the request for the caller to apply.
+    fn batch(&mut self, particles: &mut Vec<Particle>, steps: usize, dt: Scalar) -> (usize, Option<SpawnRequest>) {
+        let pool = self.pool.clone();
+        let handoff = Handoff::default();
+        let main = Mutex::new((self, particles, 0, None));
+        pool.run(&|thread_id, num_threads| {
+            if thread_id != 0 {
+                return handoff.work(thread_id, num_threads);
+            }
+            let mut main = main.lock();
+            let (world, particles, done, request) = &mut *main;
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                while *done < steps {
+                    if let Some(next) = world.spawns.next() {
+                        *request = Some(next);
+                        break;
+                    }
+                    world.step(particles, dt, &|evaluation| handoff.share(evaluation, num_threads));
+                    world.time += to_f64(dt);
+                    world.observers.notify(particles, dt);
+                    *done += 1;
+                }
+            }));
+            // the other threads wait for another calculation until they are released, even if the update panicked
+            handoff.finish(num_threads);
+            if let Err(payload) = result {
+                panic::resume_unwind(payload);
+            }
         });
+        let (_, _, done, request) = main.into_inner();
+        (done, request)
+    }
+
+    /// Moves the particles forward by dt, passing each calculation of the
+    /// accelerations the integrator needs to share.
+    fn step(&mut self, particles: &mut Vec<Particle>, dt: Scalar, share: &dyn Fn(&Evaluation)) {
+        let physics = self.physics;
+        self.substeps = physics.substeps(particles, dt);
+        let (mut scratch, mut gravity) = (std::mem::take(&mut self.scratch), std::mem::take(&mut self.gravity));
+        let (forces, params) = (&self.forces, physics.force_params());
+        for _ in 0..self.substeps {
+            self.integrator.step(particles, dt / self.substeps as Scalar, &mut |state, accelerations| {
+                // the approximation of gravity is built by the main thread while the other threads wait at the barrier
+                gravity.build(state, &physics, false);
+                accelerations.clear();
+                accelerations.resize(state.len(), Vector::ZERO);
+                let output = SharedBuffer(accelerations.as_mut_ptr());
+                share(&Evaluation { state, forces, field: gravity.field(physics.gravity_solver), params, output });
+            }, &mut scratch);
+            physics.boundary.apply(particles);
+        }
+        (self.scratch, self.gravity) = (scratch, gravity);
+
+        if physics.collision_mode != CollisionMode::None {
+            self.grid.rebuild(particles);
+            let mut pairs = self.grid.overlapping_pairs(particles);
+            self.split_grace.retain_pairs(particles, &mut pairs);
+            let merges = resolve_collisions(particles, &pairs, physics.collision_mode, physics.density, physics.bounce);
+            record_merges(&mut self.events, merges, self.time + to_f64(dt));
+        }
+        self.split_grace.tick();
+    }
+
+    /// Calculates the accelerations of a state using the threads of the pool,
+    /// and the approximation of gravity built from the state for the solver.
+    fn calculate_accelerations(&self, state: &[Particle], accelerations: &mut Vec<Vector>, gravity: &GravityFields, solver: GravitySolver) {
+        accelerations.clear();
+        accelerations.resize(state.len(), Vector::ZERO);
+        let output = SharedBuffer(accelerations.as_mut_ptr());
+        let evaluation = Evaluation { state, forces: &self.forces, field: gravity.field(solver), params: self.physics.force_params(), output };
+        self.pool.run(&|thread_id, num_threads| evaluation.run(thread_id, num_threads));
+    }
+}
+
+/// Calculation of the accelerations of a state, shared by the threads of a pool.
+#[derive(Clone, Copy)]
+struct Evaluation<'a> {
+    state: &'a [Particle],
+    forces: &'a [Box<dyn Force>],
+    field: Option<&'a dyn GravityField>,
+    params: ForceParams,
+    /// Buffer of an acceleration for each particle of the state
+    output: SharedBuffer,
+}
+
+impl Evaluation<'_> {
+    /// Calculates and stores the accelerations of the contiguous chunk of particles owned by the thread.
+    fn run(&self, thread_id: usize, num_threads: usize) {
+        let range = chunk_range(self.state.len(), thread_id, num_threads);
+        // SAFETY: the buffer outlives the evaluation and the ranges of different threads don't overlap
+        let chunk = unsafe { self.output.slice(range.clone()) };
+        for (index, acceleration) in range.zip(chunk) {
+            *acceleration = field_net_acceleration_at(self.forces, index, self.state, self.field, self.params);
+        }
+    }
+}
+
+/// Calculations of the accelerations handed from the main thread of a job,
+/// which runs the integrator, to the other threads of the job.
+#[derive(Default)]
+struct Handoff {
+    /// Barrier of every thread of the job, created by the first thread to reach it
+    barrier: OnceLock<Barrier>,
+    /// Calculation run by every thread after the next barrier, or None once the job is over
+    evaluation: Mutex<Option<Evaluation<'static>>>,
+}
+
+impl Handoff {
+    fn barrier(&self, num_threads: usize) -> &Barrier {
+        self.barrier.get_or_init(|| Barrier::new(num_threads))
+    }
+
+    /// Runs the calculation on every thread of the job from the main thread,
+    /// returning once every thread has stored its accelerations.
+    fn share(&self, evaluation: &Evaluation, num_threads: usize) {
+        // SAFETY: only the lifetime is erased, and the other threads only use the evaluation
+        // between the two barriers, which this thread waits at before it returns
+        *self.evaluation.lock() = Some(unsafe { std::mem::transmute::<Evaluation<'_>, Evaluation<'static>>(*evaluation) });
+        self.barrier(num_threads).wait();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| evaluation.run(0, num_threads)));
+        self.barrier(num_threads).wait();
+        *self.evaluation.lock() = None;
+        if let Err(payload) = result {
+            panic::resume_unwind(payload);
+        }
+    }
+
+    /// Releases the other threads of the job from the main thread.
+    fn finish(&self, num_threads: usize) {
+        *self.evaluation.lock() = None;
+        self.barrier(num_threads).wait();
+    }
+
+    /// Runs each calculation shared by the main thread on one of the other
+    /// threads of the job, until the main thread releases them.
+    fn work(&self, thread_id: usize, num_threads: usize) {
+        let mut panicked = None;
+        loop {
+            self.barrier(num_threads).wait();
+            let Some(evaluation) = *self.evaluation.lock() else {
+                break;
+            };
+            // a thread which panicked still meets the others, so they don't wait for it forever
+            if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| evaluation.run(thread_id, num_threads))) {
+                panicked.get_or_insert(payload);
+            }
+            self.barrier(num_threads).wait();
+        }
+        if let Some(payload) = panicked {
+            panic::resume_unwind(payload);
+        }
+    }
+}
+
+/// Approximations of gravity kept by a world between steps, so their buffers are reused.
+#[derive(Default)]
+struct GravityFields {
+    tree: BarnesHutTree,
+    mesh: ParticleMesh,
+    pairs: PairwiseGravity,
+}
+
+impl GravityFields {
+    /// Builds the approximation of gravity used by the physics from the state,
+    /// using parallel iterators from the rayon library if parallel is true.
+    fn build(&mut self, state: &[Particle], physics: &Physics, parallel: bool) {
+        let (theta, cells) = (physics.barnes_hut_theta, physics.mesh_cells);
+        match physics.gravity_solver {
+            GravitySolver::Direct => (),
+            GravitySolver::BarnesHut if parallel => self.tree.par_build(state, theta),
+            GravitySolver::BarnesHut => self.tree.build(state, theta),
+            GravitySolver::ParticleMesh if parallel => self.mesh.par_build(state, physics.force_params(), cells),
+            GravitySolver::ParticleMesh => self.mesh.build(state, physics.force_params(), cells),
+            GravitySolver::Pairwise if parallel => self.pairs.par_build(state, physics.force_params()),
+            GravitySolver::Pairwise => self.pairs.build(state, physics.force_params()),
+        }
+    }
+
+    /// The approximation of gravity built for the solver, or None if gravity is summed directly.
+    fn field(&self, solver: GravitySolver) -> Option<&dyn GravityField> {
+        match solver {
+            GravitySolver::Direct => None,
+            GravitySolver::BarnesHut => Some(&self.tree),
+            GravitySolver::ParticleMesh => Some(&self.mesh),
+            GravitySolver::Pairwise => Some(&self.pairs),
+        }
+    }
+}
+
+/// Adds the merges made by an update to the events, at the time the update ends.
+fn record_merges(events: &mut EventLog, merges: Vec<Merge>, time: f64) {
+    events.extend(merges.into_iter().map(|merge| Event { time, kind: EventKind::Merge(merge) }));
+}
+
+/// Replaces the particle with the id by its fragments, returning their ids.
+fn split_particle(particles: &mut Vec<Particle>, next_id: &mut ParticleId, density: Scalar, id: ParticleId, fragments: usize, energy: Scalar) -> Vec<ParticleId> {
+    let Some(index) = particles.iter().position(|particle| particle.id == id) else {
+        return Vec::new();
+    };
+    let pieces = split(&particles[index], fragments, energy, density, *next_id);
+    if pieces.is_empty() {
+        return Vec::new();
+    }
+    particles.remove(index);
+    let ids = pieces.iter().map(|_| take_id(next_id)).collect();
+    particles.extend(pieces);
+    ids
+}
+
+/// Starts the grace period of the fragments of a split and records the split.
+fn record_split(grace: &mut SplitGrace, events: &mut EventLog, time: f64, id: ParticleId, fragments: &[ParticleId]) {
+    if !fragments.is_empty() {
+        grace.add(fragments);
+        events.push(Event { time, kind: EventKind::Split { id, fragments: fragments.len() } });
+    }
+}
+
+/// Takes an id from the counter of a world, which only increases so ids are never reused.
+fn take_id(next_id: &mut ParticleId) -> ParticleId {
+    let id = *next_id;
+    *next_id = id.next();
+    id
+}
+
+/// Whether no two of the particles have the same id.
+fn has_unique_ids(particles: &[Particle]) -> bool {
+    let mut ids = HashSet::with_capacity(particles.len());
+    particles.iter().all(|particle| ids.insert(particle.id))
+}
+
+/// Adds dv to the velocity of the particle with the given id unless it is fixed,
+/// returning whether it was found.
+fn apply_impulse(particles: &mut [Particle], id: ParticleId, dv: Vector) -> bool {
+    match particles.iter_mut().find(|particle| particle.id == id) {
+        Some(particle) => {
+            if !particle.fixed {
+                particle.velocity += dv;
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+/// Replaces the position, velocity and mass of the particle with the given id,
+/// recalculating its radius and acceleration, and returns whether it was found.
+fn set_particle(particles: &mut [Particle], forces: &[Box<dyn Force>], physics: &Physics, id: ParticleId, position: Vector, velocity: Vector, mass: Scalar) -> bool {
+    let Some(index) = particles.iter().position(|particle| particle.id == id) else {
+        return false;
+    };
+    let particle = &mut particles[index];
+    particle.position = position;
+    particle.velocity = if particle.fixed { Vector::ZERO } else { velocity };
+    particle.mass = mass;
+    particle.radius = Particle::radius_from_mass(mass, physics.density);
+    // the acceleration at the new position keeps the next Verlet step accurate
+    particles[index].acceleration = net_acceleration_at(forces, index, particles, physics.force_params());
+    true
+}
+
+/// Id following the largest id of the particles, so new particles don't reuse an id.
+fn next_id(particles: &[Particle]) -> ParticleId {
+    particles.iter().map(|particle| particle.id.next()).max().unwrap_or_default()
+}
+
+/// Output buffer the threads of a pool write their own parts of without locking.
+#[derive(Clone, Copy)]
+struct SharedBuffer(*mut Vector);
+
+// the parts written by different threads never overlap
+unsafe impl Send for SharedBuffer {}
+unsafe impl Sync for SharedBuffer {}
+
+impl SharedBuffer {
+    /// # Safety
+    /// The range must be inside the buffer, which must outlive the returned
+    /// slice, and no other thread may use an overlapping range at the same time.
+    unsafe fn slice<'a>(self, range: Range<usize>) -> &'a mut [Vector] {
+        if range.is_empty() {
+            return &mut [];
+        }
+        // SAFETY: guaranteed by the caller
+        unsafe { std::slice::from_raw_parts_mut(self.0.add(range.start), range.len()) }
+    }
+}
+
+/// Range of the particles owned by a thread when len particles are split into
+/// num_threads contiguous chunks whose sizes differ by at most one.
+fn chunk_range(len: usize, thread_id: usize, num_threads: usize) -> Range<usize> {
+    thread_id * len / num_threads..(thread_id + 1) * len / num_threads
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collision::SPLIT_GRACE_UPDATES;
+    use crate::diagnostics::{momentum, total_mass};
+    use crate::integrator::{Rk4Integrator, VerletIntegrator};
+    use crate::presets;
+    use crate::vector;
+
+    /// One world of each kind holding the same particles.
+    fn worlds(particles: Vec<Particle>, physics: Physics) -> Vec<(&'static str, Box<dyn World>)> {
+        vec![
+            ("sequential", Box::new(SequentialWorld::new(particles.clone(), Box::new(VerletIntegrator), physics))),
+            ("rayon", Box::new(RayonWorld::new(particles.clone(), Box::new(VerletIntegrator), physics))),
+            ("threads", Box::new(ThreadsWorld::new(3, particles, Box::new(VerletIntegrator), physics))),
+        ]
+    }
+
+    #[test]
+    fn circular_orbit_radius_stays_bounded_over_many_orbits() {
+        const ORBITS: usize = 20;
+        const STEPS_PER_ORBIT: usize = 500;
+        let dt = presets::two_body_period() / STEPS_PER_ORBIT as Scalar;
+        for (name, mut world) in worlds(presets::two_body(), Physics::default()) {
+            let distance = |world: &dyn World| {
+                let particles = world.particles();
+                to_f64(particles[0].position.distance(particles[1].position))
+            };
+            let initial = distance(world.as_ref());
+            let mut drift: f64 = 0.;
+            for _ in 0..ORBITS {
+                world.advance(STEPS_PER_ORBIT, dt);
+                drift = drift.max((distance(world.as_ref()) - initial).abs() / initial);
+            }
+            // velocity Verlet keeps the error of a circular orbit near (2 pi / steps)^2 instead of letting it grow
+            assert!(drift <= 1e-4, "{} world: orbit radius drifted by {:e} over {} orbits", name, drift, ORBITS);
+        }
+    }
+
+    #[test]
+    fn doubling_gravity_shortens_the_orbital_period_by_root_two() {
+        const STEPS: usize = 500;
+        let mut particles = presets::two_body();
+        for particle in &mut particles {
+            particle.velocity *= (2. as Scalar).sqrt();
+        }
+        let start = particles[1].position - particles[0].position;
+        let dt = presets::two_body_period() / (2. as Scalar).sqrt() / STEPS as Scalar;
+        let doubled = [
+            Physics { gravity_multiplier: 2., ..Physics::default() },
+            Physics { gravitational_constant: Physics::default().gravitational_constant * 2., ..Physics::default() },
+        ];
+        for physics in doubled.iter().copied().chain(std::iter::once(Physics::default())) {
+            for (name, mut world) in worlds(particles.clone(), physics) {
+                world.advance(STEPS, dt);
+                let particles = world.particles();
+                let error = (particles[1].position - particles[0].position).distance(start) / start.length();
+                // the faster orbit is only circular, and so only back at its start after the shorter period, with gravity doubled
+                let returned = physics.force_params().g != Physics::default().force_params().g;
+                assert_eq!(error <= 1e-2, returned, "{} world with g {}: planet ended {:e} of its distance from its start", name, physics.force_params().g, error);
+            }
+        }
+    }
+
+    #[test]
+    fn negative_gravity_multiplier_pushes_particles_apart() {
+        let particles = vec![
+            Particle::new(ParticleId::new(0), Vector::ZERO, Vector::ZERO, 1e12, 1.),
+            Particle::new(ParticleId::new(1), vector::planar(100., 0.), Vector::ZERO, 1e12, 1.),
+        ];
+        for (name, mut world) in worlds(particles, Physics { gravity_multiplier: -1., ..Physics::default() }) {
+            world.advance(100, 1.);
+            let particles = world.particles();
+            assert!(particles[0].position.x < 0. && particles[1].position.x > 100., "{} world: {:?}", name, particles.iter().map(|particle| particle.position).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn inserting_massless_particles_between_updates_leaves_the_others_unchanged() {
+        const COUNT: usize = 300;
+        let particles = presets::random(COUNT, 7);
+        let references = worlds(particles.clone(), Physics::default());
+        for ((name, mut world), (_, mut reference)) in worlds(particles, Physics::default()).into_iter().zip(references) {
+            for step in 0..50 {
+                let position = vector::planar(step as Scalar * 7. - 150., 40.);
+                let inserted = world.create_particle(position, vector::planar(0., 1.), 0., false);
+                world.spawn_queue().push(-position, Vector::ZERO, 0., false);
+                if step % 3 == 0 {
+                    world.remove_particle(inserted);
+                } else if step % 3 == 1 {
+                    world.spawn_queue().push_removal(inserted);
+                }
+                world.update(1.);
+                reference.update(1.);
+            }
+
+            let reference = reference.get_particles();
+            let particles = world.get_particles();
+            let kept: Vec<&Particle> = particles.iter().filter(|particle| particle.id < ParticleId::new(COUNT)).collect();
+            assert!(particles.len() > COUNT, "{} world kept none of the inserted particles", name);
+            assert_eq!(kept.len(), COUNT, "{} world", name);
+            for (particle, expected) in kept.into_iter().zip(reference.iter()) {
+                assert_eq!(particle.id, expected.id, "{} world", name);
+                assert_eq!((particle.position, particle.velocity), (expected.position, expected.velocity), "{} world, particle {}", name, particle.id);
+            }
+        }
+    }
+
+    #[test]
+    fn particles_spawned_from_another_thread_during_updates_all_arrive() {
+        const SPAWNS: usize = 5000;
+        for (name, mut world) in worlds(presets::random(20, 2), Physics::default()) {
+            // without forces the updates are quick enough for the spawns to arrive between many of them
+            world.set_forces(Vec::new());
+            let queue = world.spawn_queue();
+            let spawner = std::thread::spawn(move || {
+                for i in 0..SPAWNS {
+                    assert!(queue.push(vector::planar(i as Scalar, -500.), Vector::ZERO, 1., false));
+                }
+            });
+            let mut updates = 0;
+            while !spawner.is_finished() {
+                world.update(1.);
+                updates += 1;
+            }
+            spawner.join().unwrap();
+            world.update(1.);
+            let particles = world.get_particles();
+            assert_eq!((world.len(), particles.len()), (20 + SPAWNS, 20 + SPAWNS), "{} world after {} updates", name, updates);
+            assert_eq!(particles.iter().map(|particle| particle.id).collect::<HashSet<_>>().len(), particles.len(), "{} world", name);
+        }
+    }
 
-    // wait until each thread is finished updating particle positions
-    let _ = barrier.wait();
+    #[test]
+    fn threads_world_runs_on_one_thread_and_falls_back_to_every_core() {
+        let particles = presets::random(50, 1);
+        let mut sequential = SequentialWorld::new(particles.clone(), Box::new(VerletIntegrator), Physics::default());
+        let mut single = ThreadsWorld::new(1, particles.clone(), Box::new(VerletIntegrator), Physics::default());
+        assert_eq!(single.num_threads(), 1);
+        for _ in 0..10 {
+            sequential.update(1.);
+            single.update(1.);
+        }
+        let (expected, particles) = (sequential.get_particles(), single.get_particles());
+        assert!(particles.iter().zip(expected.iter()).all(|(particle, expected)| particle.position == expected.position));
+
+        let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+        assert_eq!(ThreadsWorld::new(0, Vec::new(), Box::new(VerletIntegrator), Physics::default()).num_threads(), cores);
+    }
+
+    #[test]
+    fn threads_world_matches_the_sequential_world_on_any_number_of_threads() {
+        let particles = presets::random(100, 5);
+        let integrators: [fn() -> Box<dyn Integrator>; 2] = [|| Box::new(Rk4Integrator), || Box::new(VerletIntegrator)];
+        for integrator in integrators {
+            let mut sequential = SequentialWorld::new(particles.clone(), integrator(), Physics::default());
+            let mut threads: Vec<ThreadsWorld> = [1, 3, 7].into_iter().map(|num_threads| ThreadsWorld::new(num_threads, particles.clone(), integrator(), Physics::default())).collect();
+            for _ in 0..5 {
+                sequential.update(1.);
+                let expected = sequential.get_particles();
+                for world in &mut threads {
+                    world.update(1.);
+                    let particles = world.get_particles();
+                    assert!(particles.iter().zip(expected.iter()).all(|(particle, expected)| (particle.position, particle.velocity, particle.acceleration) == (expected.position, expected.velocity, expected.acceleration)), "{} threads", world.num_threads());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn threads_world_advances_in_one_pool_job_until_a_spawn_arrives() {
+        let particles = presets::random(50, 4);
+        let pool = WorkerPool::new(3);
+        let mut sequential = SequentialWorld::new(particles.clone(), Box::new(Rk4Integrator), Physics::default());
+        let mut threads = ThreadsWorld::with_pool(pool.clone(), particles, Box::new(Rk4Integrator), Physics::default());
+        for world in [&mut sequential as &mut dyn World, &mut threads] {
+            // the observer asks for a particle after the third update
+            let (queue, mut updates) = (world.spawn_queue(), 0);
+            world.add_observer(Box::new(move |_, _| {
+                updates += 1;
+                if updates == 3 {
+                    assert!(queue.push(vector::planar(0., -500.), Vector::ZERO, 1., false));
+                }
+            }));
+        }
+
+        let jobs = pool.stats().jobs;
+        sequential.advance(2, 1.);
+        threads.advance(2, 1.);
+        assert_eq!(pool.stats().jobs, jobs + 1);
+        sequential.advance(8, 1.);
+        threads.advance(8, 1.);
+        assert_eq!(pool.stats().jobs, jobs + 3);
+        assert_eq!((threads.len(), threads.time()), (51, sequential.time()));
+        let (expected, particles) = (sequential.get_particles(), threads.get_particles());
+        assert!(particles.iter().zip(expected.iter()).all(|(particle, expected)| (particle.position, particle.velocity) == (expected.position, expected.velocity)));
+    }
+
+    #[test]
+    fn rayon_world_runs_on_a_pool_of_its_thread_count() {
+        let particles = presets::random(50, 3);
+        let mut sequential = SequentialWorld::new(particles.clone(), Box::new(VerletIntegrator), Physics::default());
+        let mut single = RayonWorld::with_threads(particles.clone(), Box::new(VerletIntegrator), Physics::default(), 1);
+        assert_eq!(single.num_threads(), 1);
+        for _ in 0..10 {
+            sequential.update(1.);
+            single.update(1.);
+        }
+        let (expected, particles) = (sequential.get_particles(), single.get_particles());
+        assert!(particles.iter().zip(expected.iter()).all(|(particle, expected)| (particle.position, particle.velocity) == (expected.position, expected.velocity)));
+
+        assert_eq!(RayonWorld::with_threads(Vec::new(), Box::new(VerletIntegrator), Physics::default(), 3).num_threads(), 3);
+        assert_eq!(RayonWorld::with_threads(Vec::new(), Box::new(VerletIntegrator), Physics::default(), 0).num_threads(), rayon::current_num_threads());
+    }
+
+    #[test]
+    fn new_ids_continue_after_the_largest_initial_id() {
+        let particles: Vec<Particle> = [0, 5, 2]
+            .into_iter()
+            .map(|id| Particle::new(ParticleId::new(id), vector::planar(id as Scalar * 100., 0.), Vector::ZERO, 1e6, 1.))
+            .collect();
+        for (name, mut world) in worlds(particles, Physics::default()) {
+            assert_eq!(world.len(), 3, "{} world", name);
+            assert_eq!(world.create_particle(vector::planar(0., 100.), Vector::ZERO, 1e6, false), ParticleId::new(6), "{} world", name);
+        }
+    }
+
+    #[test]
+    fn ids_stay_unique_after_setting_the_particles() {
+        for (name, mut world) in worlds(Vec::new(), Physics::default()) {
+            world.reserve(100);
+            assert!(world.is_empty(), "{} world", name);
+            let ids: Vec<ParticleId> = (0..10).map(|i| world.create_particle(vector::planar(i as Scalar * 100., 0.), Vector::ZERO, 1e6, false)).collect();
+
+            // replacing the particles with fewer of them doesn't give out their ids again
+            let kept = world.get_particles()[..3].to_vec();
+            world.set_particles(kept);
+            assert_eq!(world.create_particle(vector::planar(0., 100.), Vector::ZERO, 1e6, false), ids[9].next(), "{} world", name);
+
+            // nor the ids of particles set with larger ids
+            let far = [(100, -100.), (40, -200.)].map(|(id, y)| Particle::new(ParticleId::new(id), vector::planar(0., y), Vector::ZERO, 1e6, 1.));
+            world.set_particles(far.to_vec());
+            let new: Vec<ParticleId> = (0..3).map(|i| world.create_particle(vector::planar(i as Scalar * 100., 100.), Vector::ZERO, 1e6, false)).collect();
+            assert_eq!(new, [ParticleId::new(101), ParticleId::new(102), ParticleId::new(103)], "{} world", name);
+            let particles = world.get_particles();
+            assert_eq!(particles.iter().map(|particle| particle.id).collect::<HashSet<_>>().len(), 5, "{} world", name);
+            // the radius and acceleration of the particles set are recalculated
+            assert!(particles[0].radius > 0. && particles[0].acceleration != Vector::ZERO, "{} world", name);
+        }
+    }
+
+    #[test]
+    fn impulses_between_updates_change_only_their_particle() {
+        const PUSHES: usize = 1000;
+        let (velocity, dv) = (vector::planar(1., 0.), vector::planar(0., 0.5));
+        let particles: Vec<Particle> = (0..4).map(|id| Particle::new(ParticleId::new(id), vector::planar(id as Scalar * 10., 0.), velocity, 1., 1.)).collect();
+        for (name, mut world) in worlds(particles, Physics::default()) {
+            // without forces Verlet moves the particles exactly
+            world.set_forces(Vec::new());
+            world.advance(5, 1.);
+            assert!(world.apply_impulse(ParticleId::new(1), dv));
+            assert!(!world.apply_impulse(ParticleId::new(9), dv));
+            world.advance(5, 1.);
+            let particles = world.get_particles();
+            assert_eq!((particles[1].position, particles[1].velocity), (vector::planar(20., 2.5), velocity + dv), "{} world", name);
+            assert!(particles.iter().filter(|particle| particle.id != ParticleId::new(1)).all(|particle| particle.velocity == velocity), "{} world", name);
+
+            // impulses pushed from another thread while the world updates are each applied once
+            let queue = world.spawn_queue();
+            let pusher = std::thread::spawn(move || {
+                for _ in 0..PUSHES {
+                    queue.push_impulse(ParticleId::new(2), dv);
+                }
+            });
+            while !pusher.is_finished() {
+                world.update(1.);
+            }
+            pusher.join().unwrap();
+            world.update(1.);
+            assert_eq!(world.get_particles()[2].velocity, velocity + dv * PUSHES as Scalar, "{} world", name);
+        }
+    }
+
+    #[test]
+    fn set_particle_replaces_the_state_of_its_particle() {
+        let particles = presets::random(10, 4);
+        for (name, mut world) in worlds(particles, Physics::default()) {
+            let fixed = world.create_particle(vector::planar(500., 500.), Vector::ZERO, 1e6, true);
+            let (position, velocity) = (vector::planar(-300., 40.), vector::planar(2., -1.));
+            assert!(world.set_particle(ParticleId::new(3), position, velocity, 4e6));
+            let (particles, physics) = (world.get_particles(), Physics::default());
+            let particle = &particles[3];
+            assert_eq!((particle.position, particle.velocity, particle.mass), (position, velocity, 4e6), "{} world", name);
+            assert_eq!(particle.radius, Particle::radius_from_mass(4e6, physics.density), "{} world", name);
+            assert_eq!(particle.acceleration, Particle::net_acceleration_at(3, &particles, physics.force_params()), "{} world", name);
+
+            assert!(!world.set_particle(ParticleId::new(99), position, velocity, 1.));
+            assert!(!world.apply_impulse(ParticleId::new(99), velocity));
+            // fixed particles move but stay at rest
+            assert!(world.set_particle(fixed, position * 2., velocity, 1e6));
+            assert!(world.apply_impulse(fixed, velocity));
+            let particles = world.get_particles();
+            assert_eq!((particles[10].position, particles[10].velocity), (position * 2., Vector::ZERO), "{} world", name);
+        }
+    }
+
+    #[test]
+    fn observers_watch_every_update_and_spawn_before_the_next() {
+        let particles = vec![
+            Particle::new(ParticleId::new(0), vector::planar(-10., 0.), vector::planar(1., 0.), 1., 1.),
+            Particle::new(ParticleId::new(1), vector::planar(10., 0.), vector::planar(-1., 0.), 1., 1.),
+        ];
+        for (name, mut world) in worlds(particles, Physics::default()) {
+            // log the times the first two particles pass within 5 m of each other
+            let encounters = Arc::new(Mutex::new(Vec::new()));
+            let (log, mut time) = (encounters.clone(), 0.);
+            world.add_observer(Box::new(move |particles, dt| {
+                time += dt;
+                if particles[0].position.distance(particles[1].position) < 5. {
+                    log.lock().push(time);
+                }
+            }));
+
+            // add a particle every 4 seconds of simulated time
+            let (queue, mut elapsed) = (world.spawn_queue(), 0.);
+            world.add_observer(Box::new(move |_, dt| {
+                elapsed += dt;
+                if elapsed >= 4. {
+                    elapsed -= 4.;
+                    queue.push(vector::planar(0., 100.), Vector::ZERO, 1., false);
+                }
+            }));
+
+            for _ in 0..20 {
+                world.update(1.);
+            }
+            assert_eq!(*encounters.lock(), [8., 9., 10., 11., 12.], "{} world", name);
+            // the particle requested after the last update is created by the next one
+            assert_eq!(world.len(), 6, "{} world", name);
+        }
+    }
+
+    #[test]
+    fn split_fragments_merge_again_once_their_grace_period_ends() {
+        let physics = Physics { collision_mode: CollisionMode::Merge, gravitational_constant: 0., ..Physics::default() };
+        for (name, mut world) in worlds(Vec::new(), physics) {
+            world.create_particle(Vector::ZERO, vector::planar(3., 0.), 1e3, false);
+            let id = world.create_particle(vector::planar(100., 0.), vector::planar(0., -1.), 2e6, false);
+            let (mass, before) = (total_mass(&world.particles()), momentum(&world.particles()));
+
+            let fragments = world.split_particle(id, 5, 0.);
+            assert_eq!(fragments, (2..7).map(ParticleId::new).collect::<Vec<_>>(), "{} world", name);
+            assert_eq!(world.len(), 6, "{} world", name);
+            assert!((total_mass(&world.particles()) / mass - 1.).abs() < 1e-6, "{} world", name);
+            assert!((momentum(&world.particles()) - before).length() < 1e-6 * before.length(), "{} world", name);
+
+            // without an explosion the fragments stay together, merging again once the grace period ends
+            for _ in 0..SPLIT_GRACE_UPDATES - 1 {
+                world.update(1e-3);
+            }
+            assert_eq!(world.len(), 6, "{} world", name);
+            world.update(1e-3);
+            world.update(1e-3);
+            assert!(world.len() < 6, "{} world", name);
+        }
+    }
+
+    #[test]
+    fn merging_two_particles_records_a_single_event() {
+        let physics = Physics { collision_mode: CollisionMode::Merge, ..Physics::default() };
+        let particles = vec![
+            Particle::new(ParticleId::new(988), Vector::ZERO, Vector::ZERO, 1.2e12, physics.density),
+            Particle::new(ParticleId::new(1032), vector::planar(1., 0.), Vector::ZERO, 2e12, physics.density),
+        ];
+        for (name, mut world) in worlds(particles, physics) {
+            world.update(1.);
+            let events = world.drain_events();
+            assert_eq!(events.len(), 1, "{} world", name);
+            let (survivor, absorbed) = (ParticleId::new(1032), ParticleId::new(988));
+            assert_eq!(events[0].kind, EventKind::Merge(Merge { survivor, absorbed, mass: 3.2e12 }), "{} world", name);
+            assert_eq!(events[0].kind.to_string(), "particle 1032 absorbed 988, new mass 3.2e12");
+            assert_eq!(events[0].time, 1.);
+            assert!(world.drain_events().is_empty(), "{} world", name);
+        }
+    }
+
+    #[test]
+    fn chunks_cover_the_particles_in_order_with_sizes_within_one() {
+        for num_threads in 1..=8 {
+            for len in 0..50 {
+                let chunks: Vec<Range<usize>> = (0..num_threads).map(|thread_id| chunk_range(len, thread_id, num_threads)).collect();
+                assert_eq!((chunks[0].start, chunks[num_threads - 1].end), (0, len));
+                assert!(chunks.windows(2).all(|pair| pair[0].end == pair[1].start), "{} particles on {} threads: {:?}", len, num_threads, chunks);
+                let sizes = chunks.iter().map(|chunk| chunk.len());
+                assert!(sizes.clone().max().unwrap() - sizes.min().unwrap() <= 1, "{} particles on {} threads: {:?}", len, num_threads, chunks);
+            }
+        }
+    }
+
+    #[test]
+    fn len_counts_the_particles_after_every_change() {
+        for (name, mut world) in worlds(Vec::new(), Physics::default()) {
+            let check = |world: &dyn World| assert_eq!(world.len(), world.particles().len(), "{} world", name);
+            let ids: Vec<ParticleId> = (0..10).map(|i| world.create_particle(vector::planar(i as Scalar * 100., 0.), Vector::ZERO, 1e6, false)).collect();
+            check(world.as_ref());
+            world.remove_particle(ids[0]);
+            check(world.as_ref());
+            world.split_particle(ids[1], 4, 0.);
+            check(world.as_ref());
+            world.spawn_queue().push(Vector::ZERO, Vector::ZERO, 1., false);
+            world.spawn_queue().push_removal(ids[2]);
+            world.update(1.);
+            check(world.as_ref());
+            world.advance(3, 1.);
+            check(world.as_ref());
+            let kept = world.get_particles()[..5].to_vec();
+            world.set_particles(kept);
+            check(world.as_ref());
+            world.clear();
+            check(world.as_ref());
+        }
+    }
+
+    #[test]
+    fn ids_stay_unique_past_70k_particles() {
+        const COUNT: usize = 70_001;
+        for (name, mut world) in worlds(Vec::new(), Physics::default()) {
+            // without forces creating a particle doesn't sum the pull of every other particle
+            world.set_forces(Vec::new());
+            world.reserve(COUNT);
+            let ids: Vec<ParticleId> = (0..COUNT).map(|i| world.create_particle(vector::planar(i as Scalar, 0.), Vector::ZERO, 1., false)).collect();
+            assert_eq!(ids.iter().collect::<HashSet<_>>().len(), COUNT, "{} world", name);
+            assert!(ids.windows(2).all(|pair| pair[0] < pair[1]), "{} world", name);
+
+            // the id of a removed particle isn't given out again
+            let last = ids[COUNT - 1];
+            assert!(world.remove_particle(last));
+            assert_eq!(world.create_particle(Vector::ZERO, Vector::ZERO, 1., false), last.next(), "{} world", name);
+        }
+    }
+
+    #[test]
+    fn particles_exclude_only_themselves_past_70k_ids() {
+        // ids which would be equal if they had wrapped around as 16 bit numbers
+        let kept = [ParticleId::new(3), ParticleId::new(3 + 65_536), ParticleId::new(70_000)];
+        for (name, mut world) in worlds(Vec::new(), Physics::default()) {
+            world.set_forces(Vec::new());
+            for i in 0..=70_000 {
+                let angle = i as Scalar;
+                world.create_particle(vector::planar(angle.cos(), angle.sin()) * 1000., Vector::ZERO, 1e12, false);
+            }
+            let sample: Vec<Particle> = world.particles().iter().filter(|particle| kept.contains(&particle.id)).cloned().collect();
+            world.set_forces(default_forces());
+            world.set_particles(sample);
+
+            let (particles, force) = (world.get_particles(), Physics::default().force_params());
+            assert_eq!(particles.len(), kept.len(), "{} world", name);
+            for particle in &particles {
+                let others = particles.iter().filter(|other| other.id != particle.id);
+                let expected: Vector = others.map(|other| particle.acceleration(other, force)).sum();
+                assert!(expected.length() > 0.);
+                assert!((particle.acceleration - expected).length() <= 4. * Scalar::EPSILON * expected.length(), "{} world, particle {}", name, particle.id);
+            }
+        }
+    }
 }